@@ -1,5 +1,14 @@
+use std::{
+    collections::BTreeSet,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+};
+
 use bitcoinsuite_error::{ErrorMeta, Result};
 use flatbuffers::VerifierOptions;
+use futures::Stream;
 use nng::{
     options::{
         protocol::pubsub::{Subscribe, Unsubscribe},
@@ -18,12 +27,38 @@ use crate::{
     structs,
 };
 
+/// How many messages [`PubStream`] buffers before it stops receiving from the socket.
+pub const PUB_STREAM_CAPACITY: usize = 64;
+/// How long [`PubStream`] waits before reconnecting after a socket error.
+pub const PUB_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
 #[derive(Clone)]
 pub struct PubInterface {
+    url: String,
     sock: Socket,
+    topics: Arc<Mutex<BTreeSet<Topic>>>,
     fbb_opts: VerifierOptions,
 }
 
+/// Kind of message published by the node, one per variant of [`structs::Message`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Topic {
+    UpdatedBlockTip,
+    TransactionAddedToMempool,
+    TransactionRemovedFromMempool,
+    BlockConnected,
+    BlockDisconnected,
+    ChainStateFlushed,
+}
+
+/// [`Stream`] of the messages of a [`PubInterface`], see [`PubInterface::into_stream`].
+///
+/// The socket is read on a background task, which is closed when this is dropped.
+#[derive(Debug)]
+pub struct PubStream {
+    receiver: mpsc::Receiver<Result<structs::Message>>,
+}
+
 #[derive(Error, Debug, ErrorMeta)]
 pub enum PubInterfaceError {
     #[bug()]
@@ -37,12 +72,56 @@ pub enum PubInterfaceError {
 
 use self::PubInterfaceError::*;
 
+impl Topic {
+    pub const ALL: [Topic; 6] = [
+        Topic::UpdatedBlockTip,
+        Topic::TransactionAddedToMempool,
+        Topic::TransactionRemovedFromMempool,
+        Topic::BlockConnected,
+        Topic::BlockDisconnected,
+        Topic::ChainStateFlushed,
+    ];
+
+    /// Prefix of the messages of this topic, also used by `-nngpubmsg`.
+    pub fn prefix(self) -> &'static [u8; 12] {
+        match self {
+            Topic::UpdatedBlockTip => b"updateblktip",
+            Topic::TransactionAddedToMempool => b"mempooltxadd",
+            Topic::TransactionRemovedFromMempool => b"mempooltxrem",
+            Topic::BlockConnected => b"blkconnected",
+            Topic::BlockDisconnected => b"blkdisconctd",
+            Topic::ChainStateFlushed => b"chainstflush",
+        }
+    }
+
+    pub fn from_prefix(prefix: &[u8]) -> Option<Topic> {
+        Topic::ALL
+            .into_iter()
+            .find(|topic| topic.prefix().as_slice() == prefix)
+    }
+}
+
+impl structs::Message {
+    pub fn topic(&self) -> Topic {
+        match self {
+            structs::Message::UpdatedBlockTip(_) => Topic::UpdatedBlockTip,
+            structs::Message::TransactionAddedToMempool(_) => Topic::TransactionAddedToMempool,
+            structs::Message::TransactionRemovedFromMempool(_) => {
+                Topic::TransactionRemovedFromMempool
+            }
+            structs::Message::BlockConnected(_) => Topic::BlockConnected,
+            structs::Message::BlockDisconnected(_) => Topic::BlockDisconnected,
+            structs::Message::ChainStateFlushed(_) => Topic::ChainStateFlushed,
+        }
+    }
+}
+
 impl PubInterface {
     pub fn open(pub_url: &str) -> Result<Self> {
-        let sock = Socket::new(Protocol::Sub0)?;
-        sock.dial(pub_url)?;
         Ok(PubInterface {
-            sock,
+            url: pub_url.to_string(),
+            sock: dial(pub_url)?,
+            topics: Default::default(),
             fbb_opts: VerifierOptions {
                 max_tables: 0xffff_ffff,
                 ..Default::default()
@@ -50,28 +129,21 @@ impl PubInterface {
         })
     }
 
-    pub fn subscribe(&self, topic: &str) -> Result<()> {
-        self.sock.set_opt::<Subscribe>(topic.as_bytes().to_vec())?;
+    /// Receive messages of the topic; topics are subscribed again after reconnecting.
+    pub fn subscribe(&self, topic: Topic) -> Result<()> {
+        self.sock.set_opt::<Subscribe>(topic.prefix().to_vec())?;
+        self.topics.lock().unwrap().insert(topic);
         Ok(())
     }
 
-    pub fn unsubscribe(&self, topic: &str) -> Result<()> {
-        self.sock
-            .set_opt::<Unsubscribe>(topic.as_bytes().to_vec())?;
+    pub fn unsubscribe(&self, topic: Topic) -> Result<()> {
+        self.sock.set_opt::<Unsubscribe>(topic.prefix().to_vec())?;
+        self.topics.lock().unwrap().remove(&topic);
         Ok(())
     }
 
     pub async fn recv_async(&self) -> Result<structs::Message> {
-        let (sender, mut receiver) = mpsc::unbounded_channel::<nng::AioResult>();
-        let aio = nng::Aio::new(move |_, result| {
-            sender.send(result).unwrap();
-        })?;
-        self.sock.recv_async(&aio)?;
-        let result = receiver.recv().await.ok_or(NoMessageReceived)?;
-        let msg = match result {
-            nng::AioResult::Recv(msg) => msg?,
-            _ => unreachable!(),
-        };
+        let msg = self.recv_msg_async().await?;
         self.parse_msg(msg)
     }
 
@@ -80,6 +152,68 @@ impl PubInterface {
         self.parse_msg(msg)
     }
 
+    /// Receive messages as a [`Stream`], read from the socket on a background task.
+    ///
+    /// Once [`PUB_STREAM_CAPACITY`] messages are buffered, the task stops reading until
+    /// the stream is polled again; NNG drops messages published in the meantime.
+    /// Socket errors are yielded, after which the socket is reopened and the topics
+    /// are subscribed again. Invalid messages are yielded as errors and skipped.
+    pub fn into_stream(self) -> PubStream {
+        let (sender, receiver) = mpsc::channel(PUB_STREAM_CAPACITY);
+        tokio::spawn(self.run_stream(sender));
+        PubStream { receiver }
+    }
+
+    async fn run_stream(mut self, sender: mpsc::Sender<Result<structs::Message>>) {
+        loop {
+            let msg = tokio::select! {
+                _ = sender.closed() => return,
+                msg = self.recv_msg_async() => msg,
+            };
+            let result = match msg {
+                Ok(msg) => self.parse_msg(msg),
+                Err(err) => {
+                    if sender.send(Err(err)).await.is_err() {
+                        return;
+                    }
+                    while let Err(err) = self.reconnect().await {
+                        if sender.send(Err(err)).await.is_err() {
+                            return;
+                        }
+                    }
+                    continue;
+                }
+            };
+            if sender.send(result).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    async fn reconnect(&mut self) -> Result<()> {
+        tokio::time::sleep(PUB_RECONNECT_DELAY).await;
+        let sock = dial(&self.url)?;
+        for topic in self.topics.lock().unwrap().iter() {
+            sock.set_opt::<Subscribe>(topic.prefix().to_vec())?;
+        }
+        self.sock = sock;
+        Ok(())
+    }
+
+    async fn recv_msg_async(&self) -> Result<nng::Message> {
+        let (sender, mut receiver) = mpsc::unbounded_channel::<nng::AioResult>();
+        let aio = nng::Aio::new(move |_, result| {
+            // The receiver is gone if this future has been dropped, e.g. by `select!`
+            let _ = sender.send(result);
+        })?;
+        self.sock.recv_async(&aio)?;
+        let result = receiver.recv().await.ok_or(NoMessageReceived)?;
+        match result {
+            nng::AioResult::Recv(msg) => Ok(msg?),
+            _ => unreachable!(),
+        }
+    }
+
     fn parse_msg(&self, msg: nng::Message) -> Result<structs::Message> {
         const PREFIX_LEN: usize = 12;
         if msg.len() < PREFIX_LEN {
@@ -88,12 +222,19 @@ impl PubInterface {
         }
         let prefix = &msg[..PREFIX_LEN];
         let payload = &msg[PREFIX_LEN..];
-        Ok(match prefix {
-            b"updateblktip" => {
+        let topic = match Topic::from_prefix(prefix) {
+            Some(topic) => topic,
+            None => {
+                eprintln!("Unknown message prefix: {prefix:?}");
+                return Err(InvalidPubMessage(msg).into());
+            }
+        };
+        Ok(match topic {
+            Topic::UpdatedBlockTip => {
                 let msg = flatbuffers::root_with_opts::<UpdatedBlockTip>(&self.fbb_opts, payload)?;
                 structs::Message::UpdatedBlockTip(structs::UpdatedBlockTip::from_fbs(msg)?)
             }
-            b"mempooltxadd" => {
+            Topic::TransactionAddedToMempool => {
                 let msg = flatbuffers::root_with_opts::<TransactionAddedToMempool>(
                     &self.fbb_opts,
                     payload,
@@ -102,7 +243,7 @@ impl PubInterface {
                     structs::TransactionAddedToMempool::from_fbs(msg)?,
                 )
             }
-            b"mempooltxrem" => {
+            Topic::TransactionRemovedFromMempool => {
                 let msg = flatbuffers::root_with_opts::<TransactionRemovedFromMempool>(
                     &self.fbb_opts,
                     payload,
@@ -111,39 +252,63 @@ impl PubInterface {
                     structs::TransactionRemovedFromMempool::from_fbs(msg)?,
                 )
             }
-            b"blkconnected" => {
+            Topic::BlockConnected => {
                 let msg = flatbuffers::root_with_opts::<BlockConnected>(&self.fbb_opts, payload)?;
                 structs::Message::BlockConnected(structs::BlockConnected::from_fbs(msg)?)
             }
-            b"blkdisconctd" => {
+            Topic::BlockDisconnected => {
                 let msg =
                     flatbuffers::root_with_opts::<BlockDisconnected>(&self.fbb_opts, payload)?;
                 structs::Message::BlockDisconnected(structs::BlockDisconnected::from_fbs(msg)?)
             }
-            b"chainstflush" => {
+            Topic::ChainStateFlushed => {
                 let msg =
                     flatbuffers::root_with_opts::<ChainStateFlushed>(&self.fbb_opts, payload)?;
                 structs::Message::ChainStateFlushed(structs::ChainStateFlushed::from_fbs(msg)?)
             }
-            _ => {
-                eprintln!("Unknown message prefix: {prefix:?}");
-                return Err(InvalidPubMessage(msg).into());
-            }
         })
     }
 }
 
+impl Stream for PubStream {
+    type Item = Result<structs::Message>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+fn dial(url: &str) -> Result<Socket> {
+    let sock = Socket::new(Protocol::Sub0)?;
+    sock.dial(url)?;
+    Ok(sock)
+}
+
 #[cfg(test)]
 mod tests {
-    use std::{ffi::OsString, str::FromStr};
+    use std::{ffi::OsString, str::FromStr, time::Duration};
 
     use bitcoinsuite_bitcoind::instance::{BitcoindChain, BitcoindConf, BitcoindInstance};
     use bitcoinsuite_core::{AddressType, CashAddress, Hashed, ShaRmd160, BCHREG};
     use bitcoinsuite_error::Result;
     use bitcoinsuite_test_utils::bin_folder;
+    use futures::StreamExt;
     use tempdir::TempDir;
 
-    use crate::{Message, PubInterface};
+    use crate::{Message, PubInterface, PubStream, Topic};
+
+    #[test]
+    fn test_topic_prefix() {
+        for topic in Topic::ALL {
+            assert_eq!(Topic::from_prefix(topic.prefix()), Some(topic));
+        }
+        assert_eq!(
+            Topic::from_prefix(b"updateblktip"),
+            Some(Topic::UpdatedBlockTip)
+        );
+        assert_eq!(Topic::from_prefix(b"updateblkti"), None);
+        assert_eq!(Topic::from_prefix(b"unknownprefx"), None);
+    }
 
     #[tokio::test]
     async fn test_pub() -> Result<()> {
@@ -153,18 +318,19 @@ mod tests {
             "ipc://{}",
             ipc_dir.path().join("pub.pipe").to_string_lossy()
         );
-        let conf = BitcoindConf::from_chain_regtest(
-            bin_folder(),
-            BitcoindChain::XPI,
-            vec![
-                OsString::from_str(&format!("-nngpub={pub_url}"))?,
-                OsString::from_str("-nngpubmsg=updateblktip")?,
-            ],
-        )?;
+        let args = vec![
+            OsString::from_str(&format!("-nngpub={pub_url}"))?,
+            OsString::from_str("-nngpubmsg=updateblktip")?,
+        ];
+        let conf =
+            BitcoindConf::from_chain_regtest(bin_folder(), BitcoindChain::XPI, args.clone())?;
         let mut instance = BitcoindInstance::setup(conf)?;
         instance.wait_for_ready()?;
         let pub_interface = PubInterface::open(&pub_url)?;
         test_update_block_tip(&mut instance, &pub_interface).await?;
+        let mut stream = pub_interface.into_stream();
+        test_stream(&mut instance, &mut stream).await?;
+        test_stream_reconnect(&mut instance, &args, &mut stream).await?;
         instance.cleanup()?;
         Ok(())
     }
@@ -173,7 +339,7 @@ mod tests {
         instance: &mut BitcoindInstance,
         pub_interface: &PubInterface,
     ) -> Result<()> {
-        pub_interface.subscribe("updateblktip")?;
+        pub_interface.subscribe(Topic::UpdatedBlockTip)?;
         let address = CashAddress::from_hash(BCHREG, AddressType::P2SH, ShaRmd160::new([0; 20]));
         {
             let hashes = instance.cmd_json("generatetoaddress", &["1", address.as_str()])?;
@@ -197,4 +363,51 @@ mod tests {
         }
         Ok(())
     }
+
+    async fn test_stream(instance: &mut BitcoindInstance, stream: &mut PubStream) -> Result<()> {
+        let address = CashAddress::from_hash(BCHREG, AddressType::P2SH, ShaRmd160::new([0; 20]));
+        let hashes = instance.cmd_json("generatetoaddress", &["2", address.as_str()])?;
+        for hash in hashes.members() {
+            let msg = stream.next().await.unwrap()?;
+            assert_eq!(msg.topic(), Topic::UpdatedBlockTip);
+            match msg {
+                Message::UpdatedBlockTip(msg) => {
+                    assert_eq!(&msg.block_hash.to_hex_be(), hash.as_str().unwrap());
+                }
+                _ => panic!("Invalid message received"),
+            }
+        }
+        Ok(())
+    }
+
+    async fn test_stream_reconnect(
+        instance: &mut BitcoindInstance,
+        args: &[OsString],
+        stream: &mut PubStream,
+    ) -> Result<()> {
+        instance.restart_with_args(args)?;
+        instance.wait_for_ready()?;
+        let address = CashAddress::from_hash(BCHREG, AddressType::P2SH, ShaRmd160::new([0; 20]));
+        // Blocks mined before the socket is connected again are missed, so mine until one
+        // arrives. Socket errors of the restart are yielded before.
+        let mut num_attempts = 0;
+        loop {
+            num_attempts += 1;
+            assert!(num_attempts <= 10, "No message after reconnecting");
+            let hashes = instance.cmd_json("generatetoaddress", &["1", address.as_str()])?;
+            let expected_hash = hashes[0].as_str().unwrap();
+            let deadline = tokio::time::Instant::now() + Duration::from_secs(3);
+            while let Ok(msg) = tokio::time::timeout_at(deadline, stream.next()).await {
+                match msg.unwrap() {
+                    Ok(Message::UpdatedBlockTip(msg)) => {
+                        if msg.block_hash.to_hex_be() == expected_hash {
+                            return Ok(());
+                        }
+                    }
+                    Ok(_) => panic!("Invalid message received"),
+                    Err(_) => {}
+                }
+            }
+        }
+    }
 }
@@ -25,6 +25,18 @@ pub enum BitcoindError {
     #[error("JSON error")]
     JsonError,
 
+    #[critical()]
+    #[error("Unexpected JSON RPC response: {0}")]
+    UnexpectedResponse(String),
+
+    #[critical()]
+    #[error("Cannot read cookie file {0}")]
+    CookieFile(String),
+
+    #[critical()]
+    #[error("Invalid cookie file {0}, expected <user>:<password>")]
+    InvalidCookie(String),
+
     #[critical()]
     #[error("Invalid UTF8")]
     UTF8,
@@ -36,6 +48,10 @@ pub enum BitcoindError {
         debug_log_tail: String,
     },
 
+    #[critical()]
+    #[error("Nodes synced to unexpected tip {0}")]
+    UnexpectedTip(String),
+
     #[critical()]
     #[error("Timeout {0}")]
     Timeout(Cow<'static, str>),
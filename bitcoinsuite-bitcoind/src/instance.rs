@@ -174,6 +174,7 @@ rpcport={rpc_port}
             url: format!("http://127.0.0.1:{}", conf.rpc_port),
             rpc_user: rpc_user.to_string(),
             rpc_pass: rpc_pass.to_string(),
+            ..Default::default()
         });
         Ok(BitcoindInstance {
             conf,
@@ -1,6 +1,8 @@
 pub mod cli;
 pub mod error;
 pub mod instance;
+pub mod network;
 pub mod rpc_client;
+pub mod rpc_types;
 
 pub use crate::error::BitcoindError;
@@ -0,0 +1,252 @@
+use std::{ffi::OsString, path::Path, time::Duration};
+
+use bitcoinsuite_core::{Hashed, Sha256d};
+use bitcoinsuite_error::Result;
+
+use crate::{
+    instance::{BitcoindChain, BitcoindConf, BitcoindInstance},
+    BitcoindError,
+};
+
+/// How often [`BitcoindNetwork`] polls the nodes while waiting for a condition.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How many times [`BitcoindNetwork`] polls the nodes before timing out.
+const MAX_POLLS: usize = 200;
+
+/// Multiple regtest nodes of the same chain, connected via P2P, for testing reorgs and
+/// double spends.
+///
+/// Connections are made to `127.0.0.1`, which bitcoind never adds to its address manager,
+/// so nodes only connect to each other via [`BitcoindNetwork::connect`].
+#[derive(Debug)]
+pub struct BitcoindNetwork {
+    nodes: Vec<BitcoindInstance>,
+}
+
+/// Blocks of an induced reorg, see [`BitcoindNetwork::induce_reorg`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reorg {
+    /// Blocks of the old chain which got disconnected, in mining order.
+    pub orphaned: Vec<Sha256d>,
+    /// Blocks of the new chain replacing them, in mining order.
+    pub replacement: Vec<Sha256d>,
+}
+
+impl BitcoindNetwork {
+    /// Start `num_nodes` regtest nodes and connect every node to every other node.
+    pub fn setup(
+        bin_folder: impl AsRef<Path>,
+        chain: BitcoindChain,
+        num_nodes: usize,
+        additional_args: Vec<OsString>,
+    ) -> Result<Self> {
+        let mut nodes = Vec::with_capacity(num_nodes);
+        for _ in 0..num_nodes {
+            let conf =
+                BitcoindConf::from_chain_regtest(&bin_folder, chain, additional_args.clone())?;
+            let mut node = BitcoindInstance::setup(conf)?;
+            node.wait_for_ready()?;
+            nodes.push(node);
+        }
+        let network = BitcoindNetwork { nodes };
+        network.rejoin()?;
+        Ok(network)
+    }
+
+    pub fn nodes(&self) -> &[BitcoindInstance] {
+        &self.nodes
+    }
+
+    pub fn node(&self, idx: usize) -> &BitcoindInstance {
+        &self.nodes[idx]
+    }
+
+    pub fn node_mut(&mut self, idx: usize) -> &mut BitcoindInstance {
+        &mut self.nodes[idx]
+    }
+
+    /// Connect node `a` to node `b`, if not connected already.
+    pub fn connect(&self, a: usize, b: usize) -> Result<()> {
+        if self.is_connected(a, b)? {
+            return Ok(());
+        }
+        self.nodes[a].cmd_string("addnode", &[&self.p2p_addr(b), "onetry"])?;
+        self.wait_until("connect", || self.is_connected(a, b))
+    }
+
+    /// Disconnect nodes `a` and `b`, regardless of which one initiated the connection.
+    pub fn disconnect(&self, a: usize, b: usize) -> Result<()> {
+        for (from, to) in [(a, b), (b, a)] {
+            let to_addr = self.p2p_addr(to);
+            if self.outbound_peer(from, &to_addr)?.is_some() {
+                self.nodes[from].cmd_string("disconnectnode", &[&to_addr])?;
+            }
+        }
+        self.wait_until("disconnect", || Ok(!self.is_connected(a, b)?))
+    }
+
+    /// Whether either node has a connection to the other.
+    pub fn is_connected(&self, a: usize, b: usize) -> Result<bool> {
+        for (from, to) in [(a, b), (b, a)] {
+            let Some(addr_bind) = self.outbound_peer(from, &self.p2p_addr(to))? else {
+                continue;
+            };
+            // The other node might still have the inbound side of a closed connection open
+            let peers = self.nodes[to].cmd_json("getpeerinfo", &[])?;
+            if peers.members().any(|peer| peer["addr"] == addr_bind) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Split the network into the groups of node indices, disconnecting nodes of
+    /// different groups. Nodes within a group stay connected as they were.
+    pub fn partition(&self, groups: &[&[usize]]) -> Result<()> {
+        for (group_idx, group) in groups.iter().enumerate() {
+            for other_group in &groups[group_idx + 1..] {
+                for &a in group.iter() {
+                    for &b in other_group.iter() {
+                        self.disconnect(a, b)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Connect every node to every other node again, e.g. after a partition.
+    pub fn rejoin(&self) -> Result<()> {
+        for a in 0..self.nodes.len() {
+            for b in a + 1..self.nodes.len() {
+                self.connect(a, b)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Wait until the nodes have the same tip; all nodes if `node_indices` is empty.
+    pub fn wait_for_sync(&self, node_indices: &[usize]) -> Result<Sha256d> {
+        let all_indices = (0..self.nodes.len()).collect::<Vec<_>>();
+        let node_indices = match node_indices {
+            [] => &all_indices,
+            _ => node_indices,
+        };
+        let mut tip = None;
+        self.wait_until("tip sync", || {
+            let tips = node_indices
+                .iter()
+                .map(|&idx| self.best_block_hash(idx))
+                .collect::<Result<Vec<_>>>()?;
+            let is_synced = tips.windows(2).all(|pair| pair[0] == pair[1]);
+            tip = tips.into_iter().next();
+            Ok(is_synced)
+        })?;
+        Ok(tip.unwrap_or_default())
+    }
+
+    /// Mine blocks on the node to the address and return their hashes.
+    pub fn generate(&self, idx: usize, num_blocks: usize, address: &str) -> Result<Vec<Sha256d>> {
+        let hashes =
+            self.nodes[idx].cmd_json("generatetoaddress", &[&num_blocks.to_string(), address])?;
+        hashes
+            .members()
+            .map(|hash| Ok(Sha256d::from_hex_be(hash.as_str().unwrap_or_default())?))
+            .collect()
+    }
+
+    /// Make node `reorg_idx` reorg away its last `depth` blocks.
+    ///
+    /// The node is isolated and mines `depth` blocks, while node `miner_idx` mines
+    /// `depth + 1` blocks. After rejoining, all nodes sync to the chain of `miner_idx`.
+    pub fn induce_reorg(
+        &self,
+        reorg_idx: usize,
+        miner_idx: usize,
+        depth: usize,
+        address: &str,
+    ) -> Result<Reorg> {
+        let others = (0..self.nodes.len())
+            .filter(|&idx| idx != reorg_idx)
+            .collect::<Vec<_>>();
+        self.partition(&[&[reorg_idx], &others])?;
+        let orphaned = self.generate(reorg_idx, depth, address)?;
+        let replacement = self.generate(miner_idx, depth + 1, address)?;
+        self.rejoin()?;
+        let tip = self.wait_for_sync(&[])?;
+        if replacement.last() != Some(&tip) {
+            return Err(BitcoindError::UnexpectedTip(tip.to_hex_be()).into());
+        }
+        Ok(Reorg {
+            orphaned,
+            replacement,
+        })
+    }
+
+    pub fn best_block_hash(&self, idx: usize) -> Result<Sha256d> {
+        let hash = self.nodes[idx].cmd_string("getbestblockhash", &[])?;
+        Ok(Sha256d::from_hex_be(&hash)?)
+    }
+
+    fn p2p_addr(&self, idx: usize) -> String {
+        format!("127.0.0.1:{}", self.nodes[idx].p2p_port())
+    }
+
+    /// Local address of the node's outbound connection to `addr`, if connected.
+    fn outbound_peer(&self, idx: usize, addr: &str) -> Result<Option<String>> {
+        let peers = self.nodes[idx].cmd_json("getpeerinfo", &[])?;
+        let peer = peers.members().find(|peer| peer["addr"] == addr);
+        Ok(peer.map(|peer| peer["addrbind"].as_str().unwrap_or_default().to_string()))
+    }
+
+    fn wait_until(&self, name: &str, mut condition: impl FnMut() -> Result<bool>) -> Result<()> {
+        for _ in 0..MAX_POLLS {
+            if condition()? {
+                return Ok(());
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        Err(BitcoindError::Timeout(format!("BitcoindNetwork {name}").into()).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoinsuite_core::{AddressType, CashAddress, Hashed, ShaRmd160, BCHREG};
+    use bitcoinsuite_error::Result;
+    use bitcoinsuite_test_utils::bin_folder;
+
+    use crate::{instance::BitcoindChain, network::BitcoindNetwork};
+
+    #[test]
+    fn test_bitcoind_network() -> Result<()> {
+        let network = BitcoindNetwork::setup(bin_folder(), BitcoindChain::XEC, 3, vec![])?;
+        let address = CashAddress::from_hash(BCHREG, AddressType::P2SH, ShaRmd160::new([0; 20]));
+        let address = address.as_str();
+        assert!(network.is_connected(0, 1)?);
+        assert!(network.is_connected(2, 1)?);
+
+        let blocks = network.generate(1, 2, address)?;
+        assert_eq!(network.wait_for_sync(&[])?, blocks[1]);
+
+        // Partitioned nodes don't see each other's blocks
+        network.partition(&[&[0], &[1, 2]])?;
+        assert!(!network.is_connected(0, 2)?);
+        assert!(network.is_connected(1, 2)?);
+        let blocks = network.generate(2, 1, address)?;
+        assert_eq!(network.wait_for_sync(&[1, 2])?, blocks[0]);
+        assert_ne!(network.best_block_hash(0)?, blocks[0]);
+        network.rejoin()?;
+        assert_eq!(network.wait_for_sync(&[])?, blocks[0]);
+
+        let reorg = network.induce_reorg(0, 1, 2, address)?;
+        assert_eq!(reorg.orphaned.len(), 2);
+        assert_eq!(reorg.replacement.len(), 3);
+        assert_eq!(network.best_block_hash(0)?, reorg.replacement[2]);
+        let orphaned_block = network
+            .node(0)
+            .cmd_json("getblock", &[&reorg.orphaned[0].to_hex_be()])?;
+        assert_eq!(orphaned_block["confirmations"], -1);
+        Ok(())
+    }
+}
@@ -1,22 +1,48 @@
-use std::sync::{atomic, Arc};
+use std::{
+    path::PathBuf,
+    sync::{atomic, Arc},
+};
 
+use bitcoinsuite_core::{BitcoinBlock, BitcoinHeader, Hashed, Sha256d, Tx};
 use bitcoinsuite_error::{Result, WrapErr};
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
 
-use crate::BitcoindError;
+use crate::{
+    rpc_types::{
+        parse_hash, parse_hashes, parse_hex, BlockchainInfo, MempoolEntry, NetworkInfo,
+        VerboseBlock,
+    },
+    BitcoindError,
+};
 
-#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+/// Connection settings of a [`BitcoindRpcClient`]; construct with
+/// `..Default::default()` to leave unused options unset.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
 pub struct BitcoindRpcClientConf {
     pub url: String,
+    #[serde(default)]
     pub rpc_user: String,
+    #[serde(default)]
     pub rpc_pass: String,
+    /// Authenticate with bitcoind's `.cookie` file instead of `rpc_user`/`rpc_pass`. It is
+    /// read for each request, as bitcoind writes a new cookie on every start.
+    #[serde(default)]
+    pub cookie_file: Option<PathBuf>,
 }
 
+/// Default limit of concurrent requests, matching bitcoind's default `-rpcworkqueue`.
+pub const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 16;
+
+/// JSON-RPC client; connections are kept alive and reused, with at most
+/// `max_concurrent_requests` requests (and therefore connections) in flight.
 #[derive(Debug, Clone)]
 pub struct BitcoindRpcClient {
     conf: BitcoindRpcClientConf,
     client: reqwest::Client,
     last_id: Arc<atomic::AtomicUsize>,
+    request_slots: Arc<Semaphore>,
 }
 
 impl BitcoindRpcClient {
@@ -25,16 +51,77 @@ impl BitcoindRpcClient {
             conf,
             client: reqwest::Client::new(),
             last_id: Arc::new(atomic::AtomicUsize::new(1)),
+            request_slots: Arc::new(Semaphore::new(DEFAULT_MAX_CONCURRENT_REQUESTS)),
         }
     }
 
+    /// Limit how many requests are sent at the same time, further requests wait for a slot.
+    pub fn with_max_concurrent_requests(mut self, max_concurrent_requests: usize) -> Self {
+        self.request_slots = Arc::new(Semaphore::new(max_concurrent_requests));
+        self
+    }
+
     pub async fn cmd_text(&self, cmd: &str, args: &[json::JsonValue]) -> Result<String> {
         Ok(self.cmd_json(cmd, args).await?.to_string())
     }
 
     pub async fn cmd_json(&self, cmd: &str, args: &[json::JsonValue]) -> Result<json::JsonValue> {
-        let response = self.cmd_response(cmd, args).await?;
-        Self::cmd_handle_error(response).await
+        let id = self.last_id.fetch_add(1, atomic::Ordering::SeqCst);
+        let (status, mut response_json) = self.post(request_json(cmd, args, id)).await?;
+        let result = take_result(&mut response_json)?;
+        if !status.is_success() {
+            return Err(BitcoindError::JsonRpc(format!("HTTP status {status}")).into());
+        }
+        Ok(result)
+    }
+
+    /// Send all calls in one HTTP request (JSON-RPC batch), returning the results in the
+    /// order of `calls`. Fails as a whole only if the request itself fails.
+    pub async fn cmd_batch(
+        &self,
+        calls: &[(&str, Vec<json::JsonValue>)],
+    ) -> Result<Vec<Result<json::JsonValue>>> {
+        if calls.is_empty() {
+            return Ok(vec![]);
+        }
+        let first_id = self
+            .last_id
+            .fetch_add(calls.len(), atomic::Ordering::SeqCst);
+        let requests = calls
+            .iter()
+            .enumerate()
+            .map(|(idx, (cmd, args))| request_json(cmd, args, first_id + idx))
+            .collect::<Vec<_>>();
+        let (_, mut response_json) = self.post(json::JsonValue::Array(requests)).await?;
+        let json::JsonValue::Array(responses) = response_json else {
+            // bitcoind responds with a single error if the batch is malformed
+            take_result(&mut response_json)?;
+            return Err(BitcoindError::UnexpectedResponse(
+                "Expected array of responses".to_string(),
+            )
+            .into());
+        };
+        let mut results = calls.iter().map(|_| None).collect::<Vec<_>>();
+        for mut response in responses {
+            let idx = response["id"]
+                .as_usize()
+                .and_then(|id| id.checked_sub(first_id))
+                .filter(|&idx| idx < calls.len())
+                .ok_or_else(|| {
+                    BitcoindError::UnexpectedResponse(format!("Unknown id {}", response["id"]))
+                })?;
+            results[idx] = Some(take_result(&mut response));
+        }
+        results
+            .into_iter()
+            .enumerate()
+            .map(|(idx, result)| {
+                result.ok_or_else(|| {
+                    let msg = format!("Missing response for id {}", first_id + idx);
+                    BitcoindError::UnexpectedResponse(msg).into()
+                })
+            })
+            .collect()
     }
 
     pub async fn test_mempool_accept(
@@ -62,58 +149,373 @@ impl BitcoindRpcClient {
         }
     }
 
-    pub(crate) async fn cmd_response(
+    pub async fn get_blockchain_info(&self) -> Result<BlockchainInfo> {
+        BlockchainInfo::from_json(&self.cmd_json("getblockchaininfo", &[]).await?)
+    }
+
+    pub async fn get_network_info(&self) -> Result<NetworkInfo> {
+        NetworkInfo::from_json(&self.cmd_json("getnetworkinfo", &[]).await?)
+    }
+
+    /// Raw block, for nodes using the Bitcoin block format (i.e. not XPI).
+    pub async fn get_block(&self, block_hash: &Sha256d) -> Result<BitcoinBlock> {
+        let args = [block_hash.to_hex_be().into(), 0.into()];
+        parse_hex(&self.cmd_json("getblock", &args).await?)
+    }
+
+    /// Raw blocks fetched in one batch, e.g. for backfilling.
+    pub async fn get_blocks(&self, block_hashes: &[Sha256d]) -> Result<Vec<BitcoinBlock>> {
+        let calls = block_hashes
+            .iter()
+            .map(|block_hash| ("getblock", vec![block_hash.to_hex_be().into(), 0.into()]))
+            .collect::<Vec<_>>();
+        self.cmd_batch(&calls)
+            .await?
+            .into_iter()
+            .map(|result| parse_hex(&result?))
+            .collect()
+    }
+
+    pub async fn get_block_verbose(&self, block_hash: &Sha256d) -> Result<VerboseBlock> {
+        let args = [block_hash.to_hex_be().into(), 2.into()];
+        VerboseBlock::from_json(&self.cmd_json("getblock", &args).await?)
+    }
+
+    /// Raw block header, for nodes using the Bitcoin block format (i.e. not XPI).
+    pub async fn get_block_header(&self, block_hash: &Sha256d) -> Result<BitcoinHeader> {
+        let args = [block_hash.to_hex_be().into(), false.into()];
+        parse_hex(&self.cmd_json("getblockheader", &args).await?)
+    }
+
+    /// Tx from the mempool, or from the blockchain if the node has `-txindex`.
+    pub async fn get_raw_transaction(&self, txid: &Sha256d) -> Result<Tx> {
+        let args = [txid.to_hex_be().into()];
+        parse_hex(&self.cmd_json("getrawtransaction", &args).await?)
+    }
+
+    pub async fn send_raw_transaction(&self, raw_tx: &[u8]) -> Result<Sha256d> {
+        let args = [hex::encode(raw_tx).into()];
+        parse_hash(&self.cmd_json("sendrawtransaction", &args).await?)
+    }
+
+    pub async fn get_mempool_entry(&self, txid: &Sha256d) -> Result<MempoolEntry> {
+        let args = [txid.to_hex_be().into()];
+        MempoolEntry::from_json(&self.cmd_json("getmempoolentry", &args).await?)
+    }
+
+    pub async fn get_raw_mempool(&self) -> Result<Vec<Sha256d>> {
+        parse_hashes(&self.cmd_json("getrawmempool", &[]).await?)
+    }
+
+    /// Estimated fee, in the node's coin unit per kB.
+    pub async fn estimate_fee(&self) -> Result<f64> {
+        let fee = self.cmd_json("estimatefee", &[]).await?;
+        Ok(fee
+            .as_f64()
+            .ok_or_else(|| BitcoindError::UnexpectedResponse(format!("Expected fee, got {fee}")))?)
+    }
+
+    /// Mine blocks to the address, returning their hashes.
+    pub async fn generate_to_address(
         &self,
-        cmd: &str,
-        args: &[json::JsonValue],
-    ) -> Result<reqwest::Response> {
-        self.client
+        num_blocks: u32,
+        address: &str,
+    ) -> Result<Vec<Sha256d>> {
+        let args = [num_blocks.into(), address.into()];
+        parse_hashes(&self.cmd_json("generatetoaddress", &args).await?)
+    }
+
+    pub async fn invalidate_block(&self, block_hash: &Sha256d) -> Result<()> {
+        let args = [block_hash.to_hex_be().into()];
+        self.cmd_json("invalidateblock", &args).await?;
+        Ok(())
+    }
+
+    async fn post(&self, body: json::JsonValue) -> Result<(StatusCode, json::JsonValue)> {
+        let (rpc_user, rpc_pass) = self.credentials()?;
+        let _slot = self
+            .request_slots
+            .acquire()
+            .await
+            .wrap_err(BitcoindError::Client)?;
+        let response = self
+            .client
             .post(&self.conf.url)
-            .basic_auth(&self.conf.rpc_user, Some(&self.conf.rpc_pass))
+            .basic_auth(rpc_user, Some(rpc_pass))
             .header(reqwest::header::CONTENT_TYPE, "text/plain")
-            .body(
-                json::object! {
-                    jsonrpc: "1.0",
-                    method: cmd,
-                    id: self.last_id.fetch_add(1, atomic::Ordering::SeqCst),
-                    params: args,
-                }
-                .to_string(),
-            )
+            .body(body.to_string())
             .send()
             .await
-            .wrap_err(BitcoindError::Client)
+            .wrap_err(BitcoindError::Client)?;
+        let status = response.status();
+        let response_str = response.text().await.wrap_err(BitcoindError::UTF8)?;
+        let response_json = json::parse(&response_str).wrap_err(BitcoindError::JsonError)?;
+        Ok((status, response_json))
     }
 
-    pub(crate) async fn cmd_handle_error(response: reqwest::Response) -> Result<json::JsonValue> {
-        let is_success = response.status().is_success();
-        let response_str = response.text().await.wrap_err(BitcoindError::UTF8)?;
-        let mut response_json = json::parse(&response_str).wrap_err(BitcoindError::JsonError)?;
-        if !is_success {
-            let error = &response_json["error"];
-            return Err(BitcoindError::JsonRpcCode {
-                code: error["code"].as_i32().expect("Unexpected JSON"),
-                message: error["message"]
-                    .as_str()
-                    .expect("Unexpected JSON")
-                    .to_string(),
-            }
-            .into());
+    fn credentials(&self) -> Result<(String, String)> {
+        let Some(cookie_file) = &self.conf.cookie_file else {
+            return Ok((self.conf.rpc_user.clone(), self.conf.rpc_pass.clone()));
+        };
+        let cookie_file_str = cookie_file.to_string_lossy().into_owned();
+        let cookie = std::fs::read_to_string(cookie_file)
+            .wrap_err_with(|| BitcoindError::CookieFile(cookie_file_str.clone()))?;
+        let (rpc_user, rpc_pass) = cookie
+            .trim_end()
+            .split_once(':')
+            .ok_or(BitcoindError::InvalidCookie(cookie_file_str))?;
+        Ok((rpc_user.to_string(), rpc_pass.to_string()))
+    }
+}
+
+fn request_json(cmd: &str, args: &[json::JsonValue], id: usize) -> json::JsonValue {
+    json::object! {
+        jsonrpc: "1.0",
+        method: cmd,
+        id: id,
+        params: args,
+    }
+}
+
+/// Result of the JSON-RPC response, or its error.
+fn take_result(response_json: &mut json::JsonValue) -> Result<json::JsonValue> {
+    let error = &response_json["error"];
+    if !error.is_null() {
+        return Err(BitcoindError::JsonRpcCode {
+            code: error["code"].as_i32().unwrap_or_default(),
+            message: error["message"].as_str().unwrap_or_default().to_string(),
         }
-        Ok(response_json["result"].take())
+        .into());
     }
+    Ok(response_json["result"].take())
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use bitcoinsuite_core::{AddressType, CashAddress, ShaRmd160, BCHREG};
     use bitcoinsuite_error::Result;
     use bitcoinsuite_test_utils::bin_folder;
+    use tempdir::TempDir;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        sync::Semaphore,
+    };
 
     use crate::{
         instance::{BitcoindChain, BitcoindConf, BitcoindInstance},
+        rpc_client::{BitcoindRpcClient, BitcoindRpcClientConf},
         BitcoindError,
     };
 
+    #[derive(Default)]
+    struct StubStats {
+        auth_headers: Vec<String>,
+        num_active: usize,
+        max_active: usize,
+        /// If set, requests are held until a permit is added.
+        gate: Option<Arc<Semaphore>>,
+    }
+
+    /// Serves JSON-RPC requests, answering each call with `respond`, batches in reverse
+    /// order.
+    async fn serve_stub(
+        respond: fn(&json::JsonValue) -> json::JsonValue,
+        stats: Arc<Mutex<StubStats>>,
+    ) -> Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let stats = stats.clone();
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0; 1024];
+                    let (header, body) = loop {
+                        let n = stream.read(&mut buf).await.unwrap();
+                        request.extend_from_slice(&buf[..n]);
+                        let request = String::from_utf8_lossy(&request).into_owned();
+                        let Some((header, body)) = request.split_once("\r\n\r\n") else {
+                            continue;
+                        };
+                        let content_length = header
+                            .lines()
+                            .find_map(|line| line.strip_prefix("content-length: "))
+                            .unwrap();
+                        if body.len() == content_length.parse::<usize>().unwrap() {
+                            break (header.to_string(), body.to_string());
+                        }
+                    };
+                    let gate = {
+                        let mut stats = stats.lock().unwrap();
+                        let auth = header
+                            .lines()
+                            .find_map(|line| line.strip_prefix("authorization: "))
+                            .unwrap();
+                        stats.auth_headers.push(auth.to_string());
+                        stats.num_active += 1;
+                        stats.max_active = stats.max_active.max(stats.num_active);
+                        stats.gate.clone()
+                    };
+                    match gate {
+                        Some(gate) => gate.acquire().await.unwrap().forget(),
+                        None => tokio::time::sleep(Duration::from_millis(20)).await,
+                    }
+                    let response = match json::parse(&body).unwrap() {
+                        json::JsonValue::Array(calls) => {
+                            json::JsonValue::Array(calls.iter().rev().map(respond).collect())
+                        }
+                        call => respond(&call),
+                    }
+                    .to_string();
+                    stats.lock().unwrap().num_active -= 1;
+                    let header = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        response.len(),
+                    );
+                    stream.write_all(header.as_bytes()).await.unwrap();
+                    stream.write_all(response.as_bytes()).await.unwrap();
+                });
+            }
+        });
+        Ok(url)
+    }
+
+    fn respond_double(call: &json::JsonValue) -> json::JsonValue {
+        match call["method"].as_str().unwrap() {
+            "double" => json::object! {
+                result: call["params"][0].as_i32().unwrap() * 2,
+                error: null,
+                id: call["id"].clone(),
+            },
+            _ => json::object! {
+                result: null,
+                error: { code: -32601, message: "Method not found" },
+                id: call["id"].clone(),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cmd_batch() -> Result<()> {
+        let stats = Arc::new(Mutex::new(StubStats::default()));
+        let url = serve_stub(respond_double, stats.clone()).await?;
+        let tempdir = TempDir::new("bitcoind_cookie")?;
+        let cookie_file = tempdir.path().join(".cookie");
+        std::fs::write(&cookie_file, "__cookie__:secret")?;
+        let client = BitcoindRpcClient::new(BitcoindRpcClientConf {
+            url,
+            rpc_user: "ignored".to_string(),
+            rpc_pass: "ignored".to_string(),
+            cookie_file: Some(cookie_file),
+        });
+        let results = client
+            .cmd_batch(&[
+                ("double", vec![1.into()]),
+                ("triple", vec![2.into()]),
+                ("double", vec![3.into()]),
+            ])
+            .await?;
+        assert_eq!(results.len(), 3);
+        let mut results = results.into_iter();
+        assert_eq!(results.next().unwrap()?, 2);
+        assert_eq!(
+            results
+                .next()
+                .unwrap()
+                .unwrap_err()
+                .downcast::<BitcoindError>()?,
+            BitcoindError::JsonRpcCode {
+                code: -32601,
+                message: "Method not found".to_string(),
+            },
+        );
+        assert_eq!(results.next().unwrap()?, 6);
+        assert_eq!(client.cmd_json("double", &[5.into()]).await?, 10);
+        assert!(client.cmd_batch(&[]).await?.is_empty());
+        assert_eq!(
+            stats.lock().unwrap().auth_headers,
+            vec!["Basic X19jb29raWVfXzpzZWNyZXQ="; 2],
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_concurrent_requests() -> Result<()> {
+        let gate = Arc::new(Semaphore::new(0));
+        let stats = Arc::new(Mutex::new(StubStats {
+            gate: Some(gate.clone()),
+            ..Default::default()
+        }));
+        let url = serve_stub(respond_double, stats.clone()).await?;
+        let client = BitcoindRpcClient::new(BitcoindRpcClientConf {
+            url,
+            rpc_user: "user".to_string(),
+            rpc_pass: "pass".to_string(),
+            ..Default::default()
+        })
+        .with_max_concurrent_requests(2);
+        let calls = (0..6)
+            .map(|num| {
+                let client = client.clone();
+                tokio::spawn(async move { client.cmd_json("double", &[num.into()]).await })
+            })
+            .collect::<Vec<_>>();
+        // All requests are held by the stub, so the limit is reached
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while stats.lock().unwrap().num_active < 2 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await?;
+        // Give excess requests the chance to arrive before releasing any
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(stats.lock().unwrap().num_active, 2);
+        gate.add_permits(6);
+        for (num, call) in calls.into_iter().enumerate() {
+            assert_eq!(call.await??, num * 2);
+        }
+        let stats = stats.lock().unwrap();
+        assert_eq!(stats.max_active, 2);
+        assert_eq!(stats.auth_headers, vec!["Basic dXNlcjpwYXNz"; 6]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invalid_cookie() -> Result<()> {
+        let tempdir = TempDir::new("bitcoind_cookie")?;
+        let cookie_file = tempdir.path().join(".cookie");
+        let client = BitcoindRpcClient::new(BitcoindRpcClientConf {
+            url: "http://127.0.0.1:1".to_string(),
+            cookie_file: Some(cookie_file.clone()),
+            ..Default::default()
+        });
+        let cookie_file_str = cookie_file.to_string_lossy().into_owned();
+        assert_eq!(
+            client
+                .cmd_json("getblockcount", &[])
+                .await
+                .unwrap_err()
+                .downcast::<BitcoindError>()?,
+            BitcoindError::CookieFile(cookie_file_str.clone()),
+        );
+        std::fs::write(&cookie_file, "nocolon")?;
+        assert_eq!(
+            client
+                .cmd_json("getblockcount", &[])
+                .await
+                .unwrap_err()
+                .downcast::<BitcoindError>()?,
+            BitcoindError::InvalidCookie(cookie_file_str),
+        );
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_bitcoind_client() -> Result<()> {
         let conf = BitcoindConf::from_chain_regtest(bin_folder(), BitcoindChain::XEC, vec![])?;
@@ -142,4 +544,42 @@ mod tests {
         }
         Ok(())
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_bitcoind_client_typed() -> Result<()> {
+        let conf = BitcoindConf::from_chain_regtest(bin_folder(), BitcoindChain::XEC, vec![])?;
+        let mut instance = BitcoindInstance::setup(conf)?;
+        instance.wait_for_ready()?;
+        let client = instance.rpc_client();
+        let info = client.get_blockchain_info().await?;
+        assert_eq!(info.chain, "regtest");
+        assert_eq!(info.blocks, 0);
+        let genesis_hash = info.best_block_hash;
+
+        let genesis = client.get_block(&genesis_hash).await?;
+        let header = client.get_block_header(&genesis_hash).await?;
+        assert_eq!(genesis.header, header);
+        let verbose = client.get_block_verbose(&genesis_hash).await?;
+        assert_eq!(verbose.hash, genesis_hash);
+        assert_eq!(verbose.header, header);
+        assert_eq!(verbose.txs, genesis.txs);
+        assert_eq!(verbose.next_block_hash, None);
+
+        let address = CashAddress::from_hash(BCHREG, AddressType::P2SH, ShaRmd160::new([0; 20]));
+        let block_hashes = client.generate_to_address(2, address.as_str()).await?;
+        assert_eq!(block_hashes.len(), 2);
+        assert_eq!(client.get_blockchain_info().await?.blocks, 2);
+        let coinbase = &client.get_block(&block_hashes[0]).await?.txs[0];
+        // Coinbase txs aren't in the mempool and there's no -txindex
+        assert!(client.get_raw_transaction(coinbase.hash()).await.is_err());
+        assert_eq!(client.get_raw_mempool().await?, vec![]);
+
+        client.invalidate_block(&block_hashes[1]).await?;
+        assert_eq!(
+            client.get_blockchain_info().await?.best_block_hash,
+            block_hashes[0],
+        );
+        assert!(client.get_network_info().await?.network_active);
+        Ok(())
+    }
 }
@@ -0,0 +1,254 @@
+use bitcoinsuite_core::{BitcoinCode, BitcoinHeader, Bytes, Hashed, Sha256d, Tx};
+use bitcoinsuite_error::Result;
+use json::JsonValue;
+
+use crate::BitcoindError;
+
+/// Result of `getblockchaininfo`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockchainInfo {
+    pub chain: String,
+    pub blocks: i32,
+    pub headers: i32,
+    pub best_block_hash: Sha256d,
+    pub difficulty: f64,
+    pub median_time: i64,
+    pub verification_progress: f64,
+    pub initial_block_download: bool,
+    pub pruned: bool,
+}
+
+/// Result of `getblock` with verbosity 2, with the txs parsed from their hex.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerboseBlock {
+    pub hash: Sha256d,
+    pub header: BitcoinHeader,
+    pub height: i32,
+    /// -1 if the block is not on the main chain.
+    pub confirmations: i32,
+    pub size: u64,
+    pub median_time: i64,
+    pub next_block_hash: Option<Sha256d>,
+    pub txs: Vec<Tx>,
+}
+
+/// Result of `getmempoolentry`; fees are in the node's coin unit, e.g. XEC or BCH.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MempoolEntry {
+    pub size: u64,
+    pub fee: f64,
+    pub modified_fee: f64,
+    /// Time the tx entered the mempool, in seconds since epoch.
+    pub time: i64,
+    /// Block height when the tx entered the mempool.
+    pub height: i32,
+    /// Unconfirmed txs this tx spends from.
+    pub depends: Vec<Sha256d>,
+}
+
+/// Result of `getnetworkinfo`; the relay fee is in the node's coin unit per kB.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkInfo {
+    pub version: i32,
+    pub subversion: String,
+    pub protocol_version: i32,
+    pub connections: i32,
+    pub network_active: bool,
+    pub relay_fee: f64,
+}
+
+impl BlockchainInfo {
+    pub(crate) fn from_json(json: &JsonValue) -> Result<Self> {
+        Ok(BlockchainInfo {
+            chain: parse_str(json, "chain")?.to_string(),
+            blocks: parse_num(json, "blocks", JsonValue::as_i32)?,
+            headers: parse_num(json, "headers", JsonValue::as_i32)?,
+            best_block_hash: parse_hash(&json["bestblockhash"])?,
+            difficulty: parse_num(json, "difficulty", JsonValue::as_f64)?,
+            median_time: parse_num(json, "mediantime", JsonValue::as_i64)?,
+            verification_progress: parse_num(json, "verificationprogress", JsonValue::as_f64)?,
+            initial_block_download: parse_num(json, "initialblockdownload", JsonValue::as_bool)?,
+            pruned: parse_num(json, "pruned", JsonValue::as_bool)?,
+        })
+    }
+}
+
+impl VerboseBlock {
+    pub(crate) fn from_json(json: &JsonValue) -> Result<Self> {
+        let bits = parse_str(json, "bits")?;
+        let prev_block = match &json["previousblockhash"] {
+            JsonValue::Null => Sha256d::default(),
+            hash => parse_hash(hash)?,
+        };
+        Ok(VerboseBlock {
+            hash: parse_hash(&json["hash"])?,
+            header: BitcoinHeader {
+                version: parse_num(json, "version", JsonValue::as_i32)?,
+                prev_block,
+                merkle_root: parse_hash(&json["merkleroot"])?,
+                timestamp: parse_num(json, "time", JsonValue::as_u32)?,
+                bits: u32::from_str_radix(bits, 16).map_err(|_| invalid_field("bits"))?,
+                nonce: parse_num(json, "nonce", JsonValue::as_u32)?,
+            },
+            height: parse_num(json, "height", JsonValue::as_i32)?,
+            confirmations: parse_num(json, "confirmations", JsonValue::as_i32)?,
+            size: parse_num(json, "size", JsonValue::as_u64)?,
+            median_time: parse_num(json, "mediantime", JsonValue::as_i64)?,
+            next_block_hash: match &json["nextblockhash"] {
+                JsonValue::Null => None,
+                hash => Some(parse_hash(hash)?),
+            },
+            txs: json["tx"]
+                .members()
+                .map(|tx| parse_hex(&tx["hex"]))
+                .collect::<Result<Vec<_>>>()?,
+        })
+    }
+}
+
+impl MempoolEntry {
+    pub(crate) fn from_json(json: &JsonValue) -> Result<Self> {
+        let fees = &json["fees"];
+        Ok(MempoolEntry {
+            size: parse_num(json, "size", JsonValue::as_u64)?,
+            fee: parse_num(fees, "base", JsonValue::as_f64)?,
+            modified_fee: parse_num(fees, "modified", JsonValue::as_f64)?,
+            time: parse_num(json, "time", JsonValue::as_i64)?,
+            height: parse_num(json, "height", JsonValue::as_i32)?,
+            depends: parse_hashes(&json["depends"])?,
+        })
+    }
+}
+
+impl NetworkInfo {
+    pub(crate) fn from_json(json: &JsonValue) -> Result<Self> {
+        Ok(NetworkInfo {
+            version: parse_num(json, "version", JsonValue::as_i32)?,
+            subversion: parse_str(json, "subversion")?.to_string(),
+            protocol_version: parse_num(json, "protocolversion", JsonValue::as_i32)?,
+            connections: parse_num(json, "connections", JsonValue::as_i32)?,
+            network_active: parse_num(json, "networkactive", JsonValue::as_bool)?,
+            relay_fee: parse_num(json, "relayfee", JsonValue::as_f64)?,
+        })
+    }
+}
+
+fn invalid_field(name: &str) -> BitcoindError {
+    BitcoindError::UnexpectedResponse(format!("Missing or invalid field {name:?}"))
+}
+
+fn parse_str<'a>(json: &'a JsonValue, name: &str) -> Result<&'a str> {
+    Ok(json[name].as_str().ok_or_else(|| invalid_field(name))?)
+}
+
+fn parse_num<T>(
+    json: &JsonValue,
+    name: &str,
+    as_num: impl Fn(&JsonValue) -> Option<T>,
+) -> Result<T> {
+    Ok(as_num(&json[name]).ok_or_else(|| invalid_field(name))?)
+}
+
+/// Parse a hash, which bitcoind encodes as big-endian hex.
+pub(crate) fn parse_hash(json: &JsonValue) -> Result<Sha256d> {
+    let hash = json.as_str().ok_or_else(|| unexpected_json("hash", json))?;
+    Ok(Sha256d::from_hex_be(hash).map_err(|_| unexpected_json("hash", json))?)
+}
+
+pub(crate) fn parse_hashes(json: &JsonValue) -> Result<Vec<Sha256d>> {
+    if !json.is_array() {
+        return Err(unexpected_json("array of hashes", json).into());
+    }
+    json.members().map(parse_hash).collect()
+}
+
+/// Parse a hex string as serialized object, e.g. a raw tx.
+pub(crate) fn parse_hex<T: BitcoinCode>(json: &JsonValue) -> Result<T> {
+    let hex = json.as_str().ok_or_else(|| unexpected_json("hex", json))?;
+    let raw = hex::decode(hex).map_err(|_| unexpected_json("hex", json))?;
+    Ok(T::deser(&mut Bytes::from_bytes(raw)).map_err(|err| {
+        BitcoindError::UnexpectedResponse(format!("Invalid serialization: {err}"))
+    })?)
+}
+
+fn unexpected_json(expected: &str, json: &JsonValue) -> BitcoindError {
+    let json = json.to_string().chars().take(100).collect::<String>();
+    BitcoindError::UnexpectedResponse(format!("Expected {expected}, got {json}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoinsuite_core::{BitcoinHeader, Hashed, Sha256d, Tx};
+    use bitcoinsuite_error::Result;
+
+    use crate::{
+        rpc_types::{parse_hash, parse_hex, MempoolEntry, VerboseBlock},
+        BitcoindError,
+    };
+
+    #[test]
+    fn test_parse_verbose_block() -> Result<()> {
+        let hash = "0000000000000000000000000000000000000000000000000000000000000001";
+        let merkle_root = "00000000000000000000000000000000000000000000000000000000000000ff";
+        let json = json::object! {
+            hash: hash,
+            confirmations: 3,
+            size: 285,
+            height: 0,
+            version: 1,
+            merkleroot: merkle_root,
+            tx: [{ txid: merkle_root, hex: "01000000000000000000" }],
+            time: 1296688602,
+            mediantime: 1296688602,
+            nonce: 2,
+            bits: "207fffff",
+            nextblockhash: hash,
+        };
+        let block = VerboseBlock::from_json(&json)?;
+        assert_eq!(
+            block,
+            VerboseBlock {
+                hash: Sha256d::from_hex_be(hash)?,
+                header: BitcoinHeader {
+                    version: 1,
+                    prev_block: Sha256d::default(),
+                    merkle_root: Sha256d::from_hex_be(merkle_root)?,
+                    timestamp: 1296688602,
+                    bits: 0x207fffff,
+                    nonce: 2,
+                },
+                height: 0,
+                confirmations: 3,
+                size: 285,
+                median_time: 1296688602,
+                next_block_hash: Some(Sha256d::from_hex_be(hash)?),
+                txs: vec![parse_hex::<Tx>(&"01000000000000000000".into())?],
+            },
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_invalid() -> Result<()> {
+        let json = json::object! {
+            size: 100,
+            fees: { base: 0.01 },
+            time: 1,
+            height: 2,
+            depends: [],
+        };
+        assert_eq!(
+            MempoolEntry::from_json(&json)
+                .unwrap_err()
+                .downcast::<BitcoindError>()?,
+            BitcoindError::UnexpectedResponse("Missing or invalid field \"modified\"".to_string()),
+        );
+        assert_eq!(
+            parse_hash(&"abcd".into())
+                .unwrap_err()
+                .downcast::<BitcoindError>()?,
+            BitcoindError::UnexpectedResponse("Expected hash, got abcd".to_string()),
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,587 @@
+//! Conversions from Chronik's protobuf structs into the typed model of the suite.
+//! Hashes in the protobuf structs are little-endian, token IDs big-endian hex.
+
+use bitcoinsuite_core::{
+    ByteArray, Coin, Hashed, Network, OutPoint, Script, SequenceNo, Sha256d, Tx, TxInput, TxOutput,
+    UnhashedTx, Utxo,
+};
+use bitcoinsuite_error::{Report, Result};
+use bitcoinsuite_slp::{
+    RichTx, RichTxBlock, SlpAmount, SlpBurn, SlpGenesisInfo, SlpToken, SlpTokenType, SlpTxData,
+    SlpTxType, SlpUtxo, TokenId,
+};
+
+use crate::proto;
+
+impl TryFrom<proto::OutPoint> for OutPoint {
+    type Error = Report;
+
+    fn try_from(outpoint: proto::OutPoint) -> Result<Self> {
+        Ok(OutPoint {
+            txid: Sha256d::from_slice(&outpoint.txid)?,
+            out_idx: outpoint.out_idx,
+        })
+    }
+}
+
+/// Input spending an output, with `out_idx` being the index of the input.
+impl TryFrom<proto::SpentBy> for OutPoint {
+    type Error = Report;
+
+    fn try_from(spent_by: proto::SpentBy) -> Result<Self> {
+        Ok(OutPoint {
+            txid: Sha256d::from_slice(&spent_by.txid)?,
+            out_idx: spent_by.input_idx,
+        })
+    }
+}
+
+impl TryFrom<proto::TxInput> for TxInput {
+    type Error = Report;
+
+    fn try_from(input: proto::TxInput) -> Result<Self> {
+        Ok(TxInput {
+            prev_out: input.prev_out.unwrap_or_default().try_into()?,
+            script: Script::new(input.input_script.into()),
+            sequence: SequenceNo::from_u32(input.sequence_no),
+            sign_data: None,
+        })
+    }
+}
+
+impl From<proto::TxOutput> for TxOutput {
+    fn from(output: proto::TxOutput) -> Self {
+        TxOutput {
+            value: output.sats,
+            script: Script::new(output.output_script.into()),
+        }
+    }
+}
+
+impl TryFrom<proto::Tx> for UnhashedTx {
+    type Error = Report;
+
+    fn try_from(tx: proto::Tx) -> Result<Self> {
+        Ok(UnhashedTx {
+            version: tx.version,
+            inputs: tx
+                .inputs
+                .into_iter()
+                .map(TxInput::try_from)
+                .collect::<Result<_>>()?,
+            outputs: tx.outputs.into_iter().map(TxOutput::from).collect(),
+            lock_time: tx.lock_time,
+        })
+    }
+}
+
+impl TryFrom<proto::Tx> for Tx {
+    type Error = Report;
+
+    fn try_from(tx: proto::Tx) -> Result<Self> {
+        Ok(UnhashedTx::try_from(tx)?.hashed())
+    }
+}
+
+impl TryFrom<proto::BlockMetadata> for RichTxBlock {
+    type Error = Report;
+
+    fn try_from(block: proto::BlockMetadata) -> Result<Self> {
+        Ok(RichTxBlock {
+            height: block.height,
+            hash: Sha256d::from_slice(&block.hash)?,
+            timestamp: block.timestamp,
+        })
+    }
+}
+
+impl TryFrom<proto::BlockInfo> for RichTxBlock {
+    type Error = Report;
+
+    fn try_from(block_info: proto::BlockInfo) -> Result<Self> {
+        Ok(RichTxBlock {
+            height: block_info.height,
+            hash: Sha256d::from_slice(&block_info.hash)?,
+            timestamp: block_info.timestamp,
+        })
+    }
+}
+
+impl TryFrom<proto::Block> for RichTxBlock {
+    type Error = Report;
+
+    fn try_from(block: proto::Block) -> Result<Self> {
+        block.block_info.unwrap_or_default().try_into()
+    }
+}
+
+impl TryFrom<proto::Utxo> for Utxo {
+    type Error = Report;
+
+    fn try_from(utxo: proto::Utxo) -> Result<Self> {
+        Ok(Utxo {
+            outpoint: utxo.outpoint.unwrap_or_default().try_into()?,
+            script: Script::new(utxo.script.into()),
+            value: utxo.sats,
+        })
+    }
+}
+
+impl From<proto::Utxo> for Coin {
+    fn from(utxo: proto::Utxo) -> Self {
+        Coin {
+            tx_output: TxOutput {
+                value: utxo.sats,
+                script: Script::new(utxo.script.into()),
+            },
+            height: block_height(utxo.block_height),
+            is_coinbase: utxo.is_coinbase,
+        }
+    }
+}
+
+impl From<&proto::Token> for SlpToken {
+    fn from(token: &proto::Token) -> Self {
+        SlpToken {
+            amount: SlpAmount::new(token.atoms.into()),
+            is_mint_baton: token.is_mint_baton,
+        }
+    }
+}
+
+impl TryFrom<&proto::Token> for SlpBurn {
+    type Error = Report;
+
+    fn try_from(token: &proto::Token) -> Result<Self> {
+        Ok(SlpBurn {
+            token: token.into(),
+            token_id: TokenId::from_token_id_hex(&token.token_id)?,
+        })
+    }
+}
+
+/// ALP and unknown token types map to [`SlpTokenType::Unknown`].
+impl From<&proto::TokenType> for SlpTokenType {
+    fn from(token_type: &proto::TokenType) -> Self {
+        use proto::token_type::TokenType;
+        let slp_token_type = match token_type.token_type {
+            Some(TokenType::Slp(slp_token_type)) => proto::SlpTokenType::from_i32(slp_token_type),
+            _ => None,
+        };
+        match slp_token_type {
+            Some(proto::SlpTokenType::Fungible) => SlpTokenType::Fungible,
+            Some(proto::SlpTokenType::Nft1Group) => SlpTokenType::Nft1Group,
+            Some(proto::SlpTokenType::Nft1Child) => SlpTokenType::Nft1Child,
+            _ => SlpTokenType::Unknown,
+        }
+    }
+}
+
+impl From<proto::GenesisInfo> for SlpGenesisInfo {
+    fn from(genesis_info: proto::GenesisInfo) -> Self {
+        SlpGenesisInfo {
+            token_ticker: genesis_info.token_ticker.into(),
+            token_name: genesis_info.token_name.into(),
+            token_document_url: genesis_info.url.into(),
+            token_document_hash: <[u8; 32]>::try_from(genesis_info.hash.as_slice())
+                .ok()
+                .map(ByteArray::new),
+            decimals: genesis_info.decimals,
+        }
+    }
+}
+
+/// Chronik only indexes eCash, so the network is always [`Network::XEC`].
+impl TryFrom<proto::Tx> for RichTx {
+    type Error = Report;
+
+    fn try_from(tx: proto::Tx) -> Result<Self> {
+        let slp_tx_data = tx.slp_tx_data()?.map(Box::new);
+        let slp_burns = tx.slp_burns()?;
+        let spent_coins = (!tx.is_coinbase).then(|| tx.spent_coins());
+        let spends = tx
+            .outputs
+            .iter()
+            .map(|output| output.spent_by.clone().map(OutPoint::try_from).transpose())
+            .collect::<Result<_>>()?;
+        let burn_summaries = tx
+            .token_entries
+            .iter()
+            .map(|entry| entry.burn_summary.as_str())
+            .filter(|burn_summary| !burn_summary.is_empty())
+            .collect::<Vec<_>>();
+        Ok(RichTx {
+            txid: Sha256d::from_slice(&tx.txid)?,
+            block: tx.block.clone().map(RichTxBlock::try_from).transpose()?,
+            slp_tx_data,
+            spent_coins,
+            spends,
+            slp_burns,
+            slp_error_msg: (!burn_summaries.is_empty()).then(|| burn_summaries.join("; ")),
+            time_first_seen: tx.time_first_seen,
+            network: Network::XEC,
+            tx: tx.try_into()?,
+        })
+    }
+}
+
+impl proto::Tx {
+    /// Token data of the tx, or `None` if it doesn't have a valid token section.
+    /// The first token entry is the token of the tx, any other entries are only burned.
+    /// Genesis info is left empty, Chronik only returns it from [`ChronikClient::token`].
+    ///
+    /// [`ChronikClient::token`]: crate::ChronikClient::token
+    pub fn slp_tx_data(&self) -> Result<Option<SlpTxData>> {
+        let entry = match self.tx_token_entry() {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let group_token_id = match entry.group_token_id.as_str() {
+            "" => None,
+            group_token_id => Some(Box::new(TokenId::from_token_id_hex(group_token_id)?)),
+        };
+        let slp_tx_type = match proto::TokenTxType::from_i32(entry.tx_type) {
+            Some(proto::TokenTxType::Genesis) => SlpTxType::Genesis(Default::default()),
+            Some(proto::TokenTxType::Send) => SlpTxType::Send,
+            Some(proto::TokenTxType::Mint) => SlpTxType::Mint,
+            Some(proto::TokenTxType::Burn) => SlpTxType::Burn(entry.intentional_burn_atoms),
+            _ => SlpTxType::Unknown,
+        };
+        Ok(Some(SlpTxData {
+            input_tokens: self
+                .inputs
+                .iter()
+                .map(|input| slp_token_of_entry(input.token.as_ref(), entry))
+                .collect(),
+            output_tokens: self
+                .outputs
+                .iter()
+                .map(|output| slp_token_of_entry(output.token.as_ref(), entry))
+                .collect(),
+            slp_token_type: entry
+                .token_type
+                .as_ref()
+                .map(SlpTokenType::from)
+                .unwrap_or(SlpTokenType::Unknown),
+            slp_tx_type,
+            token_id: TokenId::from_token_id_hex(&entry.token_id)?,
+            group_token_id,
+        }))
+    }
+
+    /// Tokens burned by each input, i.e. tokens that are not of the token of the tx.
+    pub fn slp_burns(&self) -> Result<Vec<Option<Box<SlpBurn>>>> {
+        let entry = self.tx_token_entry();
+        self.inputs
+            .iter()
+            .map(|input| match &input.token {
+                Some(token) if !entry.is_some_and(|entry| is_entry_token(token, entry)) => {
+                    Ok(Some(Box::new(SlpBurn::try_from(token)?)))
+                }
+                _ => Ok(None),
+            })
+            .collect()
+    }
+
+    /// Coins spent by the inputs of the tx. Chronik doesn't return the height and
+    /// coinbase flag of spent coins, so these are left unset.
+    pub fn spent_coins(&self) -> Vec<Coin> {
+        self.inputs
+            .iter()
+            .map(|input| Coin {
+                tx_output: TxOutput {
+                    value: input.sats,
+                    script: Script::new(input.output_script.clone().into()),
+                },
+                height: None,
+                is_coinbase: false,
+            })
+            .collect()
+    }
+
+    fn tx_token_entry(&self) -> Option<&proto::TokenEntry> {
+        self.token_entries
+            .first()
+            .filter(|entry| entry.tx_type != proto::TokenTxType::None as i32 && !entry.is_invalid)
+    }
+}
+
+impl proto::ScriptUtxo {
+    /// Chronik doesn't include the script in the UTXOs of a script, so it has to be given.
+    pub fn to_utxo(&self, script: Script) -> Result<Utxo> {
+        Ok(Utxo {
+            outpoint: self.outpoint.clone().unwrap_or_default().try_into()?,
+            script,
+            value: self.sats,
+        })
+    }
+
+    pub fn to_coin(&self, script: Script) -> Coin {
+        Coin {
+            tx_output: TxOutput {
+                value: self.sats,
+                script,
+            },
+            height: block_height(self.block_height),
+            is_coinbase: self.is_coinbase,
+        }
+    }
+
+    pub fn to_slp_utxo(&self, script: Script) -> Result<SlpUtxo> {
+        let token_id = match &self.token {
+            Some(token) => Some(TokenId::from_token_id_hex(&token.token_id)?),
+            None => None,
+        };
+        Ok(SlpUtxo {
+            utxo: self.to_utxo(script)?,
+            token: self.token.as_ref().map(SlpToken::from).unwrap_or_default(),
+            token_id,
+        })
+    }
+}
+
+fn block_height(height: i32) -> Option<i32> {
+    (height >= 0).then_some(height)
+}
+
+/// NFT1 child genesis txs spend the group token, so it counts as the token of the tx.
+fn is_entry_token(token: &proto::Token, entry: &proto::TokenEntry) -> bool {
+    token.token_id == entry.token_id || token.token_id == entry.group_token_id
+}
+
+fn slp_token_of_entry(token: Option<&proto::Token>, entry: &proto::TokenEntry) -> SlpToken {
+    match token {
+        Some(token) if is_entry_token(token, entry) => token.into(),
+        _ => SlpToken::EMPTY,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoinsuite_core::{Coin, Network, OutPoint, Script, Sha256d, Tx, TxOutput, UnhashedTx};
+    use bitcoinsuite_error::Result;
+    use bitcoinsuite_slp::{
+        RichTx, RichTxBlock, SlpAmount, SlpBurn, SlpToken, SlpTokenType, SlpTxType, TokenId,
+    };
+    use pretty_assertions::assert_eq;
+
+    use crate::proto;
+
+    fn token(token_id: &str, atoms: u64) -> Option<proto::Token> {
+        Some(proto::Token {
+            token_id: token_id.to_string(),
+            token_type: Some(proto::TokenType {
+                token_type: Some(proto::token_type::TokenType::Slp(
+                    proto::SlpTokenType::Fungible as i32,
+                )),
+            }),
+            atoms,
+            ..Default::default()
+        })
+    }
+
+    fn amount(atoms: i128) -> SlpToken {
+        SlpToken {
+            amount: SlpAmount::new(atoms),
+            is_mint_baton: false,
+        }
+    }
+
+    fn send_tx(token_id: &str, other_token_id: &str) -> proto::Tx {
+        proto::Tx {
+            txid: vec![1; 32],
+            version: 2,
+            inputs: vec![
+                proto::TxInput {
+                    prev_out: Some(proto::OutPoint {
+                        txid: vec![3; 32],
+                        out_idx: 1,
+                    }),
+                    output_script: vec![0x51],
+                    sats: 2000,
+                    sequence_no: 0xffff_ffff,
+                    token: token(token_id, 100),
+                    ..Default::default()
+                },
+                proto::TxInput {
+                    prev_out: Some(proto::OutPoint {
+                        txid: vec![4; 32],
+                        out_idx: 2,
+                    }),
+                    token: token(other_token_id, 7),
+                    ..Default::default()
+                },
+            ],
+            outputs: vec![
+                proto::TxOutput {
+                    output_script: vec![0x6a],
+                    ..Default::default()
+                },
+                proto::TxOutput {
+                    sats: 546,
+                    token: token(token_id, 100),
+                    spent_by: Some(proto::SpentBy {
+                        txid: vec![5; 32],
+                        input_idx: 3,
+                    }),
+                    ..Default::default()
+                },
+            ],
+            block: Some(proto::BlockMetadata {
+                height: 100,
+                hash: vec![6; 32],
+                timestamp: 1_600_000_000,
+                is_final: false,
+            }),
+            token_entries: vec![
+                proto::TokenEntry {
+                    token_id: token_id.to_string(),
+                    token_type: token(token_id, 0).unwrap().token_type,
+                    tx_type: proto::TokenTxType::Send as i32,
+                    ..Default::default()
+                },
+                proto::TokenEntry {
+                    token_id: other_token_id.to_string(),
+                    token_type: token(token_id, 0).unwrap().token_type,
+                    tx_type: proto::TokenTxType::None as i32,
+                    burn_summary: "Unexpected burn".to_string(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_tx_from_proto() -> Result<()> {
+        let tx = send_tx(&"11".repeat(32), &"22".repeat(32));
+        let unhashed_tx = UnhashedTx::try_from(tx.clone())?;
+        assert_eq!(unhashed_tx.version, 2);
+        assert_eq!(
+            unhashed_tx.inputs[0].prev_out,
+            OutPoint {
+                txid: Sha256d::new([3; 32]),
+                out_idx: 1,
+            },
+        );
+        assert_eq!(unhashed_tx.inputs[0].sequence.as_u32(), 0xffff_ffff);
+        assert_eq!(
+            unhashed_tx.outputs,
+            vec![
+                TxOutput {
+                    value: 0,
+                    script: Script::opreturn(&[]),
+                },
+                TxOutput {
+                    value: 546,
+                    script: Script::default(),
+                },
+            ],
+        );
+        assert_eq!(Tx::try_from(tx)?, unhashed_tx.hashed());
+        Ok(())
+    }
+
+    #[test]
+    fn test_slp_tx_data_from_proto() -> Result<()> {
+        let token_id = "11".repeat(32);
+        let other_token_id = "22".repeat(32);
+        let tx = send_tx(&token_id, &other_token_id);
+        let slp_tx_data = tx.slp_tx_data()?.unwrap();
+        assert_eq!(slp_tx_data.token_id, TokenId::from_token_id_hex(&token_id)?);
+        assert_eq!(slp_tx_data.slp_token_type, SlpTokenType::Fungible);
+        assert_eq!(slp_tx_data.slp_tx_type, SlpTxType::Send);
+        assert_eq!(slp_tx_data.group_token_id, None);
+        assert_eq!(slp_tx_data.input_tokens, vec![amount(100), SlpToken::EMPTY]);
+        assert_eq!(
+            slp_tx_data.output_tokens,
+            vec![SlpToken::EMPTY, amount(100)]
+        );
+        assert_eq!(
+            tx.slp_burns()?,
+            vec![
+                None,
+                Some(Box::new(SlpBurn {
+                    token: amount(7),
+                    token_id: TokenId::from_token_id_hex(&other_token_id)?,
+                })),
+            ],
+        );
+
+        // Invalid txs burn all input tokens
+        let mut invalid_tx = tx;
+        invalid_tx.token_entries[0].is_invalid = true;
+        assert_eq!(invalid_tx.slp_tx_data()?, None);
+        assert!(invalid_tx.slp_burns()?.iter().all(|burn| burn.is_some()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rich_tx_from_proto() -> Result<()> {
+        let tx = send_tx(&"11".repeat(32), &"22".repeat(32));
+        let rich_tx = RichTx::try_from(tx.clone())?;
+        assert_eq!(rich_tx.txid, Sha256d::new([1; 32]));
+        assert_eq!(rich_tx.tx, Tx::try_from(tx.clone())?);
+        assert_eq!(
+            rich_tx.block,
+            Some(RichTxBlock {
+                height: 100,
+                hash: Sha256d::new([6; 32]),
+                timestamp: 1_600_000_000,
+            }),
+        );
+        assert_eq!(rich_tx.slp_tx_data.as_deref(), tx.slp_tx_data()?.as_ref());
+        assert_eq!(rich_tx.slp_burns, tx.slp_burns()?);
+        assert_eq!(
+            rich_tx.spent_coins.as_ref().unwrap()[0],
+            Coin {
+                tx_output: TxOutput {
+                    value: 2000,
+                    script: Script::new(vec![0x51].into()),
+                },
+                height: None,
+                is_coinbase: false,
+            },
+        );
+        assert_eq!(
+            rich_tx.spends,
+            vec![
+                None,
+                Some(OutPoint {
+                    txid: Sha256d::new([5; 32]),
+                    out_idx: 3,
+                }),
+            ],
+        );
+        assert_eq!(rich_tx.slp_error_msg.as_deref(), Some("Unexpected burn"));
+        assert_eq!(rich_tx.network, Network::XEC);
+        Ok(())
+    }
+
+    #[test]
+    fn test_utxo_from_proto() -> Result<()> {
+        let script = Script::p2pkh(&Default::default());
+        let utxo = proto::ScriptUtxo {
+            outpoint: Some(proto::OutPoint {
+                txid: vec![5; 32],
+                out_idx: 3,
+            }),
+            block_height: -1,
+            sats: 1000,
+            token: token(&"33".repeat(32), 20),
+            ..Default::default()
+        };
+        let slp_utxo = utxo.to_slp_utxo(script.clone())?;
+        assert_eq!(slp_utxo.utxo.outpoint.txid, Sha256d::new([5; 32]));
+        assert_eq!(slp_utxo.utxo.outpoint.out_idx, 3);
+        assert_eq!(slp_utxo.utxo.script, script);
+        assert_eq!(slp_utxo.utxo.value, 1000);
+        assert_eq!(slp_utxo.token, amount(20));
+        assert_eq!(
+            slp_utxo.token_id,
+            Some(TokenId::from_token_id_hex(&"33".repeat(32))?),
+        );
+        assert_eq!(utxo.to_coin(script).height, None);
+        Ok(())
+    }
+}
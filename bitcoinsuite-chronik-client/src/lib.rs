@@ -3,6 +3,16 @@ pub mod proto {
     include!(concat!(env!("OUT_DIR"), "/chronik.rs"));
 }
 
+mod convert;
+mod paginate;
+mod retry;
+mod slp_node;
+mod ws;
+
+pub use crate::retry::*;
+pub use crate::slp_node::*;
+pub use crate::ws::*;
+
 use std::fmt::Display;
 
 use bitcoinsuite_core::{Bytes, Sha256d};
@@ -15,6 +25,7 @@ pub struct ChronikClient {
     http_url: String,
     ws_url: String,
     client: reqwest::Client,
+    retry_policy: RetryPolicy,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -55,10 +66,25 @@ pub enum ChronikClientError {
     #[error("HTTP request error")]
     HttpRequestError,
 
+    #[critical()]
+    #[error("HTTP error ({status_code}): {body}")]
+    HttpStatusError {
+        status_code: StatusCode,
+        body: String,
+    },
+
     #[critical()]
     #[error("Unexpected text message: {0}")]
     UnexpectedWsTextMessage(String),
 
+    #[critical()]
+    #[error("Connecting to WebSocket failed: {0}")]
+    WsConnectError(String),
+
+    #[critical()]
+    #[error("WebSocket connection closed")]
+    WsClosed,
+
     #[critical()]
     #[error("Chronik error ({status_code}): {error_msg}")]
     ChronikError {
@@ -91,9 +117,20 @@ impl ChronikClient {
             http_url: url,
             ws_url,
             client: reqwest::Client::new(),
+            retry_policy: RetryPolicy::default(),
         })
     }
 
+    /// Use the given policy for retrying requests and timeouts.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
     pub fn ws_url(&self) -> &str {
         &self.ws_url
     }
@@ -229,11 +266,15 @@ impl ChronikClient {
         url_suffix: &str,
         request: &MRequest,
     ) -> Result<MResponse> {
-        let response = self
+        let request = self
             .client
             .post(format!("{}{}", self.http_url, url_suffix))
             .header(CONTENT_TYPE, "application/x-protobuf")
-            .body(request.encode_to_vec())
+            .body(request.encode_to_vec());
+        // Not retried, as a failed broadcast might still have reached the node
+        let response = self
+            .retry_policy
+            .apply_timeout(request)
             .send()
             .await
             .wrap_err(HttpRequestError)?;
@@ -244,14 +285,22 @@ impl ChronikClient {
         &self,
         url_suffix: &str,
     ) -> Result<MResponse> {
-        let response = self
-            .client
-            .get(format!("{}{}", self.http_url, url_suffix))
-            .header(CONTENT_TYPE, "application/x-protobuf")
-            .send()
+        let url = format!("{}{}", self.http_url, url_suffix);
+        self.retry_policy
+            .retry(|| async {
+                let request = self
+                    .client
+                    .get(&url)
+                    .header(CONTENT_TYPE, "application/x-protobuf");
+                let response = self
+                    .retry_policy
+                    .apply_timeout(request)
+                    .send()
+                    .await
+                    .wrap_err(HttpRequestError)?;
+                Self::_handle_response(response).await
+            })
             .await
-            .wrap_err(HttpRequestError)?;
-        Self::_handle_response(response).await
     }
 
     async fn _handle_response<MResponse: prost::Message + Default>(
@@ -260,9 +309,15 @@ impl ChronikClient {
         use prost::Message as _;
         let status_code = response.status();
         if status_code != StatusCode::OK {
-            let data = response.bytes().await?;
-            let error = proto::Error::decode(data.as_ref())
-                .wrap_err_with(|| InvalidProtobuf(hex::encode(&data)))?;
+            let data = response.bytes().await.wrap_err(HttpRequestError)?;
+            let Ok(error) = proto::Error::decode(data.as_ref()) else {
+                // Not from Chronik, e.g. a 502 from a reverse proxy
+                return Err(HttpStatusError {
+                    status_code,
+                    body: String::from_utf8_lossy(&data).into_owned(),
+                }
+                .into());
+            };
             return Err(ChronikError {
                 status_code,
                 error_msg: error.msg.clone(),
@@ -316,6 +371,22 @@ impl ScriptEndpoint<'_, '_> {
             .await
     }
 
+    pub async fn confirmed_txs_with_page_size(
+        &self,
+        page: usize,
+        page_size: usize,
+    ) -> Result<proto::TxHistoryPage> {
+        self.client
+            ._get(&format!(
+                "/script/{}/{}/confirmed-txs?page={}&page_size={}",
+                self.script_type,
+                hex::encode(self.script_payload),
+                page,
+                page_size,
+            ))
+            .await
+    }
+
     pub async fn unconfirmed_txs(&self, page: usize) -> Result<proto::TxHistoryPage> {
         self.client
             ._get(&format!(
@@ -379,6 +450,22 @@ impl PluginEndpoint<'_, '_> {
             .await
     }
 
+    pub async fn confirmed_txs_with_page_size(
+        &self,
+        page: usize,
+        page_size: usize,
+    ) -> Result<proto::TxHistoryPage> {
+        self.client
+            ._get(&format!(
+                "/plugin/{}/{}/confirmed-txs?page={}&page_size={}",
+                self.plugin_name,
+                hex::encode(self.payload),
+                page,
+                page_size,
+            ))
+            .await
+    }
+
     pub async fn unconfirmed_txs(&self, page: usize) -> Result<proto::TxHistoryPage> {
         self.client
             ._get(&format!(
@@ -0,0 +1,93 @@
+use std::future::Future;
+
+use bitcoinsuite_core::Sha256d;
+use bitcoinsuite_error::{Report, Result};
+use futures::{stream, Stream, TryStreamExt};
+
+use crate::{proto, ChronikClient, PluginEndpoint, ScriptEndpoint};
+
+/// Stream of the txs of all pages, fetching the next page only once the txs of the
+/// previous page have been consumed.
+pub(crate) fn tx_pages_stream<'a, Fut>(
+    mut fetch_page: impl FnMut(usize) -> Fut + 'a,
+) -> impl Stream<Item = Result<proto::Tx>> + 'a
+where
+    Fut: Future<Output = Result<proto::TxHistoryPage>> + 'a,
+{
+    stream::try_unfold(Some(0), move |page: Option<usize>| {
+        let fetched = page.map(|page| (page, fetch_page(page)));
+        async move {
+            let Some((page, fetched)) = fetched else {
+                return Ok::<_, Report>(None);
+            };
+            let history = fetched.await?;
+            let next_page = (page + 1 < history.num_pages as usize).then_some(page + 1);
+            Ok(Some((
+                stream::iter(history.txs.into_iter().map(Ok)),
+                next_page,
+            )))
+        }
+    })
+    .try_flatten()
+}
+
+impl ChronikClient {
+    /// Stream of all txs of the block, fetching pages of `page_size` txs lazily.
+    pub fn block_txs_by_height_stream(
+        &self,
+        height: i32,
+        page_size: usize,
+    ) -> impl Stream<Item = Result<proto::Tx>> + '_ {
+        tx_pages_stream(move |page| {
+            self.block_txs_by_height_with_page_size(height, page, page_size)
+        })
+    }
+
+    /// Stream of all txs of the block, fetching pages of `page_size` txs lazily.
+    pub fn block_txs_by_hash_stream(
+        &self,
+        hash: &Sha256d,
+        page_size: usize,
+    ) -> impl Stream<Item = Result<proto::Tx>> + '_ {
+        let hash = hash.clone();
+        tx_pages_stream(move |page| {
+            let hash = hash.clone();
+            async move {
+                self.block_txs_by_hash_with_page_size(&hash, page, page_size)
+                    .await
+            }
+        })
+    }
+}
+
+impl ScriptEndpoint<'_, '_> {
+    /// Stream of the entire tx history, most recent first, fetching pages of `page_size`
+    /// txs lazily.
+    pub fn history_stream(&self, page_size: usize) -> impl Stream<Item = Result<proto::Tx>> + '_ {
+        tx_pages_stream(move |page| self.history_with_page_size(page, page_size))
+    }
+
+    /// Stream of all confirmed txs, oldest first, fetching pages of `page_size` txs lazily.
+    pub fn confirmed_txs_stream(
+        &self,
+        page_size: usize,
+    ) -> impl Stream<Item = Result<proto::Tx>> + '_ {
+        tx_pages_stream(move |page| self.confirmed_txs_with_page_size(page, page_size))
+    }
+}
+
+impl PluginEndpoint<'_, '_> {
+    /// Stream of the entire tx history, most recent first, fetching pages of `page_size`
+    /// txs lazily.
+    pub fn history_stream(&self, page_size: usize) -> impl Stream<Item = Result<proto::Tx>> + '_ {
+        tx_pages_stream(move |page| self.history_with_page_size(page, page_size))
+    }
+
+    /// Stream of all confirmed txs, oldest first, fetching pages of `page_size` txs lazily.
+    pub fn confirmed_txs_stream(
+        &self,
+        page_size: usize,
+    ) -> impl Stream<Item = Result<proto::Tx>> + '_ {
+        tx_pages_stream(move |page| self.confirmed_txs_with_page_size(page, page_size))
+    }
+}
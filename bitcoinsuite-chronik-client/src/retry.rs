@@ -0,0 +1,128 @@
+use std::{future::Future, time::Duration};
+
+use bitcoinsuite_error::{Report, Result};
+use reqwest::{RequestBuilder, StatusCode};
+
+use crate::ChronikClientError::{self, *};
+
+/// How [`crate::ChronikClient`] retries requests failing with transient errors, like
+/// connection failures, timeouts or a 502 from a reverse proxy.
+///
+/// Only queries are retried; broadcasts are never retried, but still time out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// How often a request is retried before giving up, 0 disables retries.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubling with each further retry.
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between retries.
+    pub max_backoff: Duration,
+    /// Timeout of each individual request, or `None` to wait indefinitely.
+    pub timeout: Option<Duration>,
+}
+
+/// HTTP status codes which indicate the request might succeed if tried again.
+const TRANSIENT_STATUS_CODES: [StatusCode; 4] = [
+    StatusCode::TOO_MANY_REQUESTS,
+    StatusCode::BAD_GATEWAY,
+    StatusCode::SERVICE_UNAVAILABLE,
+    StatusCode::GATEWAY_TIMEOUT,
+];
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+            timeout: Some(Duration::from_secs(30)),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before the retry with the given index, starting at 0.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff)
+    }
+
+    pub(crate) fn apply_timeout(&self, request: RequestBuilder) -> RequestBuilder {
+        match self.timeout {
+            Some(timeout) => request.timeout(timeout),
+            None => request,
+        }
+    }
+
+    /// Run the request, retrying it with backoff while it fails with a transient error.
+    pub(crate) async fn retry<T, Fut>(&self, mut request: impl FnMut() -> Fut) -> Result<T>
+    where
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut retry = 0;
+        loop {
+            match request().await {
+                Err(report) if retry < self.max_retries && is_transient_error(&report) => {
+                    tokio::time::sleep(self.backoff(retry)).await;
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Whether the request failing with the error might succeed if tried again.
+pub fn is_transient_error(report: &Report) -> bool {
+    match report.downcast_ref::<ChronikClientError>() {
+        Some(HttpRequestError) => true,
+        Some(ChronikError { status_code, .. } | HttpStatusError { status_code, .. }) => {
+            TRANSIENT_STATUS_CODES.contains(status_code)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use reqwest::StatusCode;
+
+    use crate::{is_transient_error, ChronikClientError, RetryPolicy};
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(3),
+            ..Default::default()
+        };
+        assert_eq!(policy.backoff(0), Duration::from_millis(500));
+        assert_eq!(policy.backoff(1), Duration::from_millis(1000));
+        assert_eq!(policy.backoff(2), Duration::from_millis(2000));
+        assert_eq!(policy.backoff(3), Duration::from_secs(3));
+        assert_eq!(policy.backoff(100), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn test_is_transient_error() {
+        let http_status = |status_code| ChronikClientError::HttpStatusError {
+            status_code,
+            body: String::new(),
+        };
+        assert!(is_transient_error(
+            &ChronikClientError::HttpRequestError.into()
+        ));
+        assert!(is_transient_error(
+            &http_status(StatusCode::BAD_GATEWAY).into()
+        ));
+        assert!(!is_transient_error(
+            &http_status(StatusCode::NOT_FOUND).into()
+        ));
+        assert!(!is_transient_error(
+            &ChronikClientError::InvalidProtobuf(String::new()).into()
+        ));
+    }
+}
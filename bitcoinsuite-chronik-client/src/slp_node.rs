@@ -0,0 +1,343 @@
+use std::{collections::HashMap, pin::Pin};
+
+use async_trait::async_trait;
+use bitcoinsuite_core::{AddressType, CashAddress, Hashed, Sha256d};
+use bitcoinsuite_error::Result;
+use bitcoinsuite_slp::{SlpNodeInterface, SlpTx, SlpTxType, SlpUtxo, TokenId, TokenMetadata};
+use futures::{Stream, TryStreamExt};
+
+use crate::{paginate::tx_pages_stream, proto, ChronikClient, ScriptType};
+
+/// [`SlpNodeInterface`] backed by a Chronik indexer.
+#[derive(Debug, Clone)]
+pub struct ChronikSlpNode {
+    client: ChronikClient,
+}
+
+impl ChronikSlpNode {
+    pub fn new(client: ChronikClient) -> Self {
+        ChronikSlpNode { client }
+    }
+
+    pub fn client(&self) -> &ChronikClient {
+        &self.client
+    }
+}
+
+#[async_trait]
+impl SlpNodeInterface for ChronikSlpNode {
+    async fn submit_tx(&self, raw_tx: Vec<u8>) -> Result<Sha256d> {
+        let response = self.client.broadcast_tx(raw_tx).await?;
+        Ok(Sha256d::from_slice(&response.txid)?)
+    }
+
+    async fn get_token_metadata(
+        &self,
+        token_ids: &[TokenId],
+    ) -> Result<HashMap<TokenId, TokenMetadata>> {
+        let token_infos = futures::future::try_join_all(
+            token_ids
+                .iter()
+                .map(|token_id| self.client.token(token_id.hash())),
+        )
+        .await?;
+        Ok(token_ids
+            .iter()
+            .cloned()
+            .zip(token_infos)
+            .map(|(token_id, token_info)| {
+                let decimals = token_info.genesis_info.unwrap_or_default().decimals;
+                (token_id, TokenMetadata { decimals })
+            })
+            .collect())
+    }
+
+    async fn address_tx_stream(
+        &self,
+        address: &CashAddress,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<SlpTx>> + Send>>> {
+        let (script_type, payload) = address_script(address);
+        let client = self.client.clone();
+        // Pages through the history, most recent txs first
+        let history = tx_pages_stream(move |page| {
+            let client = client.clone();
+            let payload = payload.clone();
+            async move { client.script(script_type, &payload).history(page).await }
+        });
+        let client = self.client.clone();
+        let txs = history.and_then(move |tx| {
+            let client = client.clone();
+            async move { fetch_slp_tx(&client, tx).await }
+        });
+        Ok(Box::pin(txs))
+    }
+
+    async fn address_utxos(&self, address: &CashAddress) -> Result<Vec<SlpUtxo>> {
+        let (script_type, payload) = address_script(address);
+        let utxos = self.client.script(script_type, &payload).utxos().await?;
+        let script = address.to_script();
+        utxos
+            .iter()
+            .map(|utxo| utxo.to_slp_utxo(script.clone()))
+            .collect()
+    }
+}
+
+fn address_script(address: &CashAddress) -> (ScriptType, Vec<u8>) {
+    let script_type = match address.addr_type() {
+        AddressType::P2PKH => ScriptType::P2pkh,
+        AddressType::P2SH => ScriptType::P2sh,
+    };
+    (script_type, address.hash().as_slice().to_vec())
+}
+
+/// Convert the tx, fetching the genesis info from Chronik for GENESIS txs.
+async fn fetch_slp_tx(client: &ChronikClient, tx: proto::Tx) -> Result<SlpTx> {
+    let mut slp_tx_data = tx.slp_tx_data()?;
+    if let Some(SlpTxType::Genesis(genesis_info)) =
+        slp_tx_data.as_mut().map(|slp| &mut slp.slp_tx_type)
+    {
+        let token_info = client.token(&Sha256d::from_slice(&tx.txid)?).await?;
+        **genesis_info = token_info.genesis_info.unwrap_or_default().into();
+    }
+    let slp_burns = tx.slp_burns()?;
+    Ok(SlpTx::new(tx.try_into()?, slp_tx_data, slp_burns))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use bitcoinsuite_core::{AddressType, CashAddress, OutPoint, Script, Sha256d, ShaRmd160, Utxo};
+    use bitcoinsuite_error::Result;
+    use bitcoinsuite_slp::{
+        SlpAmount, SlpGenesisInfo, SlpNodeInterface, SlpToken, SlpTxType, SlpUtxo, TokenId,
+    };
+    use futures::TryStreamExt;
+    use prost::Message;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use crate::{proto, ChronikClient, ChronikSlpNode};
+
+    type Requests = Arc<Mutex<Vec<String>>>;
+
+    /// Serve the protobuf responses by path, recording the requested paths.
+    async fn serve(responses: HashMap<String, Vec<u8>>) -> Result<(ChronikClient, Requests)> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let client = ChronikClient::new(format!("http://{}", listener.local_addr()?))?;
+        let requests = Requests::default();
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    match socket.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let request = String::from_utf8_lossy(&request);
+                let path = request.split(' ').nth(1).unwrap_or_default().to_string();
+                let (status, body) = match responses.get(&path) {
+                    Some(body) => ("200 OK", body.clone()),
+                    None => ("404 Not Found", vec![]),
+                };
+                recorded.lock().unwrap().push(path);
+                let head = format!(
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len(),
+                );
+                let _ = socket.write_all(&[head.as_bytes(), &body].concat()).await;
+            }
+        });
+        Ok((client, requests))
+    }
+
+    fn address() -> CashAddress<'static> {
+        CashAddress::from_hash("ecash", AddressType::P2PKH, ShaRmd160::new([1; 20]))
+    }
+
+    fn token(token_id: &str, atoms: u64) -> Option<proto::Token> {
+        Some(proto::Token {
+            token_id: token_id.to_string(),
+            token_type: Some(proto::TokenType {
+                token_type: Some(proto::token_type::TokenType::Slp(
+                    proto::SlpTokenType::Fungible as i32,
+                )),
+            }),
+            atoms,
+            ..Default::default()
+        })
+    }
+
+    fn token_tx(txid: [u8; 32], token_id: &str, tx_type: proto::TokenTxType) -> proto::Tx {
+        proto::Tx {
+            txid: txid.to_vec(),
+            version: 1,
+            inputs: vec![proto::TxInput {
+                prev_out: Some(proto::OutPoint {
+                    txid: vec![3; 32],
+                    out_idx: 0,
+                }),
+                ..Default::default()
+            }],
+            outputs: vec![
+                proto::TxOutput {
+                    output_script: vec![0x6a],
+                    ..Default::default()
+                },
+                proto::TxOutput {
+                    sats: 546,
+                    token: token(token_id, 1000),
+                    ..Default::default()
+                },
+            ],
+            token_entries: vec![proto::TokenEntry {
+                token_id: token_id.to_string(),
+                token_type: token(token_id, 0).unwrap().token_type,
+                tx_type: tx_type as i32,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_address_tx_stream() -> Result<()> {
+        let genesis_txid = Sha256d::new([7; 32]);
+        let token_id = genesis_txid.to_string();
+        let send_tx = token_tx([8; 32], &token_id, proto::TokenTxType::Send);
+        let genesis_tx = token_tx([7; 32], &token_id, proto::TokenTxType::Genesis);
+        let history = format!("/script/p2pkh/{}/history", "01".repeat(20));
+        let page = |txs: Vec<proto::Tx>| {
+            proto::TxHistoryPage {
+                txs,
+                num_pages: 2,
+                num_txs: 2,
+            }
+            .encode_to_vec()
+        };
+        let token_info = proto::TokenInfo {
+            token_id: token_id.clone(),
+            genesis_info: Some(proto::GenesisInfo {
+                token_ticker: b"TKN".to_vec(),
+                decimals: 4,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let responses = HashMap::from([
+            (format!("{history}?page=0"), page(vec![send_tx])),
+            (format!("{history}?page=1"), page(vec![genesis_tx])),
+            (format!("/token/{token_id}"), token_info.encode_to_vec()),
+        ]);
+        let (client, requests) = serve(responses).await?;
+        let node = ChronikSlpNode::new(client);
+
+        let txs = node
+            .address_tx_stream(&address())
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert_eq!(txs.len(), 2);
+        let send_slp = txs[0].slp().unwrap();
+        assert_eq!(send_slp.slp_tx_type, SlpTxType::Send);
+        assert_eq!(send_slp.token_id, TokenId::new(genesis_txid.clone()));
+        // GENESIS metadata is fetched from the token endpoint
+        let genesis_slp = txs[1].slp().unwrap();
+        assert_eq!(
+            genesis_slp.slp_tx_type,
+            SlpTxType::Genesis(Box::new(SlpGenesisInfo {
+                token_ticker: b"TKN".as_ref().into(),
+                decimals: 4,
+                ..Default::default()
+            })),
+        );
+        assert_eq!(genesis_slp.output_tokens[1].amount, SlpAmount::new(1000),);
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![
+                format!("{history}?page=0"),
+                format!("{history}?page=1"),
+                format!("/token/{token_id}"),
+            ],
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_address_utxos() -> Result<()> {
+        let token_id = "22".repeat(32);
+        let utxos = proto::ScriptUtxos {
+            utxos: vec![
+                proto::ScriptUtxo {
+                    outpoint: Some(proto::OutPoint {
+                        txid: vec![5; 32],
+                        out_idx: 3,
+                    }),
+                    block_height: 100,
+                    sats: 546,
+                    token: token(&token_id, 20),
+                    ..Default::default()
+                },
+                proto::ScriptUtxo {
+                    outpoint: Some(proto::OutPoint {
+                        txid: vec![6; 32],
+                        out_idx: 0,
+                    }),
+                    block_height: -1,
+                    sats: 10_000,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let path = format!("/script/p2pkh/{}/utxos", "01".repeat(20));
+        let (client, requests) =
+            serve(HashMap::from([(path.clone(), utxos.encode_to_vec())])).await?;
+        let node = ChronikSlpNode::new(client);
+
+        let script = Script::p2pkh(&ShaRmd160::new([1; 20]));
+        assert_eq!(
+            node.address_utxos(&address()).await?,
+            vec![
+                SlpUtxo {
+                    utxo: Utxo {
+                        outpoint: OutPoint {
+                            txid: Sha256d::new([5; 32]),
+                            out_idx: 3,
+                        },
+                        script: script.clone(),
+                        value: 546,
+                    },
+                    token: SlpToken {
+                        amount: SlpAmount::new(20),
+                        is_mint_baton: false,
+                    },
+                    token_id: Some(TokenId::from_token_id_hex(&token_id)?),
+                },
+                SlpUtxo {
+                    utxo: Utxo {
+                        outpoint: OutPoint {
+                            txid: Sha256d::new([6; 32]),
+                            out_idx: 0,
+                        },
+                        script,
+                        value: 10_000,
+                    },
+                    token: SlpToken::EMPTY,
+                    token_id: None,
+                },
+            ],
+        );
+        assert_eq!(*requests.lock().unwrap(), vec![path]);
+        Ok(())
+    }
+}
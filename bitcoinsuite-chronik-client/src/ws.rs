@@ -0,0 +1,408 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use bitcoinsuite_core::Sha256d;
+use bitcoinsuite_error::{Result, WrapErr};
+use futures::{SinkExt, Stream, StreamExt};
+use prost::Message as _;
+use tokio::{net::TcpStream, sync::mpsc};
+use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+use crate::{proto, ChronikClient, ChronikClientError::*, ScriptType};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Number of messages buffered by a [`WsEndpoint`] before the connection stops reading
+/// from the WebSocket.
+pub const WS_MSG_CAPACITY: usize = 64;
+
+/// Config for a WebSocket connection to Chronik.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WsConfig {
+    /// Whether to automatically reconnect and re-subscribe on disconnect, default true.
+    pub auto_reconnect: bool,
+    /// How long to wait between reconnection attempts.
+    pub reconnect_delay: Duration,
+}
+
+/// What to receive updates for from the WebSocket.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum WsSubscription {
+    /// Blocks being connected, disconnected, finalized or invalidated.
+    Blocks,
+    /// Txs sending to or spending from the script.
+    Script {
+        script_type: ScriptType,
+        payload: Vec<u8>,
+    },
+    /// Txs involving tokens of the token ID.
+    TokenId(Sha256d),
+    /// Txs with the LOKAD ID in the OP_RETURN or an input.
+    LokadId([u8; 4]),
+    /// Status changes of the tx.
+    TxId(Sha256d),
+}
+
+/// Message received from the WebSocket.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WsMessage {
+    /// Error, e.g. when a bad subscription has been sent.
+    Error(proto::Error),
+    /// Block got connected, disconnected, finalized, etc.
+    Block(proto::MsgBlock),
+    /// Tx got added to/removed from the mempool, or confirmed in a block.
+    Tx(proto::MsgTx),
+    /// The connection dropped and has been re-established, and all subscriptions have been
+    /// sent again. Updates that happened while disconnected are lost.
+    Reconnected,
+}
+
+/// WebSocket connection to Chronik, yielding a [`Stream`] of [`WsMessage`]s.
+///
+/// The connection is run on a background task, which is closed when this is dropped.
+/// Once [`WS_MSG_CAPACITY`] messages are buffered, the task waits for them to be consumed.
+#[derive(Debug)]
+pub struct WsEndpoint {
+    cmd_sender: mpsc::UnboundedSender<WsCommand>,
+    msg_receiver: mpsc::Receiver<Result<WsMessage>>,
+}
+
+#[derive(Debug)]
+enum WsCommand {
+    Subscribe(WsSubscription),
+    Unsubscribe(WsSubscription),
+}
+
+struct WsConnection {
+    url: String,
+    config: WsConfig,
+    socket: Socket,
+    subs: Vec<WsSubscription>,
+    cmd_receiver: mpsc::UnboundedReceiver<WsCommand>,
+    msg_sender: mpsc::Sender<Result<WsMessage>>,
+}
+
+impl Default for WsConfig {
+    fn default() -> Self {
+        WsConfig {
+            auto_reconnect: true,
+            reconnect_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+impl ChronikClient {
+    /// Open a WebSocket connection to listen for updates.
+    pub async fn ws(&self) -> Result<WsEndpoint> {
+        self.ws_with_config(WsConfig::default()).await
+    }
+
+    pub async fn ws_with_config(&self, config: WsConfig) -> Result<WsEndpoint> {
+        let socket = connect(self.ws_url()).await?;
+        let (cmd_sender, cmd_receiver) = mpsc::unbounded_channel();
+        let (msg_sender, msg_receiver) = mpsc::channel(WS_MSG_CAPACITY);
+        let connection = WsConnection {
+            url: self.ws_url().to_string(),
+            config,
+            socket,
+            subs: Vec::new(),
+            cmd_receiver,
+            msg_sender,
+        };
+        tokio::spawn(connection.run());
+        Ok(WsEndpoint {
+            cmd_sender,
+            msg_receiver,
+        })
+    }
+}
+
+impl WsEndpoint {
+    /// Subscribe to updates; subscribing twice to the same updates has no effect.
+    pub fn subscribe(&self, sub: WsSubscription) -> Result<()> {
+        self.send_cmd(WsCommand::Subscribe(sub))
+    }
+
+    pub fn unsubscribe(&self, sub: WsSubscription) -> Result<()> {
+        self.send_cmd(WsCommand::Unsubscribe(sub))
+    }
+
+    fn send_cmd(&self, cmd: WsCommand) -> Result<()> {
+        self.cmd_sender.send(cmd).map_err(|_| WsClosed)?;
+        Ok(())
+    }
+}
+
+impl Stream for WsEndpoint {
+    type Item = Result<WsMessage>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.msg_receiver.poll_recv(cx)
+    }
+}
+
+impl WsSubscription {
+    fn to_proto(&self, is_unsub: bool) -> proto::WsSub {
+        use proto::ws_sub::SubType;
+        let sub_type = match self {
+            WsSubscription::Blocks => SubType::Blocks(proto::WsSubBlocks {}),
+            WsSubscription::Script {
+                script_type,
+                payload,
+            } => SubType::Script(proto::WsSubScript {
+                script_type: script_type.to_string(),
+                payload: payload.clone(),
+            }),
+            WsSubscription::TokenId(token_id) => SubType::TokenId(proto::WsSubTokenId {
+                token_id: token_id.to_string(),
+            }),
+            WsSubscription::LokadId(lokad_id) => SubType::LokadId(proto::WsSubLokadId {
+                lokad_id: lokad_id.to_vec(),
+            }),
+            WsSubscription::TxId(txid) => SubType::Txid(proto::WsSubTxId {
+                txid: txid.to_string(),
+            }),
+        };
+        proto::WsSub {
+            is_unsub,
+            sub_type: Some(sub_type),
+        }
+    }
+}
+
+impl WsConnection {
+    async fn run(mut self) {
+        loop {
+            tokio::select! {
+                cmd = self.cmd_receiver.recv() => {
+                    let Some(cmd) = cmd else {
+                        // Endpoint dropped
+                        let _ = self.socket.close(None).await;
+                        return;
+                    };
+                    let (sub, is_unsub) = match cmd {
+                        WsCommand::Subscribe(sub) if !self.subs.contains(&sub) => {
+                            self.subs.push(sub.clone());
+                            (sub, false)
+                        }
+                        WsCommand::Unsubscribe(sub) if self.subs.contains(&sub) => {
+                            self.subs.retain(|other| other != &sub);
+                            (sub, true)
+                        }
+                        _ => continue,
+                    };
+                    let msg = Message::Binary(sub.to_proto(is_unsub).encode_to_vec());
+                    if self.socket.send(msg).await.is_ok() {
+                        continue;
+                    }
+                    // Reconnecting sends the updated subscriptions
+                    if !self.reconnect().await
+                        || self.msg_sender.send(Ok(WsMessage::Reconnected)).await.is_err()
+                    {
+                        return;
+                    }
+                }
+                msg = self.socket.next() => {
+                    let msg = match msg {
+                        Some(Ok(Message::Binary(data))) => decode_msg(&data),
+                        Some(Ok(Message::Text(text))) => Err(UnexpectedWsTextMessage(text).into()),
+                        // Pings are answered by tungstenite
+                        Some(Ok(_)) => continue,
+                        Some(Err(_)) | None => {
+                            if !self.reconnect().await {
+                                return;
+                            }
+                            Ok(WsMessage::Reconnected)
+                        }
+                    };
+                    if self.msg_sender.send(msg).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reconnect and re-send all subscriptions, returns false if the connection should end.
+    async fn reconnect(&mut self) -> bool {
+        if !self.config.auto_reconnect {
+            return false;
+        }
+        loop {
+            if self.msg_sender.is_closed() {
+                return false;
+            }
+            tokio::time::sleep(self.config.reconnect_delay).await;
+            let Ok(mut socket) = connect(&self.url).await else {
+                continue;
+            };
+            let mut resubscribed = true;
+            for sub in &self.subs {
+                let msg = Message::Binary(sub.to_proto(false).encode_to_vec());
+                if socket.send(msg).await.is_err() {
+                    resubscribed = false;
+                    break;
+                }
+            }
+            if resubscribed {
+                self.socket = socket;
+                return true;
+            }
+        }
+    }
+}
+
+async fn connect(url: &str) -> Result<Socket> {
+    let (socket, _) = connect_async(url)
+        .await
+        .wrap_err_with(|| WsConnectError(url.to_string()))?;
+    Ok(socket)
+}
+
+fn decode_msg(data: &[u8]) -> Result<WsMessage> {
+    use proto::ws_msg::MsgType;
+    let msg = proto::WsMsg::decode(data).wrap_err_with(|| InvalidProtobuf(hex::encode(data)))?;
+    match msg.msg_type {
+        Some(MsgType::Error(error)) => Ok(WsMessage::Error(error)),
+        Some(MsgType::Block(block)) => Ok(WsMessage::Block(block)),
+        Some(MsgType::Tx(tx)) => Ok(WsMessage::Tx(tx)),
+        None => Err(InvalidProtobuf(hex::encode(data)).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bitcoinsuite_core::Sha256d;
+    use bitcoinsuite_error::Result;
+    use futures::{SinkExt, StreamExt};
+    use prost::Message as _;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::{accept_async, tungstenite::Message};
+
+    use crate::{
+        proto, ChronikClient, ScriptType, WsConfig, WsMessage, WsSubscription, WS_MSG_CAPACITY,
+    };
+
+    #[test]
+    fn test_subscription_to_proto() {
+        use proto::ws_sub::SubType;
+        let sub = WsSubscription::Script {
+            script_type: ScriptType::P2pkh,
+            payload: vec![1; 20],
+        };
+        assert_eq!(
+            sub.to_proto(true),
+            proto::WsSub {
+                is_unsub: true,
+                sub_type: Some(SubType::Script(proto::WsSubScript {
+                    script_type: "p2pkh".to_string(),
+                    payload: vec![1; 20],
+                })),
+            },
+        );
+        let txid = Sha256d::new([1; 32]);
+        assert_eq!(
+            WsSubscription::TxId(txid).to_proto(false).sub_type,
+            Some(SubType::Txid(proto::WsSubTxId {
+                txid: "01".repeat(32),
+            })),
+        );
+        assert_eq!(
+            WsSubscription::LokadId(*b"SLP2").to_proto(false).sub_type,
+            Some(SubType::LokadId(proto::WsSubLokadId {
+                lokad_id: b"SLP2".to_vec(),
+            })),
+        );
+    }
+
+    async fn recv_sub(
+        socket: &mut tokio_tungstenite::WebSocketStream<tokio::net::TcpStream>,
+    ) -> Result<proto::WsSub> {
+        match socket.next().await {
+            Some(Ok(Message::Binary(data))) => Ok(proto::WsSub::decode(data.as_slice())?),
+            msg => panic!("Unexpected message: {msg:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ws_reconnect() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let client = ChronikClient::new(url)?;
+        let config = WsConfig {
+            auto_reconnect: true,
+            reconnect_delay: Duration::from_millis(10),
+        };
+        let accept = async { accept_async(listener.accept().await?.0).await };
+        let (ws, socket) = tokio::join!(client.ws_with_config(config), accept);
+        let (mut ws, mut socket) = (ws?, socket?);
+
+        ws.subscribe(WsSubscription::Blocks)?;
+        ws.subscribe(WsSubscription::Blocks)?;
+        ws.subscribe(WsSubscription::LokadId(*b"SLP2"))?;
+        let blocks_sub = WsSubscription::Blocks.to_proto(false);
+        let lokad_sub = WsSubscription::LokadId(*b"SLP2").to_proto(false);
+        assert_eq!(recv_sub(&mut socket).await?, blocks_sub);
+        assert_eq!(recv_sub(&mut socket).await?, lokad_sub);
+
+        let msg_block = proto::MsgBlock {
+            block_height: 7,
+            ..Default::default()
+        };
+        let msg = proto::WsMsg {
+            msg_type: Some(proto::ws_msg::MsgType::Block(msg_block.clone())),
+        };
+        socket.send(Message::Binary(msg.encode_to_vec())).await?;
+        assert_eq!(ws.next().await.unwrap()?, WsMessage::Block(msg_block));
+
+        ws.unsubscribe(WsSubscription::Blocks)?;
+        assert_eq!(
+            recv_sub(&mut socket).await?,
+            WsSubscription::Blocks.to_proto(true),
+        );
+
+        // Drop the connection, client reconnects and re-subscribes
+        drop(socket);
+        let mut socket = accept_async(listener.accept().await?.0).await?;
+        assert_eq!(recv_sub(&mut socket).await?, lokad_sub);
+        assert_eq!(ws.next().await.unwrap()?, WsMessage::Reconnected);
+
+        socket.send(Message::Text("hello".to_string())).await?;
+        assert!(ws.next().await.unwrap().is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ws_buffered_messages() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let client = ChronikClient::new(url)?;
+        let accept = async { accept_async(listener.accept().await?.0).await };
+        let (ws, socket) = tokio::join!(client.ws(), accept);
+        let (mut ws, mut socket) = (ws?, socket?);
+
+        // Messages beyond the capacity wait in the socket, none get lost
+        let num_msgs = WS_MSG_CAPACITY as i32 * 2;
+        for block_height in 0..num_msgs {
+            let msg = proto::WsMsg {
+                msg_type: Some(proto::ws_msg::MsgType::Block(proto::MsgBlock {
+                    block_height,
+                    ..Default::default()
+                })),
+            };
+            socket.send(Message::Binary(msg.encode_to_vec())).await?;
+        }
+        for block_height in 0..num_msgs {
+            match ws.next().await.unwrap()? {
+                WsMessage::Block(block) => assert_eq!(block.block_height, block_height),
+                msg => panic!("Unexpected message: {msg:?}"),
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bitcoinsuite_chronik_client::{
+    proto, ChronikClient, ChronikClientError, RetryPolicy, ScriptType,
+};
+use bitcoinsuite_error::Result;
+use futures::TryStreamExt;
+use prost::Message;
+use reqwest::StatusCode;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+type Responses = Arc<Mutex<HashMap<String, Vec<(u16, Vec<u8>)>>>>;
+
+/// Serves the queued responses for each path and counts requests.
+async fn serve_stub(responses: Responses, num_requests: Arc<Mutex<usize>>) -> Result<String> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("http://{}", listener.local_addr()?);
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let n = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            let request = String::from_utf8(request).unwrap();
+            let path = request.split(' ').nth(1).unwrap().to_string();
+            *num_requests.lock().unwrap() += 1;
+            let response = responses
+                .lock()
+                .unwrap()
+                .get_mut(&path)
+                .and_then(|queue| (!queue.is_empty()).then(|| queue.remove(0)));
+            let Some((status, body)) = response else {
+                // Never answer, to provoke a timeout
+                tokio::spawn(async move {
+                    tokio::time::sleep(Duration::from_secs(10)).await;
+                    drop(stream);
+                });
+                continue;
+            };
+            let header = format!(
+                "HTTP/1.1 {status} Stub\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len(),
+            );
+            stream.write_all(header.as_bytes()).await.unwrap();
+            stream.write_all(&body).await.unwrap();
+        }
+    });
+    Ok(url)
+}
+
+fn page(txids: &[u8], num_pages: u32) -> Vec<u8> {
+    proto::TxHistoryPage {
+        txs: txids
+            .iter()
+            .map(|&txid| proto::Tx {
+                txid: vec![txid; 32],
+                ..Default::default()
+            })
+            .collect(),
+        num_pages,
+        num_txs: 3,
+    }
+    .encode_to_vec()
+}
+
+fn fast_retries(max_retries: u32) -> RetryPolicy {
+    RetryPolicy {
+        max_retries,
+        initial_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(10),
+        timeout: Some(Duration::from_millis(200)),
+    }
+}
+
+#[tokio::test]
+async fn test_history_stream_with_retries() -> Result<()> {
+    let history_url = |page: usize| {
+        format!(
+            "/script/p2pkh/{}/history?page={page}&page_size=2",
+            "00".repeat(20)
+        )
+    };
+    let responses = Responses::default();
+    responses.lock().unwrap().extend([
+        (
+            history_url(0),
+            vec![
+                (502, b"<html>Bad Gateway</html>".to_vec()),
+                (200, page(&[1, 2], 2)),
+            ],
+        ),
+        (history_url(1), vec![(200, page(&[3], 2))]),
+    ]);
+    let num_requests = Arc::new(Mutex::new(0));
+    let url = serve_stub(responses, num_requests.clone()).await?;
+    let client = ChronikClient::new(url)?.with_retry_policy(fast_retries(1));
+    let script = client.script(ScriptType::P2pkh, &[0; 20]);
+    let txids = script
+        .history_stream(2)
+        .map_ok(|tx| tx.txid[0])
+        .try_collect::<Vec<_>>()
+        .await?;
+    assert_eq!(txids, vec![1, 2, 3]);
+    assert_eq!(*num_requests.lock().unwrap(), 3);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_block_txs_stream_errors() -> Result<()> {
+    let block_url = |page: usize| format!("/block-txs/5?page={page}&page_size=2");
+    let not_found = proto::Error {
+        msg: "404: Block not found".to_string(),
+    };
+    let responses = Responses::default();
+    responses.lock().unwrap().extend([
+        (block_url(0), vec![(200, page(&[1, 2], 3))]),
+        (
+            block_url(1),
+            vec![(404, not_found.encode_to_vec()), (200, page(&[3], 3))],
+        ),
+    ]);
+    let num_requests = Arc::new(Mutex::new(0));
+    let url = serve_stub(responses, num_requests.clone()).await?;
+    let client = ChronikClient::new(url)?.with_retry_policy(fast_retries(3));
+
+    // First page is yielded before the second one fails; 404s are not retried
+    let mut stream = Box::pin(client.block_txs_by_height_stream(5, 2));
+    assert_eq!(stream.try_next().await?.unwrap().txid, vec![1; 32]);
+    assert_eq!(stream.try_next().await?.unwrap().txid, vec![2; 32]);
+    let err = stream.try_next().await.unwrap_err();
+    assert_eq!(
+        err.downcast::<ChronikClientError>()?,
+        ChronikClientError::ChronikError {
+            status_code: StatusCode::NOT_FOUND,
+            error_msg: not_found.msg.clone(),
+            error: not_found,
+        },
+    );
+    assert_eq!(*num_requests.lock().unwrap(), 2);
+
+    // Timeouts are retried, then give up
+    let err = client.block_txs_by_height(6, 0).await.unwrap_err();
+    assert_eq!(
+        err.downcast::<ChronikClientError>()?,
+        ChronikClientError::HttpRequestError,
+    );
+    assert_eq!(*num_requests.lock().unwrap(), 6);
+    Ok(())
+}
@@ -0,0 +1,545 @@
+use std::{fmt::Display, str::FromStr};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha512;
+use thiserror::Error;
+
+use crate::{
+    ecc::{Ecc, EccError, PubKey, SecKey, PUBKEY_LENGTH},
+    BytesMut, Hashed, Net, P2PKHSignatory, Sha256d, ShaRmd160, SigHashType,
+};
+
+/// Child indices at or above this are hardened.
+pub const HARDENED_BIT: u32 = 1 << 31;
+/// Size of a serialized extended key, without the base58 checksum.
+pub const EXTENDED_KEY_LENGTH: usize = 78;
+
+const XPRV_VERSION: [u8; 4] = [0x04, 0x88, 0xad, 0xe4];
+const XPUB_VERSION: [u8; 4] = [0x04, 0x88, 0xb2, 0x1e];
+const TPRV_VERSION: [u8; 4] = [0x04, 0x35, 0x83, 0x94];
+const TPUB_VERSION: [u8; 4] = [0x04, 0x35, 0x87, 0xcf];
+const CHECKSUM_LENGTH: usize = 4;
+
+/// Fingerprint of a key, the first 4 bytes of its HASH160.
+pub type Fingerprint = [u8; 4];
+
+#[derive(Error, Debug, PartialEq)]
+pub enum Bip32Error {
+    #[error("Seed must be between 16 and 64 bytes, but got {0}")]
+    InvalidSeedLength(usize),
+    #[error("Derived key is invalid")]
+    InvalidDerivedKey,
+    #[error("Cannot derive hardened child {0} from a public key")]
+    HardenedFromPubKey(u32),
+    #[error("Max. derivation depth reached")]
+    MaxDepthReached,
+    #[error("Invalid base58: {0}")]
+    InvalidBase58(String),
+    #[error("Invalid extended key length, expected {expected} but got {actual}")]
+    InvalidLength { expected: usize, actual: usize },
+    #[error("Invalid checksum")]
+    InvalidChecksum,
+    #[error("Unknown extended key version: {0}")]
+    UnknownVersion(String),
+    #[error("Invalid private key prefix: {0:02x}")]
+    InvalidSecKeyPrefix(u8),
+    #[error("Zero depth with non-zero parent fingerprint or child number")]
+    InvalidMasterKey,
+    #[error("Invalid derivation path: {0}")]
+    InvalidPath(String),
+    #[error("Ecc error: {0}")]
+    Ecc(#[from] EccError),
+}
+
+use self::Bip32Error::*;
+
+/// Path of child indices, e.g. `m/44'/899'/0'/0/1`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct DerivationPath(Vec<u32>);
+
+#[derive(Debug, Clone)]
+pub struct ExtendedSecKey {
+    net: Net,
+    depth: u8,
+    parent_fingerprint: Fingerprint,
+    child_number: u32,
+    chain_code: [u8; 32],
+    seckey: SecKey,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ExtendedPubKey {
+    net: Net,
+    depth: u8,
+    parent_fingerprint: Fingerprint,
+    child_number: u32,
+    chain_code: [u8; 32],
+    pubkey: PubKey,
+}
+
+impl DerivationPath {
+    pub fn new(indices: Vec<u32>) -> Self {
+        DerivationPath(indices)
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.0
+    }
+
+    /// Path extended by the given child index.
+    pub fn child(&self, index: u32) -> Self {
+        let mut indices = self.0.clone();
+        indices.push(index);
+        DerivationPath(indices)
+    }
+}
+
+impl FromStr for DerivationPath {
+    type Err = Bip32Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split('/');
+        if parts.next() != Some("m") {
+            return Err(InvalidPath(s.to_string()));
+        }
+        let indices = parts
+            .map(|part| {
+                let (digits, hardened_bit) = match part.strip_suffix(['\'', 'h']) {
+                    Some(digits) => (digits, HARDENED_BIT),
+                    None => (part, 0),
+                };
+                if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(InvalidPath(s.to_string()));
+                }
+                match digits.parse::<u32>() {
+                    Ok(index) if index < HARDENED_BIT => Ok(index | hardened_bit),
+                    _ => Err(InvalidPath(s.to_string())),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(DerivationPath(indices))
+    }
+}
+
+impl Display for DerivationPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "m")?;
+        for &index in &self.0 {
+            match index & HARDENED_BIT {
+                0 => write!(f, "/{index}")?,
+                _ => write!(f, "/{}'", index & !HARDENED_BIT)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ExtendedSecKey {
+    /// Master key from a seed of 16 to 64 bytes, e.g. from a BIP39 mnemonic.
+    pub fn from_seed(ecc: &dyn Ecc, net: Net, seed: &[u8]) -> Result<Self, Bip32Error> {
+        if !(16..=64).contains(&seed.len()) {
+            return Err(InvalidSeedLength(seed.len()));
+        }
+        let (key, chain_code) = hmac_sha512(b"Bitcoin seed", &[seed]);
+        let seckey = ecc.seckey_from_array(key).map_err(|_| InvalidDerivedKey)?;
+        Ok(ExtendedSecKey {
+            net,
+            depth: 0,
+            parent_fingerprint: [0; 4],
+            child_number: 0,
+            chain_code,
+            seckey,
+        })
+    }
+
+    pub fn net(&self) -> Net {
+        self.net
+    }
+
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    pub fn parent_fingerprint(&self) -> Fingerprint {
+        self.parent_fingerprint
+    }
+
+    pub fn child_number(&self) -> u32 {
+        self.child_number
+    }
+
+    pub fn chain_code(&self) -> &[u8; 32] {
+        &self.chain_code
+    }
+
+    pub fn seckey(&self) -> &SecKey {
+        &self.seckey
+    }
+
+    pub fn pubkey(&self, ecc: &dyn Ecc) -> PubKey {
+        ecc.derive_pubkey(&self.seckey)
+    }
+
+    pub fn fingerprint(&self, ecc: &dyn Ecc) -> Fingerprint {
+        fingerprint(&self.pubkey(ecc))
+    }
+
+    /// Signatory for spending P2PKH outputs of this key, e.g. of a key derived from a mnemonic.
+    pub fn to_p2pkh_signatory(&self, ecc: &dyn Ecc, sig_hash_type: SigHashType) -> P2PKHSignatory {
+        P2PKHSignatory {
+            seckey: self.seckey.clone(),
+            pubkey: self.pubkey(ecc),
+            sig_hash_type,
+        }
+    }
+
+    pub fn to_extended_pubkey(&self, ecc: &dyn Ecc) -> ExtendedPubKey {
+        ExtendedPubKey {
+            net: self.net,
+            depth: self.depth,
+            parent_fingerprint: self.parent_fingerprint,
+            child_number: self.child_number,
+            chain_code: self.chain_code,
+            pubkey: self.pubkey(ecc),
+        }
+    }
+
+    /// Derive the child at `index`, which is hardened if `index >= HARDENED_BIT`.
+    pub fn derive_child(&self, ecc: &dyn Ecc, index: u32) -> Result<Self, Bip32Error> {
+        let depth = self.depth.checked_add(1).ok_or(MaxDepthReached)?;
+        let pubkey = self.pubkey(ecc);
+        let (tweak, chain_code) = if index & HARDENED_BIT != 0 {
+            let data: &[&[u8]] = &[&[0], self.seckey.as_slice(), &index.to_be_bytes()];
+            hmac_sha512(&self.chain_code, data)
+        } else {
+            let data: &[&[u8]] = &[pubkey.as_slice(), &index.to_be_bytes()];
+            hmac_sha512(&self.chain_code, data)
+        };
+        let seckey = ecc
+            .seckey_tweak_add(&self.seckey, tweak)
+            .map_err(|_| InvalidDerivedKey)?;
+        Ok(ExtendedSecKey {
+            net: self.net,
+            depth,
+            parent_fingerprint: fingerprint(&pubkey),
+            child_number: index,
+            chain_code,
+            seckey,
+        })
+    }
+
+    pub fn derive_path(&self, ecc: &dyn Ecc, path: &DerivationPath) -> Result<Self, Bip32Error> {
+        let mut key = self.clone();
+        for &index in path.indices() {
+            key = key.derive_child(ecc, index)?;
+        }
+        Ok(key)
+    }
+
+    /// Serialize as base58 encoded xprv (mainnet) or tprv (other nets).
+    pub fn to_base58(&self) -> String {
+        let version = match self.net {
+            Net::Mainnet => XPRV_VERSION,
+            Net::Regtest => TPRV_VERSION,
+        };
+        let mut key_data = [0; PUBKEY_LENGTH];
+        key_data[1..].copy_from_slice(self.seckey.as_slice());
+        ser_extended_key(
+            version,
+            self.depth,
+            self.parent_fingerprint,
+            self.child_number,
+            &self.chain_code,
+            &key_data,
+        )
+    }
+
+    pub fn from_base58(ecc: &dyn Ecc, s: &str) -> Result<Self, Bip32Error> {
+        let data = deser_extended_key(s)?;
+        let net = match data.version {
+            XPRV_VERSION => Net::Mainnet,
+            TPRV_VERSION => Net::Regtest,
+            _ => return Err(UnknownVersion(hex::encode(data.version))),
+        };
+        if data.key_data[0] != 0 {
+            return Err(InvalidSecKeyPrefix(data.key_data[0]));
+        }
+        let seckey = ecc.seckey_from_array(data.key_data[1..].try_into().unwrap())?;
+        Ok(ExtendedSecKey {
+            net,
+            depth: data.depth,
+            parent_fingerprint: data.parent_fingerprint,
+            child_number: data.child_number,
+            chain_code: data.chain_code,
+            seckey,
+        })
+    }
+}
+
+impl ExtendedPubKey {
+    pub fn net(&self) -> Net {
+        self.net
+    }
+
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    pub fn parent_fingerprint(&self) -> Fingerprint {
+        self.parent_fingerprint
+    }
+
+    pub fn child_number(&self) -> u32 {
+        self.child_number
+    }
+
+    pub fn chain_code(&self) -> &[u8; 32] {
+        &self.chain_code
+    }
+
+    pub fn pubkey(&self) -> &PubKey {
+        &self.pubkey
+    }
+
+    pub fn fingerprint(&self) -> Fingerprint {
+        fingerprint(&self.pubkey)
+    }
+
+    /// Derive the non-hardened child at `index`.
+    pub fn derive_child(&self, ecc: &dyn Ecc, index: u32) -> Result<Self, Bip32Error> {
+        if index & HARDENED_BIT != 0 {
+            return Err(HardenedFromPubKey(index));
+        }
+        let depth = self.depth.checked_add(1).ok_or(MaxDepthReached)?;
+        let data: &[&[u8]] = &[self.pubkey.as_slice(), &index.to_be_bytes()];
+        let (tweak, chain_code) = hmac_sha512(&self.chain_code, data);
+        let pubkey = ecc
+            .pubkey_tweak_add(&self.pubkey, tweak)
+            .map_err(|_| InvalidDerivedKey)?;
+        Ok(ExtendedPubKey {
+            net: self.net,
+            depth,
+            parent_fingerprint: self.fingerprint(),
+            child_number: index,
+            chain_code,
+            pubkey,
+        })
+    }
+
+    pub fn derive_path(&self, ecc: &dyn Ecc, path: &DerivationPath) -> Result<Self, Bip32Error> {
+        let mut key = self.clone();
+        for &index in path.indices() {
+            key = key.derive_child(ecc, index)?;
+        }
+        Ok(key)
+    }
+
+    /// Serialize as base58 encoded xpub (mainnet) or tpub (other nets).
+    pub fn to_base58(&self) -> String {
+        let version = match self.net {
+            Net::Mainnet => XPUB_VERSION,
+            Net::Regtest => TPUB_VERSION,
+        };
+        ser_extended_key(
+            version,
+            self.depth,
+            self.parent_fingerprint,
+            self.child_number,
+            &self.chain_code,
+            self.pubkey.as_slice(),
+        )
+    }
+
+    pub fn from_base58(ecc: &dyn Ecc, s: &str) -> Result<Self, Bip32Error> {
+        let data = deser_extended_key(s)?;
+        let net = match data.version {
+            XPUB_VERSION => Net::Mainnet,
+            TPUB_VERSION => Net::Regtest,
+            _ => return Err(UnknownVersion(hex::encode(data.version))),
+        };
+        let pubkey = ecc.pubkey_from_array(data.key_data)?;
+        Ok(ExtendedPubKey {
+            net,
+            depth: data.depth,
+            parent_fingerprint: data.parent_fingerprint,
+            child_number: data.child_number,
+            chain_code: data.chain_code,
+            pubkey,
+        })
+    }
+}
+
+impl Display for ExtendedPubKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_base58())
+    }
+}
+
+fn fingerprint(pubkey: &PubKey) -> Fingerprint {
+    let hash = ShaRmd160::digest(pubkey.as_slice().into());
+    hash.as_slice()[..4].try_into().unwrap()
+}
+
+/// HMAC-SHA512 of the concatenated `data`, split into two halves.
+fn hmac_sha512(key: &[u8], data: &[&[u8]]) -> ([u8; 32], [u8; 32]) {
+    let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC accepts any key length");
+    for part in data {
+        mac.update(part);
+    }
+    let result = mac.finalize().into_bytes();
+    (
+        result[..32].try_into().unwrap(),
+        result[32..].try_into().unwrap(),
+    )
+}
+
+struct ExtendedKeyData {
+    version: [u8; 4],
+    depth: u8,
+    parent_fingerprint: Fingerprint,
+    child_number: u32,
+    chain_code: [u8; 32],
+    key_data: [u8; PUBKEY_LENGTH],
+}
+
+fn ser_extended_key(
+    version: [u8; 4],
+    depth: u8,
+    parent_fingerprint: Fingerprint,
+    child_number: u32,
+    chain_code: &[u8; 32],
+    key_data: &[u8],
+) -> String {
+    let mut data = BytesMut::new();
+    data.put_slice(&version);
+    data.put_slice(&[depth]);
+    data.put_slice(&parent_fingerprint);
+    data.put_slice(&child_number.to_be_bytes());
+    data.put_slice(chain_code);
+    data.put_slice(key_data);
+    let checksum = Sha256d::digest(data.clone().freeze());
+    data.put_slice(&checksum.as_slice()[..CHECKSUM_LENGTH]);
+    bs58::encode(data.as_slice()).into_string()
+}
+
+fn deser_extended_key(s: &str) -> Result<ExtendedKeyData, Bip32Error> {
+    let data = bs58::decode(s)
+        .into_vec()
+        .map_err(|err| InvalidBase58(err.to_string()))?;
+    if data.len() != EXTENDED_KEY_LENGTH + CHECKSUM_LENGTH {
+        return Err(InvalidLength {
+            expected: EXTENDED_KEY_LENGTH + CHECKSUM_LENGTH,
+            actual: data.len(),
+        });
+    }
+    let (data, checksum) = data.split_at(EXTENDED_KEY_LENGTH);
+    let expected_checksum = Sha256d::digest(data.into());
+    if checksum != &expected_checksum.as_slice()[..CHECKSUM_LENGTH] {
+        return Err(InvalidChecksum);
+    }
+    let key_data = ExtendedKeyData {
+        version: data[0..4].try_into().unwrap(),
+        depth: data[4],
+        parent_fingerprint: data[5..9].try_into().unwrap(),
+        child_number: u32::from_be_bytes(data[9..13].try_into().unwrap()),
+        chain_code: data[13..45].try_into().unwrap(),
+        key_data: data[45..78].try_into().unwrap(),
+    };
+    if key_data.depth == 0 && (key_data.parent_fingerprint != [0; 4] || key_data.child_number != 0)
+    {
+        return Err(InvalidMasterKey);
+    }
+    Ok(key_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ecc::{DummyEcc, PubKey},
+        Bip32Error, DerivationPath, ExtendedPubKey, ExtendedSecKey, Net, HARDENED_BIT,
+    };
+
+    #[test]
+    fn test_derivation_path() -> Result<(), Bip32Error> {
+        let path = "m/44'/899'/0'/0/1".parse::<DerivationPath>()?;
+        assert_eq!(
+            path.indices(),
+            &[44 | HARDENED_BIT, 899 | HARDENED_BIT, HARDENED_BIT, 0, 1],
+        );
+        assert_eq!(path.to_string(), "m/44'/899'/0'/0/1");
+        assert_eq!("m/1h/2".parse::<DerivationPath>()?.to_string(), "m/1'/2");
+        assert_eq!("m".parse::<DerivationPath>()?, DerivationPath::default());
+        assert_eq!(
+            DerivationPath::default().child(7).child(HARDENED_BIT | 1),
+            "m/7/1'".parse()?,
+        );
+        for invalid in [
+            "",
+            "44'/0",
+            "m/",
+            "m/x",
+            "m/2147483648",
+            "m/-1",
+            "m/+1",
+            "m/1''",
+        ] {
+            assert_eq!(
+                invalid.parse::<DerivationPath>(),
+                Err(Bip32Error::InvalidPath(invalid.to_string())),
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_seed_length() {
+        assert_eq!(
+            ExtendedSecKey::from_seed(&DummyEcc, Net::Mainnet, &[0; 15]).unwrap_err(),
+            Bip32Error::InvalidSeedLength(15),
+        );
+        assert_eq!(
+            ExtendedSecKey::from_seed(&DummyEcc, Net::Mainnet, &[0; 65]).unwrap_err(),
+            Bip32Error::InvalidSeedLength(65),
+        );
+    }
+
+    #[test]
+    fn test_roundtrip_base58() -> Result<(), Bip32Error> {
+        let master = ExtendedSecKey::from_seed(&DummyEcc, Net::Mainnet, &[1; 32])?;
+        let child = master.derive_child(&DummyEcc, HARDENED_BIT | 3)?;
+        let xprv = child.to_base58();
+        assert!(xprv.starts_with("xprv"));
+        let parsed = ExtendedSecKey::from_base58(&DummyEcc, &xprv)?;
+        assert_eq!(parsed.to_base58(), xprv);
+        assert_eq!(parsed.depth(), 1);
+        assert_eq!(parsed.child_number(), HARDENED_BIT | 3);
+
+        let xpub = ExtendedPubKey {
+            net: Net::Regtest,
+            depth: 2,
+            parent_fingerprint: [1, 2, 3, 4],
+            child_number: 5,
+            chain_code: [6; 32],
+            pubkey: PubKey::new_unchecked([0; 33]),
+        };
+        let tpub = xpub.to_base58();
+        assert!(tpub.starts_with("tpub"));
+        assert_eq!(ExtendedPubKey::from_base58(&DummyEcc, &tpub)?, xpub);
+        assert_eq!(
+            ExtendedSecKey::from_base58(&DummyEcc, &tpub).unwrap_err(),
+            Bip32Error::UnknownVersion("043587cf".to_string()),
+        );
+        let mut invalid_checksum = tpub.clone();
+        invalid_checksum.pop();
+        invalid_checksum.push('1');
+        assert_eq!(
+            ExtendedPubKey::from_base58(&DummyEcc, &invalid_checksum).unwrap_err(),
+            Bip32Error::InvalidChecksum,
+        );
+        assert_eq!(
+            xpub.derive_child(&DummyEcc, HARDENED_BIT).unwrap_err(),
+            Bip32Error::HardenedFromPubKey(HARDENED_BIT),
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,234 @@
+use std::{fmt::Display, str::FromStr};
+
+use once_cell::sync::Lazy;
+use sha2::Sha512;
+use thiserror::Error;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::{ecc::Ecc, Bip32Error, ExtendedSecKey, Hashed, Net, Sha256};
+
+/// Number of PBKDF2 rounds to derive the seed from a mnemonic.
+pub const MNEMONIC_PBKDF2_ROUNDS: u32 = 2048;
+/// Size of the seed derived from a mnemonic.
+pub const MNEMONIC_SEED_LENGTH: usize = 64;
+
+const BITS_PER_WORD: usize = 11;
+
+/// BIP39 English wordlist, sorted.
+static WORDLIST: Lazy<Vec<&'static str>> =
+    Lazy::new(|| include_str!("english.txt").lines().collect());
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum Bip39Error {
+    #[error("Entropy must be 16, 20, 24, 28 or 32 bytes, but got {0}")]
+    InvalidEntropyLength(usize),
+    #[error("Mnemonic must have 12, 15, 18, 21 or 24 words, but got {0}")]
+    InvalidWordCount(usize),
+    #[error("Unknown word in mnemonic: {0:?}")]
+    UnknownWord(String),
+    #[error("Invalid mnemonic checksum")]
+    InvalidChecksum,
+}
+
+use self::Bip39Error::*;
+
+/// BIP39 mnemonic phrase using the English wordlist.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Mnemonic {
+    word_indices: Vec<u16>,
+}
+
+impl Mnemonic {
+    /// Mnemonic encoding the given entropy, 16 bytes for 12 words up to 32 bytes for 24 words.
+    pub fn from_entropy(entropy: &[u8]) -> Result<Self, Bip39Error> {
+        if !matches!(entropy.len(), 16 | 20 | 24 | 28 | 32) {
+            return Err(InvalidEntropyLength(entropy.len()));
+        }
+        let checksum = Sha256::digest(entropy.into());
+        let num_words = entropy.len() * 8 * 3 / 32;
+        let data = [entropy, &checksum.as_slice()[..1]].concat();
+        let word_indices = (0..num_words)
+            .map(|word_idx| {
+                (0..BITS_PER_WORD).fold(0, |index, bit_idx| {
+                    let bit_pos = word_idx * BITS_PER_WORD + bit_idx;
+                    let bit = (data[bit_pos / 8] >> (7 - bit_pos % 8)) & 1;
+                    (index << 1) | bit as u16
+                })
+            })
+            .collect();
+        Ok(Mnemonic { word_indices })
+    }
+
+    /// Parse and validate the checksum of a space separated mnemonic phrase.
+    pub fn from_phrase(phrase: &str) -> Result<Self, Bip39Error> {
+        let phrase = phrase.nfkd().collect::<String>().to_lowercase();
+        let words = phrase.split_whitespace().collect::<Vec<_>>();
+        if !matches!(words.len(), 12 | 15 | 18 | 21 | 24) {
+            return Err(InvalidWordCount(words.len()));
+        }
+        let word_indices = words
+            .iter()
+            .map(|word| match WORDLIST.binary_search(word) {
+                Ok(index) => Ok(index as u16),
+                Err(_) => Err(UnknownWord(word.to_string())),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mnemonic = Mnemonic { word_indices };
+        if Mnemonic::from_entropy(&mnemonic.entropy())? != mnemonic {
+            return Err(InvalidChecksum);
+        }
+        Ok(mnemonic)
+    }
+
+    /// Entropy encoded in this mnemonic, excluding the checksum.
+    pub fn entropy(&self) -> Vec<u8> {
+        let num_bytes = self.word_indices.len() * BITS_PER_WORD * 32 / 33 / 8;
+        let mut entropy = vec![0; num_bytes];
+        for (word_idx, &index) in self.word_indices.iter().enumerate() {
+            for bit_idx in 0..BITS_PER_WORD {
+                let bit_pos = word_idx * BITS_PER_WORD + bit_idx;
+                if bit_pos >= num_bytes * 8 {
+                    break;
+                }
+                let bit = (index >> (BITS_PER_WORD - 1 - bit_idx)) & 1;
+                entropy[bit_pos / 8] |= (bit as u8) << (7 - bit_pos % 8);
+            }
+        }
+        entropy
+    }
+
+    pub fn word_count(&self) -> usize {
+        self.word_indices.len()
+    }
+
+    pub fn words(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.word_indices
+            .iter()
+            .map(|&index| WORDLIST[index as usize])
+    }
+
+    /// Space separated words of the mnemonic.
+    pub fn phrase(&self) -> String {
+        self.words().collect::<Vec<_>>().join(" ")
+    }
+
+    /// Derive the 64 byte seed using PBKDF2-HMAC-SHA512, salted with the passphrase.
+    pub fn to_seed(&self, passphrase: &str) -> [u8; MNEMONIC_SEED_LENGTH] {
+        let salt = format!("mnemonic{}", passphrase.nfkd());
+        let mut seed = [0; MNEMONIC_SEED_LENGTH];
+        pbkdf2::pbkdf2_hmac::<Sha512>(
+            self.phrase().as_bytes(),
+            salt.as_bytes(),
+            MNEMONIC_PBKDF2_ROUNDS,
+            &mut seed,
+        );
+        seed
+    }
+
+    /// BIP32 master key of the seed of this mnemonic.
+    pub fn to_master_key(
+        &self,
+        ecc: &dyn Ecc,
+        net: Net,
+        passphrase: &str,
+    ) -> Result<ExtendedSecKey, Bip32Error> {
+        ExtendedSecKey::from_seed(ecc, net, &self.to_seed(passphrase))
+    }
+}
+
+impl FromStr for Mnemonic {
+    type Err = Bip39Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Mnemonic::from_phrase(s)
+    }
+}
+
+impl Display for Mnemonic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.phrase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+
+    use crate::{bip39::WORDLIST, Bip39Error, Mnemonic};
+
+    // Test vectors from BIP39, all with passphrase "TREZOR"
+    const VECTORS: &[(&[u8], &str, [u8; 64])] = &[
+        (
+            &[0; 16],
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+             abandon about",
+            hex!(
+                "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+            ),
+        ),
+        (
+            &hex!("7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f"),
+            "legal winner thank year wave sausage worth useful legal winner thank yellow",
+            hex!(
+                "2e8905819b8723fe2c1d161860e5ee1830318dbf49a83bd451cfb8440c28bd6fa457fe1296106559a3c80937a1c1069be3a3a5bd381ee6260e8d9739fce1f607"
+            ),
+        ),
+        (
+            &hex!("ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff"),
+            "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo \
+             zoo zoo vote",
+            hex!(
+                "dd48c104698c30cfe2b6142103248622fb7bb0ff692eebb00089b32d22484e1613912f0a5b694407be899ffd31ed3992c456cdf60f5d4564b8ba3f05a69890ad"
+            ),
+        ),
+        (
+            &hex!("9e885d952ad362caeb4efe34a8e91bd2"),
+            "ozone drill grab fiber curtain grace pudding thank cruise elder eight picnic",
+            hex!(
+                "274ddc525802f7c828d8ef7ddbcdc5304e87ac3535913611fbbfa986d0c9e5476c91689f9c8a54fd55bd38606aa6a8595ad213d4c9c9f9aca3fb217069a41028"
+            ),
+        ),
+    ];
+
+    #[test]
+    fn test_wordlist() {
+        assert_eq!(WORDLIST.len(), 2048);
+        assert!(WORDLIST.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn test_mnemonic_vectors() -> Result<(), Bip39Error> {
+        for (entropy, phrase, seed) in VECTORS {
+            let mnemonic = Mnemonic::from_entropy(entropy)?;
+            assert_eq!(mnemonic.phrase(), *phrase);
+            assert_eq!(mnemonic.entropy(), *entropy);
+            assert_eq!(phrase.parse::<Mnemonic>()?, mnemonic);
+            assert_eq!(mnemonic.to_seed("TREZOR"), *seed);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_mnemonic_invalid() {
+        assert_eq!(
+            Mnemonic::from_entropy(&[0; 15]),
+            Err(Bip39Error::InvalidEntropyLength(15)),
+        );
+        assert_eq!(
+            Mnemonic::from_entropy(&[0; 36]),
+            Err(Bip39Error::InvalidEntropyLength(36)),
+        );
+        assert_eq!(
+            Mnemonic::from_phrase("abandon abandon abandon"),
+            Err(Bip39Error::InvalidWordCount(3)),
+        );
+        assert_eq!(
+            Mnemonic::from_phrase(&["abandon"; 12].join(" ")),
+            Err(Bip39Error::InvalidChecksum),
+        );
+        assert_eq!(
+            Mnemonic::from_phrase(&format!("{} xec", ["abandon"; 11].join(" "))),
+            Err(Bip39Error::UnknownWord("xec".to_string())),
+        );
+    }
+}
@@ -45,7 +45,8 @@ impl<T: BitcoinCode> BitcoinCode for Vec<T> {
 
     fn deser(data: &mut Bytes) -> Result<Self> {
         let size = read_compact_size(data)? as usize;
-        let mut vec = Vec::with_capacity(size);
+        // Items take at least one byte each, so this caps allocations for untrusted sizes
+        let mut vec = Vec::with_capacity(size.min(data.len()));
         for _ in 0..size {
             let item = T::deser(data)?;
             vec.push(item);
@@ -64,6 +65,23 @@ impl BitcoinCode for bool {
     }
 }
 
+/// Serialized as a `bool` flag, followed by the value if present.
+impl<T: BitcoinCode> BitcoinCode for Option<T> {
+    fn ser_to(&self, bytes: &mut BytesMut) {
+        self.is_some().ser_to(bytes);
+        if let Some(value) = self {
+            value.ser_to(bytes);
+        }
+    }
+
+    fn deser(data: &mut Bytes) -> Result<Self> {
+        match bool::deser(data)? {
+            true => Ok(Some(T::deser(data)?)),
+            false => Ok(None),
+        }
+    }
+}
+
 macro_rules! integer_impls {
     ($($T:ident $SIZE:literal,)+) => {
         $(
@@ -148,6 +166,9 @@ mod tests {
             Bytes::from_slice(&[1, 2, 3]),
         ];
         verify_ser(vec_bytes, &[3, 0, 1, 1, 3, 1, 2, 3]);
+        // Huge size with missing items must error instead of preallocating
+        let mut data = Bytes::from_slice(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x0f]);
+        assert!(Vec::<ByteArray<36>>::deser(&mut data).is_err());
     }
 
     #[test]
@@ -160,6 +181,13 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_ser_option() {
+        verify_ser(None::<u8>, &[0]);
+        verify_ser(Some(7u8), &[1, 7]);
+        verify_ser(Some(Bytes::from_slice(&[1, 2])), &[1, 2, 1, 2]);
+    }
+
     #[test]
     fn test_ser_integers() {
         verify_ser(128u8, &[128]);
@@ -1,6 +1,6 @@
 use crate::{
-    get_merkle_root, lotus_txid, BitcoinCode, ByteArray, Bytes, BytesMut, Hashed, MerkleMode,
-    Result, Sha256, Sha256d, Tx,
+    get_merkle_root, lotus_txid, BitcoinCode, ByteArray, Bytes, BytesMut, Hashed, MerkleError,
+    MerkleMode, PartialMerkleTree, Result, Sha256, Sha256d, Tx,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, Hash)]
@@ -19,6 +19,13 @@ pub struct BitcoinBlock {
     pub txs: Vec<Tx>,
 }
 
+/// BIP37 `merkleblock` message, proving txs are in the block of the header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitcoinMerkleBlock {
+    pub header: BitcoinHeader,
+    pub tree: PartialMerkleTree<Sha256d>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Default, Hash)]
 pub struct LotusHeader {
     pub prev_block: Sha256d,
@@ -83,6 +90,20 @@ impl BitcoinCode for BitcoinBlock {
     }
 }
 
+impl BitcoinCode for BitcoinMerkleBlock {
+    fn ser_to(&self, bytes: &mut BytesMut) {
+        self.header.ser_to(bytes);
+        self.tree.ser_to(bytes);
+    }
+
+    fn deser(data: &mut Bytes) -> Result<Self> {
+        Ok(BitcoinMerkleBlock {
+            header: BitcoinCode::deser(data)?,
+            tree: BitcoinCode::deser(data)?,
+        })
+    }
+}
+
 impl BitcoinCode for LotusHeader {
     fn ser_to(&self, bytes: &mut BytesMut) {
         self.prev_block.ser_to(bytes);
@@ -205,16 +226,39 @@ impl LotusHeader {
 }
 
 impl BitcoinBlock {
+    pub fn merkle_leaves(&self) -> Vec<Sha256d> {
+        self.txs.iter().map(|tx| tx.hash().clone()).collect()
+    }
+
     pub fn update_merkle_root(&mut self) {
-        let leaves = self.txs.iter().map(|tx| tx.hash().clone()).collect();
-        self.header.merkle_root = get_merkle_root(leaves, MerkleMode::Bitcoin);
+        self.header.merkle_root = get_merkle_root(self.merkle_leaves(), MerkleMode::Bitcoin);
+    }
+
+    /// `merkleblock` proving the txs flagged in `matches`, one flag per tx.
+    pub fn merkle_block(
+        &self,
+        matches: &[bool],
+    ) -> std::result::Result<BitcoinMerkleBlock, MerkleError> {
+        Ok(BitcoinMerkleBlock {
+            header: self.header.clone(),
+            tree: PartialMerkleTree::build(&self.merkle_leaves(), matches, MerkleMode::Bitcoin)?,
+        })
+    }
+}
+
+impl BitcoinMerkleBlock {
+    /// Verify the proof against the merkle root of the header and return the
+    /// matched txids with their index in the block.
+    pub fn matched_txids(&self) -> std::result::Result<Vec<(usize, Sha256d)>, MerkleError> {
+        self.tree
+            .verify(&self.header.merkle_root, MerkleMode::Bitcoin)
     }
 }
 
 impl LotusBlock {
-    pub fn update_merkle_root(&mut self) {
-        let leaves = self
-            .txs
+    /// Leaves of the merkle tree, which commit to both the hash and the txid of each tx.
+    pub fn merkle_leaves(&self) -> Vec<Sha256d> {
+        self.txs
             .iter()
             .map(|tx| {
                 let mut leaf_bytes = BytesMut::new();
@@ -222,8 +266,19 @@ impl LotusBlock {
                 leaf_bytes.put_byte_array(lotus_txid(tx.unhashed_tx()).byte_array().clone());
                 Sha256d::digest(leaf_bytes.freeze())
             })
-            .collect();
-        self.header.merkle_root = get_merkle_root(leaves, MerkleMode::Lotus);
+            .collect()
+    }
+
+    pub fn update_merkle_root(&mut self) {
+        self.header.merkle_root = get_merkle_root(self.merkle_leaves(), MerkleMode::Lotus);
+    }
+
+    /// Proof of the txs flagged in `matches` against the merkle root of the header.
+    pub fn partial_merkle_tree(
+        &self,
+        matches: &[bool],
+    ) -> std::result::Result<PartialMerkleTree<Sha256d>, MerkleError> {
+        PartialMerkleTree::build(&self.merkle_leaves(), matches, MerkleMode::Lotus)
     }
 
     pub fn update_extended_metadata_hash(&mut self) {
@@ -249,8 +304,9 @@ impl LotusBlock {
 #[cfg(test)]
 mod tests {
     use crate::{
-        lotus_txid, BitcoinBlock, BitcoinHeader, Hashed, LotusBlock, LotusHeader, OutPoint, Script,
-        SequenceNo, Sha256d, TxInput, TxOutput, UnhashedTx,
+        lotus_txid, BitcoinBlock, BitcoinCode, BitcoinHeader, BitcoinMerkleBlock, Hashed,
+        LotusBlock, LotusHeader, MerkleError, MerkleMode, OutPoint, Script, SequenceNo, Sha256d,
+        TxInput, TxOutput, UnhashedTx,
     };
 
     #[allow(clippy::inconsistent_digit_grouping)]
@@ -376,4 +432,45 @@ mod tests {
             Sha256d::from_hex_be(genesis_hash_hex).unwrap()
         );
     }
+
+    #[test]
+    fn test_merkle_block() -> Result<(), Box<dyn std::error::Error>> {
+        let txs = (0..3)
+            .map(|lock_time| {
+                UnhashedTx {
+                    lock_time,
+                    ..Default::default()
+                }
+                .hashed()
+            })
+            .collect::<Vec<_>>();
+        let mut block = BitcoinBlock {
+            header: BitcoinHeader::default(),
+            txs: txs.clone(),
+        };
+        block.update_merkle_root();
+        let merkle_block = block.merkle_block(&[false, true, true])?;
+        let merkle_block = BitcoinMerkleBlock::deser(&mut merkle_block.ser())?;
+        assert_eq!(
+            merkle_block.matched_txids()?,
+            vec![(1, txs[1].hash().clone()), (2, txs[2].hash().clone())],
+        );
+
+        let mut block = LotusBlock {
+            txs,
+            ..Default::default()
+        };
+        block.update_merkle_root();
+        let tree = block.partial_merkle_tree(&[true, false, false])?;
+        let matches = tree.verify(&block.header.merkle_root, MerkleMode::Lotus)?;
+        assert_eq!(matches, vec![(0, block.merkle_leaves()[0].clone())]);
+        assert_eq!(
+            block.partial_merkle_tree(&[true]),
+            Err(MerkleError::MatchesLengthMismatch {
+                num_matches: 1,
+                num_leaves: 3,
+            }),
+        );
+        Ok(())
+    }
 }
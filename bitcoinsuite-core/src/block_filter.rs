@@ -0,0 +1,427 @@
+use std::{collections::BTreeSet, hash::Hasher};
+
+use siphasher::sip::SipHasher24;
+use thiserror::Error;
+
+use crate::{
+    encoding::{read_compact_size, write_compact_size},
+    BitcoinBlock, Bytes, BytesMut, Coin, Hashed, LotusBlock, Result, Script, Sha256d, Tx,
+};
+
+/// Golomb-Rice parameter of BIP158 basic filters.
+pub const BASIC_FILTER_P: u8 = 19;
+
+/// Inverse false-positive rate of BIP158 basic filters.
+pub const BASIC_FILTER_M: u64 = 784931;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum BlockFilterError {
+    #[error("Filter ended after {decoded} of {num_elements} elements")]
+    FilterTooShort { num_elements: u64, decoded: u64 },
+    #[error("Filter has too many elements: {0}")]
+    TooManyElements(u64),
+}
+
+/// BIP158 basic block filter, a Golomb-coded set of the output scripts and spent scripts
+/// of a block.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BlockFilter {
+    block_hash: Sha256d,
+    num_elements: u64,
+    filter: Bytes,
+}
+
+impl BlockFilter {
+    /// Parse a serialized filter (e.g. from a `cfilter` message) of the block.
+    pub fn new(block_hash: Sha256d, filter: Bytes) -> Result<Self> {
+        let num_elements = read_compact_size(&mut filter.clone())?;
+        let filter = BlockFilter {
+            block_hash,
+            num_elements,
+            filter,
+        };
+        filter.range()?;
+        Ok(filter)
+    }
+
+    /// Build the filter of the given elements; duplicates are only added once.
+    pub fn from_elements<'a>(
+        block_hash: Sha256d,
+        elements: impl IntoIterator<Item = &'a [u8]>,
+    ) -> Self {
+        let elements = elements.into_iter().collect::<BTreeSet<_>>();
+        let num_elements = elements.len() as u64;
+        let keys = sip_keys(&block_hash);
+        let range = num_elements * BASIC_FILTER_M;
+        let mut values = elements
+            .into_iter()
+            .map(|element| hash_to_range(keys, range, element))
+            .collect::<Vec<_>>();
+        values.sort_unstable();
+
+        let mut filter = BytesMut::new();
+        write_compact_size(&mut filter, num_elements);
+        let mut writer = BitWriter::default();
+        let mut last_value = 0;
+        for value in values {
+            let delta = value - last_value;
+            writer.write_unary(delta >> BASIC_FILTER_P);
+            writer.write_bits(delta, BASIC_FILTER_P);
+            last_value = value;
+        }
+        filter.put_slice(&writer.finish());
+        BlockFilter {
+            block_hash,
+            num_elements,
+            filter: filter.freeze(),
+        }
+    }
+
+    /// Build the basic filter of the block; `spent_coins` are the coins spent by the
+    /// non-coinbase inputs of the block.
+    pub fn from_bitcoin_block<'a>(
+        block: &BitcoinBlock,
+        spent_coins: impl IntoIterator<Item = &'a Coin>,
+    ) -> Self {
+        Self::from_txs(block.header.calc_hash(), &block.txs, spent_coins)
+    }
+
+    /// Build the basic filter of the block; `spent_coins` are the coins spent by the
+    /// non-coinbase inputs of the block.
+    pub fn from_lotus_block<'a>(
+        block: &LotusBlock,
+        spent_coins: impl IntoIterator<Item = &'a Coin>,
+    ) -> Self {
+        Self::from_txs(block.header.calc_hash(), &block.txs, spent_coins)
+    }
+
+    fn from_txs<'a>(
+        block_hash: Sha256d,
+        txs: &[Tx],
+        spent_coins: impl IntoIterator<Item = &'a Coin>,
+    ) -> Self {
+        let output_scripts = txs
+            .iter()
+            .flat_map(|tx| tx.outputs())
+            .map(|output| &output.script)
+            .filter(|script| !script.is_opreturn());
+        let spent_scripts = spent_coins.into_iter().map(|coin| &coin.tx_output.script);
+        let elements = output_scripts
+            .chain(spent_scripts)
+            .map(|script| script.bytecode().as_ref())
+            .filter(|script| !script.is_empty());
+        Self::from_elements(block_hash, elements)
+    }
+
+    pub fn block_hash(&self) -> &Sha256d {
+        &self.block_hash
+    }
+
+    pub fn num_elements(&self) -> u64 {
+        self.num_elements
+    }
+
+    /// Serialized filter, as committed to in the filter header.
+    pub fn filter(&self) -> &Bytes {
+        &self.filter
+    }
+
+    pub fn filter_hash(&self) -> Sha256d {
+        Sha256d::digest(self.filter.clone())
+    }
+
+    /// Header committing to this filter and all previous ones; `prev_header` is null
+    /// for the genesis block.
+    pub fn filter_header(&self, prev_header: &Sha256d) -> Sha256d {
+        let mut data = BytesMut::new();
+        data.put_slice(self.filter_hash().as_slice());
+        data.put_slice(prev_header.as_slice());
+        Sha256d::digest(data.freeze())
+    }
+
+    /// Whether any of the scripts might be in the block; false positives are possible,
+    /// false negatives are not.
+    pub fn match_any_script<'a>(
+        &self,
+        scripts: impl IntoIterator<Item = &'a Script>,
+    ) -> std::result::Result<bool, BlockFilterError> {
+        self.match_any(scripts.into_iter().map(|script| script.bytecode().as_ref()))
+    }
+
+    /// Whether any of the elements might be in the filter.
+    pub fn match_any<'a>(
+        &self,
+        elements: impl IntoIterator<Item = &'a [u8]>,
+    ) -> std::result::Result<bool, BlockFilterError> {
+        let keys = sip_keys(&self.block_hash);
+        let range = self.range()?;
+        let mut queries = elements
+            .into_iter()
+            .map(|element| hash_to_range(keys, range, element))
+            .collect::<Vec<_>>();
+        queries.sort_unstable();
+        let mut queries = queries.into_iter().peekable();
+
+        let mut data = self.filter.clone();
+        read_compact_size(&mut data).expect("Checked in BlockFilter::new");
+        let mut reader = BitReader::new(&data);
+        let mut value = 0;
+        for decoded in 0..self.num_elements {
+            let delta = reader
+                .read_unary()
+                .and_then(|quotient| {
+                    Some((quotient << BASIC_FILTER_P) | reader.read_bits(BASIC_FILTER_P)?)
+                })
+                .ok_or(BlockFilterError::FilterTooShort {
+                    num_elements: self.num_elements,
+                    decoded,
+                })?;
+            value += delta;
+            while let Some(&query) = queries.peek() {
+                if query == value {
+                    return Ok(true);
+                }
+                if query > value {
+                    break;
+                }
+                queries.next();
+            }
+            if queries.peek().is_none() {
+                return Ok(false);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Range the elements are hashed to, N * M.
+    fn range(&self) -> std::result::Result<u64, BlockFilterError> {
+        self.num_elements
+            .checked_mul(BASIC_FILTER_M)
+            .ok_or(BlockFilterError::TooManyElements(self.num_elements))
+    }
+}
+
+/// Filter headers for consecutive filters, starting after `prev_header`.
+pub fn filter_header_chain<'a>(
+    prev_header: &Sha256d,
+    filters: impl IntoIterator<Item = &'a BlockFilter>,
+) -> Vec<Sha256d> {
+    let mut prev_header = prev_header.clone();
+    filters
+        .into_iter()
+        .map(|filter| {
+            prev_header = filter.filter_header(&prev_header);
+            prev_header.clone()
+        })
+        .collect()
+}
+
+/// SipHash keys from the first 16 bytes of the block hash.
+fn sip_keys(block_hash: &Sha256d) -> (u64, u64) {
+    let hash = block_hash.as_slice();
+    (
+        u64::from_le_bytes(hash[..8].try_into().unwrap()),
+        u64::from_le_bytes(hash[8..16].try_into().unwrap()),
+    )
+}
+
+/// Hash the element uniformly into [0, range).
+fn hash_to_range(keys: (u64, u64), range: u64, element: &[u8]) -> u64 {
+    let mut hasher = SipHasher24::new_with_keys(keys.0, keys.1);
+    hasher.write(element);
+    ((hasher.finish() as u128 * range as u128) >> 64) as u64
+}
+
+/// Writes bits MSB first.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    num_bits: u32,
+}
+
+impl BitWriter {
+    fn write_bit(&mut self, bit: bool) {
+        if self.num_bits.is_multiple_of(8) {
+            self.bytes.push(0);
+        }
+        if bit {
+            *self.bytes.last_mut().unwrap() |= 0x80 >> (self.num_bits % 8);
+        }
+        self.num_bits += 1;
+    }
+
+    fn write_unary(&mut self, value: u64) {
+        for _ in 0..value {
+            self.write_bit(true);
+        }
+        self.write_bit(false);
+    }
+
+    fn write_bits(&mut self, value: u64, num_bits: u8) {
+        for idx in (0..num_bits).rev() {
+            self.write_bit((value >> idx) & 1 != 0);
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Reads bits MSB first.
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        BitReader { bytes, pos: 0 }
+    }
+
+    fn read_bit(&mut self) -> Option<bool> {
+        let byte = self.bytes.get(self.pos / 8)?;
+        let bit = byte & (0x80 >> (self.pos % 8)) != 0;
+        self.pos += 1;
+        Some(bit)
+    }
+
+    fn read_unary(&mut self) -> Option<u64> {
+        let mut value = 0;
+        while self.read_bit()? {
+            value += 1;
+        }
+        Some(value)
+    }
+
+    fn read_bits(&mut self, num_bits: u8) -> Option<u64> {
+        let mut value = 0;
+        for _ in 0..num_bits {
+            value = (value << 1) | self.read_bit()? as u64;
+        }
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        filter_header_chain, BitcoinBlock, BitcoinHeader, BitcoinSuiteError, BlockFilter,
+        BlockFilterError, Bytes, Coin, Hashed, Script, Sha256d, TxInput, TxOutput, UnhashedTx,
+    };
+
+    #[test]
+    fn test_block_filter_testnet_genesis() -> crate::Result<()> {
+        // BIP158 test vector for block 0 of testnet3
+        let block_hash = Sha256d::from_hex_be(
+            "000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943",
+        )?;
+        let script = Script::from_hex(
+            "4104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4\
+             f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac",
+        )?;
+        let filter = BlockFilter::from_elements(block_hash.clone(), [script.bytecode().as_ref()]);
+        assert_eq!(filter.filter().hex(), "019dfca8");
+        assert_eq!(
+            filter.filter_header(&Sha256d::default()),
+            Sha256d::from_hex_be(
+                "21584579b7eb08997773e5aeff3a7f932700042d0ed2a6129012b7d7ae81b750"
+            )?,
+        );
+        assert_eq!(filter.match_any_script([&script]), Ok(true));
+        assert_eq!(
+            BlockFilter::new(block_hash, filter.filter().clone())?,
+            filter
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_filter_block() -> crate::Result<()> {
+        let script = |byte: u8| Script::from_slice(&[byte; 25]);
+        let tx = |input_byte: u8, output_scripts: Vec<Script>| {
+            UnhashedTx {
+                version: 1,
+                inputs: vec![TxInput {
+                    prev_out: crate::OutPoint {
+                        txid: Sha256d::new([input_byte; 32]),
+                        out_idx: 0,
+                    },
+                    ..Default::default()
+                }],
+                outputs: output_scripts
+                    .into_iter()
+                    .map(|script| TxOutput { value: 0, script })
+                    .collect(),
+                lock_time: 0,
+            }
+            .hashed()
+        };
+        let block = BitcoinBlock {
+            header: BitcoinHeader::default(),
+            txs: vec![
+                tx(0, vec![script(1)]),
+                tx(
+                    2,
+                    vec![script(3), Script::opreturn(&[b"hello"]), Script::default()],
+                ),
+                tx(4, vec![script(3), script(5)]),
+            ],
+        };
+        let spent_coins = [6, 7].map(|byte| Coin {
+            tx_output: TxOutput {
+                value: 0,
+                script: script(byte),
+            },
+            ..Default::default()
+        });
+        let filter = BlockFilter::from_bitcoin_block(&block, &spent_coins);
+        assert_eq!(filter.block_hash(), &block.header.calc_hash());
+        // 1, 3, 5, 6 and 7; duplicates, OP_RETURN and empty scripts are excluded
+        assert_eq!(filter.num_elements(), 5);
+        for byte in [1, 3, 5, 6, 7] {
+            assert_eq!(filter.match_any_script([&script(byte)]), Ok(true));
+        }
+        assert_eq!(
+            filter.match_any_script([&script(2), &script(4), &script(8)]),
+            Ok(false),
+        );
+        assert_eq!(filter.match_any_script([&script(2), &script(7)]), Ok(true));
+        assert_eq!(filter.match_any_script([]), Ok(false));
+        assert_eq!(
+            filter.match_any_script([&Script::opreturn(&[b"hello"])]),
+            Ok(false),
+        );
+
+        // Claims 2 elements, but only has 8 bits of data
+        let truncated = BlockFilter::new(Sha256d::default(), Bytes::from_slice(&[2, 0]))?;
+        assert_eq!(
+            truncated.match_any_script([&script(1)]),
+            Err(BlockFilterError::FilterTooShort {
+                num_elements: 2,
+                decoded: 0,
+            }),
+        );
+
+        // N * M would overflow
+        let huge = Bytes::from_slice(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x0f]);
+        assert!(matches!(
+            BlockFilter::new(Sha256d::default(), huge),
+            Err(BitcoinSuiteError::BlockFilter(
+                BlockFilterError::TooManyElements(0x0fff_ffff_ffff_ffff)
+            )),
+        ));
+
+        let empty = BlockFilter::from_elements(Sha256d::default(), []);
+        assert_eq!(empty.filter(), &Bytes::from_slice(&[0]));
+        assert_eq!(empty.match_any_script([&script(1)]), Ok(false));
+        let headers = filter_header_chain(&Sha256d::default(), [&empty, &filter]);
+        assert_eq!(headers[0], empty.filter_header(&Sha256d::default()));
+        assert_eq!(headers[1], filter.filter_header(&headers[0]));
+        assert_eq!(
+            empty.filter_hash(),
+            Sha256d::digest(Bytes::from_slice(&[0])),
+        );
+        Ok(())
+    }
+}
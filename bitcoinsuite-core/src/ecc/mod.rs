@@ -33,6 +33,8 @@ pub enum EccError {
     InvalidRecoveryId(i32),
     #[error("Failed recovering signature")]
     RecoveryFailed,
+    #[error("Invalid tweak")]
+    InvalidTweak,
     #[error("Invalid hex: {0}")]
     Hex(#[from] hex::FromHexError),
 }
@@ -40,6 +42,8 @@ pub enum EccError {
 pub trait Ecc {
     fn pubkey_from_array(&self, pubkey: [u8; PUBKEY_LENGTH]) -> Result<PubKey, EccError>;
 
+    fn pubkey_from_uncompressed(&self, pubkey: [u8; 65]) -> Result<PubKey, EccError>;
+
     fn seckey_from_array(&self, seckey: [u8; 32]) -> Result<SecKey, EccError>;
 
     fn sign(&self, seckey: &SecKey, msg: ByteArray<32>) -> Bytes;
@@ -62,6 +66,12 @@ pub trait Ecc {
 
     fn derive_pubkey(&self, seckey: &SecKey) -> PubKey;
 
+    /// Add `tweak` to `seckey` modulo the curve order.
+    fn seckey_tweak_add(&self, seckey: &SecKey, tweak: [u8; 32]) -> Result<SecKey, EccError>;
+
+    /// Add `tweak` times the generator point to `pubkey`.
+    fn pubkey_tweak_add(&self, pubkey: &PubKey, tweak: [u8; 32]) -> Result<PubKey, EccError>;
+
     fn serialize_pubkey_uncompressed(&self, pubkey: &PubKey) -> [u8; 65];
 
     fn normalize_sig(&self, sig: &Bytes) -> Result<Bytes, EccError>;
@@ -84,6 +94,10 @@ impl Ecc for DummyEcc {
         Ok(PubKey::new_unchecked([0; PUBKEY_LENGTH]))
     }
 
+    fn pubkey_from_uncompressed(&self, _pubkey: [u8; 65]) -> Result<PubKey, EccError> {
+        Ok(PubKey::new_unchecked([0; PUBKEY_LENGTH]))
+    }
+
     fn seckey_from_array(&self, _seckey: [u8; 32]) -> Result<SecKey, EccError> {
         Ok(SecKey::new_unchecked([0; 32]))
     }
@@ -118,6 +132,14 @@ impl Ecc for DummyEcc {
         PubKey::new_unchecked([0; PUBKEY_LENGTH])
     }
 
+    fn seckey_tweak_add(&self, seckey: &SecKey, _tweak: [u8; 32]) -> Result<SecKey, EccError> {
+        Ok(seckey.clone())
+    }
+
+    fn pubkey_tweak_add(&self, pubkey: &PubKey, _tweak: [u8; 32]) -> Result<PubKey, EccError> {
+        Ok(*pubkey)
+    }
+
     fn serialize_pubkey_uncompressed(&self, _pubkey: &PubKey) -> [u8; 65] {
         [0; 65]
     }
@@ -1,7 +1,10 @@
 use hex::FromHexError;
 use thiserror::Error;
 
-use crate::{ecc::EccError, BytesError, SignError};
+use crate::{
+    ecc::EccError, interpreter::ScriptError, Bip32Error, Bip39Error, BlockFilterError, BytesError,
+    HeaderChainError, MerkleError, SignError,
+};
 
 #[derive(Error, Debug)]
 pub enum BitcoinSuiteError {
@@ -21,10 +24,24 @@ pub enum BitcoinSuiteError {
     CodesepNotFound(usize),
     #[error("From hex error: {0}")]
     Hex(#[from] FromHexError),
+    #[error("From base64 error: {0}")]
+    Base64(#[from] base64::DecodeError),
     #[error("Sign error: {0}")]
     Sign(#[from] SignError),
     #[error("Ecc error: {0}")]
     Ecc(#[from] EccError),
+    #[error("Script error: {0}")]
+    Script(#[from] ScriptError),
+    #[error("Merkle error: {0}")]
+    Merkle(#[from] MerkleError),
+    #[error("BIP32 error: {0}")]
+    Bip32(#[from] Bip32Error),
+    #[error("BIP39 error: {0}")]
+    Bip39(#[from] Bip39Error),
+    #[error("Header chain error: {0}")]
+    HeaderChain(#[from] HeaderChainError),
+    #[error("Block filter error: {0}")]
+    BlockFilter(#[from] BlockFilterError),
 }
 
 pub type Result<T> = std::result::Result<T, BitcoinSuiteError>;
@@ -0,0 +1,490 @@
+mod pow;
+
+use std::collections::HashMap;
+
+use num_bigint::BigUint;
+use thiserror::Error;
+
+use crate::{BitcoinHeader, LotusHeader, Sha256d};
+
+pub use self::pow::*;
+
+/// Header that can be validated by a [`HeaderChain`].
+pub trait ChainHeader: Clone {
+    fn calc_hash(&self) -> Sha256d;
+    fn prev_block(&self) -> &Sha256d;
+    fn bits(&self) -> u32;
+    fn timestamp(&self) -> i64;
+    /// Height the header commits to, if any.
+    fn height(&self) -> Option<i32>;
+}
+
+impl ChainHeader for BitcoinHeader {
+    fn calc_hash(&self) -> Sha256d {
+        BitcoinHeader::calc_hash(self)
+    }
+
+    fn prev_block(&self) -> &Sha256d {
+        &self.prev_block
+    }
+
+    fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    fn timestamp(&self) -> i64 {
+        self.timestamp.into()
+    }
+
+    fn height(&self) -> Option<i32> {
+        None
+    }
+}
+
+impl ChainHeader for LotusHeader {
+    fn calc_hash(&self) -> Sha256d {
+        LotusHeader::calc_hash(self)
+    }
+
+    fn prev_block(&self) -> &Sha256d {
+        &self.prev_block
+    }
+
+    fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+
+    fn height(&self) -> Option<i32> {
+        Some(self.height)
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum HeaderChainError {
+    #[error("Parent {0} of header not found")]
+    UnknownParent(Sha256d),
+    #[error("Header has height {actual}, but expected {expected}")]
+    WrongHeight { expected: i32, actual: i32 },
+    #[error("Header has bits {actual:08x}, but expected {expected:08x}")]
+    WrongBits { expected: u32, actual: u32 },
+    #[error("Header hash {0} doesn't meet its target")]
+    HighHash(Sha256d),
+    #[error("Header timestamp {timestamp} must be after the median-time-past {median_time_past}")]
+    TimeTooOld {
+        timestamp: i64,
+        median_time_past: i64,
+    },
+    #[error(
+        "Checkpoint at height {checkpoint_height} is before the difficulty anchor at \
+         {anchor_height}, only ASERT is supported"
+    )]
+    CheckpointBeforeAnchor {
+        checkpoint_height: i32,
+        anchor_height: i32,
+    },
+}
+
+use self::HeaderChainError::*;
+
+/// Header accepted in a [`HeaderChain`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderEntry<H> {
+    pub header: H,
+    pub hash: Sha256d,
+    pub height: i32,
+    /// Total work of the chain up to and including this header, since the checkpoint.
+    pub chain_work: BigUint,
+}
+
+/// How accepting a header changed the chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderChainUpdate {
+    /// Header is already in the chain.
+    AlreadyKnown,
+    /// Header extends the tip of the active chain.
+    Extended,
+    /// Header is on a side chain with less or equal work than the active chain.
+    SideChain,
+    /// Header made a side chain the active chain. Hashes are ordered by height.
+    Reorg {
+        fork_height: i32,
+        disconnected: Vec<Sha256d>,
+        connected: Vec<Sha256d>,
+    },
+}
+
+/// Validates headers, starting at a trusted checkpoint, and tracks the chain with the most work.
+#[derive(Debug, Clone)]
+pub struct HeaderChain<H> {
+    params: ChainParams,
+    entries: HashMap<Sha256d, HeaderEntry<H>>,
+    /// Hashes of the active chain, starting at the checkpoint
+    active: Vec<Sha256d>,
+    checkpoint_height: i32,
+}
+
+impl<H: ChainHeader> HeaderChain<H> {
+    /// Chain starting at the given trusted header.
+    ///
+    /// With ASERT, the checkpoint can't be before the anchor block, as older difficulty
+    /// adjustment algorithms aren't implemented.
+    pub fn new(
+        params: ChainParams,
+        checkpoint: H,
+        checkpoint_height: i32,
+    ) -> Result<Self, HeaderChainError> {
+        if let DifficultyRule::Asert(asert) = &params.difficulty {
+            if checkpoint_height < asert.anchor_height {
+                return Err(CheckpointBeforeAnchor {
+                    checkpoint_height,
+                    anchor_height: asert.anchor_height,
+                });
+            }
+        }
+        let hash = checkpoint.calc_hash();
+        let entry = HeaderEntry {
+            chain_work: bits_to_work(checkpoint.bits()),
+            header: checkpoint,
+            hash: hash.clone(),
+            height: checkpoint_height,
+        };
+        Ok(HeaderChain {
+            params,
+            entries: HashMap::from([(hash.clone(), entry)]),
+            active: vec![hash],
+            checkpoint_height,
+        })
+    }
+
+    pub fn params(&self) -> &ChainParams {
+        &self.params
+    }
+
+    pub fn tip(&self) -> &HeaderEntry<H> {
+        &self.entries[self.active.last().unwrap()]
+    }
+
+    pub fn get(&self, hash: &Sha256d) -> Option<&HeaderEntry<H>> {
+        self.entries.get(hash)
+    }
+
+    /// Header in the active chain at the given height.
+    pub fn at_height(&self, height: i32) -> Option<&HeaderEntry<H>> {
+        let idx = usize::try_from(height - self.checkpoint_height).ok()?;
+        self.active.get(idx).map(|hash| &self.entries[hash])
+    }
+
+    pub fn is_active(&self, hash: &Sha256d) -> bool {
+        match self.entries.get(hash) {
+            Some(entry) => self.at_height(entry.height).map(|entry| &entry.hash) == Some(hash),
+            None => false,
+        }
+    }
+
+    /// Block locator of the active chain, dense near the tip and exponentially sparser
+    /// towards the checkpoint, which is always included.
+    pub fn locator(&self) -> Vec<Sha256d> {
+        let mut locator = Vec::new();
+        let mut idx = self.active.len() - 1;
+        let mut step = 1;
+        loop {
+            locator.push(self.active[idx].clone());
+            if idx == 0 {
+                return locator;
+            }
+            if locator.len() >= 10 {
+                step *= 2;
+            }
+            idx = idx.saturating_sub(step);
+        }
+    }
+
+    /// Validate the header against its parent and add it, switching to its chain if
+    /// it has more work than the active chain.
+    pub fn accept_header(&mut self, header: H) -> Result<HeaderChainUpdate, HeaderChainError> {
+        let hash = header.calc_hash();
+        if self.entries.contains_key(&hash) {
+            return Ok(HeaderChainUpdate::AlreadyKnown);
+        }
+        let parent = self
+            .entries
+            .get(header.prev_block())
+            .ok_or_else(|| UnknownParent(header.prev_block().clone()))?;
+        let height = parent.height + 1;
+        if let Some(header_height) = header.height() {
+            if header_height != height {
+                return Err(WrongHeight {
+                    expected: height,
+                    actual: header_height,
+                });
+            }
+        }
+        let expected_bits = self.params.next_bits(
+            parent.height,
+            parent.header.timestamp(),
+            parent.header.bits(),
+        );
+        if header.bits() != expected_bits {
+            return Err(WrongBits {
+                expected: expected_bits,
+                actual: header.bits(),
+            });
+        }
+        if !hash_meets_target(&hash, header.bits()) {
+            return Err(HighHash(hash));
+        }
+        let median_time_past = self.median_time_past(parent);
+        if header.timestamp() <= median_time_past {
+            return Err(TimeTooOld {
+                timestamp: header.timestamp(),
+                median_time_past,
+            });
+        }
+        let entry = HeaderEntry {
+            chain_work: &parent.chain_work + bits_to_work(header.bits()),
+            hash: hash.clone(),
+            height,
+            header,
+        };
+        let is_extending = parent.hash == self.tip().hash;
+        let has_more_work = entry.chain_work > self.tip().chain_work;
+        self.entries.insert(hash.clone(), entry);
+        if !has_more_work {
+            return Ok(HeaderChainUpdate::SideChain);
+        }
+        if is_extending {
+            self.active.push(hash);
+            return Ok(HeaderChainUpdate::Extended);
+        }
+        Ok(self.reorg(hash))
+    }
+
+    /// Accept all headers in order, stopping at the first invalid one.
+    pub fn accept_headers(
+        &mut self,
+        headers: impl IntoIterator<Item = H>,
+    ) -> Result<Vec<HeaderChainUpdate>, HeaderChainError> {
+        headers
+            .into_iter()
+            .map(|header| self.accept_header(header))
+            .collect()
+    }
+
+    fn median_time_past(&self, parent: &HeaderEntry<H>) -> i64 {
+        let mut timestamps = Vec::with_capacity(MEDIAN_TIME_SPAN);
+        let mut entry = Some(parent);
+        while let Some(current) = entry {
+            if timestamps.len() == MEDIAN_TIME_SPAN {
+                break;
+            }
+            timestamps.push(current.header.timestamp());
+            entry = self.entries.get(current.header.prev_block());
+        }
+        timestamps.sort_unstable();
+        timestamps[timestamps.len() / 2]
+    }
+
+    fn reorg(&mut self, new_tip: Sha256d) -> HeaderChainUpdate {
+        let mut connected = Vec::new();
+        let mut hash = new_tip;
+        while !self.is_active(&hash) {
+            let entry = &self.entries[&hash];
+            connected.push(hash);
+            hash = entry.header.prev_block().clone();
+        }
+        connected.reverse();
+        let fork_height = self.entries[&hash].height;
+        let fork_idx = (fork_height - self.checkpoint_height) as usize;
+        let disconnected = self.active.split_off(fork_idx + 1);
+        self.active.extend(connected.iter().cloned());
+        HeaderChainUpdate::Reorg {
+            fork_height,
+            disconnected,
+            connected,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        hash_meets_target, BitcoinHeader, ChainParams, HeaderChain, HeaderChainError,
+        HeaderChainUpdate, LotusHeader, Sha256d,
+    };
+
+    const BITS: u32 = 0x207fffff;
+
+    fn mine(prev_block: &Sha256d, timestamp: u32, nonce: u32) -> BitcoinHeader {
+        let mut header = BitcoinHeader {
+            prev_block: prev_block.clone(),
+            timestamp,
+            bits: BITS,
+            nonce,
+            ..Default::default()
+        };
+        while !hash_meets_target(&header.calc_hash(), BITS) {
+            header.nonce += 1;
+        }
+        header
+    }
+
+    fn mine_chain(
+        prev_block: &Sha256d,
+        timestamp: u32,
+        len: u32,
+        nonce: u32,
+    ) -> Vec<BitcoinHeader> {
+        let mut headers = Vec::<BitcoinHeader>::new();
+        for idx in 0..len {
+            let prev_block = headers
+                .last()
+                .map(|header| header.calc_hash())
+                .unwrap_or_else(|| prev_block.clone());
+            headers.push(mine(&prev_block, timestamp + idx, nonce));
+        }
+        headers
+    }
+
+    #[test]
+    fn test_header_chain() -> Result<(), HeaderChainError> {
+        let checkpoint = mine(&Sha256d::default(), 1000, 0);
+        let mut chain = HeaderChain::new(ChainParams::regtest(), checkpoint.clone(), 100)?;
+        let main = mine_chain(&checkpoint.calc_hash(), 1001, 3, 0);
+        assert_eq!(
+            chain.accept_headers(main.clone())?,
+            vec![HeaderChainUpdate::Extended; 3],
+        );
+        assert_eq!(chain.tip().height, 103);
+        assert_eq!(chain.tip().hash, main[2].calc_hash());
+        assert_eq!(chain.at_height(101).unwrap().header, main[0]);
+        assert_eq!(chain.at_height(104), None);
+        assert_eq!(chain.at_height(99), None);
+        assert_eq!(
+            chain.accept_header(main[1].clone())?,
+            HeaderChainUpdate::AlreadyKnown,
+        );
+
+        // Fork off after the first header, with equal and then more work
+        let fork = mine_chain(&main[0].calc_hash(), 2000, 3, 1000);
+        let updates = chain.accept_headers(fork.clone())?;
+        assert_eq!(updates[..2], vec![HeaderChainUpdate::SideChain; 2]);
+        assert_eq!(
+            updates[2],
+            HeaderChainUpdate::Reorg {
+                fork_height: 101,
+                disconnected: vec![main[1].calc_hash(), main[2].calc_hash()],
+                connected: fork.iter().map(|header| header.calc_hash()).collect(),
+            },
+        );
+        assert_eq!(chain.tip().height, 104);
+        assert!(chain.is_active(&fork[0].calc_hash()));
+        assert!(!chain.is_active(&main[1].calc_hash()));
+        assert_eq!(
+            chain.tip().chain_work,
+            chain.at_height(100).unwrap().chain_work.clone() * 5u8
+        );
+        assert_eq!(
+            chain.locator(),
+            [
+                fork[2].calc_hash(),
+                fork[1].calc_hash(),
+                fork[0].calc_hash(),
+                main[0].calc_hash(),
+                checkpoint.calc_hash()
+            ],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_header_chain_invalid() -> Result<(), HeaderChainError> {
+        let checkpoint = mine(&Sha256d::default(), 1000, 0);
+        let mut chain = HeaderChain::new(ChainParams::regtest(), checkpoint.clone(), 0)?;
+        let header = mine(&checkpoint.calc_hash(), 1001, 0);
+
+        let mut unknown_parent = header.clone();
+        unknown_parent.prev_block = Sha256d::new([1; 32]);
+        assert_eq!(
+            chain.accept_header(unknown_parent),
+            Err(HeaderChainError::UnknownParent(Sha256d::new([1; 32]))),
+        );
+        let mut wrong_bits = header.clone();
+        wrong_bits.bits = 0x207ffffe;
+        assert_eq!(
+            chain.accept_header(wrong_bits),
+            Err(HeaderChainError::WrongBits {
+                expected: BITS,
+                actual: 0x207ffffe,
+            }),
+        );
+        let mut high_hash = header.clone();
+        while hash_meets_target(&high_hash.calc_hash(), BITS) {
+            high_hash.nonce += 1;
+        }
+        assert_eq!(
+            chain.accept_header(high_hash.clone()),
+            Err(HeaderChainError::HighHash(high_hash.calc_hash())),
+        );
+        let too_old = mine(&checkpoint.calc_hash(), 1000, 0);
+        assert_eq!(
+            chain.accept_header(too_old),
+            Err(HeaderChainError::TimeTooOld {
+                timestamp: 1000,
+                median_time_past: 1000,
+            }),
+        );
+        assert_eq!(chain.tip().hash, checkpoint.calc_hash());
+        Ok(())
+    }
+
+    #[test]
+    fn test_header_chain_checkpoint() {
+        let checkpoint = mine(&Sha256d::default(), 1000, 0);
+        assert_eq!(
+            HeaderChain::new(ChainParams::ecash(), checkpoint.clone(), 661646).unwrap_err(),
+            HeaderChainError::CheckpointBeforeAnchor {
+                checkpoint_height: 661646,
+                anchor_height: 661647,
+            },
+        );
+        assert!(HeaderChain::new(ChainParams::ecash(), checkpoint.clone(), 661647).is_ok());
+        assert!(HeaderChain::new(ChainParams::regtest(), checkpoint, 0).is_ok());
+    }
+
+    #[test]
+    fn test_header_chain_lotus() -> Result<(), HeaderChainError> {
+        let mut checkpoint = LotusHeader {
+            bits: BITS,
+            timestamp: 1000,
+            height: 5,
+            ..Default::default()
+        };
+        while !hash_meets_target(&checkpoint.calc_hash(), BITS) {
+            checkpoint.nonce += 1;
+        }
+        let mut chain = HeaderChain::new(ChainParams::regtest(), checkpoint.clone(), 5)?;
+        let mut header = LotusHeader {
+            prev_block: checkpoint.calc_hash(),
+            timestamp: 1001,
+            height: 7,
+            ..checkpoint
+        };
+        assert_eq!(
+            chain.accept_header(header.clone()),
+            Err(HeaderChainError::WrongHeight {
+                expected: 6,
+                actual: 7,
+            }),
+        );
+        header.height = 6;
+        while !hash_meets_target(&header.calc_hash(), BITS) {
+            header.nonce += 1;
+        }
+        assert_eq!(chain.accept_header(header), Ok(HeaderChainUpdate::Extended));
+        Ok(())
+    }
+}
@@ -0,0 +1,289 @@
+use num_bigint::BigUint;
+
+use crate::{Hashed, Sha256d};
+
+/// How the `bits` of the next block are determined.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DifficultyRule {
+    /// aserti3-2d, used by BCH and XEC since November 2020.
+    Asert(AsertParams),
+    /// Every block has the same `bits` as its parent, used on regtest.
+    NoRetargeting,
+}
+
+/// Parameters of the ASERT difficulty adjustment.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AsertParams {
+    pub anchor_height: i32,
+    pub anchor_bits: u32,
+    /// Timestamp of the parent of the anchor block.
+    pub anchor_parent_timestamp: i64,
+    /// Time after which the difficulty halves (or doubles) if blocks are late (or early).
+    pub half_life: i64,
+}
+
+/// Consensus parameters for validating headers.
+///
+/// There's no preset for Lotus; construct its params from lotusd's consensus params.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ChainParams {
+    /// `bits` of the easiest allowed target.
+    pub pow_limit_bits: u32,
+    /// Targeted time between blocks, in seconds.
+    pub target_spacing: i64,
+    pub difficulty: DifficultyRule,
+}
+
+/// Half-life of ASERT on BCH and XEC, 2 days.
+pub const ASERT_HALF_LIFE: i64 = 2 * 24 * 60 * 60;
+
+/// Number of blocks used to compute the median-time-past.
+pub const MEDIAN_TIME_SPAN: usize = 11;
+
+impl ChainParams {
+    /// Mainnet of Bitcoin ABC (XEC), anchored at the ASERT activation block.
+    /// Only headers after the anchor can be validated, see
+    /// [`HeaderChain::new`](crate::HeaderChain::new).
+    pub fn ecash() -> Self {
+        ChainParams {
+            pow_limit_bits: 0x1d00ffff,
+            target_spacing: 600,
+            difficulty: DifficultyRule::Asert(AsertParams {
+                anchor_height: 661647,
+                anchor_bits: 0x1804dafe,
+                anchor_parent_timestamp: 1605447844,
+                half_life: ASERT_HALF_LIFE,
+            }),
+        }
+    }
+
+    /// Mainnet of Bitcoin Cash, which shares the ASERT anchor with XEC.
+    pub fn bitcoin_cash() -> Self {
+        ChainParams::ecash()
+    }
+
+    pub fn regtest() -> Self {
+        ChainParams {
+            pow_limit_bits: 0x207fffff,
+            target_spacing: 600,
+            difficulty: DifficultyRule::NoRetargeting,
+        }
+    }
+
+    /// Expected `bits` of the block following the given parent block.
+    pub fn next_bits(&self, parent_height: i32, parent_timestamp: i64, parent_bits: u32) -> u32 {
+        match &self.difficulty {
+            DifficultyRule::Asert(asert) => {
+                let pow_limit = bits_to_target(self.pow_limit_bits).unwrap_or_default();
+                next_asert_bits(
+                    asert,
+                    self.target_spacing,
+                    &pow_limit,
+                    parent_height,
+                    parent_timestamp,
+                )
+            }
+            DifficultyRule::NoRetargeting => parent_bits,
+        }
+    }
+}
+
+/// Decode the compact `bits` into a target, or `None` if negative, zero or overflowing.
+pub fn bits_to_target(bits: u32) -> Option<BigUint> {
+    let size = bits >> 24;
+    let word = bits & 0x007f_ffff;
+    let is_negative = word != 0 && (bits & 0x0080_0000) != 0;
+    let is_overflow =
+        word != 0 && (size > 34 || (word > 0xff && size > 33) || (word > 0xffff && size > 32));
+    if word == 0 || is_negative || is_overflow {
+        return None;
+    }
+    let target = match size {
+        0..=3 => BigUint::from(word >> (8 * (3 - size))),
+        _ => BigUint::from(word) << (8 * (size - 3)),
+    };
+    (target.bits() != 0).then_some(target)
+}
+
+/// Encode the target as compact `bits`, losing precision of the lower bits.
+pub fn target_to_bits(target: &BigUint) -> u32 {
+    let mut size = (target.bits() as u32).div_ceil(8);
+    let mut compact = match size {
+        0..=3 => {
+            (target.to_u64_digits().first().copied().unwrap_or_default() << (8 * (3 - size))) as u32
+        }
+        _ => (target >> (8 * (size - 3))).to_u64_digits()[0] as u32,
+    };
+    // The sign bit is set, so move everything one byte down
+    if compact & 0x0080_0000 != 0 {
+        compact >>= 8;
+        size += 1;
+    }
+    compact | (size << 24)
+}
+
+/// Expected number of hashes to find a block with the given `bits`.
+pub fn bits_to_work(bits: u32) -> BigUint {
+    match bits_to_target(bits) {
+        Some(target) => (BigUint::from(1u8) << 256u32) / (target + 1u8),
+        None => BigUint::default(),
+    }
+}
+
+/// Whether the hash, interpreted as little-endian number, is at most the target of `bits`.
+pub fn hash_meets_target(hash: &Sha256d, bits: u32) -> bool {
+    match bits_to_target(bits) {
+        Some(target) => BigUint::from_bytes_le(hash.as_slice()) <= target,
+        None => false,
+    }
+}
+
+/// `bits` of the next block according to aserti3-2d.
+pub fn next_asert_bits(
+    asert: &AsertParams,
+    target_spacing: i64,
+    pow_limit: &BigUint,
+    parent_height: i32,
+    parent_timestamp: i64,
+) -> u32 {
+    let anchor_target = match bits_to_target(asert.anchor_bits) {
+        Some(anchor_target) => anchor_target,
+        None => return target_to_bits(pow_limit),
+    };
+    // 128 bits, so large timestamps can't overflow
+    let time_delta = parent_timestamp as i128 - asert.anchor_parent_timestamp as i128;
+    let height_delta = parent_height as i128 - asert.anchor_height as i128;
+    let exponent = ((time_delta - target_spacing as i128 * (height_delta + 1)) * 65536)
+        / asert.half_life as i128;
+    // Fixed-point 2^exponent, as integer part (shifts) and 16-bit fractional part
+    let shifts = (exponent >> 16) - 16;
+    let frac = (exponent & 0xffff) as u128;
+    let factor = 65536
+        + ((195_766_423_245_049 * frac
+            + 971_821_376 * frac * frac
+            + 5127 * frac * frac * frac
+            + (1 << 47))
+            >> 48);
+    let mut next_target = anchor_target * factor as u64;
+    if shifts <= 0 {
+        next_target = match -shifts {
+            shifts if shifts > 512 => BigUint::default(),
+            shifts => next_target >> shifts as usize,
+        };
+    } else if shifts > 256 {
+        return target_to_bits(pow_limit);
+    } else {
+        next_target <<= shifts as usize;
+    }
+    if next_target.bits() == 0 {
+        next_target = BigUint::from(1u8);
+    } else if &next_target > pow_limit {
+        next_target = pow_limit.clone();
+    }
+    target_to_bits(&next_target)
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigUint;
+
+    use crate::{
+        bits_to_target, bits_to_work, hash_meets_target, next_asert_bits, target_to_bits,
+        AsertParams, ChainParams, Sha256d, ASERT_HALF_LIFE,
+    };
+
+    #[test]
+    fn test_bits_target() {
+        let target = bits_to_target(0x1d00ffff).unwrap();
+        assert_eq!(target, BigUint::from(0xffffu32) << 208u32);
+        assert_eq!(target_to_bits(&target), 0x1d00ffff);
+        assert_eq!(bits_to_target(0x01123456), Some(BigUint::from(0x12u32)));
+        assert_eq!(
+            bits_to_target(0x04123456),
+            Some(BigUint::from(0x12345600u32))
+        );
+        assert_eq!(target_to_bits(&BigUint::from(0x80u32)), 0x02008000);
+        assert_eq!(target_to_bits(&BigUint::from(0x12345600u32)), 0x04123456);
+        // Negative, zero and overflowing bits
+        assert_eq!(bits_to_target(0x04923456), None);
+        assert_eq!(bits_to_target(0x00123456), None);
+        assert_eq!(bits_to_target(0xff123456), None);
+        assert_eq!(bits_to_work(0x1d00ffff), BigUint::from(0x1_0001_0001u64));
+    }
+
+    #[test]
+    fn test_hash_meets_target() {
+        let mut hash = [0xff; 32];
+        hash[31] = 0x7f;
+        assert!(!hash_meets_target(&Sha256d::new(hash), 0x207fffff));
+        hash[28] = 0;
+        hash[29] = 0;
+        hash[30] = 0;
+        assert!(hash_meets_target(&Sha256d::new(hash), 0x207fffff));
+    }
+
+    #[test]
+    fn test_asert() {
+        let asert = AsertParams {
+            anchor_height: 1000,
+            anchor_bits: 0x1804dafe,
+            anchor_parent_timestamp: 1_600_000_000,
+            half_life: ASERT_HALF_LIFE,
+        };
+        let pow_limit = bits_to_target(0x1d00ffff).unwrap();
+        let anchor_target = bits_to_target(0x1804dafe).unwrap();
+        let next_bits = |height: i32, timestamp: i64| {
+            next_asert_bits(&asert, 600, &pow_limit, height, timestamp)
+        };
+        let on_schedule = |height: i32| 1_600_000_000 + 600 * (height - 999) as i64;
+        // Blocks on schedule keep the difficulty
+        assert_eq!(next_bits(1000, on_schedule(1000)), 0x1804dafe);
+        assert_eq!(next_bits(5000, on_schedule(5000)), 0x1804dafe);
+        // Blocks a half-life late double the target, early halve it
+        assert_eq!(
+            next_bits(1000, on_schedule(1000) + ASERT_HALF_LIFE),
+            target_to_bits(&(anchor_target.clone() << 1u32)),
+        );
+        assert_eq!(
+            next_bits(2000, on_schedule(2000) - 3 * ASERT_HALF_LIFE),
+            target_to_bits(&(anchor_target >> 3u32)),
+        );
+        // Clamped to the pow limit
+        assert_eq!(
+            next_bits(1000, on_schedule(1000) + 1000 * ASERT_HALF_LIFE),
+            0x1d00ffff
+        );
+        assert_eq!(next_bits(1000, i64::MAX), 0x1d00ffff);
+        // Target never goes below 1
+        assert_eq!(next_bits(1_000_000, 0), 0x01010000);
+        // Successor of the XEC anchor one spacing after it keeps the anchor bits
+        assert_eq!(
+            ChainParams::ecash().next_bits(661647, 1605447844 + 600, 0),
+            0x1804dafe,
+        );
+    }
+
+    #[test]
+    fn test_asert_exponential() {
+        // aserti3-2d approximates
+        // target = anchor_target * 2^((time_delta - spacing * (height_delta + 1)) / half_life),
+        // compare against the exact exponential for fractional exponents
+        let asert = AsertParams {
+            anchor_height: 0,
+            anchor_bits: 0x1d00ffff,
+            anchor_parent_timestamp: 0,
+            half_life: ASERT_HALF_LIFE,
+        };
+        let pow_limit = BigUint::from(1u8) << 255u32;
+        let anchor_target = 0xffff as f64 * 2f64.powi(208);
+        for offset in (-4 * ASERT_HALF_LIFE..=4 * ASERT_HALF_LIFE).step_by(7919) {
+            let bits = next_asert_bits(&asert, 600, &pow_limit, 0, 600 + offset);
+            let target = bits_to_target(bits).unwrap();
+            let shift = target.bits().saturating_sub(53);
+            let target = (target >> shift).to_u64_digits()[0] as f64 * 2f64.powi(shift as i32);
+            let expected = anchor_target * 2f64.powf(offset as f64 / ASERT_HALF_LIFE as f64);
+            let error = (target - expected).abs() / expected;
+            assert!(error < 2e-4, "offset {offset}: {target} vs {expected}");
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use thiserror::Error;
+
+use crate::SignError;
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScriptError {
+    #[error("Script evaluated without error but finished with a false/empty top stack element")]
+    EvalFalse,
+    #[error("OP_RETURN was encountered")]
+    OpReturn,
+    #[error("Script is too big")]
+    ScriptSize,
+    #[error("Push value size limit exceeded")]
+    PushSize,
+    #[error("Operation limit exceeded")]
+    OpCount,
+    #[error("Stack size limit exceeded")]
+    StackSize,
+    #[error("Signature count negative or greater than pubkey count")]
+    SigCount,
+    #[error("Pubkey count negative or limit exceeded")]
+    PubKeyCount,
+    #[error("Invalid script encoding")]
+    InvalidScriptEncoding,
+    #[error("Script failed an OP_VERIFY operation")]
+    Verify,
+    #[error("Script failed an OP_EQUALVERIFY operation")]
+    EqualVerify,
+    #[error("Script failed an OP_CHECKMULTISIGVERIFY operation")]
+    CheckMultisigVerify,
+    #[error("Script failed an OP_CHECKSIGVERIFY operation")]
+    CheckSigVerify,
+    #[error("Script failed an OP_CHECKDATASIGVERIFY operation")]
+    CheckDataSigVerify,
+    #[error("Script failed an OP_NUMEQUALVERIFY operation")]
+    NumEqualVerify,
+    #[error("Opcode missing or not understood: 0x{0:02x}")]
+    BadOpcode(u8),
+    #[error("Attempted to use a disabled opcode: 0x{0:02x}")]
+    DisabledOpcode(u8),
+    #[error("Operation not valid with the current stack size")]
+    InvalidStackOperation,
+    #[error("Operation not valid with the current altstack size")]
+    InvalidAltstackOperation,
+    #[error("Invalid OP_IF construction")]
+    UnbalancedConditional,
+    #[error("Invalid OP_SPLIT range")]
+    InvalidSplitRange,
+    #[error("Invalid operand size")]
+    InvalidOperandSize,
+    #[error("Given operand is not a number within the valid range")]
+    InvalidNumberRange,
+    #[error("The requested encoding is impossible to satisfy")]
+    ImpossibleEncoding,
+    #[error("Division by zero error")]
+    DivByZero,
+    #[error("Modulo by zero error")]
+    ModByZero,
+    #[error("Integer overflow")]
+    IntegerOverflow,
+    #[error("Negative locktime")]
+    NegativeLockTime,
+    #[error("Locktime requirement not satisfied")]
+    UnsatisfiedLockTime,
+    #[error("Signature hash type missing or not understood")]
+    SigHashType,
+    #[error("Non-canonical DER signature")]
+    SigDer,
+    #[error("Data push larger than necessary")]
+    MinimalData,
+    #[error("Non-minimally encoded script number")]
+    NonMinimalNumber,
+    #[error("Only push operators allowed in signatures")]
+    SigPushOnly,
+    #[error("Non-canonical signature: S value is unnecessarily high")]
+    SigHighS,
+    #[error("Dummy CHECKMULTISIG argument must be zero")]
+    SigNullDummy,
+    #[error("Public key is neither compressed nor uncompressed")]
+    PubKeyType,
+    #[error("Stack size must be exactly one after execution")]
+    CleanStack,
+    #[error("Signature must be zero for failed CHECK(MULTI)SIG operation")]
+    NullFail,
+    #[error("NOPx reserved for soft-fork upgrades")]
+    DiscourageUpgradableNops,
+    #[error("Signature must use SIGHASH_FORKID")]
+    MustUseForkId,
+    #[error("Illegal use of SIGHASH_FORKID")]
+    IllegalForkId,
+    #[error("Signature cannot be 65 bytes in CHECKMULTISIG")]
+    SigBadLength,
+    #[error("Only Schnorr signatures allowed in this operation")]
+    SigNonSchnorr,
+    #[error("Bitfield of unexpected size error")]
+    InvalidBitfieldSize,
+    #[error("Bitfield's bit out of the expected range")]
+    InvalidBitRange,
+    #[error("Bitfield's number of set bits doesn't match the number of signatures")]
+    InvalidBitCount,
+    #[error("Input index {0} out of range")]
+    InvalidTxInputIndex(i64),
+    #[error("Output index {0} out of range")]
+    InvalidTxOutputIndex(i64),
+    #[error("Opcode requires a tx context: 0x{0:02x}")]
+    MissingTxContext(u8),
+    #[error("No spent coin for input {0}")]
+    MissingSpentCoin(usize),
+    #[error("Failed computing sighash: {0}")]
+    Sighash(#[from] SignError),
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum TxVerifyError {
+    #[error("Tx has no inputs")]
+    NoInputs,
+    #[error("Tx has no outputs")]
+    NoOutputs,
+    #[error("Expected {expected} spent coins, but got {actual}")]
+    WrongNumSpentCoins { expected: usize, actual: usize },
+    #[error("Tx size {size} is below the minimum of {min_size}")]
+    TxTooSmall { size: usize, min_size: usize },
+    #[error("Tx size {size} exceeds the maximum of {max_size}")]
+    TxTooBig { size: usize, max_size: usize },
+    #[error("Inputs {first_idx} and {second_idx} spend the same output")]
+    DuplicateInput { first_idx: usize, second_idx: usize },
+    #[error("Output {output_idx} has value {value} out of range")]
+    OutputValueOutOfRange { output_idx: usize, value: i64 },
+    #[error("Spent coin of input {input_idx} has value {value} out of range")]
+    InputValueOutOfRange { input_idx: usize, value: i64 },
+    #[error("Output {output_idx} has value {value}, which is below the dust limit {dust_limit}")]
+    DustOutput {
+        output_idx: usize,
+        value: i64,
+        dust_limit: i64,
+    },
+    #[error("Outputs ({output_sum}) exceed inputs ({input_sum})")]
+    InsufficientInputs { input_sum: i64, output_sum: i64 },
+    #[error(
+        "{} input(s) failed script verification, first: {}",
+        .0.len(),
+        .0.first().map_or("none".to_string(), ToString::to_string),
+    )]
+    InvalidInputs(Vec<InputScriptError>),
+}
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[error("Input {input_idx}: {error}")]
+pub struct InputScriptError {
+    pub input_idx: usize,
+    pub error: ScriptError,
+}
@@ -0,0 +1,1516 @@
+use crate::{
+    ecc::{Ecc, PubKey, PUBKEY_LENGTH, SCHNORR_SIGNATURE_SIZE},
+    interpreter::{
+        check_data_sig_encoding, check_pubkey_encoding, check_tx_ecdsa_sig_encoding,
+        check_tx_schnorr_sig_encoding, check_tx_sig_encoding, decode_script_num, encode_script_num,
+        minimally_encode, ScriptError, ScriptFlags, MAX_SCRIPT_NUM_SIZE_32BIT,
+        MAX_SCRIPT_NUM_SIZE_64BIT,
+    },
+    opcode::*,
+    ByteArray, Bytes, Coin, Hashed, Op, Ripemd160, Script, SequenceNo, Sha1, Sha256, Sha256d,
+    ShaRmd160, SigHashType, SigHashTypeVariant, UnsignedTx, CSV_TYPE_FLAG,
+};
+
+/// Max. number of bytes of a script.
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+/// Max. number of bytes of a single stack element.
+pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+/// Max. number of non-push operations per script.
+pub const MAX_OPS_PER_SCRIPT: usize = 201;
+/// Max. number of elements on stack and altstack combined.
+pub const MAX_STACK_SIZE: usize = 1000;
+/// Max. number of pubkeys in OP_CHECKMULTISIG.
+pub const MAX_PUBKEYS_PER_MULTISIG: usize = 20;
+/// Lock times below this are block heights, above are UNIX timestamps.
+pub const LOCKTIME_THRESHOLD: i64 = 500_000_000;
+
+/// If set in a sequence number, disables its relative lock time.
+const SEQUENCE_LOCKTIME_DISABLE_FLAG: i64 = 1 << 31;
+const SEQUENCE_LOCKTIME_MASK: i64 = 0x0000_ffff;
+/// Script numbers for lock times are 5 bytes, independent of 64-bit integers.
+const LOCKTIME_NUM_SIZE: usize = 5;
+
+/// Transaction an input script is executed in, required for signature checks, lock times and
+/// native introspection.
+#[derive(Clone, Copy)]
+pub struct TxContext<'a> {
+    pub ecc: &'a dyn Ecc,
+    pub unsigned_tx: &'a UnsignedTx,
+    pub spent_coins: &'a [Coin],
+    pub input_idx: usize,
+}
+
+struct Interpreter<'s, 'c> {
+    stack: &'s mut Vec<Bytes>,
+    alt_stack: Vec<Bytes>,
+    exec_stack: Vec<bool>,
+    flags: ScriptFlags,
+    ctx: Option<&'c TxContext<'c>>,
+    script_code: Script,
+    op_count: usize,
+    max_num_size: usize,
+}
+
+/// Execute `script` on `stack`.
+///
+/// `ctx` is only required for signature checks, lock times and native introspection; if it's
+/// missing, those opcodes fail with [`ScriptError::MissingTxContext`].
+pub fn eval_script(
+    stack: &mut Vec<Bytes>,
+    script: &Script,
+    flags: ScriptFlags,
+    ctx: Option<&TxContext<'_>>,
+) -> Result<(), ScriptError> {
+    if script.bytecode().len() > MAX_SCRIPT_SIZE {
+        return Err(ScriptError::ScriptSize);
+    }
+    let mut interpreter = Interpreter {
+        stack,
+        alt_stack: Vec::new(),
+        exec_stack: Vec::new(),
+        flags,
+        ctx,
+        script_code: script.clone(),
+        op_count: 0,
+        max_num_size: if flags.contains(ScriptFlags::INTEGERS_64BIT) {
+            MAX_SCRIPT_NUM_SIZE_64BIT
+        } else {
+            MAX_SCRIPT_NUM_SIZE_32BIT
+        },
+    };
+    let mut ops = script.ops();
+    while let Some(op) = ops.next() {
+        let op = op.map_err(|_| ScriptError::InvalidScriptEncoding)?;
+        let is_executing = interpreter.exec_stack.iter().all(|&is_true| is_true);
+        match op {
+            Op::Push(opcode, data) => {
+                if data.len() > MAX_SCRIPT_ELEMENT_SIZE {
+                    return Err(ScriptError::PushSize);
+                }
+                if is_executing {
+                    if flags.contains(ScriptFlags::MINIMALDATA) && !is_minimal_push(opcode, &data) {
+                        return Err(ScriptError::MinimalData);
+                    }
+                    interpreter.stack.push(data);
+                }
+            }
+            Op::Code(opcode) => {
+                if opcode > OP_16 {
+                    interpreter.op_count += 1;
+                    if interpreter.op_count > MAX_OPS_PER_SCRIPT {
+                        return Err(ScriptError::OpCount);
+                    }
+                }
+                // Disabled opcodes fail the script even in unexecuted branches
+                if interpreter.is_disabled(opcode) {
+                    return Err(ScriptError::DisabledOpcode(opcode));
+                }
+                if opcode == OP_CODESEPARATOR && is_executing {
+                    interpreter.script_code = Script::new(ops.remaining_bytecode().clone());
+                }
+                if is_executing || (OP_IF..=OP_ENDIF).contains(&opcode) {
+                    interpreter.exec_opcode(opcode, is_executing)?;
+                }
+            }
+        }
+        if interpreter.stack.len() + interpreter.alt_stack.len() > MAX_STACK_SIZE {
+            return Err(ScriptError::StackSize);
+        }
+    }
+    if !interpreter.exec_stack.is_empty() {
+        return Err(ScriptError::UnbalancedConditional);
+    }
+    Ok(())
+}
+
+/// Whether the data is pushed with the smallest possible opcode.
+fn is_minimal_push(opcode: u8, data: &[u8]) -> bool {
+    match data {
+        // Should have used OP_0, OP_1-OP_16 or OP_1NEGATE
+        [] => false,
+        [1..=16] | [0x81] => false,
+        _ if data.len() <= 0x4b => opcode as usize == data.len(),
+        _ if data.len() <= 0xff => opcode == OP_PUSHDATA1,
+        _ if data.len() <= 0xffff => opcode == OP_PUSHDATA2,
+        _ => true,
+    }
+}
+
+/// Interpret the stack element as bool; zero and negative zero (of any length) are false.
+pub fn cast_to_bool(data: &[u8]) -> bool {
+    match data.split_last() {
+        Some((&last, rest)) => rest.iter().any(|&byte| byte != 0) || last & 0x7f != 0,
+        None => false,
+    }
+}
+
+fn bool_bytes(value: bool) -> Bytes {
+    match value {
+        true => Bytes::from_slice(&[1]),
+        false => Bytes::new(),
+    }
+}
+
+impl<'s, 'c> Interpreter<'s, 'c> {
+    fn is_disabled(&self, opcode: u8) -> bool {
+        match opcode {
+            OP_INVERT | OP_2MUL | OP_2DIV | OP_RAWLEFTBITSHIFT | OP_MULPOW2 => true,
+            OP_MUL => !self.flags.contains(ScriptFlags::INTEGERS_64BIT),
+            _ => false,
+        }
+    }
+
+    fn ctx(&self, opcode: u8) -> Result<&'c TxContext<'c>, ScriptError> {
+        self.ctx.ok_or(ScriptError::MissingTxContext(opcode))
+    }
+
+    fn require_minimal(&self) -> bool {
+        self.flags.contains(ScriptFlags::MINIMALDATA)
+    }
+
+    fn pop(&mut self) -> Result<Bytes, ScriptError> {
+        self.stack.pop().ok_or(ScriptError::InvalidStackOperation)
+    }
+
+    /// Element at `depth` from the top of the stack; the top element has depth 0.
+    fn peek(&self, depth: usize) -> Result<&Bytes, ScriptError> {
+        if depth >= self.stack.len() {
+            return Err(ScriptError::InvalidStackOperation);
+        }
+        Ok(&self.stack[self.stack.len() - 1 - depth])
+    }
+
+    fn require_stack(&self, num_elements: usize) -> Result<(), ScriptError> {
+        if self.stack.len() < num_elements {
+            return Err(ScriptError::InvalidStackOperation);
+        }
+        Ok(())
+    }
+
+    fn pop_num(&mut self) -> Result<i64, ScriptError> {
+        let data = self.pop()?;
+        decode_script_num(&data, self.max_num_size, self.require_minimal())
+    }
+
+    fn peek_num(&self, depth: usize, max_size: usize) -> Result<i64, ScriptError> {
+        decode_script_num(self.peek(depth)?, max_size, self.require_minimal())
+    }
+
+    fn pop_bool(&mut self) -> Result<bool, ScriptError> {
+        Ok(cast_to_bool(&self.pop()?))
+    }
+
+    fn push_num(&mut self, num: Option<i64>) -> Result<(), ScriptError> {
+        match num {
+            // i64::MIN can't be encoded as script number
+            Some(num) if num != i64::MIN => {
+                self.stack.push(encode_script_num(num));
+                Ok(())
+            }
+            _ => Err(ScriptError::IntegerOverflow),
+        }
+    }
+
+    fn push_bool(&mut self, value: bool) {
+        self.stack.push(bool_bytes(value));
+    }
+
+    fn push_element(&mut self, data: Bytes) -> Result<(), ScriptError> {
+        if data.len() > MAX_SCRIPT_ELEMENT_SIZE {
+            return Err(ScriptError::PushSize);
+        }
+        self.stack.push(data);
+        Ok(())
+    }
+
+    fn exec_opcode(&mut self, opcode: u8, is_executing: bool) -> Result<(), ScriptError> {
+        match opcode {
+            OP_0 => self.stack.push(Bytes::new()),
+            OP_1NEGATE => self.push_num(Some(-1))?,
+            OP_1..=OP_16 => self.push_num(Some((opcode - OP_1 + 1) as i64))?,
+
+            // control
+            OP_NOP => {}
+            OP_NOP1 | OP_NOP4..=OP_NOP10 => self.exec_upgradable_nop()?,
+            OP_CHECKLOCKTIMEVERIFY => {
+                if !self.flags.contains(ScriptFlags::CHECKLOCKTIMEVERIFY) {
+                    return self.exec_upgradable_nop();
+                }
+                let lock_time = self.peek_num(0, LOCKTIME_NUM_SIZE)?;
+                if lock_time < 0 {
+                    return Err(ScriptError::NegativeLockTime);
+                }
+                self.check_lock_time(opcode, lock_time)?;
+            }
+            OP_CHECKSEQUENCEVERIFY => {
+                if !self.flags.contains(ScriptFlags::CHECKSEQUENCEVERIFY) {
+                    return self.exec_upgradable_nop();
+                }
+                let sequence = self.peek_num(0, LOCKTIME_NUM_SIZE)?;
+                if sequence < 0 {
+                    return Err(ScriptError::NegativeLockTime);
+                }
+                if sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG == 0 {
+                    self.check_sequence(opcode, sequence)?;
+                }
+            }
+            OP_IF | OP_NOTIF => {
+                let mut value = false;
+                if is_executing {
+                    let top = self.stack.pop().ok_or(ScriptError::UnbalancedConditional)?;
+                    value = cast_to_bool(&top) == (opcode == OP_IF);
+                }
+                self.exec_stack.push(value);
+            }
+            OP_ELSE => {
+                let value = self
+                    .exec_stack
+                    .last_mut()
+                    .ok_or(ScriptError::UnbalancedConditional)?;
+                *value = !*value;
+            }
+            OP_ENDIF => {
+                self.exec_stack
+                    .pop()
+                    .ok_or(ScriptError::UnbalancedConditional)?;
+            }
+            OP_VERIFY => {
+                if !self.pop_bool()? {
+                    return Err(ScriptError::Verify);
+                }
+            }
+            OP_RETURN => return Err(ScriptError::OpReturn),
+
+            // stack ops
+            OP_TOALTSTACK => {
+                let top = self.pop()?;
+                self.alt_stack.push(top);
+            }
+            OP_FROMALTSTACK => {
+                let top = self
+                    .alt_stack
+                    .pop()
+                    .ok_or(ScriptError::InvalidAltstackOperation)?;
+                self.stack.push(top);
+            }
+            OP_2DROP => {
+                self.require_stack(2)?;
+                self.stack.truncate(self.stack.len() - 2);
+            }
+            OP_2DUP => self.dup_n(2, 2)?,
+            OP_3DUP => self.dup_n(3, 3)?,
+            OP_2OVER => self.dup_n(2, 4)?,
+            OP_2ROT => {
+                self.require_stack(6)?;
+                let idx = self.stack.len() - 6;
+                let removed = self.stack.drain(idx..idx + 2).collect::<Vec<_>>();
+                self.stack.extend(removed);
+            }
+            OP_2SWAP => {
+                self.require_stack(4)?;
+                let len = self.stack.len();
+                self.stack.swap(len - 4, len - 2);
+                self.stack.swap(len - 3, len - 1);
+            }
+            OP_IFDUP => {
+                let top = self.peek(0)?.clone();
+                if cast_to_bool(&top) {
+                    self.stack.push(top);
+                }
+            }
+            OP_DEPTH => self.push_num(Some(self.stack.len() as i64))?,
+            OP_DROP => {
+                self.pop()?;
+            }
+            OP_DUP => self.dup_n(1, 1)?,
+            OP_NIP => {
+                self.require_stack(2)?;
+                self.stack.remove(self.stack.len() - 2);
+            }
+            OP_OVER => self.dup_n(1, 2)?,
+            OP_PICK | OP_ROLL => {
+                let depth = self.pop_num()?;
+                if depth < 0 || depth as usize >= self.stack.len() {
+                    return Err(ScriptError::InvalidStackOperation);
+                }
+                let idx = self.stack.len() - 1 - depth as usize;
+                let element = match opcode {
+                    OP_PICK => self.stack[idx].clone(),
+                    _ => self.stack.remove(idx),
+                };
+                self.stack.push(element);
+            }
+            OP_ROT => {
+                self.require_stack(3)?;
+                let element = self.stack.remove(self.stack.len() - 3);
+                self.stack.push(element);
+            }
+            OP_SWAP => {
+                self.require_stack(2)?;
+                let len = self.stack.len();
+                self.stack.swap(len - 2, len - 1);
+            }
+            OP_TUCK => {
+                self.require_stack(2)?;
+                let top = self.peek(0)?.clone();
+                self.stack.insert(self.stack.len() - 2, top);
+            }
+
+            // splice ops
+            OP_CAT => {
+                self.require_stack(2)?;
+                let right = self.pop()?;
+                let left = self.pop()?;
+                self.push_element([left.as_ref(), right.as_ref()].concat().into())?;
+            }
+            OP_SPLIT => {
+                self.require_stack(2)?;
+                let position = self.pop_num()?;
+                let mut data = self.pop()?;
+                if position < 0 || position as usize > data.len() {
+                    return Err(ScriptError::InvalidSplitRange);
+                }
+                let left = data.split_to(position as usize).expect("Checked range");
+                self.stack.push(left);
+                self.stack.push(data);
+            }
+            OP_NUM2BIN => {
+                self.require_stack(2)?;
+                let size = self.pop_num()?;
+                if size < 0 || size as usize > MAX_SCRIPT_ELEMENT_SIZE {
+                    return Err(ScriptError::PushSize);
+                }
+                let size = size as usize;
+                let num = minimally_encode(&self.pop()?);
+                if num.len() > size {
+                    return Err(ScriptError::ImpossibleEncoding);
+                }
+                let mut padded = num.to_vec();
+                match padded.last_mut() {
+                    Some(last) if num.len() < size => {
+                        // Move the sign bit to the new last byte
+                        let sign_bit = *last & 0x80;
+                        *last &= 0x7f;
+                        padded.resize(size - 1, 0);
+                        padded.push(sign_bit);
+                    }
+                    Some(_) => {}
+                    None => padded.resize(size, 0),
+                }
+                self.stack.push(padded.into());
+            }
+            OP_BIN2NUM => {
+                let num = minimally_encode(&self.pop()?);
+                if num.len() > self.max_num_size {
+                    return Err(ScriptError::InvalidNumberRange);
+                }
+                self.stack.push(num);
+            }
+            OP_SIZE => self.push_num(Some(self.peek(0)?.len() as i64))?,
+
+            // bit logic
+            OP_AND | OP_OR | OP_XOR => {
+                self.require_stack(2)?;
+                let right = self.pop()?;
+                let left = self.pop()?;
+                if left.len() != right.len() {
+                    return Err(ScriptError::InvalidOperandSize);
+                }
+                let result = left.iter().zip(right.iter()).map(|(&a, &b)| match opcode {
+                    OP_AND => a & b,
+                    OP_OR => a | b,
+                    _ => a ^ b,
+                });
+                self.stack.push(result.collect::<Vec<_>>().into());
+            }
+            OP_EQUAL | OP_EQUALVERIFY => {
+                self.require_stack(2)?;
+                let right = self.pop()?;
+                let left = self.pop()?;
+                let is_equal = left == right;
+                if opcode == OP_EQUALVERIFY {
+                    if !is_equal {
+                        return Err(ScriptError::EqualVerify);
+                    }
+                } else {
+                    self.push_bool(is_equal);
+                }
+            }
+
+            // numeric
+            OP_1ADD | OP_1SUB | OP_NEGATE | OP_ABS | OP_NOT | OP_0NOTEQUAL => {
+                let num = self.pop_num()?;
+                let result = match opcode {
+                    OP_1ADD => num.checked_add(1),
+                    OP_1SUB => num.checked_sub(1),
+                    OP_NEGATE => num.checked_neg(),
+                    OP_ABS => num.checked_abs(),
+                    OP_NOT => Some((num == 0) as i64),
+                    _ => Some((num != 0) as i64),
+                };
+                self.push_num(result)?;
+            }
+            OP_ADD
+            | OP_SUB
+            | OP_MUL
+            | OP_DIV
+            | OP_MOD
+            | OP_BOOLAND
+            | OP_BOOLOR
+            | OP_NUMEQUAL
+            | OP_NUMEQUALVERIFY
+            | OP_NUMNOTEQUAL
+            | OP_LESSTHAN
+            | OP_GREATERTHAN
+            | OP_LESSTHANOREQUAL
+            | OP_GREATERTHANOREQUAL
+            | OP_MIN
+            | OP_MAX => {
+                self.require_stack(2)?;
+                let right = self.pop_num()?;
+                let left = self.pop_num()?;
+                let result = match opcode {
+                    OP_ADD => left.checked_add(right),
+                    OP_SUB => left.checked_sub(right),
+                    OP_MUL => left.checked_mul(right),
+                    OP_DIV if right == 0 => return Err(ScriptError::DivByZero),
+                    OP_DIV => left.checked_div(right),
+                    OP_MOD if right == 0 => return Err(ScriptError::ModByZero),
+                    OP_MOD => left.checked_rem(right),
+                    OP_BOOLAND => Some((left != 0 && right != 0) as i64),
+                    OP_BOOLOR => Some((left != 0 || right != 0) as i64),
+                    OP_NUMEQUAL | OP_NUMEQUALVERIFY => Some((left == right) as i64),
+                    OP_NUMNOTEQUAL => Some((left != right) as i64),
+                    OP_LESSTHAN => Some((left < right) as i64),
+                    OP_GREATERTHAN => Some((left > right) as i64),
+                    OP_LESSTHANOREQUAL => Some((left <= right) as i64),
+                    OP_GREATERTHANOREQUAL => Some((left >= right) as i64),
+                    OP_MIN => Some(left.min(right)),
+                    _ => Some(left.max(right)),
+                };
+                if opcode == OP_NUMEQUALVERIFY {
+                    if result != Some(1) {
+                        return Err(ScriptError::NumEqualVerify);
+                    }
+                } else {
+                    self.push_num(result)?;
+                }
+            }
+            OP_WITHIN => {
+                self.require_stack(3)?;
+                let max = self.pop_num()?;
+                let min = self.pop_num()?;
+                let num = self.pop_num()?;
+                self.push_bool(min <= num && num < max);
+            }
+
+            // crypto
+            OP_RIPEMD160 => self.exec_hash::<Ripemd160>()?,
+            OP_SHA1 => self.exec_hash::<Sha1>()?,
+            OP_SHA256 => self.exec_hash::<Sha256>()?,
+            OP_HASH160 => self.exec_hash::<ShaRmd160>()?,
+            OP_HASH256 => self.exec_hash::<Sha256d>()?,
+            // script code is updated in eval_script, which has access to the remaining bytecode
+            OP_CODESEPARATOR => {}
+            OP_CHECKSIG | OP_CHECKSIGVERIFY => {
+                self.require_stack(2)?;
+                let pubkey = self.pop()?;
+                let sig = self.pop()?;
+                check_tx_sig_encoding(&sig, self.flags)?;
+                check_pubkey_encoding(&pubkey, self.flags)?;
+                let ctx = self.ctx(opcode)?;
+                let script_code = self.cleanup_script_code([&sig]);
+                let is_valid =
+                    !sig.is_empty() && self.check_tx_sig(ctx, &script_code, &sig, &pubkey)?;
+                if !is_valid && !sig.is_empty() && self.flags.contains(ScriptFlags::NULLFAIL) {
+                    return Err(ScriptError::NullFail);
+                }
+                if opcode == OP_CHECKSIGVERIFY {
+                    if !is_valid {
+                        return Err(ScriptError::CheckSigVerify);
+                    }
+                } else {
+                    self.push_bool(is_valid);
+                }
+            }
+            OP_CHECKMULTISIG | OP_CHECKMULTISIGVERIFY => {
+                let is_valid = self.exec_check_multisig(opcode)?;
+                if opcode == OP_CHECKMULTISIGVERIFY {
+                    if !is_valid {
+                        return Err(ScriptError::CheckMultisigVerify);
+                    }
+                } else {
+                    self.push_bool(is_valid);
+                }
+            }
+            OP_CHECKDATASIG | OP_CHECKDATASIGVERIFY => {
+                self.require_stack(3)?;
+                let pubkey = self.pop()?;
+                let msg = self.pop()?;
+                let sig = self.pop()?;
+                check_data_sig_encoding(&sig, self.flags)?;
+                check_pubkey_encoding(&pubkey, self.flags)?;
+                let ctx = self.ctx(opcode)?;
+                let is_valid = !sig.is_empty() && {
+                    let msg_hash = Sha256::digest(msg);
+                    self.check_raw_sig(ctx, &sig, &pubkey, msg_hash.byte_array().clone())
+                };
+                if !is_valid && !sig.is_empty() && self.flags.contains(ScriptFlags::NULLFAIL) {
+                    return Err(ScriptError::NullFail);
+                }
+                if opcode == OP_CHECKDATASIGVERIFY {
+                    if !is_valid {
+                        return Err(ScriptError::CheckDataSigVerify);
+                    }
+                } else {
+                    self.push_bool(is_valid);
+                }
+            }
+            OP_REVERSEBYTES => {
+                let mut data = self.pop()?.to_vec();
+                data.reverse();
+                self.stack.push(data.into());
+            }
+
+            // native introspection
+            OP_INPUTINDEX..=OP_OUTPUTBYTECODE
+                if self.flags.contains(ScriptFlags::NATIVE_INTROSPECTION) =>
+            {
+                self.exec_introspection(opcode)?
+            }
+
+            _ => return Err(ScriptError::BadOpcode(opcode)),
+        }
+        Ok(())
+    }
+
+    fn exec_upgradable_nop(&self) -> Result<(), ScriptError> {
+        if self.flags.contains(ScriptFlags::DISCOURAGE_UPGRADABLE_NOPS) {
+            return Err(ScriptError::DiscourageUpgradableNops);
+        }
+        Ok(())
+    }
+
+    /// Duplicate `num` elements, starting at `depth` elements from the top.
+    fn dup_n(&mut self, num: usize, depth: usize) -> Result<(), ScriptError> {
+        self.require_stack(depth)?;
+        let start = self.stack.len() - depth;
+        let elements = self.stack[start..start + num].to_vec();
+        self.stack.extend(elements);
+        Ok(())
+    }
+
+    fn exec_hash<H: Hashed>(&mut self) -> Result<(), ScriptError> {
+        let data = self.pop()?;
+        self.stack.push(Bytes::from_slice(H::digest(data).as_ref()));
+        Ok(())
+    }
+
+    fn check_lock_time(&self, opcode: u8, lock_time: i64) -> Result<(), ScriptError> {
+        let ctx = self.ctx(opcode)?;
+        let tx = ctx.unsigned_tx.tx();
+        let tx_lock_time = tx.lock_time as i64;
+        // Lock times must be of the same type, either both block heights or both timestamps
+        let is_same_type = (tx_lock_time < LOCKTIME_THRESHOLD) == (lock_time < LOCKTIME_THRESHOLD);
+        if !is_same_type || lock_time > tx_lock_time {
+            return Err(ScriptError::UnsatisfiedLockTime);
+        }
+        // A finalized input would disable the tx's lock time
+        let input = tx
+            .inputs
+            .get(ctx.input_idx)
+            .ok_or(ScriptError::InvalidTxInputIndex(ctx.input_idx as i64))?;
+        if input.sequence == SequenceNo::finalized() {
+            return Err(ScriptError::UnsatisfiedLockTime);
+        }
+        Ok(())
+    }
+
+    fn check_sequence(&self, opcode: u8, sequence: i64) -> Result<(), ScriptError> {
+        let ctx = self.ctx(opcode)?;
+        let tx = ctx.unsigned_tx.tx();
+        if tx.version < 2 {
+            return Err(ScriptError::UnsatisfiedLockTime);
+        }
+        let input = tx
+            .inputs
+            .get(ctx.input_idx)
+            .ok_or(ScriptError::InvalidTxInputIndex(ctx.input_idx as i64))?;
+        let tx_sequence = input.sequence.as_u32() as i64;
+        if tx_sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+            return Err(ScriptError::UnsatisfiedLockTime);
+        }
+        let mask = CSV_TYPE_FLAG as i64 | SEQUENCE_LOCKTIME_MASK;
+        let tx_sequence = tx_sequence & mask;
+        let sequence = sequence & mask;
+        let type_flag = CSV_TYPE_FLAG as i64;
+        let is_same_type = (tx_sequence < type_flag) == (sequence < type_flag);
+        if !is_same_type || sequence > tx_sequence {
+            return Err(ScriptError::UnsatisfiedLockTime);
+        }
+        Ok(())
+    }
+
+    fn parse_pubkey(ecc: &dyn Ecc, pubkey: &[u8]) -> Option<PubKey> {
+        if let Ok(pubkey) = <[u8; PUBKEY_LENGTH]>::try_from(pubkey) {
+            return ecc.pubkey_from_array(pubkey).ok();
+        }
+        if let Ok(pubkey) = <[u8; 65]>::try_from(pubkey) {
+            return ecc.pubkey_from_uncompressed(pubkey).ok();
+        }
+        None
+    }
+
+    /// Verify a signature without sighash byte, either Schnorr or ECDSA depending on its size.
+    fn check_raw_sig(
+        &self,
+        ctx: &TxContext<'_>,
+        sig: &[u8],
+        pubkey: &[u8],
+        msg: ByteArray<32>,
+    ) -> bool {
+        let pubkey = match Self::parse_pubkey(ctx.ecc, pubkey) {
+            Some(pubkey) => pubkey,
+            None => return false,
+        };
+        if self.flags.contains(ScriptFlags::SCHNORR) && sig.len() == SCHNORR_SIGNATURE_SIZE {
+            return ctx
+                .ecc
+                .schnorr_verify(&pubkey, msg, &Bytes::from_slice(sig))
+                .is_ok();
+        }
+        match ctx.ecc.normalize_sig(&Bytes::from_slice(sig)) {
+            Ok(sig) => ctx.ecc.verify(&pubkey, msg, &sig).is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    /// Current script code with the given signatures removed, for legacy (non-FORKID)
+    /// signatures only.
+    fn cleanup_script_code<'a>(&self, sigs: impl IntoIterator<Item = &'a Bytes>) -> Script {
+        let mut script_code = self.script_code.clone();
+        for sig in sigs {
+            if sig.as_ref().last().is_some_and(|&byte| byte & 0x40 == 0) {
+                let sig_push = Script::from_ops([Op::push_bytes(sig.clone())].into_iter())
+                    .expect("Minimal push is valid");
+                script_code = script_code.find_and_delete(&sig_push);
+            }
+        }
+        script_code
+    }
+
+    /// Verify a non-empty tx signature (including sighash byte) against the script code.
+    fn check_tx_sig(
+        &self,
+        ctx: &TxContext<'_>,
+        script_code: &Script,
+        sig: &[u8],
+        pubkey: &[u8],
+    ) -> Result<bool, ScriptError> {
+        let (&sig_hash_byte, raw_sig) = sig.split_last().expect("Empty sig");
+        let sig_hash_type = match SigHashType::from_u32(sig_hash_byte as u32) {
+            Some(sig_hash_type) if sig_hash_type.variant != SigHashTypeVariant::Lotus => {
+                sig_hash_type
+            }
+            _ => return Ok(false),
+        };
+        let coin = ctx
+            .spent_coins
+            .get(ctx.input_idx)
+            .ok_or(ScriptError::MissingSpentCoin(ctx.input_idx))?;
+        let sighash = ctx.unsigned_tx.sighash_for_script_code(
+            ctx.input_idx,
+            sig_hash_type,
+            script_code,
+            coin.tx_output.value,
+        )?;
+        Ok(self.check_raw_sig(ctx, raw_sig, pubkey, sighash.byte_array().clone()))
+    }
+
+    fn exec_check_multisig(&mut self, opcode: u8) -> Result<bool, ScriptError> {
+        // Stack: <dummy> <sig_1> ... <sig_m> <m> <pubkey_1> ... <pubkey_n> <n>
+        let num_keys = self.peek_num(0, self.max_num_size)?;
+        if num_keys < 0 || num_keys as usize > MAX_PUBKEYS_PER_MULTISIG {
+            return Err(ScriptError::PubKeyCount);
+        }
+        let num_keys = num_keys as usize;
+        self.op_count += num_keys;
+        if self.op_count > MAX_OPS_PER_SCRIPT {
+            return Err(ScriptError::OpCount);
+        }
+        let num_sigs = self.peek_num(num_keys + 1, self.max_num_size)?;
+        if num_sigs < 0 || num_sigs as usize > num_keys {
+            return Err(ScriptError::SigCount);
+        }
+        let num_sigs = num_sigs as usize;
+        let num_elements = num_keys + num_sigs + 3;
+        self.require_stack(num_elements)?;
+        let mut elements = self.stack.split_off(self.stack.len() - num_elements);
+        let dummy = elements.remove(0);
+        let sigs = &elements[..num_sigs];
+        let pubkeys = &elements[num_sigs + 1..num_sigs + 1 + num_keys];
+        let script_code = self.cleanup_script_code(sigs);
+
+        if self.flags.contains(ScriptFlags::SCHNORR_MULTISIG) && !dummy.is_empty() {
+            self.check_multisig_schnorr(opcode, &script_code, &dummy, sigs, pubkeys)?;
+            return Ok(true);
+        }
+        if self.flags.contains(ScriptFlags::NULLDUMMY) && !dummy.is_empty() {
+            return Err(ScriptError::SigNullDummy);
+        }
+        let mut is_valid = true;
+        // Match signatures to pubkeys from the top of the stack downwards
+        let mut num_sigs_left = num_sigs;
+        let mut num_keys_left = num_keys;
+        while is_valid && num_sigs_left > 0 {
+            let sig = &sigs[num_sigs_left - 1];
+            let pubkey = &pubkeys[num_keys_left - 1];
+            check_tx_ecdsa_sig_encoding(sig, self.flags)?;
+            check_pubkey_encoding(pubkey, self.flags)?;
+            if !sig.is_empty() && self.check_tx_sig(self.ctx(opcode)?, &script_code, sig, pubkey)? {
+                num_sigs_left -= 1;
+            }
+            num_keys_left -= 1;
+            if num_sigs_left > num_keys_left {
+                is_valid = false;
+            }
+        }
+        let has_nonempty_sig = sigs.iter().any(|sig| !sig.is_empty());
+        if !is_valid && has_nonempty_sig && self.flags.contains(ScriptFlags::NULLFAIL) {
+            return Err(ScriptError::NullFail);
+        }
+        Ok(is_valid)
+    }
+
+    fn check_multisig_schnorr(
+        &self,
+        opcode: u8,
+        script_code: &Script,
+        dummy: &[u8],
+        sigs: &[Bytes],
+        pubkeys: &[Bytes],
+    ) -> Result<(), ScriptError> {
+        // Dummy is a little-endian bitfield of which pubkeys have a signature
+        if dummy.len() != pubkeys.len().div_ceil(8) {
+            return Err(ScriptError::InvalidBitfieldSize);
+        }
+        let mut bitfield = 0u32;
+        for (idx, &byte) in dummy.iter().enumerate() {
+            bitfield |= (byte as u32) << (8 * idx);
+        }
+        if bitfield >> pubkeys.len() != 0 {
+            return Err(ScriptError::InvalidBitRange);
+        }
+        if bitfield.count_ones() as usize != sigs.len() {
+            return Err(ScriptError::InvalidBitCount);
+        }
+        let checked_keys = pubkeys
+            .iter()
+            .enumerate()
+            .filter(|&(key_idx, _)| bitfield & (1 << key_idx) != 0)
+            .map(|(_, pubkey)| pubkey);
+        for (sig, pubkey) in sigs.iter().zip(checked_keys) {
+            check_tx_schnorr_sig_encoding(sig, self.flags)?;
+            check_pubkey_encoding(pubkey, self.flags)?;
+            let ctx = self.ctx(opcode)?;
+            if !self.check_tx_sig(ctx, script_code, sig, pubkey)? {
+                return Err(ScriptError::NullFail);
+            }
+        }
+        Ok(())
+    }
+
+    fn exec_introspection(&mut self, opcode: u8) -> Result<(), ScriptError> {
+        let ctx = self.ctx(opcode)?;
+        let tx = ctx.unsigned_tx.tx();
+        match opcode {
+            OP_INPUTINDEX => self.push_num(Some(ctx.input_idx as i64)),
+            OP_ACTIVEBYTECODE => self.push_element(self.script_code.bytecode().clone()),
+            OP_TXVERSION => self.push_num(Some(tx.version as i64)),
+            OP_TXINPUTCOUNT => self.push_num(Some(tx.inputs.len() as i64)),
+            OP_TXOUTPUTCOUNT => self.push_num(Some(tx.outputs.len() as i64)),
+            OP_TXLOCKTIME => self.push_num(Some(tx.lock_time as i64)),
+            OP_UTXOVALUE
+            | OP_UTXOBYTECODE
+            | OP_OUTPOINTTXHASH
+            | OP_OUTPOINTINDEX
+            | OP_INPUTBYTECODE
+            | OP_INPUTSEQUENCENUMBER => {
+                let idx = self.pop_num()?;
+                let input = match usize::try_from(idx).ok().and_then(|i| tx.inputs.get(i)) {
+                    Some(input) => input,
+                    None => return Err(ScriptError::InvalidTxInputIndex(idx)),
+                };
+                let spent_coin = || {
+                    ctx.spent_coins
+                        .get(idx as usize)
+                        .ok_or(ScriptError::MissingSpentCoin(idx as usize))
+                };
+                match opcode {
+                    OP_UTXOVALUE => self.push_num(Some(spent_coin()?.tx_output.value)),
+                    OP_UTXOBYTECODE => {
+                        self.push_element(spent_coin()?.tx_output.script.bytecode().clone())
+                    }
+                    OP_OUTPOINTTXHASH => {
+                        self.push_element(Bytes::from_slice(input.prev_out.txid.as_slice()))
+                    }
+                    OP_OUTPOINTINDEX => self.push_num(Some(input.prev_out.out_idx as i64)),
+                    OP_INPUTBYTECODE => self.push_element(input.script.bytecode().clone()),
+                    _ => self.push_num(Some(input.sequence.as_u32() as i64)),
+                }
+            }
+            _ => {
+                let idx = self.pop_num()?;
+                let output = match usize::try_from(idx).ok().and_then(|i| tx.outputs.get(i)) {
+                    Some(output) => output,
+                    None => return Err(ScriptError::InvalidTxOutputIndex(idx)),
+                };
+                match opcode {
+                    OP_OUTPUTVALUE => self.push_num(Some(output.value)),
+                    _ => self.push_element(output.script.bytecode().clone()),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ecc::{PubKey, PUBKEY_LENGTH},
+        interpreter::{
+            eval_script,
+            mock_ecc::{mock_sig, MockEcc},
+            ScriptError, ScriptFlags, TxContext,
+        },
+        opcode::*,
+        Bytes, Coin, Hashed, OutPoint, Script, SequenceNo, Sha256, Sha256d, SigHashType, TxInput,
+        TxOutput, UnhashedTx, UnsignedTx,
+    };
+
+    const COIN_VALUE: i64 = 10_000;
+
+    fn eval(bytecode: &[u8], flags: ScriptFlags) -> Result<Vec<Bytes>, ScriptError> {
+        let mut stack = Vec::new();
+        eval_script(&mut stack, &Script::from_slice(bytecode), flags, None)?;
+        Ok(stack)
+    }
+
+    fn stack(elements: &[&[u8]]) -> Vec<Bytes> {
+        elements.iter().map(|&element| element.into()).collect()
+    }
+
+    fn make_tx() -> UnsignedTx {
+        UnsignedTx::new(UnhashedTx {
+            version: 1,
+            inputs: vec![TxInput {
+                prev_out: OutPoint {
+                    txid: Sha256d::new([1; 32]),
+                    out_idx: 0,
+                },
+                script: Script::default(),
+                sequence: SequenceNo::finalized(),
+                sign_data: None,
+            }],
+            outputs: vec![TxOutput {
+                value: COIN_VALUE - 1000,
+                script: Script::opreturn(&[]),
+            }],
+            lock_time: 0,
+        })
+    }
+
+    /// Like `eval`, but with a tx context verifying signatures with `MockEcc`.
+    fn eval_tx(bytecode: &[u8], flags: ScriptFlags) -> Result<Vec<Bytes>, ScriptError> {
+        let unsigned_tx = make_tx();
+        let spent_coins = [Coin {
+            tx_output: TxOutput {
+                value: COIN_VALUE,
+                script: Script::from_slice(bytecode),
+            },
+            height: None,
+            is_coinbase: false,
+        }];
+        let ctx = TxContext {
+            ecc: &MockEcc,
+            unsigned_tx: &unsigned_tx,
+            spent_coins: &spent_coins,
+            input_idx: 0,
+        };
+        let mut stack = Vec::new();
+        eval_script(&mut stack, &Script::from_slice(bytecode), flags, Some(&ctx))?;
+        Ok(stack)
+    }
+
+    fn pubkey(byte: u8) -> PubKey {
+        let mut pubkey = [byte; PUBKEY_LENGTH];
+        pubkey[0] = 0x02;
+        PubKey::new_unchecked(pubkey)
+    }
+
+    /// Schnorr signature (with ALL|FORKID) of the tx, committing to the given script code.
+    fn tx_sig(script_code: &[u8], pubkey: &PubKey) -> Vec<u8> {
+        let sig_hash_type = SigHashType::ALL_BIP143;
+        let sighash = make_tx()
+            .sighash_for_script_code(
+                0,
+                sig_hash_type,
+                &Script::from_slice(script_code),
+                COIN_VALUE,
+            )
+            .unwrap();
+        let sig = mock_sig(sighash.as_slice(), pubkey);
+        [sig.as_ref(), &[sig_hash_type.to_u32() as u8]].concat()
+    }
+
+    /// Push `data` of up to 75 bytes.
+    fn push(data: &[u8]) -> Vec<u8> {
+        [&[data.len() as u8], data].concat()
+    }
+
+    #[test]
+    fn test_eval_script_stack_ops() -> Result<(), ScriptError> {
+        let flags = ScriptFlags::NONE;
+        assert_eq!(eval(&[OP_1, OP_2, OP_SWAP], flags)?, stack(&[&[2], &[1]]));
+        assert_eq!(
+            eval(&[OP_1, OP_2, OP_3, OP_ROT], flags)?,
+            stack(&[&[2], &[3], &[1]]),
+        );
+        assert_eq!(
+            eval(&[OP_1, OP_2, OP_TUCK], flags)?,
+            stack(&[&[2], &[1], &[2]]),
+        );
+        assert_eq!(
+            eval(&[OP_1, OP_2, OP_3, OP_2, OP_PICK], flags)?,
+            stack(&[&[1], &[2], &[3], &[1]]),
+        );
+        assert_eq!(
+            eval(&[OP_1, OP_2, OP_3, OP_2, OP_ROLL], flags)?,
+            stack(&[&[2], &[3], &[1]]),
+        );
+        assert_eq!(
+            eval(&[OP_1, OP_2, OP_3, OP_4, OP_5, OP_6, OP_2ROT], flags)?,
+            stack(&[&[3], &[4], &[5], &[6], &[1], &[2]]),
+        );
+        assert_eq!(
+            eval(
+                &[OP_1, OP_TOALTSTACK, OP_2, OP_FROMALTSTACK, OP_DEPTH],
+                flags
+            )?,
+            stack(&[&[2], &[1], &[2]]),
+        );
+        assert_eq!(eval(&[OP_0, OP_IFDUP], flags)?, stack(&[&[]]));
+        assert_eq!(
+            eval(&[OP_DROP], flags),
+            Err(ScriptError::InvalidStackOperation),
+        );
+        assert_eq!(
+            eval(&[OP_1, OP_1, OP_PICK], flags),
+            Err(ScriptError::InvalidStackOperation),
+        );
+        assert_eq!(
+            eval(&[OP_FROMALTSTACK], flags),
+            Err(ScriptError::InvalidAltstackOperation),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_script_conditionals() -> Result<(), ScriptError> {
+        let flags = ScriptFlags::NONE;
+        assert_eq!(
+            eval(&[OP_1, OP_IF, OP_2, OP_ELSE, OP_3, OP_ENDIF], flags)?,
+            stack(&[&[2]]),
+        );
+        assert_eq!(
+            eval(&[OP_0, OP_IF, OP_2, OP_ELSE, OP_3, OP_ENDIF], flags)?,
+            stack(&[&[3]]),
+        );
+        assert_eq!(
+            eval(&[OP_0, OP_NOTIF, OP_2, OP_ENDIF], flags)?,
+            stack(&[&[2]]),
+        );
+        // Nested, unexecuted branch skips OP_RETURN and bad opcodes
+        assert_eq!(
+            eval(
+                &[
+                    OP_0,
+                    OP_IF,
+                    OP_1,
+                    OP_IF,
+                    OP_RETURN,
+                    OP_RESERVED,
+                    OP_ENDIF,
+                    OP_ENDIF
+                ],
+                flags,
+            )?,
+            stack(&[]),
+        );
+        // Negative zero is false
+        assert_eq!(
+            eval(&[0x01, 0x80, OP_IF, OP_2, OP_ENDIF], flags)?,
+            stack(&[]),
+        );
+        assert_eq!(
+            eval(&[OP_1, OP_IF], flags),
+            Err(ScriptError::UnbalancedConditional),
+        );
+        assert_eq!(
+            eval(&[OP_ENDIF], flags),
+            Err(ScriptError::UnbalancedConditional),
+        );
+        assert_eq!(
+            eval(&[OP_IF, OP_ENDIF], flags),
+            Err(ScriptError::UnbalancedConditional),
+        );
+        assert_eq!(
+            eval(&[OP_0, OP_IF, OP_VERIF, OP_ENDIF], flags),
+            Err(ScriptError::BadOpcode(OP_VERIF)),
+        );
+        assert_eq!(
+            eval(&[OP_0, OP_IF, OP_INVERT, OP_ENDIF], flags),
+            Err(ScriptError::DisabledOpcode(OP_INVERT)),
+        );
+        assert_eq!(eval(&[OP_0, OP_VERIFY], flags), Err(ScriptError::Verify));
+        assert_eq!(eval(&[OP_RETURN], flags), Err(ScriptError::OpReturn));
+        assert_eq!(
+            eval(&[OP_RESERVED], flags),
+            Err(ScriptError::BadOpcode(OP_RESERVED)),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_script_splice_and_bitwise() -> Result<(), ScriptError> {
+        let flags = ScriptFlags::NONE;
+        assert_eq!(
+            eval(&[0x02, 0x12, 0x34, 0x01, 0x56, OP_CAT], flags)?,
+            stack(&[&[0x12, 0x34, 0x56]]),
+        );
+        assert_eq!(
+            eval(&[0x03, 0x12, 0x34, 0x56, OP_1, OP_SPLIT], flags)?,
+            stack(&[&[0x12], &[0x34, 0x56]]),
+        );
+        assert_eq!(
+            eval(&[0x01, 0x12, OP_2, OP_SPLIT], flags),
+            Err(ScriptError::InvalidSplitRange),
+        );
+        assert_eq!(
+            eval(&[0x01, 0x82, OP_4, OP_NUM2BIN], flags)?,
+            stack(&[&[0x02, 0x00, 0x00, 0x80]]),
+        );
+        assert_eq!(
+            eval(&[0x02, 0x12, 0x34, OP_1, OP_NUM2BIN], flags),
+            Err(ScriptError::ImpossibleEncoding),
+        );
+        assert_eq!(
+            eval(&[0x04, 0x02, 0x00, 0x00, 0x80, OP_BIN2NUM], flags)?,
+            stack(&[&[0x82]]),
+        );
+        assert_eq!(
+            eval(&[0x02, 0x12, 0x34, OP_SIZE], flags)?,
+            stack(&[&[0x12, 0x34], &[2]]),
+        );
+        assert_eq!(
+            eval(&[0x02, 0x12, 0x34, OP_REVERSEBYTES], flags)?,
+            stack(&[&[0x34, 0x12]]),
+        );
+        assert_eq!(
+            eval(&[0x02, 0x0f, 0xf0, 0x02, 0x3c, 0x3c, OP_XOR], flags)?,
+            stack(&[&[0x33, 0xcc]]),
+        );
+        assert_eq!(
+            eval(&[0x02, 0x0f, 0xf0, 0x01, 0x3c, OP_AND], flags),
+            Err(ScriptError::InvalidOperandSize),
+        );
+        assert_eq!(
+            eval(&[OP_1, OP_1, OP_EQUALVERIFY, OP_1, OP_2, OP_EQUAL], flags)?,
+            stack(&[&[]]),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_script_arithmetic() -> Result<(), ScriptError> {
+        let flags = ScriptFlags::NONE;
+        assert_eq!(eval(&[OP_2, OP_3, OP_ADD], flags)?, stack(&[&[5]]));
+        assert_eq!(eval(&[OP_2, OP_3, OP_SUB], flags)?, stack(&[&[0x81]]));
+        assert_eq!(eval(&[OP_7, OP_2, OP_DIV], flags)?, stack(&[&[3]]));
+        assert_eq!(eval(&[OP_7, OP_1NEGATE, OP_MOD], flags)?, stack(&[&[]]),);
+        assert_eq!(
+            eval(&[OP_1NEGATE, OP_ABS, OP_NEGATE], flags)?,
+            stack(&[&[0x81]]),
+        );
+        assert_eq!(eval(&[OP_3, OP_2, OP_5, OP_WITHIN], flags)?, stack(&[&[1]]),);
+        assert_eq!(
+            eval(&[OP_16, OP_4, OP_MAX, OP_16, OP_NUMEQUAL], flags)?,
+            stack(&[&[1]]),
+        );
+        assert_eq!(
+            eval(&[OP_1, OP_0, OP_DIV], flags),
+            Err(ScriptError::DivByZero)
+        );
+        assert_eq!(
+            eval(&[OP_1, OP_0, OP_MOD], flags),
+            Err(ScriptError::ModByZero)
+        );
+        // 32-bit: 5 byte numbers are out of range, results may be 5 bytes
+        let max_int = [0x04, 0xff, 0xff, 0xff, 0x7f];
+        assert_eq!(
+            eval(&[max_int.as_ref(), &[OP_1ADD]].concat(), flags)?,
+            stack(&[&[0x00, 0x00, 0x00, 0x80, 0x00]]),
+        );
+        assert_eq!(
+            eval(&[max_int.as_ref(), &[OP_1ADD, OP_1ADD]].concat(), flags),
+            Err(ScriptError::InvalidNumberRange),
+        );
+        assert_eq!(
+            eval(&[OP_2, OP_3, OP_MUL], flags),
+            Err(ScriptError::DisabledOpcode(OP_MUL)),
+        );
+        // 64-bit integers enable OP_MUL and check for overflows
+        let flags = ScriptFlags::INTEGERS_64BIT;
+        assert_eq!(eval(&[OP_2, OP_3, OP_MUL], flags)?, stack(&[&[6]]));
+        assert_eq!(
+            eval(&[max_int.as_ref(), &[OP_1ADD, OP_1ADD]].concat(), flags)?,
+            stack(&[&[0x01, 0x00, 0x00, 0x80, 0x00]]),
+        );
+        let max_int64 = [0x08, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f];
+        assert_eq!(
+            eval(&[max_int64.as_ref(), &[OP_1ADD]].concat(), flags),
+            Err(ScriptError::IntegerOverflow),
+        );
+        assert_eq!(
+            eval(&[max_int64.as_ref(), &[OP_NEGATE, OP_1SUB]].concat(), flags),
+            Err(ScriptError::IntegerOverflow),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_script_hashes() -> Result<(), ScriptError> {
+        let flags = ScriptFlags::NONE;
+        assert_eq!(
+            eval(&[OP_0, OP_SHA256], flags)?,
+            stack(&[&hex_literal::hex!(
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+            )]),
+        );
+        assert_eq!(
+            eval(&[OP_0, OP_HASH160], flags)?,
+            stack(&[&hex_literal::hex!(
+                "b472a266d0bd89c13706a4132ccfb16f7c3b9fcb"
+            )]),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_script_flags() {
+        assert_eq!(eval(&[OP_NOP1], ScriptFlags::NONE), Ok(vec![]));
+        assert_eq!(
+            eval(&[OP_NOP1], ScriptFlags::DISCOURAGE_UPGRADABLE_NOPS),
+            Err(ScriptError::DiscourageUpgradableNops),
+        );
+        assert_eq!(eval(&[0x01, 0x05], ScriptFlags::NONE), Ok(stack(&[&[5]])));
+        assert_eq!(
+            eval(&[0x01, 0x05], ScriptFlags::MINIMALDATA),
+            Err(ScriptError::MinimalData),
+        );
+        assert_eq!(
+            eval(&[OP_PUSHDATA1, 0x01, 0x20], ScriptFlags::MINIMALDATA),
+            Err(ScriptError::MinimalData),
+        );
+        assert_eq!(
+            eval(&[0x02, 0x01, 0x00, OP_1ADD], ScriptFlags::MINIMALDATA),
+            Err(ScriptError::NonMinimalNumber),
+        );
+        assert_eq!(
+            eval(&[OP_1, OP_CHECKLOCKTIMEVERIFY], ScriptFlags::XEC_MANDATORY),
+            Err(ScriptError::MissingTxContext(OP_CHECKLOCKTIMEVERIFY)),
+        );
+        assert_eq!(
+            eval(&[OP_INPUTINDEX], ScriptFlags::XEC_MANDATORY),
+            Err(ScriptError::BadOpcode(OP_INPUTINDEX)),
+        );
+        assert_eq!(
+            eval(&[OP_INPUTINDEX], ScriptFlags::BCH_MANDATORY),
+            Err(ScriptError::MissingTxContext(OP_INPUTINDEX)),
+        );
+        let too_many_ops = vec![OP_NOP; 202];
+        assert_eq!(
+            eval(&too_many_ops, ScriptFlags::NONE),
+            Err(ScriptError::OpCount),
+        );
+        let too_big_push = [[OP_PUSHDATA2, 0x09, 0x02].as_ref(), &[0; 521]].concat();
+        assert_eq!(
+            eval(&too_big_push, ScriptFlags::NONE),
+            Err(ScriptError::PushSize),
+        );
+    }
+
+    #[test]
+    fn test_eval_script_minimal_data() {
+        let pushdata1 = |data: &[u8]| [&[OP_PUSHDATA1, data.len() as u8], data].concat();
+        let pushdata2 = |data: &[u8]| {
+            [
+                &[OP_PUSHDATA2],
+                (data.len() as u16).to_le_bytes().as_ref(),
+                data,
+            ]
+            .concat()
+        };
+        let pushdata4 = |data: &[u8]| {
+            [
+                &[OP_PUSHDATA4],
+                (data.len() as u32).to_le_bytes().as_ref(),
+                data,
+            ]
+            .concat()
+        };
+        let ok = |elements: &[&[u8]]| Ok(stack(elements));
+        let err = Err(ScriptError::MinimalData);
+        let cases = [
+            (vec![OP_0], ok(&[&[]])),
+            (vec![OP_PUSHDATA1, 0x00], err.clone()),
+            (vec![0x01, 0x00], ok(&[&[0x00]])),
+            (vec![0x01, 0x01], err.clone()),
+            (vec![0x01, 0x10], err.clone()),
+            (vec![0x01, 0x11], ok(&[&[0x11]])),
+            (vec![0x01, 0x81], err.clone()),
+            (vec![OP_1NEGATE], ok(&[&[0x81]])),
+            (vec![0x02, 0x01, 0x02], ok(&[&[0x01, 0x02]])),
+            (pushdata1(&[0x01, 0x02]), err.clone()),
+            (pushdata1(&[0xaa; 75]), err.clone()),
+            (pushdata1(&[0xaa; 76]), ok(&[&[0xaa; 76]])),
+            (pushdata2(&[0xaa; 255]), err.clone()),
+            (pushdata2(&[0xaa; 256]), ok(&[&[0xaa; 256]])),
+            (pushdata4(&[0xaa; 256]), err.clone()),
+            // Pushes are only checked when executed
+            (vec![OP_0, OP_IF, 0x01, 0x05, OP_ENDIF], ok(&[])),
+            (
+                vec![0x02, 0x05, 0x00, OP_1ADD],
+                Err(ScriptError::NonMinimalNumber),
+            ),
+            (
+                vec![0x01, 0x80, OP_1ADD],
+                Err(ScriptError::NonMinimalNumber),
+            ),
+            (vec![0x01, 0x11, OP_1ADD], ok(&[&[0x12]])),
+        ];
+        for (bytecode, expected) in cases {
+            assert_eq!(
+                eval(&bytecode, ScriptFlags::MINIMALDATA),
+                expected,
+                "{}",
+                hex::encode(&bytecode),
+            );
+            assert!(eval(&bytecode, ScriptFlags::NONE).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_eval_script_disabled_and_bad_opcodes() {
+        let flags = ScriptFlags::NONE;
+        let disabled = [
+            OP_INVERT,
+            OP_2MUL,
+            OP_2DIV,
+            OP_RAWLEFTBITSHIFT,
+            OP_MULPOW2,
+            OP_MUL,
+        ];
+        for opcode in disabled {
+            let expected = Err(ScriptError::DisabledOpcode(opcode));
+            assert_eq!(eval(&[OP_1, OP_1, opcode], flags), expected);
+            assert_eq!(eval(&[OP_0, OP_IF, opcode, OP_ENDIF], flags), expected);
+        }
+        assert_eq!(
+            eval(&[OP_2, OP_3, OP_MUL], ScriptFlags::INTEGERS_64BIT),
+            Ok(stack(&[&[6]])),
+        );
+
+        let bad = [
+            OP_RESERVED,
+            OP_SCRIPTTYPE,
+            OP_RESERVED1,
+            OP_RESERVED2,
+            0xbd,
+            OP_INVALIDOPCODE,
+        ];
+        for opcode in bad {
+            assert_eq!(eval(&[opcode], flags), Err(ScriptError::BadOpcode(opcode)),);
+            // Unknown opcodes are fine unless executed
+            assert_eq!(eval(&[OP_0, OP_IF, opcode, OP_ENDIF], flags), Ok(vec![]));
+        }
+        // ...except OP_VERIF and OP_VERNOTIF, which are in the OP_IF..OP_ENDIF range
+        for opcode in [OP_VERIF, OP_VERNOTIF] {
+            let expected = Err(ScriptError::BadOpcode(opcode));
+            assert_eq!(eval(&[OP_1, opcode, OP_ENDIF], flags), expected);
+            assert_eq!(eval(&[OP_0, OP_IF, opcode, OP_ENDIF], flags), expected);
+        }
+        // Introspection is only enabled with NATIVE_INTROSPECTION
+        for opcode in OP_INPUTINDEX..=OP_OUTPUTBYTECODE {
+            assert_eq!(
+                eval_tx(&[OP_0, opcode], flags),
+                Err(ScriptError::BadOpcode(opcode)),
+            );
+        }
+    }
+
+    #[test]
+    fn test_eval_script_nullfail() {
+        let flags = ScriptFlags::XEC_MANDATORY;
+        let no_nullfail = ScriptFlags::from_bits(flags.bits() & !ScriptFlags::NULLFAIL.bits());
+        let pk = pubkey(1);
+        let sig = tx_sig(&[OP_CHECKSIG], &pk);
+        let wrong_sig = tx_sig(&[OP_CHECKSIG], &pubkey(2));
+        let checksig = |sig: &[u8]| {
+            [
+                push(sig),
+                push(pk.as_slice()),
+                vec![OP_CODESEPARATOR, OP_CHECKSIG],
+            ]
+            .concat()
+        };
+        let msg = b"msg";
+        let data_sig = mock_sig(Sha256::digest(msg.as_ref().into()).as_slice(), &pk);
+        let wrong_data_sig = mock_sig(Sha256::digest(b"other".as_ref().into()).as_slice(), &pk);
+        let checkdatasig = |sig: &[u8]| {
+            [
+                push(sig),
+                push(msg),
+                push(pk.as_slice()),
+                vec![OP_CHECKDATASIG],
+            ]
+            .concat()
+        };
+        // Well-formed DER signature (r = s = 1) that fails verification
+        let der_sig = [0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x01, 0x41];
+        let checkmultisig = |sig: &[u8]| {
+            [
+                vec![OP_0],
+                push(sig),
+                vec![OP_1],
+                push(pk.as_slice()),
+                vec![OP_1, OP_CODESEPARATOR, OP_CHECKMULTISIG],
+            ]
+            .concat()
+        };
+        let t = Ok(stack(&[&[1]]));
+        let f = Ok(stack(&[&[]]));
+        let nullfail = Err(ScriptError::NullFail);
+        let cases = [
+            (checksig(&sig), t.clone(), t.clone()),
+            (checksig(&wrong_sig), nullfail.clone(), f.clone()),
+            (checksig(&[]), f.clone(), f.clone()),
+            (checkdatasig(&data_sig), t.clone(), t.clone()),
+            (checkdatasig(&wrong_data_sig), nullfail.clone(), f.clone()),
+            (checkdatasig(&[]), f.clone(), f.clone()),
+            (checkmultisig(&der_sig), nullfail.clone(), f.clone()),
+            (checkmultisig(&[]), f.clone(), f.clone()),
+            // Schnorr signatures aren't allowed in legacy multisig
+            (
+                checkmultisig(&sig),
+                Err(ScriptError::SigBadLength),
+                Err(ScriptError::SigBadLength),
+            ),
+        ];
+        for (bytecode, expected, expected_no_nullfail) in cases {
+            let hex = hex::encode(&bytecode);
+            assert_eq!(eval_tx(&bytecode, flags), expected, "{}", hex);
+            assert_eq!(
+                eval_tx(&bytecode, no_nullfail),
+                expected_no_nullfail,
+                "{}",
+                hex
+            );
+        }
+    }
+
+    #[test]
+    fn test_eval_script_schnorr_multisig() {
+        let flags = ScriptFlags::XEC_MANDATORY;
+        let pubkeys = [pubkey(1), pubkey(2), pubkey(3)];
+        let script_code = [OP_CHECKMULTISIG];
+        let sigs = pubkeys
+            .iter()
+            .map(|pk| tx_sig(&script_code, pk))
+            .collect::<Vec<_>>();
+        // 2-of-3 multisig, the dummy is the bitfield of the signing pubkeys
+        let multisig = |dummy: &[u8], sigs: &[&[u8]]| {
+            let mut bytecode = push(dummy);
+            for sig in sigs {
+                bytecode.extend(push(sig));
+            }
+            bytecode.push(OP_2);
+            for pk in &pubkeys {
+                bytecode.extend(push(pk.as_slice()));
+            }
+            bytecode.extend([OP_3, OP_CODESEPARATOR]);
+            bytecode.extend(script_code);
+            bytecode
+        };
+        let der_sig = [0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x01, 0x41];
+        let cases = [
+            (
+                multisig(&[0b101], &[&sigs[0], &sigs[2]]),
+                Ok(stack(&[&[1]])),
+            ),
+            (
+                multisig(&[0b011], &[&sigs[0], &sigs[1]]),
+                Ok(stack(&[&[1]])),
+            ),
+            (
+                multisig(&[0b101, 0x00], &[&sigs[0], &sigs[2]]),
+                Err(ScriptError::InvalidBitfieldSize),
+            ),
+            (
+                multisig(&[0b1001], &[&sigs[0], &sigs[2]]),
+                Err(ScriptError::InvalidBitRange),
+            ),
+            (
+                multisig(&[0b111], &[&sigs[0], &sigs[2]]),
+                Err(ScriptError::InvalidBitCount),
+            ),
+            (
+                multisig(&[0b001], &[&sigs[0], &sigs[2]]),
+                Err(ScriptError::InvalidBitCount),
+            ),
+            // Signatures must be in pubkey order
+            (
+                multisig(&[0b101], &[&sigs[2], &sigs[0]]),
+                Err(ScriptError::NullFail),
+            ),
+            (
+                multisig(&[0b011], &[&sigs[0], &sigs[2]]),
+                Err(ScriptError::NullFail),
+            ),
+            (
+                multisig(&[0b101], &[&sigs[0], &der_sig]),
+                Err(ScriptError::SigNonSchnorr),
+            ),
+            (
+                multisig(&[0b101], &[&sigs[0], &[]]),
+                Err(ScriptError::SigNonSchnorr),
+            ),
+        ];
+        for (bytecode, expected) in cases {
+            assert_eq!(
+                eval_tx(&bytecode, flags),
+                expected,
+                "{}",
+                hex::encode(&bytecode)
+            );
+        }
+        // Without SCHNORR_MULTISIG, the dummy must be empty
+        let legacy_flags =
+            ScriptFlags::from_bits(flags.bits() & !ScriptFlags::SCHNORR_MULTISIG.bits())
+                | ScriptFlags::NULLDUMMY;
+        assert_eq!(
+            eval_tx(&multisig(&[0b101], &[&sigs[0], &sigs[2]]), legacy_flags),
+            Err(ScriptError::SigNullDummy),
+        );
+    }
+}
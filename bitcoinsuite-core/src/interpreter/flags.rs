@@ -0,0 +1,122 @@
+use std::ops::{BitOr, BitOrAssign};
+
+/// Verification flags for the script interpreter, mirroring the `SCRIPT_VERIFY_*` flags of the
+/// BCH/XEC node implementations.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ScriptFlags(u32);
+
+impl ScriptFlags {
+    pub const NONE: ScriptFlags = ScriptFlags(0);
+    /// Evaluate P2SH subscripts (BIP16)
+    pub const P2SH: ScriptFlags = ScriptFlags(1 << 0);
+    /// Enforce strict conformance to DER and SEC2 for signatures and pubkeys
+    pub const STRICTENC: ScriptFlags = ScriptFlags(1 << 1);
+    /// Enforce strict DER (BIP66) compliance
+    pub const DERSIG: ScriptFlags = ScriptFlags(1 << 2);
+    /// Enforce low S values in ECDSA signatures
+    pub const LOW_S: ScriptFlags = ScriptFlags(1 << 3);
+    /// Require the dummy element of legacy OP_CHECKMULTISIG to be empty
+    pub const NULLDUMMY: ScriptFlags = ScriptFlags(1 << 4);
+    /// Require scriptSig to be push-only
+    pub const SIGPUSHONLY: ScriptFlags = ScriptFlags(1 << 5);
+    /// Require minimal encodings for pushes and script numbers
+    pub const MINIMALDATA: ScriptFlags = ScriptFlags(1 << 6);
+    /// Fail on the upgradable NOPs OP_NOP1, OP_NOP4-OP_NOP10
+    pub const DISCOURAGE_UPGRADABLE_NOPS: ScriptFlags = ScriptFlags(1 << 7);
+    /// Require exactly one stack element after evaluation, requires P2SH
+    pub const CLEANSTACK: ScriptFlags = ScriptFlags(1 << 8);
+    /// Enable OP_CHECKLOCKTIMEVERIFY (BIP65)
+    pub const CHECKLOCKTIMEVERIFY: ScriptFlags = ScriptFlags(1 << 9);
+    /// Enable OP_CHECKSEQUENCEVERIFY (BIP112)
+    pub const CHECKSEQUENCEVERIFY: ScriptFlags = ScriptFlags(1 << 10);
+    /// Require failed signature checks to use an empty signature
+    pub const NULLFAIL: ScriptFlags = ScriptFlags(1 << 14);
+    /// Require signatures to use SIGHASH_FORKID
+    pub const SIGHASH_FORKID: ScriptFlags = ScriptFlags(1 << 16);
+    /// Interpret 64 byte signatures (excluding the sighash byte) as Schnorr signatures
+    pub const SCHNORR: ScriptFlags = ScriptFlags(1 << 17);
+    /// Enable the bitfield-based Schnorr mode of OP_CHECKMULTISIG
+    pub const SCHNORR_MULTISIG: ScriptFlags = ScriptFlags(1 << 21);
+    /// Use 64-bit script integers and enable OP_MUL (BCH May 2022)
+    pub const INTEGERS_64BIT: ScriptFlags = ScriptFlags(1 << 24);
+    /// Enable the native introspection opcodes (BCH May 2022)
+    pub const NATIVE_INTROSPECTION: ScriptFlags = ScriptFlags(1 << 25);
+
+    /// Consensus rules of eCash (XEC)
+    pub const XEC_MANDATORY: ScriptFlags = ScriptFlags(
+        Self::P2SH.0
+            | Self::STRICTENC.0
+            | Self::DERSIG.0
+            | Self::LOW_S.0
+            | Self::SIGPUSHONLY.0
+            | Self::CHECKLOCKTIMEVERIFY.0
+            | Self::CHECKSEQUENCEVERIFY.0
+            | Self::NULLFAIL.0
+            | Self::SIGHASH_FORKID.0
+            | Self::SCHNORR.0
+            | Self::SCHNORR_MULTISIG.0,
+    );
+    /// Standardness rules of eCash (XEC)
+    pub const XEC_STANDARD: ScriptFlags = ScriptFlags(
+        Self::XEC_MANDATORY.0
+            | Self::MINIMALDATA.0
+            | Self::DISCOURAGE_UPGRADABLE_NOPS.0
+            | Self::CLEANSTACK.0,
+    );
+    /// Consensus rules of Bitcoin Cash (BCH)
+    pub const BCH_MANDATORY: ScriptFlags = ScriptFlags(
+        Self::XEC_MANDATORY.0
+            | Self::MINIMALDATA.0
+            | Self::INTEGERS_64BIT.0
+            | Self::NATIVE_INTROSPECTION.0,
+    );
+    /// Standardness rules of Bitcoin Cash (BCH)
+    pub const BCH_STANDARD: ScriptFlags = ScriptFlags(
+        Self::BCH_MANDATORY.0 | Self::DISCOURAGE_UPGRADABLE_NOPS.0 | Self::CLEANSTACK.0,
+    );
+
+    pub const fn from_bits(bits: u32) -> Self {
+        ScriptFlags(bits)
+    }
+
+    pub const fn bits(&self) -> u32 {
+        self.0
+    }
+
+    pub const fn contains(&self, other: ScriptFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for ScriptFlags {
+    type Output = ScriptFlags;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        ScriptFlags(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for ScriptFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::ScriptFlags;
+
+    #[test]
+    fn test_script_flags() {
+        let flags = ScriptFlags::P2SH | ScriptFlags::CLEANSTACK;
+        assert_eq!(flags.bits(), 0x101);
+        assert!(flags.contains(ScriptFlags::P2SH));
+        assert!(flags.contains(ScriptFlags::CLEANSTACK));
+        assert!(!flags.contains(ScriptFlags::STRICTENC));
+        assert!(!flags.contains(ScriptFlags::P2SH | ScriptFlags::STRICTENC));
+        assert!(flags.contains(ScriptFlags::NONE));
+        assert!(ScriptFlags::XEC_STANDARD.contains(ScriptFlags::XEC_MANDATORY));
+        assert!(ScriptFlags::BCH_STANDARD.contains(ScriptFlags::BCH_MANDATORY));
+        assert!(!ScriptFlags::XEC_STANDARD.contains(ScriptFlags::INTEGERS_64BIT));
+    }
+}
@@ -0,0 +1,90 @@
+use crate::{
+    ecc::{DummyEcc, Ecc, EccError, PubKey, SecKey, VerifySignatureError, PUBKEY_LENGTH},
+    ByteArray, Bytes,
+};
+
+/// Accepts a signature if it consists of the message followed by the pubkey's x coordinate.
+/// Signs with secret keys equal to the pubkey's x coordinate.
+pub(crate) struct MockEcc;
+
+pub(crate) fn mock_sig(msg: &[u8], pubkey: &PubKey) -> Bytes {
+    [msg, &pubkey.as_slice()[1..]].concat().into()
+}
+
+impl Ecc for MockEcc {
+    fn pubkey_from_array(&self, pubkey: [u8; PUBKEY_LENGTH]) -> Result<PubKey, EccError> {
+        Ok(PubKey::new_unchecked(pubkey))
+    }
+
+    fn pubkey_from_uncompressed(&self, _pubkey: [u8; 65]) -> Result<PubKey, EccError> {
+        Err(EccError::InvalidPublicKey)
+    }
+
+    fn seckey_from_array(&self, seckey: [u8; 32]) -> Result<SecKey, EccError> {
+        Ok(SecKey::new_unchecked(seckey))
+    }
+
+    fn sign(&self, seckey: &SecKey, msg: ByteArray<32>) -> Bytes {
+        [msg.as_ref(), seckey.as_slice()].concat().into()
+    }
+
+    fn schnorr_sign(&self, seckey: &SecKey, msg: ByteArray<32>) -> Bytes {
+        self.sign(seckey, msg)
+    }
+
+    fn verify(
+        &self,
+        pubkey: &PubKey,
+        msg: ByteArray<32>,
+        sig: &Bytes,
+    ) -> Result<(), VerifySignatureError> {
+        match *sig == mock_sig(msg.as_ref(), pubkey) {
+            true => Ok(()),
+            false => Err(VerifySignatureError::IncorrectSignature),
+        }
+    }
+
+    fn schnorr_verify(
+        &self,
+        pubkey: &PubKey,
+        msg: ByteArray<32>,
+        sig: &Bytes,
+    ) -> Result<(), VerifySignatureError> {
+        self.verify(pubkey, msg, sig)
+    }
+
+    fn derive_pubkey(&self, seckey: &SecKey) -> PubKey {
+        let mut pubkey = [2; PUBKEY_LENGTH];
+        pubkey[1..].copy_from_slice(seckey.as_slice());
+        PubKey::new_unchecked(pubkey)
+    }
+
+    fn seckey_tweak_add(&self, _seckey: &SecKey, _tweak: [u8; 32]) -> Result<SecKey, EccError> {
+        Err(EccError::InvalidTweak)
+    }
+
+    fn pubkey_tweak_add(&self, _pubkey: &PubKey, _tweak: [u8; 32]) -> Result<PubKey, EccError> {
+        Err(EccError::InvalidTweak)
+    }
+
+    fn serialize_pubkey_uncompressed(&self, pubkey: &PubKey) -> [u8; 65] {
+        DummyEcc.serialize_pubkey_uncompressed(pubkey)
+    }
+
+    fn normalize_sig(&self, sig: &Bytes) -> Result<Bytes, EccError> {
+        Ok(sig.clone())
+    }
+
+    fn sign_recoverable(&self, seckey: &SecKey, msg: ByteArray<32>) -> (i32, Bytes) {
+        DummyEcc.sign_recoverable(seckey, msg)
+    }
+
+    fn recover_sig(
+        &self,
+        _data: &[u8],
+        _recover_id: i32,
+        _msg: ByteArray<32>,
+    ) -> Result<PubKey, EccError> {
+        Err(EccError::RecoveryFailed)
+    }
+}
@@ -0,0 +1,17 @@
+mod error;
+mod eval;
+mod flags;
+#[cfg(test)]
+pub(crate) mod mock_ecc;
+mod script_num;
+mod sig_encoding;
+mod verify;
+mod verify_tx;
+
+pub use self::error::{InputScriptError, ScriptError, TxVerifyError};
+pub use self::eval::*;
+pub use self::flags::*;
+pub use self::script_num::*;
+pub use self::sig_encoding::*;
+pub use self::verify::*;
+pub use self::verify_tx::*;
@@ -0,0 +1,185 @@
+use crate::{interpreter::ScriptError, Bytes};
+
+/// Max. byte size of script numbers without 64-bit integers.
+pub const MAX_SCRIPT_NUM_SIZE_32BIT: usize = 4;
+/// Max. byte size of script numbers with 64-bit integers.
+pub const MAX_SCRIPT_NUM_SIZE_64BIT: usize = 8;
+
+/// Decode a little-endian sign-magnitude script number of at most `max_size` bytes.
+pub fn decode_script_num(
+    data: &[u8],
+    max_size: usize,
+    require_minimal: bool,
+) -> Result<i64, ScriptError> {
+    if data.len() > max_size {
+        return Err(ScriptError::InvalidNumberRange);
+    }
+    if require_minimal && !is_minimally_encoded(data) {
+        return Err(ScriptError::NonMinimalNumber);
+    }
+    let last_byte = match data.last() {
+        Some(&last_byte) => last_byte,
+        None => return Ok(0),
+    };
+    let mut magnitude = 0u64;
+    for (idx, &byte) in data.iter().enumerate() {
+        let byte = if idx == data.len() - 1 {
+            byte & 0x7f
+        } else {
+            byte
+        };
+        magnitude |= (byte as u64) << (8 * idx);
+    }
+    if magnitude > i64::MAX as u64 {
+        return Err(ScriptError::InvalidNumberRange);
+    }
+    match last_byte & 0x80 != 0 {
+        true => Ok(-(magnitude as i64)),
+        false => Ok(magnitude as i64),
+    }
+}
+
+/// Encode a number as minimally encoded little-endian sign-magnitude script number.
+pub fn encode_script_num(num: i64) -> Bytes {
+    let mut bytes = Vec::with_capacity(9);
+    let is_neg = num < 0;
+    let mut abs_num = num.unsigned_abs();
+    while abs_num > 0 {
+        bytes.push((abs_num & 0xff) as u8);
+        abs_num >>= 8;
+    }
+    if let Some(&last_byte) = bytes.last() {
+        if last_byte & 0x80 != 0 {
+            bytes.push(if is_neg { 0x80 } else { 0 });
+        } else if is_neg {
+            *bytes.last_mut().unwrap() |= 0x80;
+        }
+    }
+    bytes.into()
+}
+
+/// Whether the number has no superfluous leading (most significant) zero bytes.
+pub fn is_minimally_encoded(data: &[u8]) -> bool {
+    match data {
+        [] => true,
+        // If the most significant byte is 0x00 or 0x80 (excluding the sign bit), it's only
+        // allowed if the next byte has its sign bit set; otherwise the number could be shorter.
+        [.., last] if last & 0x7f != 0 => true,
+        [_] => false,
+        [.., second_last, _] => second_last & 0x80 != 0,
+    }
+}
+
+/// Remove superfluous leading zero bytes from a number, keeping its value (used by OP_BIN2NUM).
+pub fn minimally_encode(data: &[u8]) -> Bytes {
+    let mut data = data.to_vec();
+    let last_byte = match data.last() {
+        Some(&last_byte) => last_byte,
+        None => return data.into(),
+    };
+    if last_byte & 0x7f != 0 {
+        return data.into();
+    }
+    if data.len() == 1 {
+        return Bytes::new();
+    }
+    if data[data.len() - 2] & 0x80 != 0 {
+        return data.into();
+    }
+    // Find the last non-zero byte and move the sign bit into it (or after it)
+    for idx in (0..data.len() - 1).rev() {
+        if data[idx] != 0 {
+            if data[idx] & 0x80 != 0 {
+                data[idx + 1] = last_byte;
+                data.truncate(idx + 2);
+            } else {
+                data[idx] |= last_byte;
+                data.truncate(idx + 1);
+            }
+            return data.into();
+        }
+    }
+    Bytes::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::{
+        decode_script_num, encode_script_num, is_minimally_encoded, minimally_encode, ScriptError,
+    };
+
+    #[test]
+    fn test_encode_script_num() {
+        let cases: &[(i64, &[u8])] = &[
+            (0, &[]),
+            (1, &[0x01]),
+            (-1, &[0x81]),
+            (0x7f, &[0x7f]),
+            (-0x7f, &[0xff]),
+            (0x80, &[0x80, 0x00]),
+            (-0x80, &[0x80, 0x80]),
+            (0xff, &[0xff, 0x00]),
+            (0x100, &[0x00, 0x01]),
+            (-0x100, &[0x00, 0x81]),
+            (0x7fff_ffff, &[0xff, 0xff, 0xff, 0x7f]),
+            (-0x7fff_ffff, &[0xff, 0xff, 0xff, 0xff]),
+            (0x8000_0000, &[0x00, 0x00, 0x00, 0x80, 0x00]),
+            (i64::MAX, &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f]),
+            (-i64::MAX, &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+        ];
+        for &(num, bytes) in cases {
+            assert_eq!(encode_script_num(num).as_ref(), bytes);
+            assert_eq!(decode_script_num(bytes, 9, true), Ok(num));
+        }
+    }
+
+    #[test]
+    fn test_decode_script_num() {
+        assert_eq!(decode_script_num(&[0x00], 4, false), Ok(0));
+        assert_eq!(decode_script_num(&[0x80], 4, false), Ok(0));
+        assert_eq!(decode_script_num(&[0x01, 0x00], 4, false), Ok(1));
+        assert_eq!(decode_script_num(&[0x01, 0x80], 4, false), Ok(-1));
+        assert_eq!(
+            decode_script_num(&[0x00], 4, true),
+            Err(ScriptError::NonMinimalNumber),
+        );
+        assert_eq!(
+            decode_script_num(&[0x01, 0x80], 4, true),
+            Err(ScriptError::NonMinimalNumber),
+        );
+        assert_eq!(
+            decode_script_num(&[0x01, 0x00, 0x00, 0x00, 0x00], 4, false),
+            Err(ScriptError::InvalidNumberRange),
+        );
+        assert_eq!(
+            decode_script_num(&[0x01, 0x00, 0x00, 0x00, 0x00], 5, false),
+            Ok(1),
+        );
+    }
+
+    #[test]
+    fn test_minimally_encode() {
+        let cases: &[(&[u8], &[u8])] = &[
+            (&[], &[]),
+            (&[0x00], &[]),
+            (&[0x80], &[]),
+            (&[0x00, 0x00, 0x80], &[]),
+            (&[0x01], &[0x01]),
+            (&[0x01, 0x00], &[0x01]),
+            (&[0x01, 0x80], &[0x81]),
+            (&[0x01, 0x00, 0x00, 0x80], &[0x81]),
+            (&[0x80, 0x00], &[0x80, 0x00]),
+            (&[0x80, 0x00, 0x00], &[0x80, 0x00]),
+            (&[0x80, 0x00, 0x80], &[0x80, 0x80]),
+            (&[0x12, 0x34, 0x00, 0x00], &[0x12, 0x34]),
+        ];
+        for &(data, expected) in cases {
+            assert_eq!(minimally_encode(data).as_ref(), expected);
+            assert!(is_minimally_encoded(expected));
+        }
+        assert!(!is_minimally_encoded(&[0x00]));
+        assert!(!is_minimally_encoded(&[0x80]));
+        assert!(!is_minimally_encoded(&[0x01, 0x00]));
+        assert!(is_minimally_encoded(&[0x80, 0x00]));
+    }
+}
@@ -0,0 +1,267 @@
+use crate::{
+    ecc::{PUBKEY_LENGTH, SCHNORR_SIGNATURE_SIZE},
+    interpreter::{ScriptError, ScriptFlags},
+    SigHashType, SigHashTypeVariant,
+};
+
+/// Half of the order of the secp256k1 curve, the highest S value allowed with LOW_S.
+const HALF_ORDER: [u8; 32] = [
+    0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0x5d, 0x57, 0x6e, 0x73, 0x57, 0xa4, 0x50, 0x1d, 0xdf, 0xe9, 0x2f, 0x46, 0x68, 0x1b, 0x20, 0xa0,
+];
+
+/// Whether `sig` (excluding the sighash byte) is a strict DER encoded ECDSA signature (BIP66).
+pub fn is_valid_der_encoding(sig: &[u8]) -> bool {
+    // Format: 0x30 [total-length] 0x02 [R-length] [R] 0x02 [S-length] [S]
+    if sig.len() < 8 || sig.len() > 72 {
+        return false;
+    }
+    if sig[0] != 0x30 || sig[1] as usize != sig.len() - 2 {
+        return false;
+    }
+    let len_r = sig[3] as usize;
+    if 5 + len_r >= sig.len() {
+        return false;
+    }
+    let len_s = sig[5 + len_r] as usize;
+    if len_r + len_s + 6 != sig.len() {
+        return false;
+    }
+    is_valid_der_int(&sig[2..4 + len_r]) && is_valid_der_int(&sig[4 + len_r..])
+}
+
+/// Checks an `0x02 [length] [int]` part of a DER signature; the length is already checked.
+fn is_valid_der_int(part: &[u8]) -> bool {
+    match part {
+        // Integers must be non-empty and must not be negative
+        [0x02, _] | [0x02, _, 0x80..=0xff, ..] => false,
+        // Null bytes at the start are only allowed if the next byte would be negative otherwise
+        [0x02, _, 0x00, 0x00..=0x7f, ..] => false,
+        [0x02, ..] => true,
+        _ => false,
+    }
+}
+
+/// Whether the S value of the DER encoded signature (excluding the sighash byte) is low.
+pub fn is_low_der_s(sig: &[u8]) -> bool {
+    if !is_valid_der_encoding(sig) {
+        return false;
+    }
+    let len_r = sig[3] as usize;
+    let s = &sig[6 + len_r..];
+    let leading_zeros = s.iter().take_while(|&&byte| byte == 0).count();
+    let s = &s[leading_zeros..];
+    if s.len() > HALF_ORDER.len() {
+        return false;
+    }
+    let mut padded_s = [0; 32];
+    padded_s[32 - s.len()..].copy_from_slice(s);
+    padded_s <= HALF_ORDER
+}
+
+fn check_raw_sig_encoding(sig: &[u8], flags: ScriptFlags) -> Result<(), ScriptError> {
+    if flags.contains(ScriptFlags::SCHNORR) && sig.len() == SCHNORR_SIGNATURE_SIZE {
+        return Ok(());
+    }
+    check_raw_ecdsa_sig_encoding(sig, flags)
+}
+
+fn check_raw_ecdsa_sig_encoding(sig: &[u8], flags: ScriptFlags) -> Result<(), ScriptError> {
+    let der_flags = ScriptFlags::DERSIG | ScriptFlags::LOW_S | ScriptFlags::STRICTENC;
+    if flags.bits() & der_flags.bits() != 0 && !is_valid_der_encoding(sig) {
+        return Err(ScriptError::SigDer);
+    }
+    if flags.contains(ScriptFlags::LOW_S) && !is_low_der_s(sig) {
+        return Err(ScriptError::SigHighS);
+    }
+    Ok(())
+}
+
+fn check_sighash_encoding(sig_hash_byte: u8, flags: ScriptFlags) -> Result<(), ScriptError> {
+    if !flags.contains(ScriptFlags::STRICTENC) {
+        return Ok(());
+    }
+    let sig_hash_type = match SigHashType::from_u32(sig_hash_byte as u32) {
+        // Lotus sighashes are not valid for this (BCH/XEC) interpreter
+        Some(sig_hash_type) if sig_hash_type.variant != SigHashTypeVariant::Lotus => sig_hash_type,
+        _ => return Err(ScriptError::SigHashType),
+    };
+    let uses_fork_id = sig_hash_type.variant == SigHashTypeVariant::Bip143;
+    let requires_fork_id = flags.contains(ScriptFlags::SIGHASH_FORKID);
+    if uses_fork_id && !requires_fork_id {
+        return Err(ScriptError::IllegalForkId);
+    }
+    if !uses_fork_id && requires_fork_id {
+        return Err(ScriptError::MustUseForkId);
+    }
+    Ok(())
+}
+
+/// Check the encoding of a tx signature (including the sighash byte) for OP_CHECKSIG.
+pub fn check_tx_sig_encoding(sig: &[u8], flags: ScriptFlags) -> Result<(), ScriptError> {
+    // Empty signatures are allowed; they simply fail the check
+    let (&sig_hash_byte, raw_sig) = match sig.split_last() {
+        Some(split) => split,
+        None => return Ok(()),
+    };
+    check_raw_sig_encoding(raw_sig, flags)?;
+    check_sighash_encoding(sig_hash_byte, flags)
+}
+
+/// Check the encoding of a tx signature for the legacy mode of OP_CHECKMULTISIG, which doesn't
+/// allow Schnorr signatures.
+pub fn check_tx_ecdsa_sig_encoding(sig: &[u8], flags: ScriptFlags) -> Result<(), ScriptError> {
+    let (&sig_hash_byte, raw_sig) = match sig.split_last() {
+        Some(split) => split,
+        None => return Ok(()),
+    };
+    if flags.contains(ScriptFlags::SCHNORR) && raw_sig.len() == SCHNORR_SIGNATURE_SIZE {
+        return Err(ScriptError::SigBadLength);
+    }
+    check_raw_ecdsa_sig_encoding(raw_sig, flags)?;
+    check_sighash_encoding(sig_hash_byte, flags)
+}
+
+/// Check the encoding of a tx signature for the Schnorr mode of OP_CHECKMULTISIG.
+pub fn check_tx_schnorr_sig_encoding(sig: &[u8], flags: ScriptFlags) -> Result<(), ScriptError> {
+    match sig.split_last() {
+        Some((&sig_hash_byte, raw_sig)) if raw_sig.len() == SCHNORR_SIGNATURE_SIZE => {
+            check_sighash_encoding(sig_hash_byte, flags)
+        }
+        _ => Err(ScriptError::SigNonSchnorr),
+    }
+}
+
+/// Check the encoding of a data signature (without sighash byte) for OP_CHECKDATASIG.
+pub fn check_data_sig_encoding(sig: &[u8], flags: ScriptFlags) -> Result<(), ScriptError> {
+    if sig.is_empty() {
+        return Ok(());
+    }
+    check_raw_sig_encoding(sig, flags)
+}
+
+/// Check that a pubkey is either compressed or uncompressed, if STRICTENC is set.
+pub fn check_pubkey_encoding(pubkey: &[u8], flags: ScriptFlags) -> Result<(), ScriptError> {
+    if !flags.contains(ScriptFlags::STRICTENC) {
+        return Ok(());
+    }
+    match pubkey {
+        [0x02 | 0x03, ..] if pubkey.len() == PUBKEY_LENGTH => Ok(()),
+        [0x04, ..] if pubkey.len() == 65 => Ok(()),
+        _ => Err(ScriptError::PubKeyType),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+
+    use crate::interpreter::{
+        check_pubkey_encoding, check_tx_ecdsa_sig_encoding, check_tx_schnorr_sig_encoding,
+        check_tx_sig_encoding, is_low_der_s, is_valid_der_encoding, ScriptError, ScriptFlags,
+    };
+
+    const SIG: [u8; 70] = hex!(
+        "304402207228f8a93734f17480911e04ee5d83d8ccb1e880c8b46f71ce1c2f99c87627bd022069a70f991882d15929b565507cb380108719c69f8105e2c16c6e1d4b4efb747f"
+    );
+
+    #[test]
+    fn test_der_encoding() {
+        assert!(is_valid_der_encoding(&SIG));
+        assert!(is_low_der_s(&SIG));
+        // Wrong total length
+        let mut sig = SIG;
+        sig[1] = 0x45;
+        assert!(!is_valid_der_encoding(&sig));
+        // Negative R
+        let mut sig = SIG;
+        sig[4] = 0x80;
+        assert!(!is_valid_der_encoding(&sig));
+        // Superfluous null byte
+        assert!(!is_valid_der_encoding(&hex!("3007020200010201 01")));
+        assert!(is_valid_der_encoding(&hex!("3007020200800201 01")));
+        assert!(!is_valid_der_encoding(&hex!("3006020002020101")));
+        // Short S is low
+        let short_s = hex!("300702010102027fff");
+        assert!(is_valid_der_encoding(&short_s));
+        assert!(is_low_der_s(&short_s));
+        // S = n / 2 is the highest low S
+        let half_order_s = hex!(
+            "30250201010220 7fffffffffffffffffffffffffffffff5d576e7357a4501ddfe92f46681b20a0"
+        );
+        assert!(is_valid_der_encoding(&half_order_s));
+        assert!(is_low_der_s(&half_order_s));
+        // High S
+        let high_s = hex!(
+            "30250201010220 7fffffffffffffffffffffffffffffff5d576e7357a4501ddfe92f46681b20a1"
+        );
+        assert!(is_valid_der_encoding(&high_s));
+        assert!(!is_low_der_s(&high_s));
+        let high_s = hex!(
+            "30260201010221 00ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff00"
+        );
+        assert!(is_valid_der_encoding(&high_s));
+        assert!(!is_low_der_s(&high_s));
+    }
+
+    #[test]
+    fn test_check_tx_sig_encoding() {
+        let flags = ScriptFlags::XEC_MANDATORY;
+        let sig = [SIG.as_ref(), &[0x41]].concat();
+        assert_eq!(check_tx_sig_encoding(&[], flags), Ok(()));
+        assert_eq!(check_tx_sig_encoding(&sig, flags), Ok(()));
+        assert_eq!(
+            check_tx_sig_encoding(&[SIG.as_ref(), &[0x01]].concat(), flags),
+            Err(ScriptError::MustUseForkId),
+        );
+        assert_eq!(
+            check_tx_sig_encoding(&sig, ScriptFlags::STRICTENC),
+            Err(ScriptError::IllegalForkId),
+        );
+        assert_eq!(
+            check_tx_sig_encoding(&[SIG.as_ref(), &[0x44]].concat(), flags),
+            Err(ScriptError::SigHashType),
+        );
+        assert_eq!(
+            check_tx_sig_encoding(&[SIG.as_ref(), &[0x61]].concat(), flags),
+            Err(ScriptError::SigHashType),
+        );
+        assert_eq!(
+            check_tx_sig_encoding(&[SIG.as_ref(), &[0, 0x41]].concat(), flags),
+            Err(ScriptError::SigDer),
+        );
+        assert_eq!(check_tx_sig_encoding(&[0; 3], ScriptFlags::NONE), Ok(()),);
+        let schnorr_sig = [[0; 64].as_ref(), &[0x41]].concat();
+        assert_eq!(check_tx_sig_encoding(&schnorr_sig, flags), Ok(()));
+        assert_eq!(
+            check_tx_sig_encoding(&schnorr_sig, ScriptFlags::DERSIG),
+            Err(ScriptError::SigDer),
+        );
+        assert_eq!(
+            check_tx_ecdsa_sig_encoding(&schnorr_sig, flags),
+            Err(ScriptError::SigBadLength),
+        );
+        assert_eq!(check_tx_schnorr_sig_encoding(&schnorr_sig, flags), Ok(()));
+        assert_eq!(
+            check_tx_schnorr_sig_encoding(&sig, flags),
+            Err(ScriptError::SigNonSchnorr),
+        );
+    }
+
+    #[test]
+    fn test_check_pubkey_encoding() {
+        let flags = ScriptFlags::STRICTENC;
+        assert_eq!(check_pubkey_encoding(&[2; 33], flags), Ok(()));
+        assert_eq!(check_pubkey_encoding(&[3; 33], flags), Ok(()));
+        assert_eq!(check_pubkey_encoding(&[4; 65], flags), Ok(()));
+        assert_eq!(
+            check_pubkey_encoding(&[4; 33], flags),
+            Err(ScriptError::PubKeyType),
+        );
+        assert_eq!(
+            check_pubkey_encoding(&[2; 65], flags),
+            Err(ScriptError::PubKeyType),
+        );
+        assert_eq!(check_pubkey_encoding(&[], ScriptFlags::NONE), Ok(()));
+    }
+}
@@ -0,0 +1,518 @@
+use crate::{
+    ecc::Ecc,
+    interpreter::{cast_to_bool, eval_script, ScriptError, ScriptFlags, TxContext},
+    opcode::OP_16,
+    Coin, Op, Script, UnsignedTx,
+};
+
+/// Whether the script only consists of pushes (including OP_1NEGATE, OP_RESERVED and OP_1-OP_16).
+pub fn is_push_only(script: &Script) -> bool {
+    script.ops().all(|op| match op {
+        Ok(Op::Push(..)) => true,
+        Ok(Op::Code(opcode)) => opcode <= OP_16,
+        Err(_) => false,
+    })
+}
+
+/// Verify `script_sig` spends `script_pubkey`, including evaluation of P2SH redeem scripts.
+pub fn verify_script(
+    script_sig: &Script,
+    script_pubkey: &Script,
+    flags: ScriptFlags,
+    ctx: Option<&TxContext<'_>>,
+) -> Result<(), ScriptError> {
+    if flags.contains(ScriptFlags::SIGPUSHONLY) && !is_push_only(script_sig) {
+        return Err(ScriptError::SigPushOnly);
+    }
+    let mut stack = Vec::new();
+    eval_script(&mut stack, script_sig, flags, ctx)?;
+    let p2sh_stack = stack.clone();
+    eval_script(&mut stack, script_pubkey, flags, ctx)?;
+    if !stack.last().is_some_and(|top| cast_to_bool(top)) {
+        return Err(ScriptError::EvalFalse);
+    }
+
+    if flags.contains(ScriptFlags::P2SH) && script_pubkey.is_p2sh() {
+        if !is_push_only(script_sig) {
+            return Err(ScriptError::SigPushOnly);
+        }
+        stack = p2sh_stack;
+        // Can't be empty, otherwise the P2SH script would have failed above
+        let redeem_script = Script::new(stack.pop().expect("Empty P2SH stack"));
+        eval_script(&mut stack, &redeem_script, flags, ctx)?;
+        if !stack.last().is_some_and(|top| cast_to_bool(top)) {
+            return Err(ScriptError::EvalFalse);
+        }
+    }
+
+    if flags.contains(ScriptFlags::CLEANSTACK) && stack.len() != 1 {
+        return Err(ScriptError::CleanStack);
+    }
+    Ok(())
+}
+
+/// Verify input `input_idx` of `unsigned_tx` spends `spent_coins[input_idx]`.
+///
+/// `spent_coins` must contain the spent coin of every input of the tx, in input order.
+pub fn verify_input(
+    ecc: &dyn Ecc,
+    unsigned_tx: &UnsignedTx,
+    spent_coins: &[Coin],
+    input_idx: usize,
+    flags: ScriptFlags,
+) -> Result<(), ScriptError> {
+    let input = unsigned_tx
+        .tx()
+        .inputs
+        .get(input_idx)
+        .ok_or(ScriptError::InvalidTxInputIndex(input_idx as i64))?;
+    let coin = spent_coins
+        .get(input_idx)
+        .ok_or(ScriptError::MissingSpentCoin(input_idx))?;
+    let ctx = TxContext {
+        ecc,
+        unsigned_tx,
+        spent_coins,
+        input_idx,
+    };
+    verify_script(&input.script, &coin.tx_output.script, flags, Some(&ctx))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::{
+        ecc::{PubKey, SecKey},
+        interpreter::{
+            mock_ecc::{mock_sig, MockEcc},
+            verify_input, verify_script, ScriptError, ScriptFlags,
+        },
+        opcode::*,
+        BitcoinSuiteError, Bytes, Coin, Hashed, MultisigSigType, MultisigSignatory, Op, OutPoint,
+        Script, SequenceNo, Sha256, Sha256d, ShaRmd160, SigHashType, SignData, SignError,
+        SignField, Signatory, TxInput, TxOutput, UnhashedTx, UnsignedTx,
+    };
+
+    fn make_tx(lock_time: u32) -> UnsignedTx {
+        UnsignedTx::new(UnhashedTx {
+            version: 1,
+            inputs: vec![TxInput {
+                prev_out: OutPoint {
+                    txid: Sha256d::new([1; 32]),
+                    out_idx: 4,
+                },
+                script: Script::default(),
+                sequence: SequenceNo::from_u32(0xffff_fffe),
+                sign_data: None,
+            }],
+            outputs: vec![TxOutput {
+                value: 9000,
+                script: Script::opreturn(&[]),
+            }],
+            lock_time,
+        })
+    }
+
+    fn sign(
+        unsigned_tx: &UnsignedTx,
+        script_code: &Script,
+        value: i64,
+        pubkey: &PubKey,
+        sig_hash_type: SigHashType,
+    ) -> crate::Result<Bytes> {
+        let preimage =
+            unsigned_tx.sighash_preimage_for_script_code(0, sig_hash_type, script_code, value)?;
+        let sighash = Sha256d::digest(preimage);
+        let sig = mock_sig(sighash.as_slice(), pubkey);
+        Ok([sig.as_ref(), &[sig_hash_type.to_u32() as u8]]
+            .concat()
+            .into())
+    }
+
+    fn coin(script: Script, value: i64) -> Coin {
+        Coin {
+            tx_output: TxOutput { value, script },
+            height: None,
+            is_coinbase: false,
+        }
+    }
+
+    /// Minimally push the elements.
+    fn push_script(elements: impl IntoIterator<Item = Bytes>) -> crate::Result<Script> {
+        Script::from_ops(elements.into_iter().map(|element| match *element.as_ref() {
+            [0x81] => Op::Code(OP_1NEGATE),
+            [num @ 1..=16] => Op::Code(OP_1 - 1 + num),
+            _ => Op::push_bytes(element),
+        }))
+    }
+
+    #[test]
+    fn test_verify_script() -> crate::Result<()> {
+        let flags = ScriptFlags::XEC_STANDARD;
+        let redeem_script = Script::new(vec![OP_1].into());
+        let script_pubkey = redeem_script.to_p2sh();
+        let script_sig = push_script([redeem_script.bytecode().clone()])?;
+        verify_script(&script_sig, &script_pubkey, flags, None)?;
+        // Extra element left on the stack
+        let script_sig = Script::new([[OP_1].as_ref(), script_sig.bytecode()].concat().into());
+        assert_eq!(
+            verify_script(&script_sig, &script_pubkey, flags, None),
+            Err(ScriptError::CleanStack),
+        );
+        verify_script(&script_sig, &script_pubkey, ScriptFlags::P2SH, None)?;
+        // Redeem script leaves false on the stack
+        let redeem_script = Script::new(vec![OP_0].into());
+        let script_sig = push_script([redeem_script.bytecode().clone()])?;
+        assert_eq!(
+            verify_script(&script_sig, &redeem_script.to_p2sh(), flags, None),
+            Err(ScriptError::EvalFalse),
+        );
+        // Without P2SH, only the hash is checked
+        verify_script(
+            &script_sig,
+            &redeem_script.to_p2sh(),
+            ScriptFlags::NONE,
+            None,
+        )?;
+        assert_eq!(
+            verify_script(
+                &Script::new(vec![OP_1, OP_NOP].into()),
+                &Script::new(vec![OP_1].into()),
+                flags,
+                None,
+            ),
+            Err(ScriptError::SigPushOnly),
+        );
+        assert_eq!(
+            verify_script(
+                &Script::default(),
+                &Script::new(vec![OP_0].into()),
+                flags,
+                None,
+            ),
+            Err(ScriptError::EvalFalse),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_input_p2pkh() -> crate::Result<()> {
+        let ecc = MockEcc;
+        let flags = ScriptFlags::XEC_STANDARD;
+        let pubkey = PubKey::new_unchecked([2; 33]);
+        let script_pubkey = Script::p2pkh(&ShaRmd160::digest(pubkey.as_slice().into()));
+        let mut unsigned_tx = make_tx(0);
+        let sig = sign(
+            &unsigned_tx,
+            &script_pubkey,
+            10_000,
+            &pubkey,
+            SigHashType::ALL_BIP143,
+        )?;
+        *unsigned_tx.input_at(0).input_script_mut() = Script::p2pkh_spend(&pubkey, sig.clone());
+        let coins = [coin(script_pubkey.clone(), 10_000)];
+        verify_input(&ecc, &unsigned_tx, &coins, 0, flags)?;
+        // Signature commits to the value
+        let coins = [coin(script_pubkey.clone(), 10_001)];
+        assert_eq!(
+            verify_input(&ecc, &unsigned_tx, &coins, 0, flags),
+            Err(ScriptError::NullFail),
+        );
+        assert_eq!(
+            verify_input(&ecc, &unsigned_tx, &coins, 0, ScriptFlags::NONE),
+            Err(ScriptError::EvalFalse),
+        );
+        assert_eq!(
+            verify_input(&ecc, &unsigned_tx, &[], 0, flags),
+            Err(ScriptError::MissingSpentCoin(0)),
+        );
+        // Sighash type must have FORKID set
+        let mut sig = sig.to_vec();
+        *sig.last_mut().unwrap() = 0x01;
+        *unsigned_tx.input_at(0).input_script_mut() = Script::p2pkh_spend(&pubkey, sig.into());
+        let coins = [coin(script_pubkey, 10_000)];
+        assert_eq!(
+            verify_input(&ecc, &unsigned_tx, &coins, 0, flags),
+            Err(ScriptError::MustUseForkId),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_input_legacy() -> crate::Result<()> {
+        let ecc = MockEcc;
+        let flags = ScriptFlags::from_bits(
+            ScriptFlags::XEC_STANDARD.bits() & !ScriptFlags::SIGHASH_FORKID.bits(),
+        );
+        let pubkey = PubKey::new_unchecked([2; 33]);
+        let script_pubkey = Script::p2pkh(&ShaRmd160::digest(pubkey.as_slice().into()));
+        let mut unsigned_tx = make_tx(0);
+        let legacy_all = SigHashType::from_u32(0x01).unwrap();
+        let sig = sign(&unsigned_tx, &script_pubkey, 0, &pubkey, legacy_all)?;
+        *unsigned_tx.input_at(0).input_script_mut() = Script::p2pkh_spend(&pubkey, sig.clone());
+        // Legacy signatures don't commit to the value
+        let coins = [coin(script_pubkey.clone(), 10_000)];
+        verify_input(&ecc, &unsigned_tx, &coins, 0, flags)?;
+        assert_eq!(
+            verify_input(&ecc, &unsigned_tx, &coins, 0, ScriptFlags::XEC_STANDARD),
+            Err(ScriptError::MustUseForkId),
+        );
+
+        // Signatures are removed from the script code, so a script can contain its own signature
+        let script_code = push_script([pubkey.array().to_vec().into()])?;
+        let script_code = Script::new(
+            [[OP_DROP].as_ref(), script_code.bytecode(), &[OP_CHECKSIG]]
+                .concat()
+                .into(),
+        );
+        let sig = sign(&unsigned_tx, &script_code, 0, &pubkey, legacy_all)?;
+        let script_pubkey = Script::new(
+            [
+                push_script([sig.clone()])?.bytecode().as_ref(),
+                script_code.bytecode(),
+            ]
+            .concat()
+            .into(),
+        );
+        *unsigned_tx.input_at(0).input_script_mut() = push_script([sig])?;
+        let coins = [coin(script_pubkey, 10_000)];
+        verify_input(&ecc, &unsigned_tx, &coins, 0, flags)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_input_multisig() -> crate::Result<()> {
+        let ecc = MockEcc;
+        let pubkeys = [
+            PubKey::new_unchecked([2; 33]),
+            PubKey::new_unchecked([3; 33]),
+            PubKey::new_unchecked([[2].as_ref(), &[4; 32]].concat().try_into().unwrap()),
+        ];
+        let redeem_script = Script::multisig(2, pubkeys.iter().map(|pubkey| pubkey.as_slice()));
+        let coins = [coin(redeem_script.to_p2sh(), 5000)];
+        let mut unsigned_tx = make_tx(0);
+        let sigs = pubkeys
+            .iter()
+            .map(|pubkey| {
+                sign(
+                    &unsigned_tx,
+                    &redeem_script,
+                    5000,
+                    pubkey,
+                    SigHashType::ALL_BIP143,
+                )
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+        let redeem_bytecode = redeem_script.bytecode().clone();
+        let mut verify = |script_sig: Script, flags: ScriptFlags| {
+            *unsigned_tx.input_at(0).input_script_mut() = script_sig;
+            verify_input(&ecc, &unsigned_tx, &coins, 0, flags)
+        };
+
+        // Schnorr mode: dummy is a bitfield for pubkeys 0 and 2
+        let flags = ScriptFlags::XEC_STANDARD;
+        let elements = [
+            vec![0b101].into(),
+            sigs[0].clone(),
+            sigs[2].clone(),
+            redeem_bytecode.clone(),
+        ];
+        assert_eq!(verify(push_script(elements.clone())?, flags), Ok(()));
+        let mut wrong_bitfield = elements.clone();
+        wrong_bitfield[0] = vec![0b011].into();
+        assert_eq!(
+            verify(push_script(wrong_bitfield.clone())?, flags),
+            Err(ScriptError::NullFail),
+        );
+        wrong_bitfield[0] = vec![0b111].into();
+        assert_eq!(
+            verify(push_script(wrong_bitfield.clone())?, flags),
+            Err(ScriptError::InvalidBitCount),
+        );
+        wrong_bitfield[0] = vec![0b1001].into();
+        assert_eq!(
+            verify(push_script(wrong_bitfield)?, flags),
+            Err(ScriptError::InvalidBitRange),
+        );
+
+        // Legacy mode: dummy is empty, Schnorr-sized signatures are not allowed
+        let mut script_sig = vec![OP_0];
+        script_sig.extend_from_slice(push_script(elements[1..].to_vec())?.bytecode());
+        let script_sig = Script::new(script_sig.into());
+        assert_eq!(
+            verify(script_sig.clone(), flags),
+            Err(ScriptError::SigBadLength),
+        );
+        assert_eq!(verify(script_sig, ScriptFlags::P2SH), Ok(()));
+        // Legacy mode requires signatures in the order of the pubkeys
+        let mut script_sig = vec![OP_0];
+        script_sig.extend_from_slice(
+            push_script([sigs[2].clone(), sigs[0].clone(), redeem_bytecode])?.bytecode(),
+        );
+        let script_sig = Script::new(script_sig.into());
+        assert_eq!(
+            verify(script_sig.clone(), ScriptFlags::P2SH),
+            Err(ScriptError::EvalFalse),
+        );
+        assert_eq!(
+            verify(script_sig, ScriptFlags::P2SH | ScriptFlags::NULLFAIL),
+            Err(ScriptError::NullFail),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_input_multisig_signatory() -> crate::Result<()> {
+        let ecc = MockEcc;
+        let seckeys = [[2; 32], [3; 32], [4; 32]].map(SecKey::new_unchecked);
+        let pubkeys = seckeys
+            .iter()
+            .map(|seckey| {
+                let pubkey = [[2].as_ref(), seckey.as_slice()].concat();
+                PubKey::new_unchecked(pubkey.try_into().unwrap())
+            })
+            .collect::<Vec<_>>();
+        let redeem_script = Script::multisig(2, pubkeys.iter().map(|pubkey| pubkey.as_slice()));
+        for (sig_type, flags) in [
+            (MultisigSigType::Schnorr, ScriptFlags::XEC_STANDARD),
+            (
+                MultisigSigType::Ecdsa,
+                ScriptFlags::P2SH | ScriptFlags::SIGHASH_FORKID,
+            ),
+        ] {
+            for output_script in [redeem_script.to_p2sh(), redeem_script.clone()] {
+                let coins = [coin(output_script.clone(), 5000)];
+                let mut unsigned_tx = make_tx(0);
+                *unsigned_tx.input_at(0).input_sign_data_mut() = Some(SignData::new(vec![
+                    SignField::Value(5000),
+                    SignField::OutputScript(output_script),
+                    SignField::RedeemScript(redeem_script.clone()),
+                ]));
+                // First party signs with key 2 and hands the signature to the second party
+                let first_party = MultisigSignatory {
+                    seckeys: BTreeMap::from([(2, seckeys[2].clone())]),
+                    partial_sigs: BTreeMap::new(),
+                    sig_type,
+                    sig_hash_type: SigHashType::ALL_BIP143,
+                };
+                assert!(matches!(
+                    first_party.sign_input(&ecc, unsigned_tx.input_at(0)),
+                    Err(BitcoinSuiteError::Sign(SignError::NotEnoughSignatures {
+                        required: 2,
+                        available: 1,
+                    })),
+                ));
+                let partial_sigs = first_party.sign_partial(&ecc, &unsigned_tx.input_at(0))?;
+                let second_party = MultisigSignatory {
+                    seckeys: BTreeMap::from([(0, seckeys[0].clone())]),
+                    partial_sigs,
+                    sig_type,
+                    sig_hash_type: SigHashType::ALL_BIP143,
+                };
+                second_party.sign_input(&ecc, unsigned_tx.input_at(0))?;
+                verify_input(&ecc, &unsigned_tx, &coins, 0, flags)?;
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_input_multisig_signatory_checkbits() -> crate::Result<()> {
+        let ecc = MockEcc;
+        let seckeys = (2..10).map(|byte| SecKey::new_unchecked([byte; 32]));
+        let seckeys = seckeys.collect::<Vec<_>>();
+        let pubkeys = seckeys
+            .iter()
+            .map(|seckey| {
+                let pubkey = [[2].as_ref(), seckey.as_slice()].concat();
+                PubKey::new_unchecked(pubkey.try_into().unwrap())
+            })
+            .collect::<Vec<_>>();
+        let redeem_script = Script::multisig(2, pubkeys.iter().map(|pubkey| pubkey.as_slice()));
+        let output_script = redeem_script.to_p2sh();
+        let coins = [coin(output_script.clone(), 5000)];
+        // Checkbits are pushed minimally, so they pass MINIMALDATA
+        for (key_idxs, dummy_push) in [
+            ([0, 7], [OP_1NEGATE].as_ref()),
+            ([1, 2], &[OP_6]),
+            ([0, 4], &[0x01, 0x11]),
+            ([6, 7], &[0x01, 0xc0]),
+        ] {
+            let mut unsigned_tx = make_tx(0);
+            *unsigned_tx.input_at(0).input_sign_data_mut() = Some(SignData::new(vec![
+                SignField::Value(5000),
+                SignField::OutputScript(output_script.clone()),
+                SignField::RedeemScript(redeem_script.clone()),
+            ]));
+            let signatory = MultisigSignatory {
+                seckeys: key_idxs
+                    .iter()
+                    .map(|&key_idx| (key_idx, seckeys[key_idx].clone()))
+                    .collect(),
+                partial_sigs: BTreeMap::new(),
+                sig_type: MultisigSigType::Schnorr,
+                sig_hash_type: SigHashType::ALL_BIP143,
+            };
+            signatory.sign_input(&ecc, unsigned_tx.input_at(0))?;
+            let input_script = &unsigned_tx.tx().inputs[0].script;
+            assert!(input_script.bytecode().starts_with(dummy_push));
+            verify_input(&ecc, &unsigned_tx, &coins, 0, ScriptFlags::XEC_STANDARD)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_input_data_sig_introspection_locktime() -> crate::Result<()> {
+        let ecc = MockEcc;
+        let flags = ScriptFlags::BCH_STANDARD | ScriptFlags::CHECKLOCKTIMEVERIFY;
+        let pubkey = PubKey::new_unchecked([3; 33]);
+        let msg = b"covenant";
+        let script_pubkey = Script::from_ops(
+            [
+                Op::push_bytes(msg.as_ref().into()),
+                Op::push_bytes(pubkey.as_slice().into()),
+                Op::Code(OP_CHECKDATASIGVERIFY),
+                Op::push_script_num(100),
+                Op::Code(OP_CHECKLOCKTIMEVERIFY),
+                Op::Code(OP_DROP),
+                Op::Code(OP_0),
+                Op::Code(OP_OUTPUTVALUE),
+                Op::push_script_num(9000),
+                Op::Code(OP_NUMEQUALVERIFY),
+                Op::Code(OP_INPUTINDEX),
+                Op::Code(OP_UTXOVALUE),
+                Op::push_script_num(7000),
+                Op::Code(OP_NUMEQUAL),
+            ]
+            .into_iter(),
+        )?;
+        let sig = mock_sig(Sha256::digest(msg.as_ref().into()).as_slice(), &pubkey);
+        let coins = [coin(script_pubkey.clone(), 7000)];
+        let mut unsigned_tx = make_tx(100);
+        *unsigned_tx.input_at(0).input_script_mut() = push_script([sig.clone()])?;
+        verify_input(&ecc, &unsigned_tx, &coins, 0, flags)?;
+        assert_eq!(
+            verify_input(&ecc, &unsigned_tx, &coins, 0, ScriptFlags::XEC_STANDARD),
+            Err(ScriptError::BadOpcode(OP_OUTPUTVALUE)),
+        );
+        assert_eq!(
+            verify_input(&ecc, &unsigned_tx, &[coin(script_pubkey, 7001)], 0, flags),
+            Err(ScriptError::EvalFalse),
+        );
+        // Lock time of the tx too low
+        let mut unsigned_tx = make_tx(99);
+        *unsigned_tx.input_at(0).input_script_mut() = push_script([sig])?;
+        assert_eq!(
+            verify_input(&ecc, &unsigned_tx, &coins, 0, flags),
+            Err(ScriptError::UnsatisfiedLockTime),
+        );
+        // Data signature doesn't match
+        *unsigned_tx.input_at(0).input_script_mut() = push_script([mock_sig(&[0; 32], &pubkey)])?;
+        assert_eq!(
+            verify_input(&ecc, &unsigned_tx, &coins, 0, flags),
+            Err(ScriptError::NullFail),
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+
+use crate::{
+    ecc::Ecc,
+    interpreter::{verify_input, InputScriptError, ScriptFlags, TxVerifyError},
+    BitcoinCode, Coin, UnhashedTx, UnsignedTx,
+};
+
+/// Max. number of satoshis in existence, outputs can't exceed this value.
+pub const MAX_MONEY: i64 = 21_000_000 * 100_000_000;
+/// Consensus min. size of a tx, to prevent confusion with merkle tree nodes.
+pub const MIN_TX_SIZE: usize = 100;
+/// Max. size of a tx to be relayed by nodes.
+pub const MAX_STANDARD_TX_SIZE: usize = 100_000;
+/// Outputs below this value are not relayed by nodes, unless they're OP_RETURN outputs.
+pub const DUST_AMOUNT: i64 = 546;
+
+/// Verify `tx` spends `spent_coins`, which must be given in input order.
+///
+/// Next to the input scripts, this checks the tx size, output values, dust and that inputs pay
+/// for outputs, like the node's mempool would. All input scripts are checked, and failures are
+/// reported per input. Returns the fee of the tx.
+pub fn verify_tx(
+    tx: &UnhashedTx,
+    spent_coins: &[Coin],
+    ecc: &dyn Ecc,
+    flags: ScriptFlags,
+) -> Result<i64, TxVerifyError> {
+    if tx.inputs.is_empty() {
+        return Err(TxVerifyError::NoInputs);
+    }
+    if tx.outputs.is_empty() {
+        return Err(TxVerifyError::NoOutputs);
+    }
+    if spent_coins.len() != tx.inputs.len() {
+        return Err(TxVerifyError::WrongNumSpentCoins {
+            expected: tx.inputs.len(),
+            actual: spent_coins.len(),
+        });
+    }
+    let size = tx.ser().len();
+    if size < MIN_TX_SIZE {
+        return Err(TxVerifyError::TxTooSmall {
+            size,
+            min_size: MIN_TX_SIZE,
+        });
+    }
+    if size > MAX_STANDARD_TX_SIZE {
+        return Err(TxVerifyError::TxTooBig {
+            size,
+            max_size: MAX_STANDARD_TX_SIZE,
+        });
+    }
+
+    let mut spent_outpoints = HashMap::with_capacity(tx.inputs.len());
+    for (input_idx, input) in tx.inputs.iter().enumerate() {
+        if let Some(first_idx) = spent_outpoints.insert(&input.prev_out, input_idx) {
+            return Err(TxVerifyError::DuplicateInput {
+                first_idx,
+                second_idx: input_idx,
+            });
+        }
+    }
+
+    let mut output_sum = 0;
+    for (output_idx, output) in tx.outputs.iter().enumerate() {
+        let value = output.value;
+        if !(0..=MAX_MONEY).contains(&value) {
+            return Err(TxVerifyError::OutputValueOutOfRange { output_idx, value });
+        }
+        if value < DUST_AMOUNT && !output.script.is_opreturn() {
+            return Err(TxVerifyError::DustOutput {
+                output_idx,
+                value,
+                dust_limit: DUST_AMOUNT,
+            });
+        }
+        output_sum += value;
+        if output_sum > MAX_MONEY {
+            return Err(TxVerifyError::OutputValueOutOfRange { output_idx, value });
+        }
+    }
+
+    let mut input_sum = 0;
+    for (input_idx, coin) in spent_coins.iter().enumerate() {
+        let value = coin.tx_output.value;
+        if !(0..=MAX_MONEY).contains(&value) {
+            return Err(TxVerifyError::InputValueOutOfRange { input_idx, value });
+        }
+        input_sum += value;
+        if input_sum > MAX_MONEY {
+            return Err(TxVerifyError::InputValueOutOfRange { input_idx, value });
+        }
+    }
+    if output_sum > input_sum {
+        return Err(TxVerifyError::InsufficientInputs {
+            input_sum,
+            output_sum,
+        });
+    }
+
+    let unsigned_tx = UnsignedTx::new(tx.clone());
+    let input_errors = (0..tx.inputs.len())
+        .filter_map(|input_idx| {
+            let result = verify_input(ecc, &unsigned_tx, spent_coins, input_idx, flags);
+            let error = result.err()?;
+            Some(InputScriptError { input_idx, error })
+        })
+        .collect::<Vec<_>>();
+    if !input_errors.is_empty() {
+        return Err(TxVerifyError::InvalidInputs(input_errors));
+    }
+
+    Ok(input_sum - output_sum)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ecc::DummyEcc,
+        interpreter::{
+            verify_tx, InputScriptError, ScriptError, ScriptFlags, TxVerifyError, MAX_MONEY,
+        },
+        opcode::*,
+        Coin, OutPoint, Script, SequenceNo, Sha256d, TxInput, TxOutput, UnhashedTx,
+    };
+
+    fn make_tx(input_scripts: &[Script], output_values: &[i64]) -> UnhashedTx {
+        UnhashedTx {
+            version: 1,
+            inputs: input_scripts
+                .iter()
+                .enumerate()
+                .map(|(idx, script)| TxInput {
+                    prev_out: OutPoint {
+                        txid: Sha256d::new([idx as u8; 32]),
+                        out_idx: 0,
+                    },
+                    script: script.clone(),
+                    sequence: SequenceNo::finalized(),
+                    sign_data: None,
+                })
+                .collect(),
+            outputs: output_values
+                .iter()
+                .map(|&value| TxOutput {
+                    value,
+                    script: Script::p2sh(&Default::default()),
+                })
+                .collect(),
+            lock_time: 0,
+        }
+    }
+
+    fn coin(value: i64, script: &Script) -> Coin {
+        Coin {
+            tx_output: TxOutput {
+                value,
+                script: script.clone(),
+            },
+            height: Some(1),
+            is_coinbase: false,
+        }
+    }
+
+    #[test]
+    fn test_verify_tx() {
+        let flags = ScriptFlags::XEC_STANDARD;
+        let redeem_script = Script::new(vec![OP_1].into());
+        let p2sh = redeem_script.to_p2sh();
+        let spend_script = Script::new(vec![0x01, OP_1].into());
+        let tx = make_tx(&[spend_script.clone(), spend_script.clone()], &[1000, 2000]);
+        let coins = [coin(1500, &p2sh), coin(2000, &p2sh)];
+        assert_eq!(verify_tx(&tx, &coins, &DummyEcc, flags), Ok(500));
+
+        assert_eq!(
+            verify_tx(&tx, &coins[..1], &DummyEcc, flags),
+            Err(TxVerifyError::WrongNumSpentCoins {
+                expected: 2,
+                actual: 1,
+            }),
+        );
+        assert_eq!(
+            verify_tx(
+                &tx,
+                &[coin(500, &p2sh), coin(2000, &p2sh)],
+                &DummyEcc,
+                flags
+            ),
+            Err(TxVerifyError::InsufficientInputs {
+                input_sum: 2500,
+                output_sum: 3000,
+            }),
+        );
+        assert_eq!(
+            verify_tx(&tx, &[coin(-1, &p2sh), coin(2000, &p2sh)], &DummyEcc, flags),
+            Err(TxVerifyError::InputValueOutOfRange {
+                input_idx: 0,
+                value: -1,
+            }),
+        );
+
+        let tx = make_tx(&[spend_script.clone(), spend_script.clone()], &[545]);
+        assert_eq!(
+            verify_tx(&tx, &coins, &DummyEcc, flags),
+            Err(TxVerifyError::DustOutput {
+                output_idx: 0,
+                value: 545,
+                dust_limit: 546,
+            }),
+        );
+        let tx = make_tx(std::slice::from_ref(&spend_script), &[MAX_MONEY, 1000]);
+        assert_eq!(
+            verify_tx(&tx, &coins[..1], &DummyEcc, flags),
+            Err(TxVerifyError::OutputValueOutOfRange {
+                output_idx: 1,
+                value: 1000,
+            }),
+        );
+
+        let mut tx = make_tx(&[spend_script.clone(), spend_script.clone()], &[1000]);
+        tx.inputs[1].prev_out = tx.inputs[0].prev_out.clone();
+        assert_eq!(
+            verify_tx(&tx, &coins, &DummyEcc, flags),
+            Err(TxVerifyError::DuplicateInput {
+                first_idx: 0,
+                second_idx: 1,
+            }),
+        );
+
+        let mut tx = make_tx(std::slice::from_ref(&spend_script), &[1000]);
+        tx.outputs[0].script = Script::default();
+        assert_eq!(
+            verify_tx(&tx, &coins[..1], &DummyEcc, flags),
+            Err(TxVerifyError::TxTooSmall {
+                size: 62,
+                min_size: 100,
+            }),
+        );
+    }
+
+    #[test]
+    fn test_verify_tx_invalid_inputs() {
+        let flags = ScriptFlags::XEC_STANDARD;
+        let p2sh = Script::new(vec![OP_1].into()).to_p2sh();
+        let tx = make_tx(
+            &[
+                Script::new(vec![0x01, OP_2].into()),
+                Script::new(vec![0x01, OP_1].into()),
+                Script::new(vec![OP_1, 0x01, OP_1].into()),
+            ],
+            &[1000],
+        );
+        let coins = [coin(1000, &p2sh), coin(1000, &p2sh), coin(1000, &p2sh)];
+        let result = verify_tx(&tx, &coins, &DummyEcc, flags);
+        assert_eq!(
+            result,
+            Err(TxVerifyError::InvalidInputs(vec![
+                InputScriptError {
+                    input_idx: 0,
+                    error: ScriptError::EvalFalse,
+                },
+                InputScriptError {
+                    input_idx: 2,
+                    error: ScriptError::CleanStack,
+                },
+            ])),
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "2 input(s) failed script verification, first: Input 0: Script evaluated without \
+             error but finished with a false/empty top stack element",
+        );
+        assert_eq!(
+            TxVerifyError::InvalidInputs(vec![]).to_string(),
+            "0 input(s) failed script verification, first: none",
+        );
+    }
+}
@@ -1,6 +1,9 @@
 mod address;
+mod bip32;
+mod bip39;
 mod bitcoin_code;
 mod block;
+mod block_filter;
 mod build_block;
 mod byte_array;
 mod bytes;
@@ -10,6 +13,8 @@ pub mod ecc;
 pub mod encoding;
 mod error;
 mod hash;
+mod header_chain;
+pub mod interpreter;
 mod merkle;
 mod network;
 mod op;
@@ -18,18 +23,23 @@ mod script;
 mod sequence;
 mod sighashtype;
 mod sign;
+mod taproot;
 mod tx;
 mod utxo;
 
 pub use crate::address::*;
+pub use crate::bip32::*;
+pub use crate::bip39::*;
 pub use crate::bitcoin_code::*;
 pub use crate::block::*;
+pub use crate::block_filter::*;
 pub use crate::build_block::*;
 pub use crate::byte_array::*;
 pub use crate::bytes::*;
 pub use crate::bytes_mut::*;
 pub use crate::error::*;
 pub use crate::hash::*;
+pub use crate::header_chain::*;
 pub use crate::merkle::*;
 pub use crate::network::*;
 pub use crate::op::*;
@@ -37,5 +47,6 @@ pub use crate::script::*;
 pub use crate::sequence::*;
 pub use crate::sighashtype::*;
 pub use crate::sign::*;
+pub use crate::taproot::*;
 pub use crate::tx::*;
 pub use crate::utxo::*;
@@ -1,4 +1,6 @@
-use crate::{BytesMut, Hashed};
+use thiserror::Error;
+
+use crate::{BitcoinCode, Bytes, BytesMut, Hashed, Result};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum MerkleMode {
@@ -6,6 +8,41 @@ pub enum MerkleMode {
     Lotus,
 }
 
+#[derive(Error, Clone, Debug, PartialEq, Eq)]
+pub enum MerkleError {
+    #[error("Partial merkle tree has no leaves")]
+    NoLeaves,
+    #[error("Partial merkle tree has more hashes ({num_hashes}) than leaves ({num_leaves})")]
+    TooManyHashes { num_hashes: usize, num_leaves: u32 },
+    #[error("Partial merkle tree ran out of hashes")]
+    NotEnoughHashes,
+    #[error("Partial merkle tree ran out of bits")]
+    NotEnoughBits,
+    #[error("Partial merkle tree has unused hashes")]
+    UnusedHashes,
+    #[error("Partial merkle tree has unused bits")]
+    UnusedBits,
+    #[error("Partial merkle tree has identical children, which could fake duplicate leaves")]
+    IdenticalChildren,
+    #[error("Merkle root mismatch: expected {expected}, but proof has {actual}")]
+    RootMismatch { expected: String, actual: String },
+    #[error("Need one match flag per leaf, but got {num_matches} flags for {num_leaves} leaves")]
+    MatchesLengthMismatch {
+        num_matches: usize,
+        num_leaves: usize,
+    },
+}
+
+/// Compact inclusion proof of a subset of the leaves of a merkle tree, encoded
+/// like BIP37's `CPartialMerkleTree`. For [`MerkleMode::Lotus`], odd layers are
+/// padded with zero hashes instead of duplicating the last hash.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PartialMerkleTree<H> {
+    num_leaves: u32,
+    hashes: Vec<H>,
+    bits: Vec<bool>,
+}
+
 pub fn get_merkle_root<H: Hashed + Clone>(leaves: Vec<H>, mode: MerkleMode) -> H {
     get_merkle_root_and_height(leaves, mode).0
 }
@@ -21,21 +58,359 @@ pub fn get_merkle_root_and_height<H: Hashed + Clone>(
     while leaves.len() > 1 {
         height += 1;
         if leaves.len() % 2 == 1 {
-            match mode {
-                // repeat last hash to make num leaves even on Bitcoin
-                MerkleMode::Bitcoin => leaves.push(leaves.last().unwrap().clone()),
-                // add 0000...000000 to make num leaves even on Lotus
-                MerkleMode::Lotus => leaves.push(H::from_array(H::Array::default())),
-            }
+            leaves.push(padding_hash(leaves.last().unwrap(), mode));
         }
         let mut next_layer = Vec::new();
         for pair in leaves.as_chunks::<2>().0 {
-            let mut bytes = BytesMut::new();
-            bytes.put_slice(pair[0].as_slice());
-            bytes.put_slice(pair[1].as_slice());
-            next_layer.push(H::digest(bytes.freeze()));
+            next_layer.push(hash_pair(&pair[0], &pair[1]));
         }
         leaves = next_layer;
     }
     (leaves.remove(0), height)
 }
+
+fn hash_pair<H: Hashed>(left: &H, right: &H) -> H {
+    let mut bytes = BytesMut::new();
+    bytes.put_slice(left.as_slice());
+    bytes.put_slice(right.as_slice());
+    H::digest(bytes.freeze())
+}
+
+fn padding_hash<H: Hashed + Clone>(last: &H, mode: MerkleMode) -> H {
+    match mode {
+        // repeat last hash to make num leaves even on Bitcoin
+        MerkleMode::Bitcoin => last.clone(),
+        // add 0000...000000 to make num leaves even on Lotus
+        MerkleMode::Lotus => H::from_array(H::Array::default()),
+    }
+}
+
+impl<H: Hashed + Clone> PartialMerkleTree<H> {
+    /// Build a proof for the leaves flagged in `matches`, which has one flag per leaf.
+    pub fn build(
+        leaves: &[H],
+        matches: &[bool],
+        mode: MerkleMode,
+    ) -> std::result::Result<Self, MerkleError> {
+        if leaves.len() != matches.len() {
+            return Err(MerkleError::MatchesLengthMismatch {
+                num_matches: matches.len(),
+                num_leaves: leaves.len(),
+            });
+        }
+        let mut tree = PartialMerkleTree {
+            num_leaves: leaves.len() as u32,
+            hashes: Vec::new(),
+            bits: Vec::new(),
+        };
+        if !leaves.is_empty() {
+            tree.build_node(tree.height(), 0, leaves, matches, mode);
+        }
+        Ok(tree)
+    }
+
+    pub fn num_leaves(&self) -> u32 {
+        self.num_leaves
+    }
+
+    pub fn hashes(&self) -> &[H] {
+        &self.hashes
+    }
+
+    pub fn bits(&self) -> &[bool] {
+        &self.bits
+    }
+
+    /// Compute the merkle root of the proof and the matched leaves with their index.
+    pub fn extract_matches(
+        &self,
+        mode: MerkleMode,
+    ) -> std::result::Result<(H, Vec<(usize, H)>), MerkleError> {
+        if self.num_leaves == 0 {
+            return Err(MerkleError::NoLeaves);
+        }
+        if self.hashes.len() > self.num_leaves as usize {
+            return Err(MerkleError::TooManyHashes {
+                num_hashes: self.hashes.len(),
+                num_leaves: self.num_leaves,
+            });
+        }
+        let mut extract = Extract {
+            tree: self,
+            mode,
+            bits_used: 0,
+            hashes_used: 0,
+            matches: Vec::new(),
+        };
+        let root = extract.node(self.height(), 0)?;
+        // Bits are serialized as bytes, so only whole unused bytes are invalid
+        if extract.bits_used.div_ceil(8) != self.bits.len().div_ceil(8) {
+            return Err(MerkleError::UnusedBits);
+        }
+        if extract.hashes_used != self.hashes.len() {
+            return Err(MerkleError::UnusedHashes);
+        }
+        Ok((root, extract.matches))
+    }
+
+    /// Verify the proof against the given merkle root and return the matched leaves.
+    pub fn verify(
+        &self,
+        merkle_root: &H,
+        mode: MerkleMode,
+    ) -> std::result::Result<Vec<(usize, H)>, MerkleError> {
+        let (root, matches) = self.extract_matches(mode)?;
+        if &root != merkle_root {
+            return Err(MerkleError::RootMismatch {
+                expected: merkle_root.to_string(),
+                actual: root.to_string(),
+            });
+        }
+        Ok(matches)
+    }
+
+    /// Number of layers above the leaves.
+    fn height(&self) -> usize {
+        let mut height = 0;
+        while self.width(height) > 1 {
+            height += 1;
+        }
+        height
+    }
+
+    /// Number of nodes at the given height, where leaves have height 0.
+    fn width(&self, height: usize) -> usize {
+        (self.num_leaves as usize + (1 << height) - 1) >> height
+    }
+
+    fn calc_hash(&self, height: usize, pos: usize, leaves: &[H], mode: MerkleMode) -> H {
+        if height == 0 {
+            return leaves[pos].clone();
+        }
+        let left = self.calc_hash(height - 1, pos * 2, leaves, mode);
+        let right = match pos * 2 + 1 < self.width(height - 1) {
+            true => self.calc_hash(height - 1, pos * 2 + 1, leaves, mode),
+            false => padding_hash(&left, mode),
+        };
+        hash_pair(&left, &right)
+    }
+
+    fn build_node(
+        &mut self,
+        height: usize,
+        pos: usize,
+        leaves: &[H],
+        matches: &[bool],
+        mode: MerkleMode,
+    ) {
+        let first_leaf = pos << height;
+        let last_leaf = ((pos + 1) << height).min(leaves.len());
+        let is_parent_of_match = matches[first_leaf..last_leaf].contains(&true);
+        self.bits.push(is_parent_of_match);
+        if height == 0 || !is_parent_of_match {
+            let hash = self.calc_hash(height, pos, leaves, mode);
+            self.hashes.push(hash);
+            return;
+        }
+        self.build_node(height - 1, pos * 2, leaves, matches, mode);
+        if pos * 2 + 1 < self.width(height - 1) {
+            self.build_node(height - 1, pos * 2 + 1, leaves, matches, mode);
+        }
+    }
+}
+
+struct Extract<'a, H> {
+    tree: &'a PartialMerkleTree<H>,
+    mode: MerkleMode,
+    bits_used: usize,
+    hashes_used: usize,
+    matches: Vec<(usize, H)>,
+}
+
+impl<H: Hashed + Clone> Extract<'_, H> {
+    fn node(&mut self, height: usize, pos: usize) -> std::result::Result<H, MerkleError> {
+        let is_parent_of_match = *self
+            .tree
+            .bits
+            .get(self.bits_used)
+            .ok_or(MerkleError::NotEnoughBits)?;
+        self.bits_used += 1;
+        if height == 0 || !is_parent_of_match {
+            let hash = self
+                .tree
+                .hashes
+                .get(self.hashes_used)
+                .ok_or(MerkleError::NotEnoughHashes)?
+                .clone();
+            self.hashes_used += 1;
+            if height == 0 && is_parent_of_match {
+                self.matches.push((pos, hash.clone()));
+            }
+            return Ok(hash);
+        }
+        let left = self.node(height - 1, pos * 2)?;
+        let right = match pos * 2 + 1 < self.tree.width(height - 1) {
+            true => {
+                let right = self.node(height - 1, pos * 2 + 1)?;
+                // Prevents CVE-2012-2459, duplicating the last leaves keeps the root
+                if self.mode == MerkleMode::Bitcoin && right == left {
+                    return Err(MerkleError::IdenticalChildren);
+                }
+                right
+            }
+            false => padding_hash(&left, self.mode),
+        };
+        Ok(hash_pair(&left, &right))
+    }
+}
+
+impl<H: Hashed + BitcoinCode> BitcoinCode for PartialMerkleTree<H> {
+    fn ser_to(&self, bytes: &mut BytesMut) {
+        self.num_leaves.ser_to(bytes);
+        self.hashes.ser_to(bytes);
+        let mut flags = vec![0u8; self.bits.len().div_ceil(8)];
+        for (idx, &bit) in self.bits.iter().enumerate() {
+            flags[idx / 8] |= (bit as u8) << (idx % 8);
+        }
+        Bytes::from(flags).ser_to(bytes);
+    }
+
+    fn deser(data: &mut Bytes) -> Result<Self> {
+        let num_leaves = u32::deser(data)?;
+        let hashes = Vec::<H>::deser(data)?;
+        let flags = Bytes::deser(data)?;
+        let bits = (0..flags.len() * 8)
+            .map(|idx| (flags[idx / 8] >> (idx % 8)) & 1 == 1)
+            .collect();
+        Ok(PartialMerkleTree {
+            num_leaves,
+            hashes,
+            bits,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        get_merkle_root, BitcoinCode, Hashed, MerkleError, MerkleMode, PartialMerkleTree, Sha256d,
+    };
+
+    fn leaves(num_leaves: usize) -> Vec<Sha256d> {
+        (0..num_leaves)
+            .map(|idx| Sha256d::digest(vec![idx as u8].into()))
+            .collect()
+    }
+
+    #[test]
+    fn test_partial_merkle_tree() -> Result<(), Box<dyn std::error::Error>> {
+        for mode in [MerkleMode::Bitcoin, MerkleMode::Lotus] {
+            for num_leaves in 1..=20 {
+                let leaves = leaves(num_leaves);
+                let root = get_merkle_root(leaves.clone(), mode);
+                // Match every n-th leaf, including none and all
+                for step in 1..=num_leaves + 1 {
+                    let matches = (0..num_leaves)
+                        .map(|idx| step <= num_leaves && idx % step == 0)
+                        .collect::<Vec<_>>();
+                    let tree = PartialMerkleTree::build(&leaves, &matches, mode)?;
+                    let expected = leaves
+                        .iter()
+                        .cloned()
+                        .enumerate()
+                        .filter(|&(idx, _)| matches[idx])
+                        .collect::<Vec<_>>();
+                    assert_eq!(tree.verify(&root, mode)?, expected);
+                    let deser = PartialMerkleTree::<Sha256d>::deser(&mut tree.ser())?;
+                    assert_eq!(deser.verify(&root, mode)?, expected);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_partial_merkle_tree_block_100000() -> Result<(), Box<dyn std::error::Error>> {
+        let txids = [
+            "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+            "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+            "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+            "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+        ]
+        .into_iter()
+        .map(Sha256d::from_hex_be)
+        .collect::<Result<Vec<_>, _>>()?;
+        let merkle_root = Sha256d::from_hex_be(
+            "f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766",
+        )?;
+        let tree =
+            PartialMerkleTree::build(&txids, &[false, true, false, false], MerkleMode::Bitcoin)?;
+        // Hashes of tx 0, tx 1 and the pair of tx 2 and 3, bits 1, 1, 0, 1, 0
+        assert_eq!(tree.hashes().len(), 3);
+        assert_eq!(tree.ser().as_ref()[4 + 1 + 3 * 32..], [1, 0b01011]);
+        assert_eq!(
+            tree.verify(&merkle_root, MerkleMode::Bitcoin)?,
+            vec![(1, txids[1].clone())],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_partial_merkle_tree_invalid() -> Result<(), MerkleError> {
+        let leaves = leaves(5);
+        let mode = MerkleMode::Bitcoin;
+        let root = get_merkle_root(leaves.clone(), mode);
+        let tree = PartialMerkleTree::build(&leaves, &[false, false, true, false, false], mode)?;
+
+        let mut wrong_hash = tree.clone();
+        wrong_hash.hashes[0] = Sha256d::default();
+        assert!(matches!(
+            wrong_hash.verify(&root, mode),
+            Err(MerkleError::RootMismatch { .. }),
+        ));
+        let mut missing_hash = tree.clone();
+        missing_hash.hashes.pop();
+        assert_eq!(
+            missing_hash.verify(&root, mode),
+            Err(MerkleError::NotEnoughHashes)
+        );
+        let mut extra_hash = tree.clone();
+        extra_hash.hashes.push(Sha256d::default());
+        assert_eq!(
+            extra_hash.verify(&root, mode),
+            Err(MerkleError::UnusedHashes)
+        );
+        let mut missing_bits = tree.clone();
+        missing_bits.bits.truncate(2);
+        assert_eq!(
+            missing_bits.verify(&root, mode),
+            Err(MerkleError::NotEnoughBits)
+        );
+        let mut extra_bits = tree.clone();
+        extra_bits.bits.extend([false; 8]);
+        assert_eq!(extra_bits.verify(&root, mode), Err(MerkleError::UnusedBits));
+        assert_eq!(
+            PartialMerkleTree::<Sha256d>::build(&[], &[], mode)?.verify(&root, mode),
+            Err(MerkleError::NoLeaves),
+        );
+        assert_eq!(
+            PartialMerkleTree::build(&leaves, &[true; 4], mode),
+            Err(MerkleError::MatchesLengthMismatch {
+                num_matches: 4,
+                num_leaves: 5,
+            }),
+        );
+
+        // Duplicating the last leaf gives the same root, but is rejected
+        let mut duplicated = leaves[..3].to_vec();
+        duplicated.push(leaves[2].clone());
+        let root = get_merkle_root(leaves[..3].to_vec(), mode);
+        assert_eq!(get_merkle_root(duplicated.clone(), mode), root);
+        let tree = PartialMerkleTree::build(&duplicated, &[false, false, false, true], mode)?;
+        assert_eq!(
+            tree.verify(&root, mode),
+            Err(MerkleError::IdenticalChildren)
+        );
+        Ok(())
+    }
+}
@@ -213,6 +213,22 @@ impl std::fmt::Display for Op {
                 // additional byte string operations
                 OP_REVERSEBYTES => "OP_REVERSEBYTES",
 
+                // native introspection (BCH May 2022 upgrade)
+                OP_INPUTINDEX => "OP_INPUTINDEX",
+                OP_ACTIVEBYTECODE => "OP_ACTIVEBYTECODE",
+                OP_TXVERSION => "OP_TXVERSION",
+                OP_TXINPUTCOUNT => "OP_TXINPUTCOUNT",
+                OP_TXOUTPUTCOUNT => "OP_TXOUTPUTCOUNT",
+                OP_TXLOCKTIME => "OP_TXLOCKTIME",
+                OP_UTXOVALUE => "OP_UTXOVALUE",
+                OP_UTXOBYTECODE => "OP_UTXOBYTECODE",
+                OP_OUTPOINTTXHASH => "OP_OUTPOINTTXHASH",
+                OP_OUTPOINTINDEX => "OP_OUTPOINTINDEX",
+                OP_INPUTBYTECODE => "OP_INPUTBYTECODE",
+                OP_INPUTSEQUENCENUMBER => "OP_INPUTSEQUENCENUMBER",
+                OP_OUTPUTVALUE => "OP_OUTPUTVALUE",
+                OP_OUTPUTBYTECODE => "OP_OUTPUTBYTECODE",
+
                 // multi-byte opcodes
                 OP_PREFIX_BEGIN => "OP_PREFIX_BEGIN",
                 OP_PREFIX_END => "OP_PREFIX_END",
@@ -139,6 +139,22 @@ pub const OP_CHECKDATASIGVERIFY: u8 = 0xbb;
 // additional byte string operations
 pub const OP_REVERSEBYTES: u8 = 0xbc;
 
+// native introspection (BCH May 2022 upgrade)
+pub const OP_INPUTINDEX: u8 = 0xc0;
+pub const OP_ACTIVEBYTECODE: u8 = 0xc1;
+pub const OP_TXVERSION: u8 = 0xc2;
+pub const OP_TXINPUTCOUNT: u8 = 0xc3;
+pub const OP_TXOUTPUTCOUNT: u8 = 0xc4;
+pub const OP_TXLOCKTIME: u8 = 0xc5;
+pub const OP_UTXOVALUE: u8 = 0xc6;
+pub const OP_UTXOBYTECODE: u8 = 0xc7;
+pub const OP_OUTPOINTTXHASH: u8 = 0xc8;
+pub const OP_OUTPOINTINDEX: u8 = 0xc9;
+pub const OP_INPUTBYTECODE: u8 = 0xca;
+pub const OP_INPUTSEQUENCENUMBER: u8 = 0xcb;
+pub const OP_OUTPUTVALUE: u8 = 0xcc;
+pub const OP_OUTPUTBYTECODE: u8 = 0xcd;
+
 // multi-byte opcodes
 pub const OP_PREFIX_BEGIN: u8 = 0xf0;
 pub const OP_PREFIX_END: u8 = 0xf7;
@@ -193,6 +193,10 @@ impl Script {
         matches!(self.bytecode.as_ref(), [OP_HASH160, 0x14, hash @ .., OP_EQUAL] if hash.len() == 20)
     }
 
+    pub fn is_p2tr(&self) -> bool {
+        matches!(self.parse_variant(), ScriptVariant::P2TR(..))
+    }
+
     pub fn is_opreturn(&self) -> bool {
         self.bytecode
             .first()
@@ -217,6 +221,32 @@ impl Script {
         Ok(self.clone())
     }
 
+    /// Remove all occurrences of `pattern` starting at op boundaries, like Bitcoin's
+    /// `FindAndDelete`. Bytecode after an invalid op is kept as is.
+    pub fn find_and_delete(&self, pattern: &Script) -> Script {
+        let pattern = pattern.bytecode.as_ref();
+        if pattern.is_empty() {
+            return self.clone();
+        }
+        let mut remaining = self.bytecode.clone();
+        let mut bytecode = BytesMut::new();
+        loop {
+            while remaining.as_ref().starts_with(pattern) {
+                remaining.split_to(pattern.len()).unwrap();
+            }
+            let op_start = remaining.clone();
+            if op_start.as_ref().is_empty() || Op::deser_op(&mut remaining).is_err() {
+                bytecode.put_slice(op_start.as_ref());
+                break;
+            }
+            let op_len = op_start.as_ref().len() - remaining.as_ref().len();
+            bytecode.put_slice(&op_start.as_ref()[..op_len]);
+        }
+        Script {
+            bytecode: bytecode.freeze(),
+        }
+    }
+
     pub fn parse_variant(&self) -> ScriptVariant {
         match self.bytecode.as_ref() {
             [0x21, pubkey @ .., OP_CHECKSIG] if pubkey.len() == PUBKEY_LENGTH => {
@@ -264,6 +294,40 @@ impl Script {
         };
         Some((pubkey, sig))
     }
+
+    /// Parse an m-of-n multisig script as built by [`Script::multisig`] into the number of
+    /// required signatures and the public keys.
+    pub fn parse_multisig(&self) -> Option<(u8, Vec<Bytes>)> {
+        let ops = self
+            .ops()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .ok()?;
+        let (num_signers, num_keys, key_ops) = match ops.as_slice() {
+            [Op::Code(num_signers @ OP_1..=OP_16), key_ops @ .., Op::Code(num_keys @ OP_1..=OP_16), Op::Code(OP_CHECKMULTISIG)] => {
+                (num_signers - OP_1 + 1, num_keys - OP_1 + 1, key_ops)
+            }
+            _ => return None,
+        };
+        if num_signers > num_keys || key_ops.len() != num_keys as usize {
+            return None;
+        }
+        let pubkeys = key_ops
+            .iter()
+            .map(|op| match op {
+                Op::Push(_, pubkey) if [PUBKEY_LENGTH, 65].contains(&pubkey.len()) => {
+                    Some(pubkey.clone())
+                }
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some((num_signers, pubkeys))
+    }
+}
+
+impl ScriptOpIter {
+    pub fn remaining_bytecode(&self) -> &Bytes {
+        &self.remaining_bytecode
+    }
 }
 
 impl Iterator for ScriptOpIter {
@@ -466,6 +530,70 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_parse_multisig() -> Result<(), Box<dyn std::error::Error>> {
+        let pubkeys = [[2; 33].as_ref(), &[3; 33], &[4; 65]];
+        let script = Script::multisig(2, pubkeys);
+        let (num_signers, parsed_pubkeys) = script.parse_multisig().unwrap();
+        assert_eq!(num_signers, 2);
+        assert_eq!(
+            parsed_pubkeys
+                .iter()
+                .map(|pubkey| pubkey.as_ref())
+                .collect::<Vec<_>>(),
+            pubkeys,
+        );
+        assert_eq!(
+            Script::multisig(1, [[2; 33].as_ref()])
+                .parse_multisig()
+                .unwrap()
+                .0,
+            1
+        );
+        for script_hex in [
+            "",
+            // 2-of-1
+            "5221020202020202020202020202020202020202020202020202020202020202020202020251ae",
+            // wrong number of keys
+            "5121020202020202020202020202020202020202020202020202020202020202020202020252ae",
+            // invalid pubkey size
+            "5120020202020202020202020202020202020202020202020202020202020202020251ae",
+            // missing OP_CHECKMULTISIG
+            "512102020202020202020202020202020202020202020202020202020202020202020202025187",
+        ] {
+            assert_eq!(Script::from_hex(script_hex)?.parse_multisig(), None);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_and_delete() -> Result<(), Box<dyn std::error::Error>> {
+        let find_and_delete =
+            |script: &str, pattern: &str| -> Result<String, Box<dyn std::error::Error>> {
+                Ok(Script::from_hex(script)?
+                    .find_and_delete(&Script::from_hex(pattern)?)
+                    .hex())
+            };
+        assert_eq!(find_and_delete("abacab87", "ab")?, "ac87");
+        assert_eq!(find_and_delete("abacab87", "")?, "abacab87");
+        assert_eq!(find_and_delete("0302ff030302ff03", "0302ff03")?, "");
+        assert_eq!(
+            find_and_delete("0302ff030302ff03", "02")?,
+            "0302ff030302ff03"
+        );
+        // Only matches at op boundaries
+        assert_eq!(find_and_delete("0302ff03", "ff")?, "0302ff03");
+        assert_eq!(find_and_delete("0302ff03", "02ff03")?, "0302ff03");
+        // Deleting can change how the following bytes are parsed
+        assert_eq!(find_and_delete("0302ff0351", "03")?, "02ff0351");
+        // Matches can span multiple ops
+        assert_eq!(find_and_delete("5152535451", "5253")?, "515451");
+        // Invalid ops are kept
+        assert_eq!(find_and_delete("ab4cff", "ab")?, "4cff");
+        assert_eq!(find_and_delete("4cffab", "ab")?, "4cffab");
+        Ok(())
+    }
+
     #[test]
     fn test_p2tr() -> Result<(), Box<dyn std::error::Error>> {
         assert_eq!(
@@ -24,6 +24,7 @@ pub enum SigHashTypeOutputs {
 pub enum SigHashTypeVariant {
     Legacy,
     Bip143,
+    Lotus,
 }
 
 impl SigHashType {
@@ -57,6 +58,36 @@ impl SigHashType {
         input_type: SigHashTypeInputs::AnyoneCanPay,
         output_type: SigHashTypeOutputs::Single,
     };
+    pub const ALL_LOTUS: SigHashType = SigHashType {
+        variant: SigHashTypeVariant::Lotus,
+        input_type: SigHashTypeInputs::Fixed,
+        output_type: SigHashTypeOutputs::All,
+    };
+    pub const NONE_LOTUS: SigHashType = SigHashType {
+        variant: SigHashTypeVariant::Lotus,
+        input_type: SigHashTypeInputs::Fixed,
+        output_type: SigHashTypeOutputs::None,
+    };
+    pub const SINGLE_LOTUS: SigHashType = SigHashType {
+        variant: SigHashTypeVariant::Lotus,
+        input_type: SigHashTypeInputs::Fixed,
+        output_type: SigHashTypeOutputs::Single,
+    };
+    pub const ALL_LOTUS_ANYONECANPAY: SigHashType = SigHashType {
+        variant: SigHashTypeVariant::Lotus,
+        input_type: SigHashTypeInputs::AnyoneCanPay,
+        output_type: SigHashTypeOutputs::All,
+    };
+    pub const NONE_LOTUS_ANYONECANPAY: SigHashType = SigHashType {
+        variant: SigHashTypeVariant::Lotus,
+        input_type: SigHashTypeInputs::AnyoneCanPay,
+        output_type: SigHashTypeOutputs::None,
+    };
+    pub const SINGLE_LOTUS_ANYONECANPAY: SigHashType = SigHashType {
+        variant: SigHashTypeVariant::Lotus,
+        input_type: SigHashTypeInputs::AnyoneCanPay,
+        output_type: SigHashTypeOutputs::Single,
+    };
 
     pub fn to_u32(&self) -> u32 {
         self.input_type.to_u32() | self.output_type.to_u32() | self.variant.to_u32()
@@ -69,6 +100,7 @@ impl SigHashType {
         let variant = match flags & 0x7c {
             0 => SigHashTypeVariant::Legacy,
             0x40 => SigHashTypeVariant::Bip143,
+            0x60 => SigHashTypeVariant::Lotus,
             _ => return None,
         };
         let input_type = match flags & 0x80 {
@@ -115,6 +147,7 @@ impl SigHashTypeVariant {
         match self {
             SigHashTypeVariant::Legacy => 0x00,
             SigHashTypeVariant::Bip143 => 0x40,
+            SigHashTypeVariant::Lotus => 0x60,
         }
     }
 }
@@ -126,8 +159,10 @@ impl Display for SigHashType {
             SigHashTypeOutputs::None => write!(f, "NONE")?,
             SigHashTypeOutputs::Single => write!(f, "SINGLE")?,
         }
-        if let SigHashTypeVariant::Bip143 = self.variant {
-            write!(f, "|FORKID")?;
+        match self.variant {
+            SigHashTypeVariant::Legacy => {}
+            SigHashTypeVariant::Bip143 => write!(f, "|FORKID")?,
+            SigHashTypeVariant::Lotus => write!(f, "|LOTUS")?,
         }
         if let SigHashTypeInputs::AnyoneCanPay = self.input_type {
             write!(f, "|ANYONECANPAY")?;
@@ -157,6 +192,11 @@ mod tests {
             SigHashType::SINGLE_BIP143_ANYONECANPAY.to_string(),
             "SINGLE|FORKID|ANYONECANPAY"
         );
+        assert_eq!(SigHashType::ALL_LOTUS.to_string(), "ALL|LOTUS");
+        assert_eq!(
+            SigHashType::SINGLE_LOTUS_ANYONECANPAY.to_string(),
+            "SINGLE|LOTUS|ANYONECANPAY"
+        );
     }
 
     #[test]
@@ -167,6 +207,12 @@ mod tests {
         assert_eq!(SigHashType::ALL_BIP143_ANYONECANPAY.to_u32(), 0xc1);
         assert_eq!(SigHashType::NONE_BIP143_ANYONECANPAY.to_u32(), 0xc2);
         assert_eq!(SigHashType::SINGLE_BIP143_ANYONECANPAY.to_u32(), 0xc3);
+        assert_eq!(SigHashType::ALL_LOTUS.to_u32(), 0x61);
+        assert_eq!(SigHashType::NONE_LOTUS.to_u32(), 0x62);
+        assert_eq!(SigHashType::SINGLE_LOTUS.to_u32(), 0x63);
+        assert_eq!(SigHashType::ALL_LOTUS_ANYONECANPAY.to_u32(), 0xe1);
+        assert_eq!(SigHashType::NONE_LOTUS_ANYONECANPAY.to_u32(), 0xe2);
+        assert_eq!(SigHashType::SINGLE_LOTUS_ANYONECANPAY.to_u32(), 0xe3);
     }
 
     #[test]
@@ -176,6 +222,13 @@ mod tests {
         assert_eq!(SigHashType::from_u32(0x11), None);
         assert_eq!(SigHashType::from_u32(0x00), None);
         assert_eq!(SigHashType::from_u32(0x40), None);
+        assert_eq!(SigHashType::from_u32(0x60), None);
+        assert_eq!(SigHashType::from_u32(0x51), None);
+        assert_eq!(SigHashType::from_u32(0x61), Some(SigHashType::ALL_LOTUS));
+        assert_eq!(
+            SigHashType::from_u32(0xe3),
+            Some(SigHashType::SINGLE_LOTUS_ANYONECANPAY),
+        );
         assert_eq!(SigHashType::from_u32(0x41), Some(SigHashType::ALL_BIP143));
         assert_eq!(SigHashType::from_u32(0x42), Some(SigHashType::NONE_BIP143));
         assert_eq!(
@@ -0,0 +1,563 @@
+use std::collections::HashMap;
+
+use crate::{
+    ecc::DummyEcc, encoding::write_compact_size, BitcoinCode, BitcoinSuiteError, BytesMut, Script,
+    SequenceNo, SignData, SignError, SignField, Signatory, TxBuilder, TxBuilderInput,
+    TxBuilderOutput, TxInput, TxOutput, UnhashedTx, UnsignedTx, Utxo,
+};
+
+/// Number of tries of branch-and-bound before falling back to largest-first.
+pub const BNB_MAX_TRIES: usize = 100_000;
+
+/// How [`TxBuilder::select_coins`] picks inputs from the UTXO pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CoinSelectStrategy {
+    /// Spend the largest UTXOs first, minimizing the number of inputs.
+    LargestFirst,
+    /// Search for UTXOs that pay for the outputs without leaving any change.
+    /// Falls back to [`CoinSelectStrategy::LargestFirst`] if there's no such set.
+    BranchAndBound,
+    /// Spend the UTXOs with the lowest block height first, unconfirmed ones last.
+    OldestFirst,
+    /// Only spend all UTXOs of a script together, and combine as few scripts as possible.
+    /// This includes UTXOs that cost more fees than they're worth, so no dust remains at a
+    /// spent script; scripts that are worth less than their fees in total are never spent.
+    Privacy,
+}
+
+/// UTXO that can be added as an input to a [`TxBuilder`].
+pub struct SpendableUtxo {
+    pub utxo: Utxo,
+    /// Height of the block of the UTXO, or `None` if unconfirmed.
+    pub height: Option<i32>,
+    pub signatory: Box<dyn Signatory>,
+}
+
+impl SpendableUtxo {
+    pub fn new(utxo: Utxo, height: Option<i32>, signatory: Box<dyn Signatory>) -> Self {
+        SpendableUtxo {
+            utxo,
+            height,
+            signatory,
+        }
+    }
+
+    /// Unsigned input spending this UTXO.
+    pub fn to_input(&self) -> TxInput {
+        TxInput {
+            prev_out: self.utxo.outpoint.clone(),
+            script: Script::default(),
+            sequence: SequenceNo::finalized(),
+            sign_data: Some(SignData::new(vec![
+                SignField::Value(self.utxo.value),
+                SignField::OutputScript(self.utxo.script.clone()),
+            ])),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Candidate {
+    idx: usize,
+    value: i64,
+    /// Size of the signed input
+    size: usize,
+    height: Option<i32>,
+}
+
+#[derive(Debug)]
+struct SelectParams {
+    existing_sum: i64,
+    fixed_output_sum: i64,
+    num_existing_inputs: usize,
+    /// Size of the tx with the existing inputs and no leftover output
+    base_size: usize,
+    /// Size of the leftover output, or 0 if there is none
+    leftover_size: usize,
+    fee_per_kb: i64,
+    dust_limit: i64,
+}
+
+impl SelectParams {
+    fn fee(&self, size: usize) -> i64 {
+        size as i64 * self.fee_per_kb / 1000
+    }
+
+    fn tx_size(&self, selected_size: usize, num_selected: usize) -> usize {
+        let num_inputs = self.num_existing_inputs + num_selected;
+        self.base_size + selected_size + compact_size_len(num_inputs)
+            - compact_size_len(self.num_existing_inputs)
+    }
+
+    /// How much the inputs exceed the outputs and fee of a tx without leftover.
+    /// The selection pays for the tx if this is non-negative.
+    fn excess(&self, selected_sum: i64, selected_size: usize, num_selected: usize) -> i64 {
+        let tx_size = self.tx_size(selected_size, num_selected);
+        self.existing_sum + selected_sum - self.fixed_output_sum - self.fee(tx_size)
+    }
+
+    /// Whether the leftover output would be dust, i.e. the tx has no change.
+    fn is_changeless(&self, selected_sum: i64, selected_size: usize, num_selected: usize) -> bool {
+        let tx_size = self.tx_size(selected_size, num_selected) + self.leftover_size;
+        let leftover = self.existing_sum + selected_sum - self.fixed_output_sum - self.fee(tx_size);
+        leftover < self.dust_limit
+    }
+
+    fn effective_value(&self, candidate: &Candidate) -> i64 {
+        candidate.value - self.fee(candidate.size)
+    }
+}
+
+impl TxBuilder {
+    /// Pick inputs from `utxos` to pay for the outputs and the fee, using the given
+    /// strategy, and add them to the inputs of the builder. Returns the unspent UTXOs.
+    /// UTXOs that cost more fees than they're worth are only picked by
+    /// [`CoinSelectStrategy::Privacy`].
+    pub fn select_coins(
+        &mut self,
+        utxos: Vec<SpendableUtxo>,
+        strategy: CoinSelectStrategy,
+        fee_per_kb: i64,
+        dust_limit: i64,
+    ) -> std::result::Result<Vec<SpendableUtxo>, BitcoinSuiteError> {
+        let params = self.select_params(fee_per_kb, dust_limit)?;
+        let mut all_candidates = Vec::with_capacity(utxos.len());
+        for (idx, utxo) in utxos.iter().enumerate() {
+            all_candidates.push(Candidate {
+                idx,
+                value: utxo.utxo.value,
+                size: signed_input_size(utxo)?,
+                height: utxo.height,
+            });
+        }
+        let candidates = all_candidates
+            .iter()
+            .filter(|candidate| params.effective_value(candidate) > 0)
+            .cloned()
+            .collect::<Vec<_>>();
+        let selected = match strategy {
+            CoinSelectStrategy::LargestFirst => largest_first(&params, candidates.clone()),
+            CoinSelectStrategy::BranchAndBound => branch_and_bound(&params, candidates.clone())
+                .or_else(|| largest_first(&params, candidates.clone())),
+            CoinSelectStrategy::OldestFirst => oldest_first(&params, candidates.clone()),
+            CoinSelectStrategy::Privacy => privacy(&params, &utxos, all_candidates),
+        };
+        let selected = match selected {
+            Some(selected) => selected,
+            None => {
+                let total_size = candidates.iter().map(|candidate| candidate.size).sum();
+                let available = candidates
+                    .iter()
+                    .map(|candidate| candidate.value)
+                    .sum::<i64>();
+                let tx_size = params.tx_size(total_size, candidates.len());
+                return Err(SignError::InsufficientCoins {
+                    available: params.existing_sum + available,
+                    required: params.fixed_output_sum + params.fee(tx_size),
+                }
+                .into());
+            }
+        };
+        let mut utxos = utxos.into_iter().map(Some).collect::<Vec<_>>();
+        for idx in selected {
+            let utxo = utxos[idx].take().expect("UTXO selected twice");
+            self.inputs
+                .push(TxBuilderInput::new(utxo.to_input(), utxo.signatory));
+        }
+        Ok(utxos.into_iter().flatten().collect())
+    }
+
+    fn select_params(
+        &self,
+        fee_per_kb: i64,
+        dust_limit: i64,
+    ) -> std::result::Result<SelectParams, BitcoinSuiteError> {
+        let existing_sum = match self.inputs.is_empty() {
+            true => 0,
+            false => self.input_sum().ok_or(SignError::MissingValue)?,
+        };
+        let mut fixed_output_sum = 0;
+        let mut leftover_size = None;
+        let mut outputs = Vec::with_capacity(self.outputs.len());
+        for builder_output in &self.outputs {
+            match builder_output {
+                TxBuilderOutput::Fixed(output) => {
+                    fixed_output_sum += output.value;
+                    outputs.push(output.clone());
+                }
+                TxBuilderOutput::Leftover(script) => {
+                    if leftover_size.is_some() {
+                        return Err(SignError::MultipleLeftover.into());
+                    }
+                    let output = TxOutput {
+                        value: 0,
+                        script: script.clone(),
+                    };
+                    leftover_size = Some(output.ser().len());
+                }
+            }
+        }
+        let mut dummy_unsigned_tx = UnsignedTx::new_dummy(UnhashedTx {
+            version: self.version,
+            inputs: self
+                .inputs
+                .iter()
+                .map(|input| input.input().clone())
+                .collect(),
+            outputs,
+            lock_time: self.lock_time,
+        });
+        for (input_idx, builder_input) in self.inputs.iter().enumerate() {
+            if let Some(signatory) = builder_input.signatory() {
+                signatory.sign_input(&DummyEcc, dummy_unsigned_tx.input_at(input_idx))?;
+            }
+        }
+        Ok(SelectParams {
+            existing_sum,
+            fixed_output_sum,
+            num_existing_inputs: self.inputs.len(),
+            base_size: dummy_unsigned_tx.tx().ser().len(),
+            leftover_size: leftover_size.unwrap_or_default(),
+            fee_per_kb,
+            dust_limit,
+        })
+    }
+}
+
+fn signed_input_size(utxo: &SpendableUtxo) -> std::result::Result<usize, BitcoinSuiteError> {
+    let mut dummy_unsigned_tx = UnsignedTx::new_dummy(UnhashedTx {
+        version: 1,
+        inputs: vec![utxo.to_input()],
+        outputs: vec![],
+        lock_time: 0,
+    });
+    utxo.signatory
+        .sign_input(&DummyEcc, dummy_unsigned_tx.input_at(0))?;
+    Ok(dummy_unsigned_tx.tx().inputs[0].ser().len())
+}
+
+fn compact_size_len(size: usize) -> usize {
+    let mut bytes = BytesMut::new();
+    write_compact_size(&mut bytes, size as u64);
+    bytes.as_slice().len()
+}
+
+/// Add candidates in the given order until they pay for the tx.
+fn accumulate(
+    params: &SelectParams,
+    candidates: impl IntoIterator<Item = Candidate>,
+) -> Option<Vec<usize>> {
+    let mut selected = Vec::new();
+    let mut selected_sum = 0;
+    let mut selected_size = 0;
+    if params.excess(0, 0, 0) >= 0 {
+        return Some(selected);
+    }
+    for candidate in candidates {
+        selected.push(candidate.idx);
+        selected_sum += candidate.value;
+        selected_size += candidate.size;
+        if params.excess(selected_sum, selected_size, selected.len()) >= 0 {
+            return Some(selected);
+        }
+    }
+    None
+}
+
+fn largest_first(params: &SelectParams, mut candidates: Vec<Candidate>) -> Option<Vec<usize>> {
+    candidates.sort_by_key(|candidate| std::cmp::Reverse(candidate.value));
+    accumulate(params, candidates)
+}
+
+fn oldest_first(params: &SelectParams, mut candidates: Vec<Candidate>) -> Option<Vec<usize>> {
+    candidates.sort_by_key(|candidate| candidate.height.unwrap_or(i32::MAX));
+    accumulate(params, candidates)
+}
+
+fn privacy(
+    params: &SelectParams,
+    utxos: &[SpendableUtxo],
+    candidates: Vec<Candidate>,
+) -> Option<Vec<usize>> {
+    let mut groups = HashMap::<&Script, Vec<Candidate>>::new();
+    for candidate in candidates {
+        groups
+            .entry(&utxos[candidate.idx].utxo.script)
+            .or_default()
+            .push(candidate);
+    }
+    let mut groups = groups
+        .into_values()
+        .filter(|group| {
+            let effective_sum = group
+                .iter()
+                .map(|candidate| params.effective_value(candidate))
+                .sum::<i64>();
+            effective_sum > 0
+        })
+        .map(|group| {
+            let sum = group.iter().map(|candidate| candidate.value).sum::<i64>();
+            (sum, group)
+        })
+        .collect::<Vec<_>>();
+    // Sort by sum and first UTXO so the selection is deterministic
+    groups.sort_by_key(|(sum, group)| (std::cmp::Reverse(*sum), group[0].idx));
+    // Prefer the smallest script that pays for the tx on its own
+    let single_group = groups.iter().rev().find(|(sum, group)| {
+        let size = group.iter().map(|candidate| candidate.size).sum();
+        params.excess(*sum, size, group.len()) >= 0
+    });
+    if let Some((_, group)) = single_group {
+        return Some(group.iter().map(|candidate| candidate.idx).collect());
+    }
+    let mut selected = Vec::new();
+    let mut selected_sum = 0;
+    let mut selected_size = 0;
+    for (sum, group) in groups {
+        selected.extend(group.iter().map(|candidate| candidate.idx));
+        selected_sum += sum;
+        selected_size += group.iter().map(|candidate| candidate.size).sum::<usize>();
+        if params.excess(selected_sum, selected_size, selected.len()) >= 0 {
+            return Some(selected);
+        }
+    }
+    None
+}
+
+fn branch_and_bound(params: &SelectParams, mut candidates: Vec<Candidate>) -> Option<Vec<usize>> {
+    candidates.sort_by_key(|candidate| std::cmp::Reverse(params.effective_value(candidate)));
+    let mut remaining = vec![0; candidates.len() + 1];
+    for (pos, candidate) in candidates.iter().enumerate().rev() {
+        remaining[pos] = remaining[pos + 1] + params.effective_value(candidate);
+    }
+    let mut search = BnbSearch {
+        params,
+        candidates: &candidates,
+        remaining,
+        tries: 0,
+        selected: Vec::new(),
+    };
+    search.search(0, 0, 0).then(|| {
+        search
+            .selected
+            .iter()
+            .map(|&pos| candidates[pos].idx)
+            .collect()
+    })
+}
+
+struct BnbSearch<'a> {
+    params: &'a SelectParams,
+    candidates: &'a [Candidate],
+    /// Sum of effective values of all candidates from the index on
+    remaining: Vec<i64>,
+    tries: usize,
+    selected: Vec<usize>,
+}
+
+impl BnbSearch<'_> {
+    fn search(&mut self, pos: usize, selected_sum: i64, selected_size: usize) -> bool {
+        self.tries += 1;
+        if self.tries > BNB_MAX_TRIES {
+            return false;
+        }
+        let num_selected = self.selected.len();
+        if self
+            .params
+            .excess(selected_sum, selected_size, num_selected)
+            >= 0
+        {
+            // Adding more inputs only increases the change
+            return self
+                .params
+                .is_changeless(selected_sum, selected_size, num_selected);
+        }
+        if pos == self.candidates.len()
+            || self
+                .params
+                .excess(selected_sum, selected_size, num_selected)
+                + self.remaining[pos]
+                < 0
+        {
+            return false;
+        }
+        let candidate = &self.candidates[pos];
+        self.selected.push(pos);
+        if self.search(
+            pos + 1,
+            selected_sum + candidate.value,
+            selected_size + candidate.size,
+        ) {
+            return true;
+        }
+        self.selected.pop();
+        self.search(pos + 1, selected_sum, selected_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ecc::{DummyEcc, Ecc},
+        BitcoinCode, BitcoinSuiteError, CoinSelectStrategy, OutPoint, Result, Script, Sha256d,
+        SignError, Signatory, SpendableUtxo, TxBuilder, TxBuilderOutput, TxOutput, UnsignedTxInput,
+        Utxo,
+    };
+
+    struct ConstSignatory(Script);
+    impl Signatory for ConstSignatory {
+        fn sign_input<'tx>(&self, _: &dyn Ecc, mut input: UnsignedTxInput<'tx>) -> Result<()> {
+            *input.input_script_mut() = self.0.clone();
+            Ok(())
+        }
+    }
+
+    fn utxos(values: &[(i64, Option<i32>, u8)]) -> Vec<SpendableUtxo> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(idx, &(value, height, script))| {
+                SpendableUtxo::new(
+                    Utxo {
+                        outpoint: OutPoint {
+                            txid: Sha256d::new([idx as u8; 32]),
+                            out_idx: 0,
+                        },
+                        script: Script::from_slice(&[script]),
+                        value,
+                    },
+                    height,
+                    Box::new(ConstSignatory(Script::from_slice(&[0; 8]))),
+                )
+            })
+            .collect()
+    }
+
+    fn builder(value: i64) -> TxBuilder {
+        TxBuilder {
+            version: 1,
+            outputs: vec![
+                TxBuilderOutput::Fixed(TxOutput {
+                    value,
+                    script: Script::default(),
+                }),
+                TxBuilderOutput::Leftover(Script::from_slice(&[0x51])),
+            ],
+            ..Default::default()
+        }
+    }
+
+    fn select(
+        value: i64,
+        pool: &[(i64, Option<i32>, u8)],
+        strategy: CoinSelectStrategy,
+    ) -> std::result::Result<Vec<i64>, BitcoinSuiteError> {
+        let mut tx_builder = builder(value);
+        tx_builder.select_coins(utxos(pool), strategy, 1000, 546)?;
+        let signed_tx = tx_builder.sign(&DummyEcc, 1000, 546)?;
+        Ok(signed_tx
+            .inputs
+            .iter()
+            .map(|input| input.sign_data.as_ref().unwrap().find_value().unwrap())
+            .collect())
+    }
+
+    // Each input is 49 bytes, the tx without inputs and leftover 19 bytes
+    const POOL: &[(i64, Option<i32>, u8)] = &[
+        (5000, Some(10), 1),
+        (20000, None, 2),
+        (3000, Some(5), 1),
+        (7000, Some(20), 3),
+        (30, Some(1), 4),
+    ];
+
+    #[test]
+    fn test_select_largest_first() -> std::result::Result<(), BitcoinSuiteError> {
+        assert_eq!(
+            select(10000, POOL, CoinSelectStrategy::LargestFirst)?,
+            vec![20000],
+        );
+        assert_eq!(
+            select(25000, POOL, CoinSelectStrategy::LargestFirst)?,
+            vec![20000, 7000],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_oldest_first() -> std::result::Result<(), BitcoinSuiteError> {
+        // The 30 sats UTXO costs more than it's worth and is skipped
+        assert_eq!(
+            select(7000, POOL, CoinSelectStrategy::OldestFirst)?,
+            vec![3000, 5000],
+        );
+        assert_eq!(
+            select(15000, POOL, CoinSelectStrategy::OldestFirst)?,
+            vec![3000, 5000, 7000, 20000],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_branch_and_bound() -> std::result::Result<(), BitcoinSuiteError> {
+        // 5000 + 3000 pays exactly for 7883 plus 117 bytes of fee
+        let mut tx_builder = builder(7883);
+        tx_builder.select_coins(utxos(POOL), CoinSelectStrategy::BranchAndBound, 1000, 546)?;
+        let signed_tx = tx_builder.sign(&DummyEcc, 1000, 546)?;
+        assert_eq!(signed_tx.ser().len(), 117);
+        assert_eq!(signed_tx.outputs.len(), 1);
+        let input_values = signed_tx
+            .inputs
+            .iter()
+            .map(|input| input.sign_data.as_ref().unwrap().find_value().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(input_values, vec![5000, 3000]);
+        // No changeless solution -> falls back to largest-first
+        assert_eq!(
+            select(1000, &POOL[..2], CoinSelectStrategy::BranchAndBound)?,
+            vec![20000],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_privacy() -> std::result::Result<(), BitcoinSuiteError> {
+        // Script 1 pays on its own, both of its UTXOs are spent together
+        assert_eq!(
+            select(7500, POOL, CoinSelectStrategy::Privacy)?,
+            vec![5000, 3000],
+        );
+        // Only script 2 is large enough
+        assert_eq!(
+            select(9000, POOL, CoinSelectStrategy::Privacy)?,
+            vec![20000],
+        );
+        // Needs to combine scripts, largest first
+        assert_eq!(
+            select(26000, POOL, CoinSelectStrategy::Privacy)?,
+            vec![20000, 5000, 3000],
+        );
+        // Dust of a spent script is spent too, dust-only scripts are never spent
+        let pool = [POOL, &[(30, Some(2), 1)]].concat();
+        assert_eq!(
+            select(7500, &pool, CoinSelectStrategy::Privacy)?,
+            vec![5000, 3000, 30],
+        );
+        assert_eq!(
+            select(26000, &pool, CoinSelectStrategy::Privacy)?,
+            vec![20000, 5000, 3000, 30],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_insufficient() {
+        match select(40000, POOL, CoinSelectStrategy::LargestFirst) {
+            Err(BitcoinSuiteError::Sign(SignError::InsufficientCoins {
+                available: 35000,
+                required: 40215,
+            })) => {}
+            result => panic!("Unexpected: {result:?}"),
+        }
+    }
+}
@@ -18,6 +18,12 @@ pub enum SignError {
     MissingScriptCodeP2SH,
     #[error("Could not find value in sign data")]
     MissingValue,
+    #[error("Could not find output script in sign data")]
+    MissingOutputScript,
+    #[error("Lotus sighash requires value and output script of input {0}")]
+    MissingSpentOutput(usize),
+    #[error("SIGHASH_SINGLE requires an output at index {0}")]
+    MissingSingleOutput(usize),
     #[error("Sighash type {0} is invalid")]
     InvalidSigHashType(SigHashType),
     #[error("Invalid script encoding")]
@@ -30,8 +36,32 @@ pub enum SignError {
         max_fee: i64,
         required_fee: i64,
     },
+    #[error("Coins ({available}) insufficient, {required} required")]
+    InsufficientCoins { available: i64, required: i64 },
     #[error("OP_CODESEPARATOR #{0} not found")]
     CodesepNotFound(usize),
+    #[error("Script code is not a multisig script")]
+    NotMultisig,
+    #[error("Multisig has no public key at index {0}")]
+    InvalidMultisigKeyIdx(usize),
+    #[error("Multisig requires {required} signatures, but only {available} available")]
+    NotEnoughSignatures { required: usize, available: usize },
+    #[error("Unknown sign field type {0}")]
+    UnknownSignField(u8),
+    #[error("Invalid partially signed tx magic {0:02x?}")]
+    InvalidPartialTxMagic([u8; 5]),
+    #[error("Tx has no input at index {0}")]
+    InvalidInputIdx(usize),
+    #[error("Cannot combine partially signed txs of different txs")]
+    PartialTxMismatch,
+    #[error("Input {0} is not finalized")]
+    InputNotFinalized(usize),
+    #[error("Partial signature for public key index {0} has an invalid sighash type")]
+    InvalidPartialSig(usize),
+    #[error("Multisig public key at index {0} is not compressed")]
+    UncompressedMultisigKey(usize),
+    #[error("Partially signed tx has {count} entries, but only {remaining} bytes left")]
+    TooManyPartialTxEntries { count: u64, remaining: usize },
 }
 
 pub type Result<T> = std::result::Result<T, SignError>;
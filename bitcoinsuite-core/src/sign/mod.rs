@@ -1,10 +1,14 @@
+mod coin_select;
 mod error;
+mod partial_tx;
 mod sign_data;
 mod signatory;
 mod tx_builder;
 mod unsigned_tx;
 
+pub use self::coin_select::*;
 pub use self::error::SignError;
+pub use self::partial_tx::*;
 pub use self::sign_data::*;
 pub use self::signatory::*;
 pub use self::tx_builder::*;
@@ -0,0 +1,547 @@
+use std::collections::BTreeMap;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::{
+    ecc::{DummyEcc, Ecc, PubKey, PUBKEY_LENGTH},
+    encoding::{read_compact_size, write_compact_size},
+    BitcoinCode, Bytes, BytesMut, DerivationPath, Fingerprint, MultisigSigType, MultisigSignatory,
+    Result, Script, SigHashType, SignData, SignError, Signatory, TxBuilder, UnhashedTx, UnsignedTx,
+};
+
+/// Magic bytes at the start of a serialized [`PartiallySignedTx`].
+pub const PARTIALLY_SIGNED_TX_MAGIC: [u8; 5] = *b"pstx\xff";
+
+/// Tx passed between parties to sign it, similar to a BIP174 PSBT.
+///
+/// Unlike [`UnhashedTx`], this serializes the [`SignData`] of each input, together with
+/// partial multisig signatures and the derivation paths of the keys involved.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PartiallySignedTx {
+    /// Tx with empty input scripts and without sign data.
+    tx: UnhashedTx,
+    inputs: Vec<PartialTxInput>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PartialTxInput {
+    pub sign_data: Option<SignData>,
+    /// Signatures (including sighash byte) by public key, combined into a multisig input
+    /// script by [`PartiallySignedTx::finalize`].
+    pub partial_sigs: BTreeMap<PubKey, Bytes>,
+    /// Where the keys of the input come from, so signers can derive them.
+    pub derivation_paths: BTreeMap<PubKey, KeySource>,
+    /// Complete input script, once the input is finalized.
+    pub final_script: Option<Script>,
+}
+
+/// Fingerprint of the master key and path a key is derived from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KeySource {
+    pub fingerprint: Fingerprint,
+    pub path: DerivationPath,
+}
+
+impl PartiallySignedTx {
+    /// Takes the sign data of the tx's inputs; inputs with a non-empty script are treated as
+    /// finalized. Accepts the output of [`TxBuilder::sign`].
+    pub fn new(mut tx: UnhashedTx) -> Self {
+        let inputs = tx
+            .inputs
+            .iter_mut()
+            .map(|input| PartialTxInput {
+                sign_data: input.sign_data.take(),
+                final_script: (!input.script.bytecode().is_empty())
+                    .then(|| std::mem::take(&mut input.script)),
+                ..Default::default()
+            })
+            .collect();
+        PartiallySignedTx { tx, inputs }
+    }
+
+    pub fn tx(&self) -> &UnhashedTx {
+        &self.tx
+    }
+
+    pub fn inputs(&self) -> &[PartialTxInput] {
+        &self.inputs
+    }
+
+    pub fn inputs_mut(&mut self) -> &mut [PartialTxInput] {
+        &mut self.inputs
+    }
+
+    /// Builder for signing inputs via signatories; the signed tx can be passed to
+    /// [`PartiallySignedTx::new`] and combined with this one.
+    pub fn tx_builder(&self) -> TxBuilder {
+        TxBuilder::from_tx(self.tx_with_sign_data())
+    }
+
+    /// Sign the input with the signatory, finalizing it.
+    pub fn sign_input(
+        &mut self,
+        ecc: &dyn Ecc,
+        input_idx: usize,
+        signatory: &dyn Signatory,
+    ) -> Result<()> {
+        self.check_input_idx(input_idx)?;
+        let mut unsigned_tx = UnsignedTx::new(self.tx_with_sign_data());
+        signatory.sign_input(ecc, unsigned_tx.input_at(input_idx))?;
+        let input = unsigned_tx.into_tx().inputs.swap_remove(input_idx);
+        self.inputs[input_idx].final_script = Some(input.script);
+        Ok(())
+    }
+
+    /// Add our signatures for the multisig input, leaving it unfinalized so other parties
+    /// can add theirs.
+    pub fn sign_multisig_partial(
+        &mut self,
+        ecc: &dyn Ecc,
+        input_idx: usize,
+        signatory: &MultisigSignatory,
+    ) -> Result<()> {
+        self.check_input_idx(input_idx)?;
+        let (_, pubkeys) = multisig_pubkeys(&self.inputs[input_idx])?;
+        let mut unsigned_tx = UnsignedTx::new(self.tx_with_sign_data());
+        let sigs = signatory.sign_partial(ecc, &unsigned_tx.input_at(input_idx))?;
+        let partial_sigs = &mut self.inputs[input_idx].partial_sigs;
+        for (key_idx, sig) in sigs {
+            partial_sigs.insert(pubkeys[key_idx], sig);
+        }
+        Ok(())
+    }
+
+    /// Merge the data of another party's copy of the same tx into this one, keeping our
+    /// data where both have it.
+    pub fn combine(&mut self, other: PartiallySignedTx) -> Result<()> {
+        if self.tx != other.tx {
+            return Err(SignError::PartialTxMismatch.into());
+        }
+        for (input, other_input) in self.inputs.iter_mut().zip(other.inputs) {
+            if input.sign_data.is_none() {
+                input.sign_data = other_input.sign_data;
+            }
+            for (pubkey, sig) in other_input.partial_sigs {
+                input.partial_sigs.entry(pubkey).or_insert(sig);
+            }
+            for (pubkey, key_source) in other_input.derivation_paths {
+                input.derivation_paths.entry(pubkey).or_insert(key_source);
+            }
+            if input.final_script.is_none() {
+                input.final_script = other_input.final_script;
+            }
+        }
+        Ok(())
+    }
+
+    /// Build the input scripts of all unfinalized (multisig) inputs from their partial
+    /// signatures.
+    pub fn finalize(&mut self) -> Result<()> {
+        for input_idx in 0..self.inputs.len() {
+            if self.inputs[input_idx].final_script.is_none() {
+                self.finalize_input(input_idx)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Build the multisig input script from the input's partial signatures.
+    pub fn finalize_input(&mut self, input_idx: usize) -> Result<()> {
+        self.check_input_idx(input_idx)?;
+        let input = &self.inputs[input_idx];
+        let (num_signers, pubkeys) = multisig_pubkeys(input)?;
+        let partial_sigs = pubkeys
+            .iter()
+            .enumerate()
+            .filter_map(|(key_idx, pubkey)| {
+                Some((key_idx, input.partial_sigs.get(pubkey)?.clone()))
+            })
+            .collect::<BTreeMap<_, _>>();
+        // Sighash type and sig type (by length, like the interpreter) of the first sig
+        let Some((&key_idx, sig)) = partial_sigs.iter().next() else {
+            return Err(SignError::NotEnoughSignatures {
+                required: num_signers as usize,
+                available: 0,
+            }
+            .into());
+        };
+        let sig_hash_type = sig
+            .as_ref()
+            .last()
+            .and_then(|&flags| SigHashType::from_u32(flags as u32))
+            .ok_or(SignError::InvalidPartialSig(key_idx))?;
+        let sig_type = match sig.len() {
+            65 => MultisigSigType::Schnorr,
+            _ => MultisigSigType::Ecdsa,
+        };
+        let signatory = MultisigSignatory {
+            seckeys: BTreeMap::new(),
+            partial_sigs,
+            sig_type,
+            sig_hash_type,
+        };
+        self.sign_input(&DummyEcc, input_idx, &signatory)
+    }
+
+    /// Signed tx, once all inputs are finalized; inputs keep their sign data.
+    pub fn extract_tx(&self) -> Result<UnhashedTx> {
+        if let Some(input_idx) = self
+            .inputs
+            .iter()
+            .position(|input| input.final_script.is_none())
+        {
+            return Err(SignError::InputNotFinalized(input_idx).into());
+        }
+        Ok(self.tx_with_sign_data())
+    }
+
+    pub fn to_base64(&self) -> String {
+        STANDARD.encode(self.ser())
+    }
+
+    pub fn from_base64(s: &str) -> Result<Self> {
+        let data = STANDARD.decode(s)?;
+        Self::deser(&mut Bytes::from_bytes(data))
+    }
+
+    fn check_input_idx(&self, input_idx: usize) -> Result<()> {
+        if input_idx >= self.inputs.len() {
+            return Err(SignError::InvalidInputIdx(input_idx).into());
+        }
+        Ok(())
+    }
+
+    fn tx_with_sign_data(&self) -> UnhashedTx {
+        let mut tx = self.tx.clone();
+        for (input, partial_input) in tx.inputs.iter_mut().zip(&self.inputs) {
+            input.sign_data = partial_input.sign_data.clone();
+            input.script = partial_input.final_script.clone().unwrap_or_default();
+        }
+        tx
+    }
+}
+
+/// Number of required signers and public keys of the input's multisig script.
+fn multisig_pubkeys(input: &PartialTxInput) -> Result<(u8, Vec<PubKey>)> {
+    let sign_data = input.sign_data.as_ref().ok_or(SignError::NoSignData)?;
+    let (num_signers, pubkeys) = sign_data
+        .find_script_code()?
+        .parse_multisig()
+        .ok_or(SignError::NotMultisig)?;
+    let pubkeys = pubkeys
+        .iter()
+        .enumerate()
+        .map(|(key_idx, pubkey)| {
+            let pubkey = pubkey.as_ref().try_into();
+            let pubkey = pubkey.map_err(|_| SignError::UncompressedMultisigKey(key_idx))?;
+            Ok(PubKey::new_unchecked(pubkey))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok((num_signers, pubkeys))
+}
+
+impl BitcoinCode for PartiallySignedTx {
+    fn ser_to(&self, bytes: &mut BytesMut) {
+        bytes.put_slice(&PARTIALLY_SIGNED_TX_MAGIC);
+        self.tx.ser_to(bytes);
+        for input in &self.inputs {
+            input.ser_to(bytes);
+        }
+    }
+
+    fn deser(data: &mut Bytes) -> Result<Self> {
+        let magic = data.split_to_array::<5>()?.array();
+        if magic != PARTIALLY_SIGNED_TX_MAGIC {
+            return Err(SignError::InvalidPartialTxMagic(magic).into());
+        }
+        let tx = UnhashedTx::deser(data)?;
+        let inputs = tx
+            .inputs
+            .iter()
+            .map(|_| PartialTxInput::deser(data))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(PartiallySignedTx { tx, inputs })
+    }
+}
+
+impl BitcoinCode for PartialTxInput {
+    fn ser_to(&self, bytes: &mut BytesMut) {
+        self.sign_data.ser_to(bytes);
+        write_compact_size(bytes, self.partial_sigs.len() as u64);
+        for (pubkey, sig) in &self.partial_sigs {
+            bytes.put_slice(pubkey.as_slice());
+            sig.ser_to(bytes);
+        }
+        write_compact_size(bytes, self.derivation_paths.len() as u64);
+        for (pubkey, key_source) in &self.derivation_paths {
+            bytes.put_slice(pubkey.as_slice());
+            key_source.ser_to(bytes);
+        }
+        self.final_script.ser_to(bytes);
+    }
+
+    fn deser(data: &mut Bytes) -> Result<Self> {
+        let sign_data = BitcoinCode::deser(data)?;
+        let mut partial_sigs = BTreeMap::new();
+        // Each entry is a pubkey followed by at least a compact size
+        for _ in 0..read_entry_count(data, PUBKEY_LENGTH + 1)? {
+            partial_sigs.insert(deser_pubkey(data)?, BitcoinCode::deser(data)?);
+        }
+        let mut derivation_paths = BTreeMap::new();
+        // Each entry is a pubkey, a fingerprint and at least a compact size
+        for _ in 0..read_entry_count(data, PUBKEY_LENGTH + 4 + 1)? {
+            derivation_paths.insert(deser_pubkey(data)?, BitcoinCode::deser(data)?);
+        }
+        Ok(PartialTxInput {
+            sign_data,
+            partial_sigs,
+            derivation_paths,
+            final_script: BitcoinCode::deser(data)?,
+        })
+    }
+}
+
+impl BitcoinCode for KeySource {
+    fn ser_to(&self, bytes: &mut BytesMut) {
+        bytes.put_slice(&self.fingerprint);
+        self.path.indices().to_vec().ser_to(bytes);
+    }
+
+    fn deser(data: &mut Bytes) -> Result<Self> {
+        Ok(KeySource {
+            fingerprint: data.split_to_array::<4>()?.array(),
+            path: DerivationPath::new(BitcoinCode::deser(data)?),
+        })
+    }
+}
+
+/// Read the number of entries of a map, rejecting counts that cannot possibly fit into the
+/// remaining bytes if each entry takes up at least `min_entry_size` bytes.
+fn read_entry_count(data: &mut Bytes, min_entry_size: usize) -> Result<u64> {
+    let count = read_compact_size(data)?;
+    if count > (data.len() / min_entry_size) as u64 {
+        return Err(SignError::TooManyPartialTxEntries {
+            count,
+            remaining: data.len(),
+        }
+        .into());
+    }
+    Ok(count)
+}
+
+fn deser_pubkey(data: &mut Bytes) -> Result<PubKey> {
+    Ok(PubKey::new_unchecked(
+        data.split_to_array::<PUBKEY_LENGTH>()?.array(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::{
+        ecc::{DummyEcc, PubKey, SecKey},
+        BitcoinCode, BitcoinSuiteError, Bytes, Hashed, KeySource, MultisigSigType,
+        MultisigSignatory, OutPoint, P2PKHSignatory, PartiallySignedTx, Script, SequenceNo,
+        ShaRmd160, SigHashType, SignData, SignError, SignField, TxInput, TxOutput, UnhashedTx,
+    };
+
+    fn multisig_tx(redeem_script: &Script, p2pkh_script: &Script) -> UnhashedTx {
+        let input = |out_idx, sign_fields| TxInput {
+            prev_out: OutPoint {
+                out_idx,
+                ..Default::default()
+            },
+            script: Script::default(),
+            sequence: SequenceNo::finalized(),
+            sign_data: Some(SignData::new(sign_fields)),
+        };
+        UnhashedTx {
+            version: 1,
+            inputs: vec![
+                input(
+                    0,
+                    vec![
+                        SignField::Value(5000),
+                        SignField::OutputScript(redeem_script.to_p2sh()),
+                        SignField::RedeemScript(redeem_script.clone()),
+                    ],
+                ),
+                input(
+                    1,
+                    vec![
+                        SignField::Value(3000),
+                        SignField::OutputScript(p2pkh_script.clone()),
+                    ],
+                ),
+            ],
+            outputs: vec![TxOutput {
+                value: 7000,
+                script: Script::from_slice(&[0x51]),
+            }],
+            lock_time: 0,
+        }
+    }
+
+    #[test]
+    fn test_partially_signed_tx_ser() -> Result<(), BitcoinSuiteError> {
+        let redeem_script = Script::multisig(1, [[2; 33].as_ref()]);
+        let p2pkh_script = Script::p2pkh(&ShaRmd160::digest(Bytes::from_slice(&[3; 33])));
+        let mut partial_tx = PartiallySignedTx::new(multisig_tx(&redeem_script, &p2pkh_script));
+        assert!(partial_tx
+            .tx()
+            .inputs
+            .iter()
+            .all(|input| input.sign_data.is_none()));
+        let input = &mut partial_tx.inputs_mut()[0];
+        input
+            .partial_sigs
+            .insert(PubKey::new_unchecked([2; 33]), Bytes::from_slice(&[7; 65]));
+        input.derivation_paths.insert(
+            PubKey::new_unchecked([2; 33]),
+            KeySource {
+                fingerprint: [1, 2, 3, 4],
+                path: "m/44'/899'/0'/0/1".parse().unwrap(),
+            },
+        );
+        partial_tx.inputs_mut()[1].final_script = Some(Script::from_slice(&[0x51]));
+
+        let ser = partial_tx.ser();
+        assert_eq!(&ser.as_ref()[..5], b"pstx\xff");
+        assert_eq!(PartiallySignedTx::deser(&mut ser.clone())?, partial_tx);
+        assert_eq!(
+            PartiallySignedTx::from_base64(&partial_tx.to_base64())?,
+            partial_tx,
+        );
+        assert_eq!(
+            PartiallySignedTx::new(UnhashedTx::default()).to_base64(),
+            "cHN0eP8AAAAAAAAAAAAA",
+        );
+
+        let mut invalid = ser.as_ref().to_vec();
+        invalid[0] = b'P';
+        match PartiallySignedTx::deser(&mut Bytes::from_slice(&invalid)) {
+            Err(BitcoinSuiteError::Sign(SignError::InvalidPartialTxMagic(magic))) => {
+                assert_eq!(&magic, b"Pstx\xff");
+            }
+            result => panic!("Unexpected: {result:?}"),
+        }
+        assert!(matches!(
+            PartiallySignedTx::from_base64("not base64!"),
+            Err(BitcoinSuiteError::Base64(_)),
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_partially_signed_tx_malformed() {
+        let partial_tx = PartiallySignedTx::new(UnhashedTx {
+            inputs: vec![TxInput::default()],
+            ..Default::default()
+        });
+        // Input ends with no sign data, no sigs, no paths and no final script
+        let ser = partial_tx.ser();
+        assert_eq!(&ser.as_ref()[ser.len() - 4..], &[0, 0, 0, 0]);
+
+        let mut huge_sigs = ser.as_ref()[..ser.len() - 3].to_vec();
+        huge_sigs.extend([0xff; 9]);
+        match PartiallySignedTx::deser(&mut Bytes::from_slice(&huge_sigs)) {
+            Err(BitcoinSuiteError::Sign(SignError::TooManyPartialTxEntries {
+                count: u64::MAX,
+                remaining: 0,
+            })) => {}
+            result => panic!("Unexpected: {result:?}"),
+        }
+
+        let mut huge_paths = ser.as_ref()[..ser.len() - 2].to_vec();
+        huge_paths.extend([0xfe, 0xff, 0xff, 0xff, 0xff, 0]);
+        match PartiallySignedTx::deser(&mut Bytes::from_slice(&huge_paths)) {
+            Err(BitcoinSuiteError::Sign(SignError::TooManyPartialTxEntries {
+                count: 0xffff_ffff,
+                remaining: 1,
+            })) => {}
+            result => panic!("Unexpected: {result:?}"),
+        }
+
+        // Huge input count must fail instead of preallocating
+        let mut huge_inputs = b"pstx\xff\x01\x00\x00\x00".to_vec();
+        huge_inputs.extend([0xff; 9]);
+        assert!(PartiallySignedTx::deser(&mut Bytes::from_slice(&huge_inputs)).is_err());
+        assert!(PartiallySignedTx::from_base64("cHN0eP8BAAAA////////////").is_err());
+    }
+
+    #[test]
+    fn test_partially_signed_tx_multisig() -> Result<(), BitcoinSuiteError> {
+        let ecc = DummyEcc;
+        let pubkeys = [[2; 33], [3; 33], [4; 33]];
+        let redeem_script = Script::multisig(2, pubkeys.iter().map(|pubkey| pubkey.as_ref()));
+        let p2pkh_pubkey = PubKey::new_unchecked([5; 33]);
+        let p2pkh_script = Script::p2pkh(&ShaRmd160::digest(p2pkh_pubkey.array().into()));
+        let tx = multisig_tx(&redeem_script, &p2pkh_script);
+        let multisig_signatory = |key_idx| MultisigSignatory {
+            seckeys: BTreeMap::from([(key_idx, SecKey::new_unchecked([1; 32]))]),
+            partial_sigs: BTreeMap::new(),
+            sig_type: MultisigSigType::Schnorr,
+            sig_hash_type: SigHashType::ALL_BIP143,
+        };
+
+        // First party adds a partial sig and passes the tx on as base64
+        let mut first_party = PartiallySignedTx::new(tx.clone());
+        first_party.sign_multisig_partial(&ecc, 0, &multisig_signatory(2))?;
+        match first_party.finalize() {
+            Err(BitcoinSuiteError::Sign(SignError::NotEnoughSignatures {
+                required: 2,
+                available: 1,
+            })) => {}
+            result => panic!("Unexpected: {result:?}"),
+        }
+        let encoded = first_party.to_base64();
+
+        // Second party signs the P2PKH input via TxBuilder, and adds their partial sig
+        let mut second_party = PartiallySignedTx::from_base64(&encoded)?;
+        let mut tx_builder = second_party.tx_builder();
+        *tx_builder.inputs[1].signatory_mut() = Some(Box::new(P2PKHSignatory {
+            seckey: SecKey::new_unchecked([1; 32]),
+            pubkey: p2pkh_pubkey,
+            sig_hash_type: SigHashType::ALL_BIP143,
+        }));
+        second_party.combine(PartiallySignedTx::new(tx_builder.sign(&ecc, 1000, 546)?))?;
+        second_party.sign_multisig_partial(&ecc, 0, &multisig_signatory(0))?;
+        match second_party.extract_tx() {
+            Err(BitcoinSuiteError::Sign(SignError::InputNotFinalized(0))) => {}
+            result => panic!("Unexpected: {result:?}"),
+        }
+
+        // First party combines and finalizes
+        first_party.combine(second_party)?;
+        assert_eq!(first_party.inputs()[0].partial_sigs.len(), 2);
+        first_party.finalize()?;
+        let signed_tx = first_party.extract_tx()?;
+        let sig = [[0; 64].as_ref(), &[0x41]].concat();
+        let redeem_push = [
+            [0x4c, redeem_script.bytecode().len() as u8].as_ref(),
+            redeem_script.bytecode(),
+        ]
+        .concat();
+        // checkbits 0b101 pushed as OP_5
+        let expected_script = [[0x55, 65].as_ref(), &sig, &[65], &sig, &redeem_push].concat();
+        assert_eq!(
+            signed_tx.inputs[0].script.bytecode().as_ref(),
+            expected_script
+        );
+        let p2pkh_sig = Bytes::from_slice(&[[0; 64].as_ref(), &[0x41]].concat());
+        assert_eq!(
+            signed_tx.inputs[1].script,
+            Script::p2pkh_spend(&p2pkh_pubkey, p2pkh_sig),
+        );
+        assert_eq!(signed_tx.inputs[0].sign_data, tx.inputs[0].sign_data);
+
+        let mut other_tx = tx;
+        other_tx.lock_time = 1;
+        match PartiallySignedTx::new(other_tx).combine(first_party) {
+            Err(BitcoinSuiteError::Sign(SignError::PartialTxMismatch)) => {}
+            result => panic!("Unexpected: {result:?}"),
+        }
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{Script, SignError};
+use crate::{BitcoinCode, Bytes, BytesMut, Script, SignError};
 
 use crate::sign::error::Result;
 
@@ -21,15 +21,21 @@ impl SignData {
 }
 
 impl SignData {
+    /// Script code of the input; for P2TR outputs, the RedeemScript is the tapscript of a
+    /// script path spend, and the output script itself is returned for key path spends.
     pub fn find_script_code(&self) -> Result<Script> {
         let mut is_p2sh = false;
+        let mut p2tr_script = None;
         for field in &self.fields {
             match field {
                 SignField::OutputScript(script) => {
-                    if !script.is_p2sh() {
+                    if script.is_p2sh() {
+                        is_p2sh = true;
+                    } else if script.is_p2tr() {
+                        p2tr_script = Some(script);
+                    } else {
                         return Ok(script.clone());
                     }
-                    is_p2sh = true;
                 }
                 SignField::RedeemScript(script) => {
                     return Ok(script.clone());
@@ -37,12 +43,38 @@ impl SignData {
                 _ => {}
             }
         }
+        if let Some(p2tr_script) = p2tr_script {
+            return Ok(p2tr_script.clone());
+        }
         match is_p2sh {
             true => Err(SignError::MissingScriptCodeP2SH),
             false => Err(SignError::MissingScriptCode),
         }
     }
 
+    pub fn find_output_script(&self) -> Result<Script> {
+        for field in &self.fields {
+            if let SignField::OutputScript(script) = field {
+                return Ok(script.clone());
+            }
+        }
+        Err(SignError::MissingOutputScript)
+    }
+
+    /// Whether the input spends a P2TR output via the script path, i.e. has a RedeemScript.
+    pub fn is_p2tr_script_path(&self) -> bool {
+        let mut is_p2tr = false;
+        let mut has_redeem_script = false;
+        for field in &self.fields {
+            match field {
+                SignField::OutputScript(script) => is_p2tr = script.is_p2tr(),
+                SignField::RedeemScript(_) => has_redeem_script = true,
+                _ => {}
+            }
+        }
+        is_p2tr && has_redeem_script
+    }
+
     pub fn find_value(&self) -> Result<i64> {
         for field in &self.fields {
             if let &SignField::Value(value) = field {
@@ -53,9 +85,50 @@ impl SignData {
     }
 }
 
+impl BitcoinCode for SignData {
+    fn ser_to(&self, bytes: &mut BytesMut) {
+        self.fields.ser_to(bytes);
+    }
+
+    fn deser(data: &mut Bytes) -> crate::Result<Self> {
+        Ok(SignData {
+            fields: BitcoinCode::deser(data)?,
+        })
+    }
+}
+
+/// Serialized as a type byte followed by the field's value.
+impl BitcoinCode for SignField {
+    fn ser_to(&self, bytes: &mut BytesMut) {
+        match self {
+            SignField::OutputScript(script) => {
+                0u8.ser_to(bytes);
+                script.ser_to(bytes);
+            }
+            SignField::RedeemScript(script) => {
+                1u8.ser_to(bytes);
+                script.ser_to(bytes);
+            }
+            SignField::Value(value) => {
+                2u8.ser_to(bytes);
+                value.ser_to(bytes);
+            }
+        }
+    }
+
+    fn deser(data: &mut Bytes) -> crate::Result<Self> {
+        match u8::deser(data)? {
+            0 => Ok(SignField::OutputScript(BitcoinCode::deser(data)?)),
+            1 => Ok(SignField::RedeemScript(BitcoinCode::deser(data)?)),
+            2 => Ok(SignField::Value(BitcoinCode::deser(data)?)),
+            field_type => Err(SignError::UnknownSignField(field_type).into()),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{sign::error::Result, Script, SignData, SignError, SignField};
+    use crate::{ecc::PubKey, sign::error::Result, Script, SignData, SignError, SignField};
 
     #[test]
     fn test_find_script_code_success() -> Result<()> {
@@ -77,6 +150,18 @@ mod tests {
             SignField::RedeemScript(Script::from_slice(&[0x55])),
         ]);
         assert_eq!(sign_data.find_script_code()?, Script::from_slice(&[0x55]));
+
+        let p2tr_script = Script::p2tr(&PubKey::new_unchecked([2; 33]), None);
+        let sign_data = SignData::new(vec![SignField::OutputScript(p2tr_script.clone())]);
+        assert_eq!(sign_data.find_script_code()?, p2tr_script);
+        assert!(!sign_data.is_p2tr_script_path());
+
+        let sign_data = SignData::new(vec![
+            SignField::OutputScript(p2tr_script),
+            SignField::RedeemScript(Script::from_slice(&[0x51])),
+        ]);
+        assert_eq!(sign_data.find_script_code()?, Script::from_slice(&[0x51]));
+        assert!(sign_data.is_p2tr_script_path());
         Ok(())
     }
 
@@ -128,4 +213,20 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_find_output_script() -> Result<()> {
+        let sign_data = SignData::new(vec![
+            SignField::RedeemScript(Script::from_slice(&[0x52])),
+            SignField::OutputScript(Script::from_slice(&[0x51])),
+        ]);
+        assert_eq!(sign_data.find_output_script()?, Script::from_slice(&[0x51]));
+
+        let sign_data = SignData::new(vec![SignField::Value(1234)]);
+        assert_eq!(
+            sign_data.find_output_script().unwrap_err(),
+            SignError::MissingOutputScript,
+        );
+        Ok(())
+    }
 }
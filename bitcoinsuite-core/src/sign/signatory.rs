@@ -1,6 +1,10 @@
+use std::collections::BTreeMap;
+
 use crate::{
     ecc::{Ecc, PubKey, SecKey},
-    BytesMut, Hashed, Result, Script, Sha256d, SigHashType, UnsignedTxInput,
+    opcode::{OP_0, OP_1, OP_1NEGATE},
+    Bytes, BytesMut, Hashed, Op, Result, Script, Sha256d, SigHashType, SigHashTypeVariant,
+    SignError, TaprootSpendInfo, UnsignedTxInput,
 };
 
 pub trait Signatory {
@@ -28,12 +32,196 @@ impl Signatory for P2PKHSignatory {
     }
 }
 
+/// Signs a P2TR input via the key path, tweaking the secret key of the internal key with the
+/// tweak of the spend info, so it matches the output's commitment. Requires a Lotus sighash
+/// type.
+pub struct P2TRKeySignatory {
+    /// Secret key of the internal key.
+    pub seckey: SecKey,
+    pub spend_info: TaprootSpendInfo,
+    pub sig_hash_type: SigHashType,
+}
+
+/// Signs a P2TR input via the script path, for tapscripts consuming a single signature, e.g.
+/// `<pubkey> OP_CHECKSIG`. The tapscript must be the RedeemScript of the input's sign data.
+/// Requires a Lotus sighash type.
+pub struct P2TRScriptSignatory {
+    pub seckey: SecKey,
+    pub control_block: Bytes,
+    pub sig_hash_type: SigHashType,
+}
+
+fn sign_lotus(
+    ecc: &dyn Ecc,
+    seckey: &SecKey,
+    input: &UnsignedTxInput<'_>,
+    sig_hash_type: SigHashType,
+) -> Result<(Bytes, Script)> {
+    if sig_hash_type.variant != SigHashTypeVariant::Lotus {
+        return Err(SignError::InvalidSigHashType(sig_hash_type).into());
+    }
+    let preimage = input.sighash_preimage(sig_hash_type, None)?;
+    let sighash = Sha256d::digest(preimage.bytes).byte_array().clone();
+    let sig = ecc.schnorr_sign(seckey, sighash);
+    let mut sig_flagged = BytesMut::new();
+    sig_flagged.put_bytes(sig);
+    sig_flagged.put_slice(&[sig_hash_type.to_u32() as u8]);
+    Ok((sig_flagged.freeze(), preimage.redeem_script))
+}
+
+impl Signatory for P2TRKeySignatory {
+    fn sign_input<'tx>(&self, ecc: &dyn Ecc, mut input: UnsignedTxInput<'tx>) -> Result<()> {
+        let seckey = self.spend_info.tweak_seckey(ecc, &self.seckey)?;
+        let (sig, _) = sign_lotus(ecc, &seckey, &input, self.sig_hash_type)?;
+        *input.input_script_mut() = Script::from_ops([Op::push_bytes(sig)].into_iter())?;
+        Ok(())
+    }
+}
+
+impl Signatory for P2TRScriptSignatory {
+    fn sign_input<'tx>(&self, ecc: &dyn Ecc, mut input: UnsignedTxInput<'tx>) -> Result<()> {
+        let (sig, tapscript) = sign_lotus(ecc, &self.seckey, &input, self.sig_hash_type)?;
+        *input.input_script_mut() = Script::from_ops(
+            [
+                Op::push_bytes(sig),
+                Op::push_bytes(tapscript.bytecode().clone()),
+                Op::push_bytes(self.control_block.clone()),
+            ]
+            .into_iter(),
+        )?;
+        Ok(())
+    }
+}
+
+/// Which kind of signatures a [`MultisigSignatory`] creates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MultisigSigType {
+    /// `OP_0 <sig_1> ... <sig_m>`
+    Ecdsa,
+    /// `<checkbits> <sig_1> ... <sig_m>`, where the checkbits dummy is a little-endian
+    /// bitfield of which public keys signed (BCH Schnorr multisig).
+    Schnorr,
+}
+
+/// Signs an m-of-n multisig input, either bare or P2SH (with the multisig script as
+/// RedeemScript). Signatures of other parties are added via `partial_sigs`, which they get
+/// from [`MultisigSignatory::sign_partial`].
+pub struct MultisigSignatory {
+    /// Our secret keys, by index of their public key in the multisig script.
+    pub seckeys: BTreeMap<usize, SecKey>,
+    /// Signatures (including sighash byte) of other parties, by public key index.
+    pub partial_sigs: BTreeMap<usize, Bytes>,
+    pub sig_type: MultisigSigType,
+    pub sig_hash_type: SigHashType,
+}
+
+impl MultisigSignatory {
+    /// Sign the input with our secret keys, returning the signatures by public key index.
+    pub fn sign_partial(
+        &self,
+        ecc: &dyn Ecc,
+        input: &UnsignedTxInput<'_>,
+    ) -> Result<BTreeMap<usize, Bytes>> {
+        let preimage = input.sighash_preimage(self.sig_hash_type, None)?;
+        let (_, pubkeys) = preimage
+            .redeem_script
+            .parse_multisig()
+            .ok_or(SignError::NotMultisig)?;
+        self.sign_preimage(ecc, preimage.bytes, pubkeys.len())
+    }
+
+    fn sign_preimage(
+        &self,
+        ecc: &dyn Ecc,
+        preimage: Bytes,
+        num_keys: usize,
+    ) -> Result<BTreeMap<usize, Bytes>> {
+        let sighash = Sha256d::digest(preimage).byte_array().clone();
+        let mut sigs = BTreeMap::new();
+        for (&key_idx, seckey) in &self.seckeys {
+            if key_idx >= num_keys {
+                return Err(SignError::InvalidMultisigKeyIdx(key_idx).into());
+            }
+            let sig = match self.sig_type {
+                MultisigSigType::Ecdsa => ecc.sign(seckey, sighash.clone()),
+                MultisigSigType::Schnorr => ecc.schnorr_sign(seckey, sighash.clone()),
+            };
+            let mut sig_flagged = BytesMut::new();
+            sig_flagged.put_bytes(sig);
+            sig_flagged.put_slice(&[self.sig_hash_type.to_u32() as u8]);
+            sigs.insert(key_idx, sig_flagged.freeze());
+        }
+        Ok(sigs)
+    }
+}
+
+impl Signatory for MultisigSignatory {
+    fn sign_input<'tx>(&self, ecc: &dyn Ecc, mut input: UnsignedTxInput<'tx>) -> Result<()> {
+        let preimage = input.sighash_preimage(self.sig_hash_type, None)?;
+        let redeem_script = preimage.redeem_script;
+        let (num_signers, pubkeys) = redeem_script
+            .parse_multisig()
+            .ok_or(SignError::NotMultisig)?;
+        let mut sigs = self.partial_sigs.clone();
+        sigs.extend(self.sign_preimage(ecc, preimage.bytes, pubkeys.len())?);
+        if let Some(&key_idx) = sigs.keys().find(|&&key_idx| key_idx >= pubkeys.len()) {
+            return Err(SignError::InvalidMultisigKeyIdx(key_idx).into());
+        }
+        let num_signers = num_signers as usize;
+        if sigs.len() < num_signers {
+            return Err(SignError::NotEnoughSignatures {
+                required: num_signers,
+                available: sigs.len(),
+            }
+            .into());
+        }
+        // Use the signatures of the first public keys, in order of the public keys
+        let sigs = sigs.into_iter().take(num_signers).collect::<Vec<_>>();
+        let dummy = match self.sig_type {
+            MultisigSigType::Ecdsa => Op::Code(OP_0),
+            MultisigSigType::Schnorr => {
+                let mut checkbits = vec![0u8; pubkeys.len().div_ceil(8)];
+                for &(key_idx, _) in &sigs {
+                    checkbits[key_idx / 8] |= 1 << (key_idx % 8);
+                }
+                push_minimal(checkbits.into())
+            }
+        };
+        let mut ops = vec![dummy];
+        ops.extend(sigs.into_iter().map(|(_, sig)| Op::push_bytes(sig)));
+        // Bare multisig outputs don't need the redeem script
+        let is_bare = input
+            .input_sign_data()
+            .and_then(|sign_data| sign_data.find_output_script().ok())
+            .is_some_and(|output_script| output_script == redeem_script);
+        if !is_bare {
+            ops.push(Op::push_bytes(redeem_script.bytecode().clone()));
+        }
+        *input.input_script_mut() = Script::from_ops(ops.into_iter())?;
+        Ok(())
+    }
+}
+
+/// Push the bytes, using OP_1NEGATE and OP_1-OP_16 for single bytes of that value, as required
+/// by MINIMALDATA.
+fn push_minimal(bytes: Bytes) -> Op {
+    match *bytes.as_ref() {
+        [0x81] => Op::Code(OP_1NEGATE),
+        [num @ 1..=16] => Op::Code(OP_1 - 1 + num),
+        _ => Op::push_bytes(bytes),
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
+
     use crate::{
         ecc::{DummyEcc, PubKey, SecKey},
-        Hashed, OutPoint, P2PKHSignatory, Script, SequenceNo, ShaRmd160, SigHashType, SignData,
-        SignField, Signatory, TxInput, TxOutput, UnhashedTx, UnsignedTx,
+        BitcoinSuiteError, Hashed, MultisigSigType, MultisigSignatory, OutPoint, P2PKHSignatory,
+        P2TRKeySignatory, P2TRScriptSignatory, Script, SequenceNo, ShaRmd160, SigHashType,
+        SignData, SignError, SignField, Signatory, TapLeaf, TapTree, TaprootBuilder, TxBuilder,
+        TxBuilderInput, TxBuilderOutput, TxInput, TxOutput, UnhashedTx, UnsignedTx,
     };
 
     #[test]
@@ -66,6 +254,12 @@ mod tests {
             SigHashType::ALL_BIP143_ANYONECANPAY,
             SigHashType::NONE_BIP143_ANYONECANPAY,
             SigHashType::SINGLE_BIP143_ANYONECANPAY,
+            SigHashType::ALL_LOTUS,
+            SigHashType::NONE_LOTUS,
+            SigHashType::SINGLE_LOTUS,
+            SigHashType::ALL_LOTUS_ANYONECANPAY,
+            SigHashType::NONE_LOTUS_ANYONECANPAY,
+            SigHashType::SINGLE_LOTUS_ANYONECANPAY,
         ];
         for sig_hash_type in sig_hash_types {
             let signatory = P2PKHSignatory {
@@ -90,4 +284,167 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn test_p2tr_signatories() -> Result<(), Box<dyn std::error::Error>> {
+        let ecc = DummyEcc;
+        let seckey = SecKey::new_unchecked([1; 32]);
+        let internal_key = PubKey::new_unchecked([2; 33]);
+        let tapscript = Script::from_slice(&[[33].as_ref(), &[2; 33], &[0xac]].concat());
+        let leaf = TapLeaf::new(tapscript.clone());
+        let spend_info = TaprootBuilder::new(internal_key)
+            .with_tree(TapTree::Leaf(leaf.clone()))
+            .build(&ecc)?;
+        let control_block = spend_info.control_block(&leaf).unwrap();
+        let p2tr_script = spend_info.output_script(None);
+
+        let input = |out_idx, sign_fields| TxInput {
+            prev_out: OutPoint {
+                out_idx,
+                ..Default::default()
+            },
+            script: Script::default(),
+            sequence: SequenceNo::finalized(),
+            sign_data: Some(SignData::new(sign_fields)),
+        };
+        let key_path_signatory = |sig_hash_type| P2TRKeySignatory {
+            seckey: seckey.clone(),
+            spend_info: spend_info.clone(),
+            sig_hash_type,
+        };
+        let tx_builder = |sig_hash_type| TxBuilder {
+            version: 2,
+            inputs: vec![
+                TxBuilderInput::new(
+                    input(
+                        0,
+                        vec![
+                            SignField::Value(10000),
+                            SignField::OutputScript(p2tr_script.clone()),
+                        ],
+                    ),
+                    Box::new(key_path_signatory(sig_hash_type)),
+                ),
+                TxBuilderInput::new(
+                    input(
+                        1,
+                        vec![
+                            SignField::Value(20000),
+                            SignField::OutputScript(p2tr_script.clone()),
+                            SignField::RedeemScript(tapscript.clone()),
+                        ],
+                    ),
+                    Box::new(P2TRScriptSignatory {
+                        seckey: seckey.clone(),
+                        control_block: control_block.clone(),
+                        sig_hash_type,
+                    }),
+                ),
+            ],
+            outputs: vec![TxBuilderOutput::Leftover(Script::from_slice(&[0x51]))],
+            lock_time: 0,
+        };
+
+        let tx = tx_builder(SigHashType::ALL_LOTUS).sign(&ecc, 1000, 546)?;
+        let sig = [[0; 64].as_ref(), &[0x61]].concat();
+        assert_eq!(
+            tx.inputs[0].script,
+            Script::from_slice(&[[65].as_ref(), &sig].concat()),
+        );
+        assert_eq!(
+            tx.inputs[1].script,
+            Script::from_slice(
+                &[
+                    [65].as_ref(),
+                    &sig,
+                    &[35],
+                    tapscript.bytecode(),
+                    &[33],
+                    &control_block,
+                ]
+                .concat()
+            ),
+        );
+        assert_eq!(tx.outputs[0].value, 30000 - 304);
+
+        match tx_builder(SigHashType::ALL_BIP143).sign(&ecc, 1000, 546) {
+            Err(BitcoinSuiteError::Sign(SignError::InvalidSigHashType(
+                SigHashType::ALL_BIP143,
+            ))) => {}
+            result => panic!("Unexpected: {result:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_multisig_signatory() -> Result<(), Box<dyn std::error::Error>> {
+        let ecc = DummyEcc;
+        let pubkeys = [[2; 33], [3; 33], [4; 33]];
+        let redeem_script = Script::multisig(2, pubkeys.iter().map(|pubkey| pubkey.as_ref()));
+        let tx = UnhashedTx {
+            version: 1,
+            inputs: vec![TxInput {
+                sign_data: Some(SignData::new(vec![
+                    SignField::Value(5000),
+                    SignField::OutputScript(redeem_script.to_p2sh()),
+                    SignField::RedeemScript(redeem_script.clone()),
+                ])),
+                ..Default::default()
+            }],
+            outputs: vec![TxOutput::default()],
+            lock_time: 0,
+        };
+        let signatory = |seckeys: &[usize], sig_type| MultisigSignatory {
+            seckeys: seckeys
+                .iter()
+                .map(|&key_idx| (key_idx, SecKey::new_unchecked([1; 32])))
+                .collect(),
+            partial_sigs: BTreeMap::new(),
+            sig_type,
+            sig_hash_type: SigHashType::ALL_BIP143,
+        };
+        let redeem_push = [
+            [0x4c, redeem_script.bytecode().len() as u8].as_ref(),
+            redeem_script.bytecode(),
+        ]
+        .concat();
+
+        // Schnorr: checkbits 0b101 pushed as OP_5, followed by the sigs and the redeem script
+        let mut unsigned_tx = UnsignedTx::new_dummy(tx.clone());
+        signatory(&[0, 2], MultisigSigType::Schnorr).sign_input(&ecc, unsigned_tx.input_at(0))?;
+        let sig = [[0; 64].as_ref(), &[0x41]].concat();
+        let expected_script = [[0x55, 65].as_ref(), &sig, &[65], &sig, &redeem_push].concat();
+        assert_eq!(
+            unsigned_tx.tx().inputs[0].script.bytecode().as_ref(),
+            expected_script
+        );
+
+        // ECDSA: OP_0 dummy, only the first 2 of 3 sigs are used
+        let mut unsigned_tx = UnsignedTx::new_dummy(tx.clone());
+        signatory(&[0, 1, 2], MultisigSigType::Ecdsa).sign_input(&ecc, unsigned_tx.input_at(0))?;
+        let sig = [[0; 71].as_ref(), &[0x41]].concat();
+        let expected_script = [[0x00, 72].as_ref(), &sig, &[72], &sig, &redeem_push].concat();
+        assert_eq!(
+            unsigned_tx.tx().inputs[0].script.bytecode().as_ref(),
+            expected_script
+        );
+
+        let mut unsigned_tx = UnsignedTx::new_dummy(tx.clone());
+        match signatory(&[3], MultisigSigType::Ecdsa).sign_input(&ecc, unsigned_tx.input_at(0)) {
+            Err(BitcoinSuiteError::Sign(SignError::InvalidMultisigKeyIdx(3))) => {}
+            result => panic!("Unexpected: {result:?}"),
+        }
+
+        let mut tx = tx;
+        tx.inputs[0].sign_data = Some(SignData::new(vec![
+            SignField::Value(5000),
+            SignField::OutputScript(Script::opreturn(&[])),
+        ]));
+        let mut unsigned_tx = UnsignedTx::new_dummy(tx);
+        match signatory(&[0], MultisigSigType::Ecdsa).sign_input(&ecc, unsigned_tx.input_at(0)) {
+            Err(BitcoinSuiteError::Sign(SignError::NotMultisig)) => {}
+            result => panic!("Unexpected: {result:?}"),
+        }
+        Ok(())
+    }
 }
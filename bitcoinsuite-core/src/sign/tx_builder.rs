@@ -38,7 +38,7 @@ impl TxBuilder {
         }
     }
 
-    fn input_sum(&self) -> Option<i64> {
+    pub(crate) fn input_sum(&self) -> Option<i64> {
         let mut input_sum = 0;
         for builder_input in &self.inputs {
             let sign_data = builder_input.input.sign_data.as_ref()?;
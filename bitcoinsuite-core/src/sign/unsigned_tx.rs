@@ -1,6 +1,10 @@
+use std::cell::OnceCell;
+
 use crate::{
-    BitcoinCode, BitcoinSuiteError, Bytes, BytesMut, Hashed, Script, Sha256d, SigHashType,
-    SigHashTypeInputs, SigHashTypeOutputs, SigHashTypeVariant, SignData, SignError, UnhashedTx,
+    encoding::write_compact_size, lotus_tx_inputs_merkle_root, lotus_tx_outputs_merkle_root,
+    opcode::OP_CODESEPARATOR, tapleaf_hash, BitcoinCode, BitcoinSuiteError, Bytes, BytesMut,
+    Hashed, Op, Script, Sha256, Sha256d, SigHashType, SigHashTypeInputs, SigHashTypeOutputs,
+    SigHashTypeVariant, SignData, SignError, TxInput, TxOutput, UnhashedTx, TAPROOT_LEAF_TAPSCRIPT,
 };
 
 use crate::sign::error::Result;
@@ -9,9 +13,27 @@ pub struct UnsignedTx {
     prevouts_hash: Sha256d,
     sequences_hash: Sha256d,
     outputs_hash: Sha256d,
+    lotus_hashes: LotusHashes,
     tx: UnhashedTx,
 }
 
+struct LotusHashes {
+    inputs_merkle_root: Sha256d,
+    inputs_merkle_height: usize,
+    outputs_merkle_root: Sha256d,
+    outputs_merkle_height: usize,
+    total_output_amount: i64,
+    /// Computed when first needed and reset when sign data changes; `None` for dummy txs.
+    spent_outputs: Option<OnceCell<Result<LotusSpentOutputs>>>,
+}
+
+#[derive(Default)]
+struct LotusSpentOutputs {
+    merkle_root: Sha256d,
+    merkle_height: usize,
+    total_amount: i64,
+}
+
 pub struct UnsignedTxInput<'tx> {
     idx: usize,
     unsigned_tx: &'tx mut UnsignedTx,
@@ -30,6 +52,7 @@ impl UnsignedTx {
             prevouts_hash: calc_prevouts_hash(&tx),
             sequences_hash: calc_sequences_hash(&tx),
             outputs_hash: calc_outputs_hash(&tx),
+            lotus_hashes: calc_lotus_hashes(&tx),
             tx,
         }
     }
@@ -39,6 +62,14 @@ impl UnsignedTx {
             prevouts_hash: Sha256d::default(),
             sequences_hash: Sha256d::default(),
             outputs_hash: Sha256d::default(),
+            lotus_hashes: LotusHashes {
+                inputs_merkle_root: Sha256d::default(),
+                inputs_merkle_height: 0,
+                outputs_merkle_root: Sha256d::default(),
+                outputs_merkle_height: 0,
+                total_output_amount: 0,
+                spent_outputs: None,
+            },
             tx,
         }
     }
@@ -65,9 +96,16 @@ impl<'tx> UnsignedTxInput<'tx> {
     }
 
     pub fn input_sign_data_mut(&mut self) -> &mut Option<SignData> {
+        if let Some(spent_outputs) = &mut self.unsigned_tx.lotus_hashes.spent_outputs {
+            spent_outputs.take();
+        }
         &mut self.unsigned_tx.tx.inputs[self.idx].sign_data
     }
 
+    pub fn input_sign_data(&self) -> Option<&SignData> {
+        self.unsigned_tx.tx.inputs[self.idx].sign_data.as_ref()
+    }
+
     pub fn unsigned_tx(&self) -> &UnsignedTx {
         self.unsigned_tx
     }
@@ -81,11 +119,7 @@ impl<'tx> UnsignedTxInput<'tx> {
         sig_hash_type: SigHashType,
         codesep_idx: Option<usize>,
     ) -> Result<SighashPreimage> {
-        if sig_hash_type.variant != SigHashTypeVariant::Bip143 {
-            return Err(SignError::InvalidSigHashType(sig_hash_type));
-        }
-        let tx = &self.unsigned_tx.tx;
-        let input = &tx.inputs[self.idx];
+        let input = &self.unsigned_tx.tx.inputs[self.idx];
         let sign_data = match &input.sign_data {
             Some(sign_data) => sign_data,
             None => return Err(SignError::NoSignData),
@@ -98,10 +132,164 @@ impl<'tx> UnsignedTxInput<'tx> {
                 BitcoinSuiteError::CodesepNotFound(idx) => SignError::CodesepNotFound(idx),
                 _ => unreachable!(),
             })?;
+        let bytes = match sig_hash_type.variant {
+            SigHashTypeVariant::Lotus => {
+                let script_path = match sign_data.is_p2tr_script_path() {
+                    true => Some((
+                        tapleaf_hash(TAPROOT_LEAF_TAPSCRIPT, &redeem_script),
+                        codesep_pos(&redeem_script, codesep_idx)?,
+                    )),
+                    false => None,
+                };
+                self.unsigned_tx.lotus_sighash_preimage(
+                    self.idx,
+                    sig_hash_type,
+                    script_path.as_ref().map(|(hash, pos)| (hash, *pos)),
+                )?
+            }
+            SigHashTypeVariant::Legacy => {
+                self.unsigned_tx
+                    .legacy_sighash_preimage(self.idx, sig_hash_type, &script_code)?
+            }
+            SigHashTypeVariant::Bip143 => self.unsigned_tx.sighash_preimage_for_script_code(
+                self.idx,
+                sig_hash_type,
+                &script_code,
+                sign_data.find_value()?,
+            )?,
+        };
+        Ok(SighashPreimage {
+            bytes,
+            script_code,
+            redeem_script,
+        })
+    }
+}
+
+impl UnsignedTx {
+    /// Build the sighash preimage of the input at `input_idx`, using the given script code and
+    /// value instead of the input's [`SignData`]. Supports legacy and BIP143 sighashes.
+    pub fn sighash_preimage_for_script_code(
+        &self,
+        input_idx: usize,
+        sig_hash_type: SigHashType,
+        script_code: &Script,
+        value: i64,
+    ) -> Result<Bytes> {
+        match sig_hash_type.variant {
+            SigHashTypeVariant::Legacy => {
+                self.legacy_sighash_preimage(input_idx, sig_hash_type, script_code)
+            }
+            SigHashTypeVariant::Bip143 => {
+                self.bip143_sighash_preimage(input_idx, sig_hash_type, script_code, value)
+            }
+            SigHashTypeVariant::Lotus => Err(SignError::InvalidSigHashType(sig_hash_type)),
+        }
+    }
+
+    /// Sighash of the input at `input_idx` for verifying signatures, like
+    /// [`UnsignedTx::sighash_preimage_for_script_code`].
+    ///
+    /// For legacy SIGHASH_SINGLE without a corresponding output, this is 1 (as little-endian
+    /// uint256), replicating a bug of the original Bitcoin client.
+    pub fn sighash_for_script_code(
+        &self,
+        input_idx: usize,
+        sig_hash_type: SigHashType,
+        script_code: &Script,
+        value: i64,
+    ) -> Result<Sha256d> {
+        if sig_hash_type.variant == SigHashTypeVariant::Legacy
+            && sig_hash_type.output_type == SigHashTypeOutputs::Single
+            && input_idx >= self.tx.outputs.len()
+        {
+            let mut one = [0; 32];
+            one[0] = 1;
+            return Ok(Sha256d::new(one));
+        }
+        let preimage =
+            self.sighash_preimage_for_script_code(input_idx, sig_hash_type, script_code, value)?;
+        Ok(Sha256d::digest(preimage))
+    }
+
+    /// Legacy sighash preimage, which is the tx with the script code as the input's script and
+    /// all OP_CODESEPARATORs removed from it. Other inputs and outputs are blanked out
+    /// depending on the sighash type.
+    ///
+    /// Fails for SIGHASH_SINGLE without a corresponding output, where the original Bitcoin
+    /// client signs the constant 1; signing this would allow spending the coin arbitrarily.
+    pub fn legacy_sighash_preimage(
+        &self,
+        input_idx: usize,
+        sig_hash_type: SigHashType,
+        script_code: &Script,
+    ) -> Result<Bytes> {
+        if sig_hash_type.variant != SigHashTypeVariant::Legacy {
+            return Err(SignError::InvalidSigHashType(sig_hash_type));
+        }
+        let tx = &self.tx;
+        let script_code =
+            script_code.find_and_delete(&Script::from_static_slice(&[OP_CODESEPARATOR]));
+        let mut preimage = BytesMut::new();
+        preimage.put_bytes(tx.version.ser());
+        let is_anyonecanpay = sig_hash_type.input_type == SigHashTypeInputs::AnyoneCanPay;
+        let is_outputs_all = sig_hash_type.output_type == SigHashTypeOutputs::All;
+        let signed_inputs = match is_anyonecanpay {
+            true => input_idx..input_idx + 1,
+            false => 0..tx.inputs.len(),
+        };
+        write_compact_size(&mut preimage, signed_inputs.len() as u64);
+        for other_idx in signed_inputs {
+            let input = &tx.inputs[other_idx];
+            preimage.put_bytes(input.prev_out.ser());
+            if other_idx == input_idx {
+                preimage.put_bytes(script_code.ser());
+                preimage.put_bytes(input.sequence.ser());
+            } else {
+                preimage.put_bytes(Script::default().ser());
+                match is_outputs_all {
+                    true => preimage.put_bytes(input.sequence.ser()),
+                    false => preimage.put_bytes(0u32.ser()),
+                }
+            }
+        }
+        match sig_hash_type.output_type {
+            SigHashTypeOutputs::All => preimage.put_bytes(tx.outputs.ser()),
+            SigHashTypeOutputs::None => write_compact_size(&mut preimage, 0),
+            SigHashTypeOutputs::Single => {
+                let output = tx
+                    .outputs
+                    .get(input_idx)
+                    .ok_or(SignError::MissingSingleOutput(input_idx))?;
+                write_compact_size(&mut preimage, input_idx as u64 + 1);
+                let null_output = TxOutput {
+                    value: -1,
+                    script: Script::default(),
+                };
+                for _ in 0..input_idx {
+                    preimage.put_bytes(null_output.ser());
+                }
+                preimage.put_bytes(output.ser());
+            }
+        }
+        preimage.put_bytes(tx.lock_time.ser());
+        preimage.put_bytes(sig_hash_type.to_u32().ser());
+        Ok(preimage.freeze())
+    }
+
+    fn bip143_sighash_preimage(
+        &self,
+        input_idx: usize,
+        sig_hash_type: SigHashType,
+        script_code: &Script,
+        value: i64,
+    ) -> Result<Bytes> {
+        let tx = &self.tx;
+        let input = &tx.inputs[input_idx];
         let mut preimage = BytesMut::new();
         preimage.put_bytes(tx.version.ser());
         preimage.put_byte_array(if sig_hash_type.input_type == SigHashTypeInputs::Fixed {
-            self.unsigned_tx.prevouts_hash.byte_array().clone()
+            self.prevouts_hash.byte_array().clone()
         } else {
             [0; 32].into()
         });
@@ -109,19 +297,19 @@ impl<'tx> UnsignedTxInput<'tx> {
             if sig_hash_type.input_type == SigHashTypeInputs::Fixed
                 && sig_hash_type.output_type == SigHashTypeOutputs::All
             {
-                self.unsigned_tx.sequences_hash.byte_array().clone()
+                self.sequences_hash.byte_array().clone()
             } else {
                 [0; 32].into()
             },
         );
         preimage.put_bytes(input.prev_out.ser());
         preimage.put_bytes(script_code.ser());
-        preimage.put_bytes(sign_data.find_value()?.ser());
+        preimage.put_bytes(value.ser());
         preimage.put_bytes(input.sequence.ser());
         preimage.put_byte_array(match sig_hash_type.output_type {
-            SigHashTypeOutputs::All => self.unsigned_tx.outputs_hash.byte_array().clone(),
-            SigHashTypeOutputs::Single if self.idx < tx.outputs.len() => {
-                Sha256d::digest(tx.outputs[self.idx].ser())
+            SigHashTypeOutputs::All => self.outputs_hash.byte_array().clone(),
+            SigHashTypeOutputs::Single if input_idx < tx.outputs.len() => {
+                Sha256d::digest(tx.outputs[input_idx].ser())
                     .byte_array()
                     .clone()
             }
@@ -129,12 +317,98 @@ impl<'tx> UnsignedTxInput<'tx> {
         });
         preimage.put_bytes(tx.lock_time.ser());
         preimage.put_bytes(sig_hash_type.to_u32().ser());
-        Ok(SighashPreimage {
-            bytes: preimage.freeze(),
-            script_code,
-            redeem_script,
-        })
+        Ok(preimage.freeze())
+    }
+
+    /// Build the Lotus sighash preimage of the input at `input_idx`.
+    ///
+    /// Unlike BIP143, it commits to the spent outputs of all inputs (or only this input's if
+    /// ANYONECANPAY), so every input requires both value and output script in its
+    /// [`SignData`]. Inputs and outputs are committed to by the merkle roots also used for the
+    /// Lotus txid. P2TR script path spends pass the tapleaf hash of the executed script and
+    /// the opcode position of the last executed OP_CODESEPARATOR (or `u32::MAX`).
+    pub fn lotus_sighash_preimage(
+        &self,
+        input_idx: usize,
+        sig_hash_type: SigHashType,
+        script_path: Option<(&Sha256, u32)>,
+    ) -> Result<Bytes> {
+        if sig_hash_type.variant != SigHashTypeVariant::Lotus {
+            return Err(SignError::InvalidSigHashType(sig_hash_type));
+        }
+        let tx = &self.tx;
+        let input = &tx.inputs[input_idx];
+        let hashes = &self.lotus_hashes;
+        let dummy_spent_outputs = LotusSpentOutputs::default();
+        let mut preimage = BytesMut::new();
+        preimage.put_bytes(sig_hash_type.to_u32().ser());
+        match sig_hash_type.input_type {
+            SigHashTypeInputs::Fixed => {
+                let spent_outputs = match &hashes.spent_outputs {
+                    Some(spent_outputs) => spent_outputs
+                        .get_or_init(|| calc_lotus_spent_outputs(tx))
+                        .as_ref()
+                        .map_err(|err| *err)?,
+                    None => &dummy_spent_outputs,
+                };
+                preimage.put_bytes((input_idx as u32).ser());
+                preimage.put_bytes(spent_outputs.merkle_root.ser());
+                preimage.put_bytes((spent_outputs.merkle_height as u8).ser());
+                preimage.put_bytes(spent_outputs.total_amount.ser());
+            }
+            SigHashTypeInputs::AnyoneCanPay => {
+                preimage.put_bytes(input.prev_out.ser());
+                preimage.put_bytes(lotus_spent_output(input_idx, input)?.ser());
+                preimage.put_bytes(input.sequence.ser());
+            }
+        }
+        if sig_hash_type.output_type == SigHashTypeOutputs::All {
+            preimage.put_bytes(hashes.total_output_amount.ser());
+        }
+        if let Some((tapleaf_hash, codesep_pos)) = script_path {
+            preimage.put_slice(tapleaf_hash.as_slice());
+            preimage.put_bytes(codesep_pos.ser());
+        }
+        preimage.put_bytes(tx.version.ser());
+        if sig_hash_type.input_type == SigHashTypeInputs::Fixed {
+            preimage.put_bytes(hashes.inputs_merkle_root.ser());
+            preimage.put_bytes((hashes.inputs_merkle_height as u8).ser());
+        }
+        match sig_hash_type.output_type {
+            SigHashTypeOutputs::All => {
+                preimage.put_bytes(hashes.outputs_merkle_root.ser());
+                preimage.put_bytes((hashes.outputs_merkle_height as u8).ser());
+            }
+            SigHashTypeOutputs::Single => {
+                let output = tx
+                    .outputs
+                    .get(input_idx)
+                    .ok_or(SignError::MissingSingleOutput(input_idx))?;
+                preimage.put_bytes(Sha256d::digest(output.ser()).ser());
+            }
+            SigHashTypeOutputs::None => {}
+        }
+        preimage.put_bytes(tx.lock_time.ser());
+        Ok(preimage.freeze())
+    }
+}
+
+/// Position of the `codesep_idx`-th OP_CODESEPARATOR in the script, counted in opcodes.
+fn codesep_pos(script: &Script, codesep_idx: Option<usize>) -> Result<u32> {
+    let codesep_idx = match codesep_idx {
+        Some(codesep_idx) => codesep_idx,
+        None => return Ok(u32::MAX),
+    };
+    let mut n_codeseps_found = 0;
+    for (op_idx, op) in script.ops().enumerate() {
+        if let Op::Code(OP_CODESEPARATOR) = op.map_err(|_| SignError::InvalidScriptEncoding)? {
+            if n_codeseps_found == codesep_idx {
+                return Ok(op_idx as u32);
+            }
+            n_codeseps_found += 1;
+        }
     }
+    Err(SignError::CodesepNotFound(codesep_idx))
 }
 
 fn calc_prevouts_hash(tx: &UnhashedTx) -> Sha256d {
@@ -153,6 +427,43 @@ fn calc_sequences_hash(tx: &UnhashedTx) -> Sha256d {
     Sha256d::digest(hashes.freeze())
 }
 
+fn calc_lotus_hashes(tx: &UnhashedTx) -> LotusHashes {
+    let (inputs_merkle_root, inputs_merkle_height) = lotus_tx_inputs_merkle_root(&tx.inputs);
+    let (outputs_merkle_root, outputs_merkle_height) = lotus_tx_outputs_merkle_root(&tx.outputs);
+    LotusHashes {
+        inputs_merkle_root,
+        inputs_merkle_height,
+        outputs_merkle_root,
+        outputs_merkle_height,
+        total_output_amount: tx.outputs.iter().map(|output| output.value).sum(),
+        spent_outputs: Some(OnceCell::new()),
+    }
+}
+
+fn calc_lotus_spent_outputs(tx: &UnhashedTx) -> Result<LotusSpentOutputs> {
+    let spent_outputs = tx
+        .inputs
+        .iter()
+        .enumerate()
+        .map(|(input_idx, input)| lotus_spent_output(input_idx, input))
+        .collect::<Result<Vec<_>>>()?;
+    let (merkle_root, merkle_height) = lotus_tx_outputs_merkle_root(&spent_outputs);
+    Ok(LotusSpentOutputs {
+        merkle_root,
+        merkle_height,
+        total_amount: spent_outputs.iter().map(|output| output.value).sum(),
+    })
+}
+
+fn lotus_spent_output(input_idx: usize, input: &TxInput) -> Result<TxOutput> {
+    let missing = SignError::MissingSpentOutput(input_idx);
+    let sign_data = input.sign_data.as_ref().ok_or(missing)?;
+    Ok(TxOutput {
+        value: sign_data.find_value().map_err(|_| missing)?,
+        script: sign_data.find_output_script().map_err(|_| missing)?,
+    })
+}
+
 fn calc_outputs_hash(tx: &UnhashedTx) -> Sha256d {
     let mut hashes = BytesMut::new();
     for output in &tx.outputs {
@@ -164,8 +475,11 @@ fn calc_outputs_hash(tx: &UnhashedTx) -> Sha256d {
 #[cfg(test)]
 mod tests {
     use crate::{
-        Hashed, OutPoint, Script, SequenceNo, Sha256d, SigHashType, SignData, SignError, SignField,
-        TxInput, TxOutput, UnhashedTx, UnsignedTx,
+        ecc::{PubKey, SecKey},
+        interpreter::mock_ecc::MockEcc,
+        tapleaf_hash, BitcoinSuiteError, Hashed, OutPoint, P2PKHSignatory, Script, SequenceNo,
+        Sha256d, SigHashType, SignData, SignError, SignField, Signatory, TxInput, TxOutput,
+        UnhashedTx, UnsignedTx, TAPROOT_LEAF_TAPSCRIPT,
     };
 
     #[test]
@@ -422,4 +736,359 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn test_legacy_sighash_preimage() -> Result<(), Box<dyn std::error::Error>> {
+        let script_code = Script::from_slice(&[0x51, 0xab, 0xac, 0xab, 0x87]);
+        let tx = UnhashedTx {
+            version: 1,
+            inputs: vec![
+                TxInput {
+                    prev_out: OutPoint {
+                        txid: Sha256d::new([0xae; 32]),
+                        out_idx: 0x12345678,
+                    },
+                    script: Script::default(),
+                    sequence: SequenceNo::finalized(),
+                    sign_data: None,
+                },
+                TxInput {
+                    prev_out: OutPoint {
+                        txid: Sha256d::new([0xbf; 32]),
+                        out_idx: 7,
+                    },
+                    script: Script::default(),
+                    sequence: SequenceNo::from_u32(0xffff_fffe),
+                    sign_data: Some(SignData::new(vec![SignField::OutputScript(
+                        script_code.clone(),
+                    )])),
+                },
+            ],
+            outputs: vec![
+                TxOutput {
+                    value: 1000,
+                    script: Script::from_slice(&[0x51]),
+                },
+                TxOutput {
+                    value: 2000,
+                    script: Script::from_slice(&[0x52]),
+                },
+            ],
+            lock_time: 10,
+        };
+        let legacy = |flags| SigHashType::from_u32(flags).unwrap();
+        let mut unsigned_tx = UnsignedTx::new(tx);
+        let input = unsigned_tx.input_at(1);
+        // OP_CODESEPARATORs are removed from the script code
+        {
+            let preimage = input.sighash_preimage(legacy(0x01), Some(0))?;
+            assert_eq!(
+                preimage.script_code,
+                Script::from_slice(&[0xac, 0xab, 0x87])
+            );
+            assert_eq!(
+                preimage.bytes.hex(),
+                "01000000\
+                02\
+                aeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeae78563412\
+                00\
+                ffffffff\
+                bfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbf07000000\
+                02ac87\
+                feffffff\
+                02\
+                e8030000000000000151\
+                d0070000000000000152\
+                0a000000\
+                01000000"
+            );
+        }
+        {
+            let preimage = input.sighash_preimage(legacy(0x82), Some(0))?.bytes;
+            assert_eq!(
+                preimage.hex(),
+                "01000000\
+                01\
+                bfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbf07000000\
+                02ac87\
+                feffffff\
+                00\
+                0a000000\
+                82000000"
+            );
+        }
+        {
+            let preimage = input.sighash_preimage(legacy(0x03), Some(0))?.bytes;
+            assert_eq!(
+                preimage.hex(),
+                "01000000\
+                02\
+                aeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeae78563412\
+                00\
+                00000000\
+                bfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbfbf07000000\
+                02ac87\
+                feffffff\
+                02\
+                ffffffffffffffff00\
+                d0070000000000000152\
+                0a000000\
+                03000000"
+            );
+        }
+        assert_eq!(
+            unsigned_tx.sighash_for_script_code(
+                1,
+                legacy(0x01),
+                &Script::from_slice(&[0xac, 0xab, 0x87]),
+                0,
+            )?,
+            Sha256d::from_hex_be(
+                "a249f3385af82877ac147d546301fc83d9841774f1e7acd9ad6919a25b616ea6"
+            )?,
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_legacy_sighash_single_bug() -> Result<(), Box<dyn std::error::Error>> {
+        let input = TxInput {
+            prev_out: OutPoint::default(),
+            script: Script::default(),
+            sequence: SequenceNo::finalized(),
+            sign_data: Some(SignData::new(vec![SignField::OutputScript(
+                Script::from_slice(&[0x51]),
+            )])),
+        };
+        let tx = UnhashedTx {
+            version: 1,
+            inputs: vec![input.clone(), input],
+            outputs: vec![TxOutput::default()],
+            lock_time: 0,
+        };
+        let single = SigHashType::from_u32(0x03).unwrap();
+        let mut unsigned_tx = UnsignedTx::new(tx);
+        // Signing the constant 1 is refused, but it is the sighash for verification
+        assert_eq!(
+            unsigned_tx
+                .input_at(1)
+                .sighash_preimage(single, None)
+                .unwrap_err(),
+            SignError::MissingSingleOutput(1),
+        );
+        let mut one = [0; 32];
+        one[0] = 1;
+        assert_eq!(
+            unsigned_tx.sighash_for_script_code(1, single, &Script::default(), 0)?,
+            Sha256d::new(one),
+        );
+        assert_ne!(
+            unsigned_tx.sighash_for_script_code(0, single, &Script::default(), 0)?,
+            Sha256d::new(one),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_lotus_sighash_preimage() -> Result<(), Box<dyn std::error::Error>> {
+        let tx = UnhashedTx {
+            version: 1,
+            inputs: vec![TxInput {
+                prev_out: OutPoint {
+                    txid: Sha256d::new([0xae; 32]),
+                    out_idx: 0x12345678,
+                },
+                script: Script::default(),
+                sequence: SequenceNo::finalized(),
+                sign_data: Some(SignData::new(vec![
+                    SignField::Value(12345),
+                    SignField::OutputScript(Script::from_slice(&[0x51])),
+                ])),
+            }],
+            outputs: vec![TxOutput::default()],
+            lock_time: 0,
+        };
+        let mut unsigned_tx = UnsignedTx::new(tx.clone());
+        let input = unsigned_tx.input_at(0);
+        {
+            let preimage = input.sighash_preimage(SigHashType::ALL_LOTUS, None)?.bytes;
+            assert_eq!(
+                preimage.hex(),
+                "61000000\
+                00000000\
+                eb74587eed307518dc0ac81645ec9ebcbfd1799e8fb04fe49226a42784fc2c5a01\
+                3930000000000000\
+                0000000000000000\
+                01000000\
+                6afe16ce4d60afd0d0057ee94daef50218caa3af9eb9a2a32937c34fbac3d51801\
+                edb908054ac1409be5f77d5369c6e03490b2f6676d68d0b3370f8159e0fdadf901\
+                00000000"
+            );
+        }
+        {
+            let preimage = input
+                .sighash_preimage(SigHashType::SINGLE_LOTUS_ANYONECANPAY, None)?
+                .bytes;
+            assert_eq!(
+                preimage.hex(),
+                "e3000000\
+                aeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeaeae78563412\
+                39300000000000000151\
+                ffffffff\
+                01000000\
+                edb908054ac1409be5f77d5369c6e03490b2f6676d68d0b3370f8159e0fdadf9\
+                00000000"
+            );
+        }
+        {
+            let preimage = input.sighash_preimage(SigHashType::NONE_LOTUS, None)?.bytes;
+            assert_eq!(preimage.len(), 4 + 4 + 33 + 8 + 4 + 33 + 4);
+        }
+
+        // Spending a P2TR output via the script path commits to the tapleaf
+        let tapscript = Script::from_slice(&[0x51, 0xab, 0x87]);
+        let mut p2tr_tx = tx.clone();
+        p2tr_tx.inputs[0].sign_data = Some(SignData::new(vec![
+            SignField::Value(12345),
+            SignField::OutputScript(Script::p2tr(&PubKey::new_unchecked([2; 33]), None)),
+            SignField::RedeemScript(tapscript.clone()),
+        ]));
+        let mut unsigned_tx = UnsignedTx::new(p2tr_tx);
+        let input = unsigned_tx.input_at(0);
+        let tapleaf_hash = tapleaf_hash(TAPROOT_LEAF_TAPSCRIPT, &tapscript);
+        let preimage = input.sighash_preimage(SigHashType::ALL_LOTUS, None)?;
+        assert_eq!(preimage.redeem_script, tapscript);
+        assert_eq!(
+            preimage.bytes,
+            input.unsigned_tx().lotus_sighash_preimage(
+                0,
+                SigHashType::ALL_LOTUS,
+                Some((&tapleaf_hash, u32::MAX))
+            )?,
+        );
+        assert_eq!(
+            input
+                .sighash_preimage(SigHashType::ALL_LOTUS, Some(0))?
+                .bytes,
+            input.unsigned_tx().lotus_sighash_preimage(
+                0,
+                SigHashType::ALL_LOTUS,
+                Some((&tapleaf_hash, 1))
+            )?,
+        );
+        assert_eq!(
+            input
+                .sighash_preimage(SigHashType::ALL_LOTUS, Some(1))
+                .unwrap_err(),
+            SignError::CodesepNotFound(1),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_lotus_sighash_sign_data_changed() -> Result<(), Box<dyn std::error::Error>> {
+        let sign_data = |value| {
+            SignData::new(vec![
+                SignField::Value(value),
+                SignField::OutputScript(Script::from_slice(&[0x51])),
+            ])
+        };
+        let input = |out_idx, sign_data| TxInput {
+            prev_out: OutPoint {
+                out_idx,
+                ..Default::default()
+            },
+            script: Script::default(),
+            sequence: SequenceNo::finalized(),
+            sign_data,
+        };
+        let make_tx = |sign_data1| UnhashedTx {
+            version: 1,
+            inputs: vec![input(0, Some(sign_data(1000))), input(1, sign_data1)],
+            outputs: vec![TxOutput::default()],
+            lock_time: 0,
+        };
+        let signatory = P2PKHSignatory {
+            seckey: SecKey::new_unchecked([3; 32]),
+            pubkey: PubKey::new_unchecked([2; 33]),
+            sig_hash_type: SigHashType::ALL_LOTUS,
+        };
+        let sign = |unsigned_tx: &mut UnsignedTx| -> Result<Script, BitcoinSuiteError> {
+            signatory.sign_input(&MockEcc, unsigned_tx.input_at(0))?;
+            Ok(unsigned_tx.tx().inputs[0].script.clone())
+        };
+
+        // Sign data of input 1 is only added after construction
+        let mut unsigned_tx = UnsignedTx::new(make_tx(None));
+        assert!(matches!(
+            sign(&mut unsigned_tx),
+            Err(BitcoinSuiteError::Sign(SignError::MissingSpentOutput(1))),
+        ));
+        *unsigned_tx.input_at(1).input_sign_data_mut() = Some(sign_data(2000));
+        let expected = sign(&mut UnsignedTx::new(make_tx(Some(sign_data(2000)))))?;
+        assert_eq!(sign(&mut unsigned_tx)?, expected);
+
+        // Sign data of input 1 changes after signing input 0
+        *unsigned_tx.input_at(1).input_sign_data_mut() = Some(sign_data(3000));
+        let expected_changed = sign(&mut UnsignedTx::new(make_tx(Some(sign_data(3000)))))?;
+        assert_ne!(expected_changed, expected);
+        assert_eq!(sign(&mut unsigned_tx)?, expected_changed);
+        Ok(())
+    }
+
+    #[test]
+    fn test_lotus_sighash_preimage_failure() {
+        let input = TxInput {
+            prev_out: OutPoint::default(),
+            script: Script::default(),
+            sequence: SequenceNo::finalized(),
+            sign_data: Some(SignData::new(vec![
+                SignField::Value(12345),
+                SignField::OutputScript(Script::from_slice(&[0x51])),
+            ])),
+        };
+        let mut tx = UnhashedTx {
+            version: 1,
+            inputs: vec![input.clone(), input],
+            outputs: vec![TxOutput::default()],
+            lock_time: 0,
+        };
+        tx.inputs[1].sign_data = Some(SignData::new(vec![SignField::Value(12345)]));
+        let mut unsigned_tx = UnsignedTx::new(tx);
+        assert_eq!(
+            unsigned_tx
+                .input_at(0)
+                .sighash_preimage(SigHashType::ALL_LOTUS, None)
+                .unwrap_err(),
+            SignError::MissingSpentOutput(1),
+        );
+        // ANYONECANPAY only needs the spent output of the signed input
+        assert!(unsigned_tx
+            .input_at(0)
+            .sighash_preimage(SigHashType::ALL_LOTUS_ANYONECANPAY, None)
+            .is_ok());
+        assert_eq!(
+            unsigned_tx
+                .lotus_sighash_preimage(1, SigHashType::SINGLE_LOTUS_ANYONECANPAY, None)
+                .unwrap_err(),
+            SignError::MissingSpentOutput(1),
+        );
+        let mut input = unsigned_tx.input_at(1);
+        *input.input_sign_data_mut() = Some(SignData::new(vec![
+            SignField::Value(12345),
+            SignField::OutputScript(Script::from_slice(&[0x51])),
+        ]));
+        assert_eq!(
+            input
+                .sighash_preimage(SigHashType::SINGLE_LOTUS_ANYONECANPAY, None)
+                .unwrap_err(),
+            SignError::MissingSingleOutput(1),
+        );
+        assert_eq!(
+            unsigned_tx
+                .lotus_sighash_preimage(0, SigHashType::ALL_BIP143, None)
+                .unwrap_err(),
+            SignError::InvalidSigHashType(SigHashType::ALL_BIP143),
+        );
+    }
 }
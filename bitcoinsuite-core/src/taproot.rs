@@ -0,0 +1,310 @@
+use crate::{
+    ecc::{Ecc, EccError, PubKey, SecKey},
+    BitcoinCode, Bytes, BytesMut, Hashed, Script, Sha256,
+};
+
+/// Leaf version of tapscripts, the only leaf version defined on Lotus.
+pub const TAPROOT_LEAF_TAPSCRIPT: u8 = 0xc0;
+
+/// Mask for the leaf version in the first byte of a control block; the lowest bit holds the
+/// parity of the internal key.
+pub const TAPROOT_LEAF_MASK: u8 = 0xfe;
+
+/// Leaf of a taproot script tree.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TapLeaf {
+    pub leaf_version: u8,
+    pub script: Script,
+}
+
+/// Script tree committed to by a taproot output, spendable via the script path.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TapTree {
+    Leaf(TapLeaf),
+    Branch(Box<TapTree>, Box<TapTree>),
+}
+
+/// Builds the commitment of a P2TR output from an internal key and an optional script tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaprootBuilder {
+    internal_key: PubKey,
+    tree: Option<TapTree>,
+}
+
+/// Everything required to spend a P2TR output, via either the key path or script path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaprootSpendInfo {
+    internal_key: PubKey,
+    merkle_root: Option<Sha256>,
+    tweak: Sha256,
+    commitment: PubKey,
+    leaves: Vec<(TapLeaf, Vec<Sha256>)>,
+}
+
+/// BIP340 tagged hash, i.e. SHA256(SHA256(tag) || SHA256(tag) || data).
+pub fn tagged_hash(tag: &str, data: &[u8]) -> Sha256 {
+    let tag_hash = Sha256::digest(Bytes::from_slice(tag.as_bytes()));
+    let mut preimage = BytesMut::new();
+    preimage.put_slice(tag_hash.as_slice());
+    preimage.put_slice(tag_hash.as_slice());
+    preimage.put_slice(data);
+    Sha256::digest(preimage.freeze())
+}
+
+/// Hash of a leaf of a taproot script tree, which script path signatures commit to.
+pub fn tapleaf_hash(leaf_version: u8, script: &Script) -> Sha256 {
+    let mut data = BytesMut::new();
+    data.put_slice(&[leaf_version]);
+    script.ser_to(&mut data);
+    tagged_hash("TapLeaf", &data.freeze())
+}
+
+/// Hash of a branch of a taproot script tree; children are sorted, so order doesn't matter.
+pub fn tapbranch_hash(a: &Sha256, b: &Sha256) -> Sha256 {
+    let (left, right) = if a.as_slice() <= b.as_slice() {
+        (a, b)
+    } else {
+        (b, a)
+    };
+    let mut data = BytesMut::new();
+    data.put_slice(left.as_slice());
+    data.put_slice(right.as_slice());
+    tagged_hash("TapBranch", &data.freeze())
+}
+
+/// Tweak added to the internal key to get the commitment of a P2TR output.
+pub fn taproot_tweak(internal_key: &PubKey, merkle_root: Option<&Sha256>) -> Sha256 {
+    let mut data = BytesMut::new();
+    data.put_slice(internal_key.as_slice());
+    if let Some(merkle_root) = merkle_root {
+        data.put_slice(merkle_root.as_slice());
+    }
+    tagged_hash("TapTweak", &data.freeze())
+}
+
+impl TapLeaf {
+    /// Leaf with a tapscript.
+    pub fn new(script: Script) -> Self {
+        TapLeaf {
+            leaf_version: TAPROOT_LEAF_TAPSCRIPT,
+            script,
+        }
+    }
+
+    pub fn hash(&self) -> Sha256 {
+        tapleaf_hash(self.leaf_version, &self.script)
+    }
+}
+
+impl TapTree {
+    /// Leaf with a tapscript.
+    pub fn leaf(script: Script) -> Self {
+        TapTree::Leaf(TapLeaf::new(script))
+    }
+
+    pub fn branch(left: TapTree, right: TapTree) -> Self {
+        TapTree::Branch(Box::new(left), Box::new(right))
+    }
+
+    pub fn hash(&self) -> Sha256 {
+        match self {
+            TapTree::Leaf(leaf) => leaf.hash(),
+            TapTree::Branch(left, right) => tapbranch_hash(&left.hash(), &right.hash()),
+        }
+    }
+
+    /// All leaves with their merkle paths, from the leaf up to the root.
+    pub fn leaves_with_paths(&self) -> Vec<(TapLeaf, Vec<Sha256>)> {
+        match self {
+            TapTree::Leaf(leaf) => vec![(leaf.clone(), vec![])],
+            TapTree::Branch(left, right) => {
+                let mut leaves = Vec::new();
+                for (child, sibling) in [(left, right), (right, left)] {
+                    let sibling_hash = sibling.hash();
+                    for (leaf, mut path) in child.leaves_with_paths() {
+                        path.push(sibling_hash.clone());
+                        leaves.push((leaf, path));
+                    }
+                }
+                leaves
+            }
+        }
+    }
+}
+
+impl TaprootBuilder {
+    pub fn new(internal_key: PubKey) -> Self {
+        TaprootBuilder {
+            internal_key,
+            tree: None,
+        }
+    }
+
+    pub fn with_tree(mut self, tree: TapTree) -> Self {
+        self.tree = Some(tree);
+        self
+    }
+
+    /// Tweak the internal key with the script tree to get the commitment.
+    pub fn build(self, ecc: &dyn Ecc) -> Result<TaprootSpendInfo, EccError> {
+        let merkle_root = self.tree.as_ref().map(TapTree::hash);
+        let tweak = taproot_tweak(&self.internal_key, merkle_root.as_ref());
+        let commitment = ecc.pubkey_tweak_add(&self.internal_key, tweak.byte_array().array())?;
+        Ok(TaprootSpendInfo {
+            internal_key: self.internal_key,
+            merkle_root,
+            tweak,
+            commitment,
+            leaves: self
+                .tree
+                .as_ref()
+                .map(TapTree::leaves_with_paths)
+                .unwrap_or_default(),
+        })
+    }
+}
+
+impl TaprootSpendInfo {
+    pub fn internal_key(&self) -> &PubKey {
+        &self.internal_key
+    }
+
+    pub fn merkle_root(&self) -> Option<&Sha256> {
+        self.merkle_root.as_ref()
+    }
+
+    pub fn tweak(&self) -> &Sha256 {
+        &self.tweak
+    }
+
+    pub fn commitment(&self) -> &PubKey {
+        &self.commitment
+    }
+
+    pub fn leaves(&self) -> impl Iterator<Item = &TapLeaf> {
+        self.leaves.iter().map(|(leaf, _)| leaf)
+    }
+
+    pub fn output_script(&self, state: Option<[u8; 32]>) -> Script {
+        Script::p2tr(&self.commitment, state)
+    }
+
+    /// Tweak the internal secret key for signing via the key path.
+    pub fn tweak_seckey(&self, ecc: &dyn Ecc, seckey: &SecKey) -> Result<SecKey, EccError> {
+        ecc.seckey_tweak_add(seckey, self.tweak.byte_array().array())
+    }
+
+    /// Control block proving the leaf is part of the script tree, or `None` if it isn't.
+    ///
+    /// It consists of the leaf version (with the parity of the internal key in the lowest
+    /// bit), the x coordinate of the internal key and the merkle path of the leaf.
+    pub fn control_block(&self, leaf: &TapLeaf) -> Option<Bytes> {
+        let (_, path) = self.leaves.iter().find(|(other, _)| other == leaf)?;
+        let internal_key = self.internal_key.array();
+        let parity = internal_key[0] & 1;
+        let mut control_block = BytesMut::new();
+        control_block.put_slice(&[(leaf.leaf_version & TAPROOT_LEAF_MASK) | parity]);
+        control_block.put_slice(&internal_key[1..]);
+        for hash in path {
+            control_block.put_slice(hash.as_slice());
+        }
+        Some(control_block.freeze())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ecc::{DummyEcc, PubKey},
+        tagged_hash, tapbranch_hash, tapleaf_hash, Hashed, Script, Sha256, TapLeaf, TapTree,
+        TaprootBuilder, TAPROOT_LEAF_TAPSCRIPT,
+    };
+
+    #[test]
+    fn test_tapleaf_hash() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(
+            tagged_hash("TapLeaf", &[]),
+            Sha256::from_hex("5212c288a377d1f8164962a5a13429f9ba6a7b84e59776a52c6637df2106facb")?,
+        );
+        assert_eq!(
+            tapleaf_hash(TAPROOT_LEAF_TAPSCRIPT, &Script::from_slice(&[0x51])),
+            Sha256::from_hex("a85b2107f791b26a84e7586c28cec7cb61202ed3d01944d832500f363782d675")?,
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_taproot_builder() -> Result<(), Box<dyn std::error::Error>> {
+        let leaf_a = TapLeaf::new(Script::from_slice(&[0x51]));
+        let leaf_b = TapLeaf::new(Script::from_slice(&[0x52]));
+        let leaf_c = TapLeaf::new(Script::from_slice(&[0x53]));
+        let hash_a = leaf_a.hash();
+        let hash_b = leaf_b.hash();
+        let hash_c = leaf_c.hash();
+        assert_eq!(
+            tapbranch_hash(&hash_b, &hash_c),
+            Sha256::from_hex("96f5ef67a1641de7ef38da0361f157ad5b6874db8efd0ee0ef7cb84e4976ace6")?,
+        );
+        assert_eq!(
+            tapbranch_hash(&hash_b, &hash_c),
+            tapbranch_hash(&hash_c, &hash_b),
+        );
+        let tree = TapTree::branch(
+            TapTree::Leaf(leaf_a.clone()),
+            TapTree::branch(TapTree::Leaf(leaf_b.clone()), TapTree::Leaf(leaf_c)),
+        );
+        let merkle_root =
+            Sha256::from_hex("49e49cd9cb39ef9dc8ddc4e55f918a2b53530516d1b9de4a0c3b646b8e7633cb")?;
+        assert_eq!(tree.hash(), merkle_root);
+
+        let internal_key =
+            PubKey::new_unchecked([[3].as_ref(), &[7; 32]].concat().try_into().unwrap());
+        let spend_info = TaprootBuilder::new(internal_key)
+            .with_tree(tree)
+            .build(&DummyEcc)?;
+        assert_eq!(spend_info.merkle_root(), Some(&merkle_root));
+        assert_eq!(
+            spend_info.tweak(),
+            &Sha256::from_hex("dc9b13abcd292a66430e6d67ebdcda60288dc6c9fd4482b23de4a21b3a7a6c13")?,
+        );
+        assert_eq!(spend_info.leaves().count(), 3);
+        assert_eq!(
+            spend_info.output_script(None),
+            Script::p2tr(spend_info.commitment(), None),
+        );
+        assert_eq!(
+            spend_info.control_block(&leaf_a).unwrap().as_ref(),
+            [
+                [0xc1].as_ref(),
+                &[7; 32],
+                hex::decode("96f5ef67a1641de7ef38da0361f157ad5b6874db8efd0ee0ef7cb84e4976ace6")?
+                    .as_ref()
+            ]
+            .concat(),
+        );
+        assert_eq!(
+            spend_info.control_block(&leaf_b).unwrap().as_ref(),
+            [
+                [0xc1].as_ref(),
+                &[7; 32],
+                hash_c.as_slice(),
+                hash_a.as_slice()
+            ]
+            .concat(),
+        );
+        assert_eq!(
+            spend_info.control_block(&TapLeaf::new(Script::from_slice(&[0x54]))),
+            None,
+        );
+
+        // Key path only
+        let spend_info = TaprootBuilder::new(internal_key).build(&DummyEcc)?;
+        assert_eq!(spend_info.merkle_root(), None);
+        assert_eq!(
+            spend_info.tweak(),
+            &Sha256::from_hex("b895bcc770c405a129252b002a365010d4474b8e6f90baa3c42e055c127553bb")?,
+        );
+        assert_eq!(spend_info.control_block(&leaf_a), None);
+        Ok(())
+    }
+}
@@ -72,7 +72,7 @@ pub fn lotus_txid(tx: &UnhashedTx) -> Sha256d {
     Sha256d::digest(data.freeze())
 }
 
-fn lotus_tx_inputs_merkle_root(inputs: &[TxInput]) -> (Sha256d, usize) {
+pub(crate) fn lotus_tx_inputs_merkle_root(inputs: &[TxInput]) -> (Sha256d, usize) {
     let leaves = inputs
         .iter()
         .map(|input| {
@@ -85,7 +85,7 @@ fn lotus_tx_inputs_merkle_root(inputs: &[TxInput]) -> (Sha256d, usize) {
     get_merkle_root_and_height(leaves, MerkleMode::Lotus)
 }
 
-fn lotus_tx_outputs_merkle_root(outputs: &[TxOutput]) -> (Sha256d, usize) {
+pub(crate) fn lotus_tx_outputs_merkle_root(outputs: &[TxOutput]) -> (Sha256d, usize) {
     let leaves = outputs
         .iter()
         .map(|output| Sha256d::digest(output.ser()))
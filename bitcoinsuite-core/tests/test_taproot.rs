@@ -0,0 +1,175 @@
+use bitcoinsuite_core::{
+    ecc::{Ecc, PubKey},
+    tapbranch_hash, tapleaf_hash, taproot_tweak, Bytes, Hashed, Op, OutPoint, P2TRKeySignatory,
+    P2TRScriptSignatory, Script, SequenceNo, Sha256, Sha256d, SigHashType, SignData, SignField,
+    Signatory, TapLeaf, TapTree, TaprootBuilder, TxBuilder, TxBuilderInput, TxBuilderOutput,
+    TxInput, UnsignedTx, TAPROOT_LEAF_TAPSCRIPT,
+};
+use bitcoinsuite_ecc_secp256k1::EccSecp256k1;
+use hex_literal::hex;
+
+fn pushed_data(script: &Script) -> Result<Vec<Bytes>, Box<dyn std::error::Error>> {
+    let mut pushes = Vec::new();
+    for op in script.ops() {
+        match op? {
+            Op::Push(_, data) => pushes.push(data),
+            op => panic!("Unexpected op: {op:?}"),
+        }
+    }
+    Ok(pushes)
+}
+
+/// Recompute the commitment from a control block and the tapscript it's for.
+fn commitment_from_control_block(
+    ecc: &dyn Ecc,
+    control_block: &[u8],
+    tapscript: &Script,
+) -> Result<PubKey, Box<dyn std::error::Error>> {
+    let mut internal_key = [0; 33];
+    internal_key[0] = 0x02 | (control_block[0] & 1);
+    internal_key[1..].copy_from_slice(&control_block[1..33]);
+    let internal_key = ecc.pubkey_from_array(internal_key)?;
+    let mut hash = tapleaf_hash(control_block[0] & 0xfe, tapscript);
+    for node in control_block[33..].chunks(32) {
+        hash = tapbranch_hash(&hash, &Sha256::from_slice(node)?);
+    }
+    let tweak = taproot_tweak(&internal_key, Some(&hash));
+    Ok(ecc.pubkey_tweak_add(&internal_key, tweak.byte_array().array())?)
+}
+
+#[test]
+fn test_p2tr_sign_verify() -> Result<(), Box<dyn std::error::Error>> {
+    let ecc = EccSecp256k1::default();
+    let seckey = ecc.seckey_from_array([1; 32])?;
+    let internal_key = ecc.derive_pubkey(&seckey);
+    let leaf_seckey = ecc.seckey_from_array([2; 32])?;
+    let leaf_pubkey = ecc.derive_pubkey(&leaf_seckey);
+    let tapscript = Script::from_slice(&[[33].as_ref(), leaf_pubkey.as_slice(), &[0xac]].concat());
+    let leaf = TapLeaf::new(tapscript.clone());
+    let spend_info = TaprootBuilder::new(internal_key)
+        .with_tree(TapTree::branch(
+            TapTree::Leaf(leaf.clone()),
+            TapTree::leaf(Script::from_slice(&[0x51])),
+        ))
+        .build(&ecc)?;
+    // Computed independently from BIP341-style tagged hashes and plain EC arithmetic
+    assert_eq!(
+        internal_key.hex(),
+        "031b84c5567b126440995d3ed5aaba0565d71e1834604819ff9c17f5e9d5dd078f",
+    );
+    assert_eq!(
+        spend_info.merkle_root().unwrap().as_slice(),
+        hex!("25d4cc18ad8896e196f3526ef92501671beda511f1be0b7d53b6cc78e820f0a1"),
+    );
+    assert_eq!(
+        spend_info.commitment().hex(),
+        "02ca10a907c685d47472d4862dfb04086d0b40dfc0d40c0b9640dd5435b64704c7",
+    );
+    let control_block = spend_info.control_block(&leaf).unwrap();
+    assert_eq!(control_block[0], TAPROOT_LEAF_TAPSCRIPT | 1);
+    let p2tr_script = spend_info.output_script(None);
+
+    let input = |out_idx, sign_fields| TxInput {
+        prev_out: OutPoint {
+            txid: Sha256d::new([7; 32]),
+            out_idx,
+        },
+        script: Script::default(),
+        sequence: SequenceNo::finalized(),
+        sign_data: Some(SignData::new(sign_fields)),
+    };
+    let tx = TxBuilder {
+        version: 2,
+        inputs: vec![
+            TxBuilderInput::new(
+                input(
+                    0,
+                    vec![
+                        SignField::Value(10000),
+                        SignField::OutputScript(p2tr_script.clone()),
+                    ],
+                ),
+                Box::new(P2TRKeySignatory {
+                    seckey: seckey.clone(),
+                    spend_info: spend_info.clone(),
+                    sig_hash_type: SigHashType::ALL_LOTUS,
+                }),
+            ),
+            TxBuilderInput::new(
+                input(
+                    1,
+                    vec![
+                        SignField::Value(20000),
+                        SignField::OutputScript(p2tr_script.clone()),
+                        SignField::RedeemScript(tapscript.clone()),
+                    ],
+                ),
+                Box::new(P2TRScriptSignatory {
+                    seckey: leaf_seckey,
+                    control_block: control_block.clone(),
+                    sig_hash_type: SigHashType::ALL_LOTUS,
+                }),
+            ),
+        ],
+        outputs: vec![TxBuilderOutput::Leftover(Script::from_slice(&[0x51]))],
+        lock_time: 0,
+    }
+    .sign(&ecc, 1000, 546)?;
+
+    let key_path_pushes = pushed_data(&tx.inputs[0].script)?;
+    let script_path_pushes = pushed_data(&tx.inputs[1].script)?;
+    assert_eq!(key_path_pushes.len(), 1);
+    assert_eq!(script_path_pushes.len(), 3);
+    assert_eq!(&script_path_pushes[1], tapscript.bytecode());
+    assert_eq!(script_path_pushes[2], control_block);
+
+    let mut unsigned_tx = UnsignedTx::new(tx);
+    let mut sighash = |input_idx| -> Result<_, Box<dyn std::error::Error>> {
+        let preimage = unsigned_tx
+            .input_at(input_idx)
+            .sighash_preimage(SigHashType::ALL_LOTUS, None)?;
+        Ok(Sha256d::digest(preimage.bytes).byte_array().clone())
+    };
+    let key_path_sighash = sighash(0)?;
+    let script_path_sighash = sighash(1)?;
+
+    // Key path: signed by the tweaked internal key, i.e. the commitment of the output
+    let sig = &key_path_pushes[0];
+    assert_eq!(sig.len(), 65);
+    assert_eq!(sig[64], SigHashType::ALL_LOTUS.to_u32() as u8);
+    let sig = Bytes::from_slice(&sig[..64]);
+    ecc.schnorr_verify(spend_info.commitment(), key_path_sighash.clone(), &sig)?;
+    assert!(ecc
+        .schnorr_verify(&internal_key, key_path_sighash.clone(), &sig)
+        .is_err());
+    // Tweaking for a different tree yields a signature not valid for this output
+    let key_only_spend_info = TaprootBuilder::new(internal_key).build(&ecc)?;
+    let mut wrong_tweak_tx = UnsignedTx::new(unsigned_tx.tx().clone());
+    P2TRKeySignatory {
+        seckey,
+        spend_info: key_only_spend_info,
+        sig_hash_type: SigHashType::ALL_LOTUS,
+    }
+    .sign_input(&ecc, wrong_tweak_tx.input_at(0))?;
+    let wrong_sig = &pushed_data(&wrong_tweak_tx.tx().inputs[0].script)?[0];
+    assert!(ecc
+        .schnorr_verify(
+            spend_info.commitment(),
+            key_path_sighash,
+            &Bytes::from_slice(&wrong_sig[..64]),
+        )
+        .is_err());
+
+    // Script path: control block proves the tapscript, which is signed by the leaf key
+    assert_eq!(
+        &commitment_from_control_block(&ecc, &script_path_pushes[2], &tapscript)?,
+        spend_info.commitment(),
+    );
+    let sig = &script_path_pushes[0];
+    assert_eq!(sig.len(), 65);
+    assert_eq!(sig[64], SigHashType::ALL_LOTUS.to_u32() as u8);
+    let sig = Bytes::from_slice(&sig[..64]);
+    ecc.schnorr_verify(&leaf_pubkey, script_path_sighash, &sig)?;
+
+    Ok(())
+}
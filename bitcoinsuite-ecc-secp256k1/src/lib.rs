@@ -26,6 +26,11 @@ impl Ecc for EccSecp256k1 {
         Ok(PubKey::new_unchecked(pubkey))
     }
 
+    fn pubkey_from_uncompressed(&self, pubkey: [u8; 65]) -> Result<PubKey, EccError> {
+        let pubkey = PublicKey::from_slice(&pubkey).map_err(|_| EccError::InvalidPublicKey)?;
+        Ok(PubKey::new_unchecked(pubkey.serialize()))
+    }
+
     fn seckey_from_array(&self, seckey: [u8; 32]) -> Result<SecKey, EccError> {
         SecretKey::from_slice(&seckey).map_err(|_| EccError::InvalidSecretKey)?;
         Ok(SecKey::new_unchecked(seckey))
@@ -80,6 +85,26 @@ impl Ecc for EccSecp256k1 {
         PubKey::new_unchecked(pubkey.serialize())
     }
 
+    fn seckey_tweak_add(&self, seckey: &SecKey, tweak: [u8; 32]) -> Result<SecKey, EccError> {
+        let mut seckey =
+            SecretKey::from_slice(seckey.as_slice()).map_err(|_| EccError::InvalidSecretKey)?;
+        seckey
+            .add_assign(&tweak)
+            .map_err(|_| EccError::InvalidTweak)?;
+        let mut tweaked = [0; 32];
+        tweaked.copy_from_slice(&seckey[..]);
+        Ok(SecKey::new_unchecked(tweaked))
+    }
+
+    fn pubkey_tweak_add(&self, pubkey: &PubKey, tweak: [u8; 32]) -> Result<PubKey, EccError> {
+        let mut pubkey =
+            PublicKey::from_slice(pubkey.as_slice()).map_err(|_| EccError::InvalidPublicKey)?;
+        pubkey
+            .add_exp_assign(&self.curve, &tweak)
+            .map_err(|_| EccError::InvalidTweak)?;
+        Ok(PubKey::new_unchecked(pubkey.serialize()))
+    }
+
     fn serialize_pubkey_uncompressed(&self, pubkey: &PubKey) -> [u8; 65] {
         PublicKey::from_slice(pubkey.as_slice())
             .expect("Invalid pubkey")
@@ -122,7 +147,7 @@ impl Ecc for EccSecp256k1 {
 #[cfg(test)]
 mod tests {
     use super::EccSecp256k1;
-    use bitcoinsuite_core::ecc::{Ecc, EccError, PubKey, VerifySignatureError};
+    use bitcoinsuite_core::ecc::{Ecc, EccError, PubKey, SecKey, VerifySignatureError};
     use hex_literal::hex;
 
     #[test]
@@ -144,6 +169,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_pubkey_from_uncompressed() {
+        let ecc = EccSecp256k1::default();
+        let pubkey = ecc.pubkey_from_array(PubKey::default().array()).unwrap();
+        let uncompressed = ecc.serialize_pubkey_uncompressed(&pubkey);
+        assert_eq!(ecc.pubkey_from_uncompressed(uncompressed).unwrap(), pubkey);
+        assert_eq!(
+            ecc.pubkey_from_uncompressed([4; 65]).unwrap_err(),
+            EccError::InvalidPublicKey
+        );
+    }
+
     #[test]
     fn test_seckey_from_array() {
         let ecc = EccSecp256k1::default();
@@ -154,6 +191,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_tweak_add() {
+        let ecc = EccSecp256k1::default();
+        let seckey = ecc.seckey_from_array([2; 32]).unwrap();
+        let pubkey = ecc.derive_pubkey(&seckey);
+        let mut tweak = [0; 32];
+        tweak[31] = 1;
+        let tweaked_seckey = ecc.seckey_tweak_add(&seckey, tweak).unwrap();
+        let mut expected = [2; 32];
+        expected[31] = 3;
+        assert_eq!(tweaked_seckey.as_slice(), &expected);
+        assert_eq!(
+            ecc.pubkey_tweak_add(&pubkey, tweak).unwrap(),
+            ecc.derive_pubkey(&tweaked_seckey),
+        );
+        let order = hex!("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141");
+        assert_eq!(
+            ecc.seckey_tweak_add(&seckey, order).unwrap_err(),
+            EccError::InvalidTweak
+        );
+        assert_eq!(
+            ecc.pubkey_tweak_add(&pubkey, order).unwrap_err(),
+            EccError::InvalidTweak
+        );
+        assert_eq!(
+            ecc.seckey_tweak_add(&SecKey::new_unchecked([0; 32]), tweak)
+                .unwrap_err(),
+            EccError::InvalidSecretKey
+        );
+        assert_eq!(
+            ecc.pubkey_tweak_add(&PubKey::new_unchecked([0; 33]), tweak)
+                .unwrap_err(),
+            EccError::InvalidPublicKey
+        );
+    }
+
     #[test]
     fn test_sign() {
         let ecc = EccSecp256k1::default();
@@ -0,0 +1,225 @@
+use bitcoinsuite_core::{BitcoinCode, Bytes, BytesMut, Hashed, Net, Network, Sha256d};
+use bitcoinsuite_error::Result;
+
+use crate::{P2pError::*, P2pMessage};
+
+/// Size of the header in front of every P2P message.
+pub const MESSAGE_HEADER_SIZE: usize = 24;
+
+/// Largest payload we accept from peers, 32 MB.
+pub const MAX_MESSAGE_SIZE: u32 = 32 * 1024 * 1024;
+
+/// Header of a P2P message, wrapping its payload.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MessageHeader {
+    /// Identifies the network, see [`network_magic`].
+    pub magic: [u8; 4],
+    /// ASCII command, padded with zeros.
+    pub command: [u8; 12],
+    pub payload_size: u32,
+    /// First 4 bytes of the double-SHA256 of the payload.
+    pub checksum: [u8; 4],
+}
+
+/// Magic bytes at the start of each message for the network.
+///
+/// Only networks using the Bitcoin header format are supported, so this is `None` for XPI
+/// and XRG. Other magics can still be set in [`crate::PeerConfig`] directly.
+pub fn network_magic(network: Network, net: Net) -> Option<[u8; 4]> {
+    match (network, net) {
+        (Network::BCH | Network::XEC, Net::Mainnet) => Some([0xe3, 0xe1, 0xf3, 0xe8]),
+        (Network::BCH | Network::XEC, Net::Regtest) => Some([0xda, 0xb5, 0xbf, 0xfa]),
+        (Network::XPI | Network::XRG, _) => None,
+    }
+}
+
+pub fn payload_checksum(payload: &[u8]) -> [u8; 4] {
+    let hash = Sha256d::digest(Bytes::from_slice(payload));
+    hash.as_slice()[..4].try_into().unwrap()
+}
+
+impl MessageHeader {
+    /// Header for the payload; `command` must be at most 12 ASCII characters.
+    pub fn new(magic: [u8; 4], command: &str, payload: &[u8]) -> Result<Self> {
+        let mut command_bytes = [0; 12];
+        if command.len() > command_bytes.len() || !command.is_ascii() || command.contains('\0') {
+            return Err(InvalidCommandStr(command.to_string()).into());
+        }
+        command_bytes[..command.len()].copy_from_slice(command.as_bytes());
+        Ok(MessageHeader {
+            magic,
+            command: command_bytes,
+            payload_size: payload.len() as u32,
+            checksum: payload_checksum(payload),
+        })
+    }
+
+    /// Command as string; must be ASCII, padded with zeros.
+    pub fn command_str(&self) -> Result<&str> {
+        let len = self
+            .command
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(self.command.len());
+        let (command, padding) = self.command.split_at(len);
+        if !command.is_ascii() || padding.iter().any(|&byte| byte != 0) {
+            return Err(InvalidCommand(self.command).into());
+        }
+        Ok(std::str::from_utf8(command).unwrap())
+    }
+
+    /// Check the magic and size, before reading the payload.
+    pub fn validate(&self, expected_magic: [u8; 4]) -> Result<()> {
+        if self.magic != expected_magic {
+            return Err(WrongMagic {
+                expected: expected_magic,
+                actual: self.magic,
+            }
+            .into());
+        }
+        if self.payload_size > MAX_MESSAGE_SIZE {
+            return Err(MessageTooLarge(self.payload_size).into());
+        }
+        self.command_str()?;
+        Ok(())
+    }
+}
+
+impl BitcoinCode for MessageHeader {
+    fn ser_to(&self, bytes: &mut BytesMut) {
+        bytes.put_slice(&self.magic);
+        bytes.put_slice(&self.command);
+        self.payload_size.ser_to(bytes);
+        bytes.put_slice(&self.checksum);
+    }
+
+    fn deser(data: &mut Bytes) -> bitcoinsuite_core::Result<Self> {
+        Ok(MessageHeader {
+            magic: data.split_to_array::<4>()?.array(),
+            command: data.split_to_array::<12>()?.array(),
+            payload_size: BitcoinCode::deser(data)?,
+            checksum: data.split_to_array::<4>()?.array(),
+        })
+    }
+}
+
+impl P2pMessage {
+    /// Serialize the message with the header in front; fails for [`P2pMessage::Unknown`]
+    /// with an invalid command.
+    pub fn ser_framed(&self, magic: [u8; 4]) -> Result<Bytes> {
+        let payload = self.ser_payload();
+        let mut bytes = BytesMut::new();
+        MessageHeader::new(magic, self.command(), &payload)?.ser_to(&mut bytes);
+        bytes.put_bytes(payload);
+        Ok(bytes.freeze())
+    }
+
+    /// Parse the payload following the (validated) header, verifying its checksum.
+    pub fn deser_framed(header: &MessageHeader, payload: Bytes) -> Result<Self> {
+        let command = header.command_str()?;
+        let checksum = payload_checksum(&payload);
+        if checksum != header.checksum {
+            return Err(InvalidChecksum {
+                command: command.to_string(),
+                expected: header.checksum,
+                actual: checksum,
+            }
+            .into());
+        }
+        P2pMessage::deser_payload(command, payload).map_err(|err| {
+            InvalidPayload {
+                command: command.to_string(),
+                msg: err.to_string(),
+            }
+            .into()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoinsuite_core::{BitcoinCode, Bytes, Net, Network};
+    use bitcoinsuite_error::Result;
+    use hex_literal::hex;
+
+    use crate::{network_magic, MessageHeader, P2pError, P2pMessage};
+
+    #[test]
+    fn test_envelope() -> Result<()> {
+        let magic = network_magic(Network::XEC, Net::Mainnet).unwrap();
+        let verack = P2pMessage::Verack.ser_framed(magic)?;
+        assert_eq!(
+            verack.as_ref(),
+            hex!("e3e1f3e8 76657261636b000000000000 00000000 5df6e0e2"),
+        );
+        let ping = P2pMessage::Ping(0x1234).ser_framed(magic)?;
+        let mut data = ping.clone();
+        let header = MessageHeader::deser(&mut data)?;
+        header.validate(magic)?;
+        assert_eq!(header.command_str()?, "ping");
+        assert_eq!(header.payload_size, 8);
+        assert_eq!(
+            P2pMessage::deser_framed(&header, data)?,
+            P2pMessage::Ping(0x1234),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_envelope_invalid() -> Result<()> {
+        let magic = network_magic(Network::BCH, Net::Regtest).unwrap();
+        let mainnet_magic = network_magic(Network::BCH, Net::Mainnet).unwrap();
+        let header = MessageHeader::new(mainnet_magic, "ping", &[0; 8])?;
+        assert_eq!(
+            header.validate(magic).unwrap_err().downcast::<P2pError>()?,
+            P2pError::WrongMagic {
+                expected: magic,
+                actual: mainnet_magic,
+            },
+        );
+        let mut header = MessageHeader::new(magic, "ping", &[0; 8])?;
+        header.command[5] = b'x';
+        assert_eq!(
+            header.validate(magic).unwrap_err().downcast::<P2pError>()?,
+            P2pError::InvalidCommand(*b"ping\0x\0\0\0\0\0\0"),
+        );
+        let mut header = MessageHeader::new(magic, "block", &[])?;
+        header.payload_size = 0x0200_0001;
+        assert_eq!(
+            header.validate(magic).unwrap_err().downcast::<P2pError>()?,
+            P2pError::MessageTooLarge(0x0200_0001),
+        );
+        let header = MessageHeader::new(magic, "ping", &[0; 8])?;
+        assert_eq!(
+            P2pMessage::deser_framed(&header, Bytes::from_slice(&[1; 8]))
+                .unwrap_err()
+                .downcast::<P2pError>()?,
+            P2pError::InvalidChecksum {
+                command: "ping".to_string(),
+                expected: header.checksum,
+                actual: hex!("728338d9"),
+            },
+        );
+        for command in ["thirteenchars", "pïng", "ping\0"] {
+            assert_eq!(
+                MessageHeader::new(magic, command, &[])
+                    .unwrap_err()
+                    .downcast::<P2pError>()?,
+                P2pError::InvalidCommandStr(command.to_string()),
+            );
+        }
+        let unknown = P2pMessage::Unknown {
+            command: "thirteenchars".to_string(),
+            payload: Bytes::new(),
+        };
+        assert!(unknown.ser_framed(magic).is_err());
+        let header = MessageHeader::new(magic, "ping", &[0; 4])?;
+        assert!(matches!(
+            P2pMessage::deser_framed(&header, Bytes::from_slice(&[0; 4]))
+                .unwrap_err()
+                .downcast::<P2pError>()?,
+            P2pError::InvalidPayload { .. },
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,55 @@
+use bitcoinsuite_error::{ErrorMeta, Report};
+use thiserror::Error;
+
+use crate::MAX_INV_SZ;
+
+#[derive(Debug, Error, ErrorMeta, PartialEq, Eq)]
+pub enum P2pError {
+    #[critical()]
+    #[error("Peer IO error")]
+    PeerIo,
+
+    #[invalid_client_input()]
+    #[error("Wrong network magic: expected {expected:02x?}, got {actual:02x?}")]
+    WrongMagic { expected: [u8; 4], actual: [u8; 4] },
+
+    #[invalid_client_input()]
+    #[error("Invalid command: {0:02x?}")]
+    InvalidCommand([u8; 12]),
+
+    #[invalid_user_input()]
+    #[error("Invalid command {0:?}: must be at most 12 ASCII characters without zeros")]
+    InvalidCommandStr(String),
+
+    #[invalid_client_input()]
+    #[error("Message too large: {0} bytes")]
+    MessageTooLarge(u32),
+
+    #[invalid_client_input()]
+    #[error("Invalid checksum for {command:?}: expected {expected:02x?}, got {actual:02x?}")]
+    InvalidChecksum {
+        command: String,
+        expected: [u8; 4],
+        actual: [u8; 4],
+    },
+
+    #[invalid_client_input()]
+    #[error("Invalid payload for {command:?}: {msg}")]
+    InvalidPayload { command: String, msg: String },
+
+    #[invalid_client_input()]
+    #[error("Too many inventory entries: {0}, at most {MAX_INV_SZ}")]
+    TooManyInvs(u64),
+
+    #[critical()]
+    #[error("Version handshake timed out")]
+    HandshakeTimeout,
+}
+
+pub fn extract_error_meta(report: &Report) -> Option<&dyn ErrorMeta> {
+    if let Some(err) = report.downcast_ref::<P2pError>() {
+        Some(err)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,9 @@
+mod envelope;
+pub mod error;
+mod message;
+mod peer;
+
+pub use crate::envelope::*;
+pub use crate::error::P2pError;
+pub use crate::message::*;
+pub use crate::peer::*;
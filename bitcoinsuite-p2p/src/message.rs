@@ -0,0 +1,588 @@
+use std::net::{Ipv6Addr, SocketAddr};
+
+use bitcoinsuite_core::{
+    encoding::{read_compact_size, write_compact_size},
+    BitcoinBlock, BitcoinCode, BitcoinHeader, Bytes, BytesMut, Result, Sha256d, Tx,
+};
+
+use crate::P2pError::*;
+
+/// Protocol version we send in `version` messages.
+pub const PROTOCOL_VERSION: i32 = 70016;
+
+/// Service bit of nodes serving the full block chain.
+pub const NODE_NETWORK: u64 = 1;
+
+/// Max. number of entries in `inv`, `getdata` and `notfound` messages.
+pub const MAX_INV_SZ: u64 = 50_000;
+
+/// Address of a node, as used in `version` and `addr` messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NetAddress {
+    pub services: u64,
+    /// IPv4 addresses are IPv6-mapped.
+    pub ip: Ipv6Addr,
+    pub port: u16,
+}
+
+/// Entry of an `addr` message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimestampedNetAddress {
+    pub timestamp: u32,
+    pub addr: NetAddress,
+}
+
+/// `version` message, sent by both sides when connecting.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VersionMessage {
+    pub version: i32,
+    pub services: u64,
+    pub timestamp: i64,
+    pub addr_recv: NetAddress,
+    pub addr_from: NetAddress,
+    /// Random nonce, used to detect connections to self.
+    pub nonce: u64,
+    pub user_agent: String,
+    pub start_height: i32,
+    /// Whether the peer wants to receive `inv`s for txs.
+    pub relay: bool,
+}
+
+/// Type of an [`Inventory`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InvType {
+    Error,
+    Tx,
+    Block,
+    FilteredBlock,
+    CompactBlock,
+    Unknown(u32),
+}
+
+/// Entry of `inv`, `getdata` and `notfound` messages.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Inventory {
+    pub inv_type: InvType,
+    pub hash: Sha256d,
+}
+
+/// `getheaders` message, requesting headers following the first known locator hash.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GetHeadersMessage {
+    pub version: u32,
+    pub locator: Vec<Sha256d>,
+    /// Last header to send, or null to send as many as possible.
+    pub hash_stop: Sha256d,
+}
+
+/// `reject` message, sent by some nodes when a message got rejected.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RejectMessage {
+    /// Command of the rejected message.
+    pub message: String,
+    pub code: u8,
+    pub reason: String,
+    /// Extra data, e.g. the hash of the rejected tx or block.
+    pub data: Bytes,
+}
+
+/// Message of the P2P protocol, without the envelope.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum P2pMessage {
+    Version(VersionMessage),
+    Verack,
+    Ping(u64),
+    Pong(u64),
+    Inv(Vec<Inventory>),
+    GetData(Vec<Inventory>),
+    NotFound(Vec<Inventory>),
+    GetHeaders(GetHeadersMessage),
+    Headers(Vec<BitcoinHeader>),
+    Tx(Tx),
+    Block(BitcoinBlock),
+    Addr(Vec<TimestampedNetAddress>),
+    Reject(RejectMessage),
+    /// Message not decoded by this crate, e.g. `sendheaders` or `feefilter`.
+    Unknown {
+        command: String,
+        payload: Bytes,
+    },
+}
+
+impl NetAddress {
+    pub fn socket_addr(&self) -> SocketAddr {
+        match self.ip.to_ipv4_mapped() {
+            Some(ipv4) => SocketAddr::new(ipv4.into(), self.port),
+            None => SocketAddr::new(self.ip.into(), self.port),
+        }
+    }
+}
+
+impl From<SocketAddr> for NetAddress {
+    fn from(addr: SocketAddr) -> Self {
+        let ip = match addr {
+            SocketAddr::V4(addr) => addr.ip().to_ipv6_mapped(),
+            SocketAddr::V6(addr) => *addr.ip(),
+        };
+        NetAddress {
+            services: 0,
+            ip,
+            port: addr.port(),
+        }
+    }
+}
+
+impl From<u32> for InvType {
+    fn from(inv_type: u32) -> Self {
+        match inv_type {
+            0 => InvType::Error,
+            1 => InvType::Tx,
+            2 => InvType::Block,
+            3 => InvType::FilteredBlock,
+            4 => InvType::CompactBlock,
+            _ => InvType::Unknown(inv_type),
+        }
+    }
+}
+
+impl From<InvType> for u32 {
+    fn from(inv_type: InvType) -> Self {
+        match inv_type {
+            InvType::Error => 0,
+            InvType::Tx => 1,
+            InvType::Block => 2,
+            InvType::FilteredBlock => 3,
+            InvType::CompactBlock => 4,
+            InvType::Unknown(inv_type) => inv_type,
+        }
+    }
+}
+
+impl P2pMessage {
+    /// Command of the message in the envelope, e.g. "version".
+    pub fn command(&self) -> &str {
+        match self {
+            P2pMessage::Version(_) => "version",
+            P2pMessage::Verack => "verack",
+            P2pMessage::Ping(_) => "ping",
+            P2pMessage::Pong(_) => "pong",
+            P2pMessage::Inv(_) => "inv",
+            P2pMessage::GetData(_) => "getdata",
+            P2pMessage::NotFound(_) => "notfound",
+            P2pMessage::GetHeaders(_) => "getheaders",
+            P2pMessage::Headers(_) => "headers",
+            P2pMessage::Tx(_) => "tx",
+            P2pMessage::Block(_) => "block",
+            P2pMessage::Addr(_) => "addr",
+            P2pMessage::Reject(_) => "reject",
+            P2pMessage::Unknown { command, .. } => command,
+        }
+    }
+
+    pub fn ser_payload(&self) -> Bytes {
+        let mut bytes = BytesMut::new();
+        match self {
+            P2pMessage::Version(version) => version.ser_to(&mut bytes),
+            P2pMessage::Verack => {}
+            P2pMessage::Ping(nonce) | P2pMessage::Pong(nonce) => nonce.ser_to(&mut bytes),
+            P2pMessage::Inv(invs) | P2pMessage::GetData(invs) | P2pMessage::NotFound(invs) => {
+                invs.ser_to(&mut bytes)
+            }
+            P2pMessage::GetHeaders(get_headers) => get_headers.ser_to(&mut bytes),
+            P2pMessage::Headers(headers) => {
+                write_compact_size(&mut bytes, headers.len() as u64);
+                for header in headers {
+                    header.ser_to(&mut bytes);
+                    // Number of txs, always 0
+                    write_compact_size(&mut bytes, 0);
+                }
+            }
+            P2pMessage::Tx(tx) => tx.ser_to(&mut bytes),
+            P2pMessage::Block(block) => block.ser_to(&mut bytes),
+            P2pMessage::Addr(addrs) => addrs.ser_to(&mut bytes),
+            P2pMessage::Reject(reject) => reject.ser_to(&mut bytes),
+            P2pMessage::Unknown { payload, .. } => bytes.put_bytes(payload.clone()),
+        }
+        bytes.freeze()
+    }
+
+    /// Parse the payload of the message with the given command, which has to be consumed
+    /// entirely for known commands.
+    pub fn deser_payload(command: &str, mut payload: Bytes) -> bitcoinsuite_error::Result<Self> {
+        let data = &mut payload;
+        let msg = match command {
+            "version" => P2pMessage::Version(BitcoinCode::deser(data)?),
+            "verack" => P2pMessage::Verack,
+            "ping" => P2pMessage::Ping(BitcoinCode::deser(data)?),
+            "pong" => P2pMessage::Pong(BitcoinCode::deser(data)?),
+            "inv" => P2pMessage::Inv(deser_invs(data)?),
+            "getdata" => P2pMessage::GetData(deser_invs(data)?),
+            "notfound" => P2pMessage::NotFound(deser_invs(data)?),
+            "getheaders" => P2pMessage::GetHeaders(BitcoinCode::deser(data)?),
+            "headers" => {
+                let num_headers = read_compact_size(data)?;
+                let mut headers = Vec::new();
+                for _ in 0..num_headers {
+                    headers.push(BitcoinHeader::deser(data)?);
+                    read_compact_size(data)?;
+                }
+                P2pMessage::Headers(headers)
+            }
+            "tx" => P2pMessage::Tx(BitcoinCode::deser(data)?),
+            "block" => P2pMessage::Block(BitcoinCode::deser(data)?),
+            "addr" => P2pMessage::Addr(BitcoinCode::deser(data)?),
+            "reject" => P2pMessage::Reject(BitcoinCode::deser(data)?),
+            _ => {
+                return Ok(P2pMessage::Unknown {
+                    command: command.to_string(),
+                    payload,
+                })
+            }
+        };
+        if !payload.is_empty() {
+            return Err(InvalidPayload {
+                command: command.to_string(),
+                msg: format!("{} trailing byte(s)", payload.len()),
+            }
+            .into());
+        }
+        Ok(msg)
+    }
+}
+
+fn deser_invs(data: &mut Bytes) -> bitcoinsuite_error::Result<Vec<Inventory>> {
+    let num_invs = read_compact_size(data)?;
+    if num_invs > MAX_INV_SZ {
+        return Err(TooManyInvs(num_invs).into());
+    }
+    let mut invs = Vec::with_capacity(num_invs as usize);
+    for _ in 0..num_invs {
+        invs.push(Inventory::deser(data)?);
+    }
+    Ok(invs)
+}
+
+fn ser_str(string: &str, bytes: &mut BytesMut) {
+    Bytes::from_slice(string.as_bytes()).ser_to(bytes);
+}
+
+fn deser_str(data: &mut Bytes) -> Result<String> {
+    Ok(String::from_utf8_lossy(&Bytes::deser(data)?).into_owned())
+}
+
+impl BitcoinCode for NetAddress {
+    fn ser_to(&self, bytes: &mut BytesMut) {
+        self.services.ser_to(bytes);
+        bytes.put_slice(&self.ip.octets());
+        bytes.put_slice(&self.port.to_be_bytes());
+    }
+
+    fn deser(data: &mut Bytes) -> Result<Self> {
+        Ok(NetAddress {
+            services: BitcoinCode::deser(data)?,
+            ip: data.split_to_array::<16>()?.array().into(),
+            port: u16::from_be_bytes(data.split_to_array::<2>()?.array()),
+        })
+    }
+}
+
+impl BitcoinCode for TimestampedNetAddress {
+    fn ser_to(&self, bytes: &mut BytesMut) {
+        self.timestamp.ser_to(bytes);
+        self.addr.ser_to(bytes);
+    }
+
+    fn deser(data: &mut Bytes) -> Result<Self> {
+        Ok(TimestampedNetAddress {
+            timestamp: BitcoinCode::deser(data)?,
+            addr: BitcoinCode::deser(data)?,
+        })
+    }
+}
+
+impl BitcoinCode for VersionMessage {
+    fn ser_to(&self, bytes: &mut BytesMut) {
+        self.version.ser_to(bytes);
+        self.services.ser_to(bytes);
+        self.timestamp.ser_to(bytes);
+        self.addr_recv.ser_to(bytes);
+        self.addr_from.ser_to(bytes);
+        self.nonce.ser_to(bytes);
+        ser_str(&self.user_agent, bytes);
+        self.start_height.ser_to(bytes);
+        self.relay.ser_to(bytes);
+    }
+
+    fn deser(data: &mut Bytes) -> Result<Self> {
+        Ok(VersionMessage {
+            version: BitcoinCode::deser(data)?,
+            services: BitcoinCode::deser(data)?,
+            timestamp: BitcoinCode::deser(data)?,
+            addr_recv: BitcoinCode::deser(data)?,
+            addr_from: BitcoinCode::deser(data)?,
+            nonce: BitcoinCode::deser(data)?,
+            user_agent: deser_str(data)?,
+            start_height: BitcoinCode::deser(data)?,
+            // Older nodes omit the relay flag
+            relay: data.is_empty() || bool::deser(data)?,
+        })
+    }
+}
+
+impl BitcoinCode for Inventory {
+    fn ser_to(&self, bytes: &mut BytesMut) {
+        u32::from(self.inv_type).ser_to(bytes);
+        self.hash.ser_to(bytes);
+    }
+
+    fn deser(data: &mut Bytes) -> Result<Self> {
+        Ok(Inventory {
+            inv_type: u32::deser(data)?.into(),
+            hash: BitcoinCode::deser(data)?,
+        })
+    }
+}
+
+impl BitcoinCode for GetHeadersMessage {
+    fn ser_to(&self, bytes: &mut BytesMut) {
+        self.version.ser_to(bytes);
+        self.locator.ser_to(bytes);
+        self.hash_stop.ser_to(bytes);
+    }
+
+    fn deser(data: &mut Bytes) -> Result<Self> {
+        Ok(GetHeadersMessage {
+            version: BitcoinCode::deser(data)?,
+            locator: BitcoinCode::deser(data)?,
+            hash_stop: BitcoinCode::deser(data)?,
+        })
+    }
+}
+
+impl BitcoinCode for RejectMessage {
+    fn ser_to(&self, bytes: &mut BytesMut) {
+        ser_str(&self.message, bytes);
+        self.code.ser_to(bytes);
+        ser_str(&self.reason, bytes);
+        bytes.put_bytes(self.data.clone());
+    }
+
+    fn deser(data: &mut Bytes) -> Result<Self> {
+        Ok(RejectMessage {
+            message: deser_str(data)?,
+            code: BitcoinCode::deser(data)?,
+            reason: deser_str(data)?,
+            data: data.split_to(data.len())?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    use bitcoinsuite_core::{BitcoinBlock, BitcoinCode, BitcoinHeader, Bytes, Sha256d, UnhashedTx};
+    use bitcoinsuite_error::Result;
+    use hex_literal::hex;
+
+    use crate::{
+        GetHeadersMessage, InvType, Inventory, NetAddress, P2pError, P2pMessage, RejectMessage,
+        TimestampedNetAddress, VersionMessage, MAX_INV_SZ,
+    };
+
+    fn verify_payload(msg: P2pMessage, payload: &[u8]) {
+        assert_eq!(msg.ser_payload().as_ref(), payload);
+        let deser = P2pMessage::deser_payload(msg.command(), payload.into()).unwrap();
+        assert_eq!(deser, msg);
+    }
+
+    #[test]
+    fn test_version() {
+        let addr_recv = SocketAddr::new(Ipv4Addr::new(127, 0, 0, 1).into(), 8333);
+        let version = VersionMessage {
+            version: 70016,
+            services: 1,
+            timestamp: 0x6000_0000,
+            addr_recv: NetAddress {
+                services: 1,
+                ..addr_recv.into()
+            },
+            addr_from: SocketAddr::new(Ipv4Addr::new(10, 0, 0, 2).into(), 0x1234).into(),
+            nonce: 0x0102030405060708,
+            user_agent: "/bitcoinsuite:0.1/".to_string(),
+            start_height: 700_000,
+            relay: false,
+        };
+        assert_eq!(version.addr_recv.socket_addr(), addr_recv);
+        let payload = [
+            hex!("80110100 0100000000000000 0000006000000000").as_ref(),
+            &hex!("0100000000000000 00000000000000000000ffff7f000001 208d"),
+            &hex!("0000000000000000 00000000000000000000ffff0a000002 1234"),
+            &hex!("0807060504030201"),
+            b"\x12/bitcoinsuite:0.1/",
+            &hex!("60ae0a00 00"),
+        ]
+        .concat();
+        verify_payload(P2pMessage::Version(version.clone()), &payload);
+        // Relay flag defaults to true if missing
+        let msg = P2pMessage::deser_payload("version", payload[..payload.len() - 1].into());
+        assert_eq!(
+            msg.unwrap(),
+            P2pMessage::Version(VersionMessage {
+                relay: true,
+                ..version
+            }),
+        );
+    }
+
+    #[test]
+    fn test_messages() {
+        verify_payload(P2pMessage::Verack, &[]);
+        verify_payload(P2pMessage::Ping(0x1234), &hex!("3412000000000000"));
+        verify_payload(P2pMessage::Pong(0x1234), &hex!("3412000000000000"));
+        let invs = vec![
+            Inventory {
+                inv_type: InvType::Tx,
+                hash: Sha256d::new([1; 32]),
+            },
+            Inventory {
+                inv_type: InvType::Unknown(0x4000_0002),
+                hash: Sha256d::new([2; 32]),
+            },
+        ];
+        let invs_payload = [
+            [2].as_ref(),
+            &[1, 0, 0, 0],
+            &[1; 32],
+            &[2, 0, 0, 0x40],
+            &[2; 32],
+        ]
+        .concat();
+        verify_payload(P2pMessage::Inv(invs.clone()), &invs_payload);
+        verify_payload(P2pMessage::GetData(invs.clone()), &invs_payload);
+        verify_payload(P2pMessage::NotFound(invs), &invs_payload);
+        verify_payload(
+            P2pMessage::GetHeaders(GetHeadersMessage {
+                version: 70016,
+                locator: vec![Sha256d::new([3; 32])],
+                hash_stop: Sha256d::default(),
+            }),
+            &[[0x80, 0x11, 1, 0, 1].as_ref(), &[3; 32], &[0; 32]].concat(),
+        );
+        let header = BitcoinHeader {
+            version: 1,
+            bits: 0x207fffff,
+            ..Default::default()
+        };
+        verify_payload(
+            P2pMessage::Headers(vec![header.clone(), header.clone()]),
+            &[[2].as_ref(), &header.ser(), &[0], &header.ser(), &[0]].concat(),
+        );
+        verify_payload(
+            P2pMessage::Addr(vec![TimestampedNetAddress {
+                timestamp: 0x6000_0000,
+                addr: NetAddress {
+                    services: 1,
+                    ..SocketAddr::new(Ipv4Addr::new(1, 2, 3, 4).into(), 8333).into()
+                },
+            }]),
+            &hex!("01 00000060 0100000000000000 00000000000000000000ffff01020304 208d"),
+        );
+        verify_payload(
+            P2pMessage::Reject(RejectMessage {
+                message: "tx".to_string(),
+                code: 0x10,
+                reason: "bad-txns".to_string(),
+                data: Bytes::from_slice(&[4; 32]),
+            }),
+            &[b"\x02tx\x10\x08bad-txns".as_ref(), &[4; 32]].concat(),
+        );
+        verify_payload(
+            P2pMessage::Unknown {
+                command: "feefilter".to_string(),
+                payload: Bytes::from_slice(&[0xe8, 3, 0, 0, 0, 0, 0, 0]),
+            },
+            &[0xe8, 3, 0, 0, 0, 0, 0, 0],
+        );
+        assert!(P2pMessage::deser_payload("ping", Bytes::from_slice(&[1, 2])).is_err());
+    }
+
+    #[test]
+    fn test_trailing_bytes() -> Result<()> {
+        let err = P2pMessage::deser_payload("verack", Bytes::from_slice(&[0]))
+            .unwrap_err()
+            .downcast::<P2pError>()?;
+        assert_eq!(
+            err,
+            P2pError::InvalidPayload {
+                command: "verack".to_string(),
+                msg: "1 trailing byte(s)".to_string(),
+            },
+        );
+        let ping = P2pMessage::Ping(7).ser_payload();
+        let payload = [ping.as_ref(), &[1, 2]].concat();
+        let err = P2pMessage::deser_payload("ping", payload.into())
+            .unwrap_err()
+            .downcast::<P2pError>()?;
+        assert_eq!(
+            err,
+            P2pError::InvalidPayload {
+                command: "ping".to_string(),
+                msg: "2 trailing byte(s)".to_string(),
+            },
+        );
+        // Unknown commands keep the entire payload
+        assert_eq!(
+            P2pMessage::deser_payload("foo", Bytes::from_slice(&[1, 2]))?,
+            P2pMessage::Unknown {
+                command: "foo".to_string(),
+                payload: Bytes::from_slice(&[1, 2]),
+            },
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_huge_counts() -> Result<()> {
+        let huge_count = hex!("ff ffffffffffffff0f");
+        for command in ["inv", "getdata", "notfound"] {
+            let err = P2pMessage::deser_payload(command, Bytes::from_slice(&huge_count))
+                .unwrap_err()
+                .downcast::<P2pError>()?;
+            assert_eq!(err, P2pError::TooManyInvs(0x0fff_ffff_ffff_ffff));
+        }
+        let too_many = hex!("fe 51c30000");
+        let err = P2pMessage::deser_payload("inv", Bytes::from_slice(&too_many))
+            .unwrap_err()
+            .downcast::<P2pError>()?;
+        assert_eq!(err, P2pError::TooManyInvs(MAX_INV_SZ + 1));
+        // Counts within the limit, but without the entries
+        let max_count = hex!("fe 50c30000");
+        assert!(P2pMessage::deser_payload("inv", Bytes::from_slice(&max_count)).is_err());
+        for command in ["addr", "getheaders", "headers", "block"] {
+            let payload = match command {
+                "getheaders" => [[0; 4].as_ref(), &huge_count].concat(),
+                "block" => [[0; 80].as_ref(), &huge_count].concat(),
+                _ => huge_count.to_vec(),
+            };
+            assert!(P2pMessage::deser_payload(command, payload.into()).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_tx_block() {
+        let tx = UnhashedTx {
+            version: 1,
+            ..Default::default()
+        }
+        .hashed();
+        verify_payload(P2pMessage::Tx(tx.clone()), tx.raw());
+        let block = BitcoinBlock {
+            header: BitcoinHeader::default(),
+            txs: vec![tx.clone()],
+        };
+        let payload = [BitcoinHeader::default().ser().as_ref(), &[1], tx.raw()].concat();
+        verify_payload(P2pMessage::Block(block), &payload);
+    }
+}
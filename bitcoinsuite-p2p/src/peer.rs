@@ -0,0 +1,137 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bitcoinsuite_core::{BitcoinCode, Bytes, Tx};
+use bitcoinsuite_error::{Result, WrapErr};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpStream, ToSocketAddrs},
+};
+
+use crate::{
+    MessageHeader, P2pError::*, P2pMessage, VersionMessage, MESSAGE_HEADER_SIZE, PROTOCOL_VERSION,
+};
+
+/// Config for a P2P connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerConfig {
+    /// Magic of the network, see [`crate::network_magic`].
+    pub magic: [u8; 4],
+    pub user_agent: String,
+    /// Height of our best block, sent in the `version` message.
+    pub start_height: i32,
+    /// Whether we want to receive `inv`s for txs.
+    pub relay: bool,
+    pub handshake_timeout: Duration,
+}
+
+/// Connection to a node using the P2P protocol, after the `version` handshake.
+#[derive(Debug)]
+pub struct Peer {
+    stream: TcpStream,
+    magic: [u8; 4],
+    peer_version: VersionMessage,
+}
+
+impl PeerConfig {
+    pub fn new(magic: [u8; 4]) -> Self {
+        PeerConfig {
+            magic,
+            user_agent: "/bitcoinsuite:0.1.0/".to_string(),
+            start_height: 0,
+            relay: false,
+            handshake_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl Peer {
+    /// Connect to the node and do the handshake.
+    pub async fn connect(addr: impl ToSocketAddrs, config: &PeerConfig) -> Result<Self> {
+        let stream = TcpStream::connect(addr).await.wrap_err(PeerIo)?;
+        Peer::handshake(stream, config).await
+    }
+
+    /// Do the handshake on an established connection; both sides send their `version` and
+    /// acknowledge the other's with a `verack`.
+    pub async fn handshake(mut stream: TcpStream, config: &PeerConfig) -> Result<Self> {
+        let version = VersionMessage {
+            version: PROTOCOL_VERSION,
+            services: 0,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs() as i64,
+            addr_recv: stream.peer_addr().wrap_err(PeerIo)?.into(),
+            addr_from: stream.local_addr().wrap_err(PeerIo)?.into(),
+            nonce: rand::random(),
+            user_agent: config.user_agent.clone(),
+            start_height: config.start_height,
+            relay: config.relay,
+        };
+        write_message(&mut stream, config.magic, &P2pMessage::Version(version)).await?;
+        let handshake = async {
+            let mut peer_version = None;
+            let mut has_verack = false;
+            while peer_version.is_none() || !has_verack {
+                match read_message(&mut stream, config.magic).await? {
+                    P2pMessage::Version(version) => {
+                        write_message(&mut stream, config.magic, &P2pMessage::Verack).await?;
+                        peer_version = Some(version);
+                    }
+                    P2pMessage::Verack => has_verack = true,
+                    // Nodes may already send e.g. `sendheaders` before the handshake is done
+                    _ => {}
+                }
+            }
+            Ok::<_, bitcoinsuite_error::Report>(peer_version.unwrap())
+        };
+        let peer_version = tokio::time::timeout(config.handshake_timeout, handshake)
+            .await
+            .map_err(|_| HandshakeTimeout)??;
+        Ok(Peer {
+            stream,
+            magic: config.magic,
+            peer_version,
+        })
+    }
+
+    /// `version` message the peer sent during the handshake.
+    pub fn peer_version(&self) -> &VersionMessage {
+        &self.peer_version
+    }
+
+    pub async fn send(&mut self, msg: &P2pMessage) -> Result<()> {
+        write_message(&mut self.stream, self.magic, msg).await
+    }
+
+    /// Receive the next message, answering `ping`s along the way.
+    pub async fn recv(&mut self) -> Result<P2pMessage> {
+        loop {
+            match read_message(&mut self.stream, self.magic).await? {
+                P2pMessage::Ping(nonce) => self.send(&P2pMessage::Pong(nonce)).await?,
+                msg => return Ok(msg),
+            }
+        }
+    }
+
+    /// Relay the tx to the peer, without waiting for it to be requested.
+    pub async fn send_tx(&mut self, tx: &Tx) -> Result<()> {
+        self.send(&P2pMessage::Tx(tx.clone())).await
+    }
+}
+
+async fn write_message(stream: &mut TcpStream, magic: [u8; 4], msg: &P2pMessage) -> Result<()> {
+    let data = msg.ser_framed(magic)?;
+    stream.write_all(&data).await.wrap_err(PeerIo)?;
+    Ok(())
+}
+
+async fn read_message(stream: &mut TcpStream, magic: [u8; 4]) -> Result<P2pMessage> {
+    let mut header = [0; MESSAGE_HEADER_SIZE];
+    stream.read_exact(&mut header).await.wrap_err(PeerIo)?;
+    let header = MessageHeader::deser(&mut Bytes::from_slice(&header))?;
+    header.validate(magic)?;
+    let mut payload = vec![0; header.payload_size as usize];
+    stream.read_exact(&mut payload).await.wrap_err(PeerIo)?;
+    P2pMessage::deser_framed(&header, payload.into())
+}
@@ -0,0 +1,139 @@
+use std::time::Duration;
+
+use bitcoinsuite_core::{Bytes, Net, Network, UnhashedTx};
+use bitcoinsuite_error::Result;
+use bitcoinsuite_p2p::{
+    network_magic, InvType, Inventory, P2pError, P2pMessage, Peer, PeerConfig, PROTOCOL_VERSION,
+};
+use tokio::net::TcpListener;
+
+#[tokio::test]
+async fn test_peer_loopback() -> Result<()> {
+    let magic = network_magic(Network::XEC, Net::Regtest).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    // Stand-in for a node, answering txs with an inv
+    let stand_in = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await?;
+        let config = PeerConfig {
+            user_agent: "/stand-in:0.1.0/".to_string(),
+            start_height: 42,
+            relay: true,
+            ..PeerConfig::new(magic)
+        };
+        let mut peer = Peer::handshake(stream, &config).await?;
+        assert_eq!(peer.peer_version().user_agent, "/bitcoinsuite:0.1.0/");
+        assert!(!peer.peer_version().relay);
+        let P2pMessage::Tx(tx) = peer.recv().await? else {
+            panic!("Expected tx");
+        };
+        peer.send(&P2pMessage::Inv(vec![Inventory {
+            inv_type: InvType::Tx,
+            hash: tx.hash().clone(),
+        }]))
+        .await?;
+        Ok::<_, bitcoinsuite_error::Report>(())
+    });
+
+    let mut peer = Peer::connect(addr, &PeerConfig::new(magic)).await?;
+    let peer_version = peer.peer_version();
+    assert_eq!(peer_version.version, PROTOCOL_VERSION);
+    assert_eq!(peer_version.user_agent, "/stand-in:0.1.0/");
+    assert_eq!(peer_version.start_height, 42);
+    assert!(peer_version.relay);
+    assert_eq!(peer_version.addr_recv.socket_addr().ip(), addr.ip());
+
+    // Stand-in answers pings while waiting for the tx
+    peer.send(&P2pMessage::Ping(1234)).await?;
+    assert_eq!(peer.recv().await?, P2pMessage::Pong(1234));
+
+    let tx = UnhashedTx {
+        version: 1,
+        ..Default::default()
+    }
+    .hashed();
+    peer.send_tx(&tx).await?;
+    assert_eq!(
+        peer.recv().await?,
+        P2pMessage::Inv(vec![Inventory {
+            inv_type: InvType::Tx,
+            hash: tx.hash().clone(),
+        }]),
+    );
+    stand_in.await??;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_peer_wrong_network() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let mainnet_magic = network_magic(Network::BCH, Net::Mainnet).unwrap();
+    let regtest_magic = network_magic(Network::BCH, Net::Regtest).unwrap();
+    let stand_in = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await?;
+        Peer::handshake(stream, &PeerConfig::new(mainnet_magic)).await
+    });
+    let err = Peer::connect(addr, &PeerConfig::new(regtest_magic))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.downcast::<P2pError>()?,
+        P2pError::WrongMagic {
+            expected: regtest_magic,
+            actual: mainnet_magic,
+        },
+    );
+    assert!(stand_in.await?.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_peer_handshake_timeout() -> Result<()> {
+    let magic = network_magic(Network::XEC, Net::Regtest).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    // Stand-in that never answers
+    let _stand_in = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await?;
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        Ok::<_, std::io::Error>(stream)
+    });
+    let config = PeerConfig {
+        handshake_timeout: Duration::from_millis(100),
+        ..PeerConfig::new(magic)
+    };
+    let err = Peer::connect(addr, &config).await.unwrap_err();
+    assert_eq!(err.downcast::<P2pError>()?, P2pError::HandshakeTimeout);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_peer_huge_inv() -> Result<()> {
+    let magic = network_magic(Network::XEC, Net::Regtest).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    // Stand-in sending an `inv` with a huge number of entries, but no entries
+    let stand_in = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await?;
+        let mut peer = Peer::handshake(stream, &PeerConfig::new(magic)).await?;
+        peer.send(&P2pMessage::Unknown {
+            command: "inv".to_string(),
+            payload: Bytes::from_slice(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x0f]),
+        })
+        .await?;
+        Ok::<_, bitcoinsuite_error::Report>(peer)
+    });
+    let mut peer = Peer::connect(addr, &PeerConfig::new(magic)).await?;
+    let err = peer.recv().await.unwrap_err().downcast::<P2pError>()?;
+    assert_eq!(
+        err,
+        P2pError::InvalidPayload {
+            command: "inv".to_string(),
+            msg: P2pError::TooManyInvs(0x0fff_ffff_ffff_ffff).to_string(),
+        },
+    );
+    stand_in.await??;
+    Ok(())
+}
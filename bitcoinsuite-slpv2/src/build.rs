@@ -2,7 +2,8 @@ use bitcoinsuite_core::{Bytes, BytesMut, Op, Script};
 
 use crate::{
     structs::{
-        Amount, GenesisInfo, MintData, TokenType, BURN, GENESIS, MINT, SEND, SLPV2_LOKAD_ID,
+        Amount, GenesisInfo, MintData, TokenType, AMOUNT_LENGTH, BURN, GENESIS, MINT, SEND,
+        SLPV2_LOKAD_ID,
     },
     token_id::TokenId,
 };
@@ -70,7 +71,7 @@ fn put_mint_data(section: &mut BytesMut, mint_data: &MintData) {
 }
 
 fn put_amount(section: &mut BytesMut, amount: Amount) {
-    section.put_slice(&amount.to_le_bytes()[..6]);
+    section.put_slice(&amount.to_le_bytes()[..AMOUNT_LENGTH]);
 }
 
 pub fn send_section(token_id: &TokenId, token_type: TokenType, send_amounts: &[Amount]) -> Bytes {
@@ -0,0 +1,46 @@
+use bitcoinsuite_core::{Bytes, BytesError};
+use thiserror::Error;
+
+use crate::TokenId;
+
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum ParseError {
+    #[error("Non-push op: 0x{opcode:02x} at op {op_idx}")]
+    NonPushOp { opcode: u8, op_idx: usize },
+    #[error("Unknown token type: 0x{0:02x}")]
+    UnknownTokenType(u8),
+    #[error("Unknown tx type: {}", .0.hex())]
+    UnknownTxType(Bytes),
+    #[error("Not enough bytes for field {field_name}: expected {expected} but only {actual} left")]
+    MissingBytes {
+        field_name: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("Superfluous bytes at the end of the section: {}", .0.hex())]
+    LeftoverBytes(Bytes),
+    #[error("Duplicate SEND section for token {0}")]
+    DuplicateSend(TokenId),
+    #[error("Duplicate BURN section for token {0}")]
+    DuplicateBurn(TokenId),
+    #[error("Bytes error: {0}")]
+    BytesError(#[from] BytesError),
+}
+
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum ValidationError {
+    #[error("GENESIS must be the first section")]
+    GenesisNotFirst,
+    #[error("Token {0} already has a section in this tx")]
+    DuplicateTokenId(TokenId),
+    #[error("Too few outputs, expected at least {expected} but only got {actual}")]
+    TooFewOutputs { expected: usize, actual: usize },
+    #[error("Output {output_idx} already colored by a previous section")]
+    OverlappingOutput { output_idx: usize },
+    #[error("Invalid MINT: No mint baton in inputs")]
+    MissingMintBaton,
+    #[error(
+        "Invalid SEND: Output and burn amounts ({required}) exceed input amounts ({input_sum})"
+    )]
+    InsufficientInputSum { required: i128, input_sum: i128 },
+}
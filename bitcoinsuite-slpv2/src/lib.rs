@@ -1,7 +1,13 @@
 mod build;
+mod error;
+mod parse;
 mod structs;
 mod token_id;
+mod validate;
 
 pub use self::build::*;
+pub use self::error::*;
+pub use self::parse::*;
 pub use self::structs::*;
 pub use self::token_id::*;
+pub use self::validate::*;
@@ -0,0 +1,524 @@
+use std::collections::HashMap;
+
+use bitcoinsuite_core::{
+    opcode::{OP_0, OP_RESERVED, OP_RETURN},
+    Bytes, Op, Sha256d, UnhashedTx,
+};
+
+use crate::{
+    structs::{
+        Amount, Genesis, GenesisInfo, MintData, Parsed, Section, SectionVariant, Send, TokenMeta,
+        TokenType, AMOUNT_LENGTH, BURN, DEFAULT_TOKEN_TYPE, GENESIS, MINT, SEND, SLPV2_LOKAD_ID,
+    },
+    ParseError, TokenId,
+};
+
+/// Parse all SLPv2 sections in the first output of the tx.
+///
+/// The output must be of the form `OP_RETURN OP_RESERVED <pushdata>...`, otherwise the tx is not
+/// considered SLPv2 and an empty [`Parsed`] is returned. Pushdata not starting with the SLPv2 LOKAD
+/// ID is ignored, and every section that fails to parse results in one entry in
+/// [`Parsed::parse_errors`], without affecting the other sections.
+///
+/// BURN sections are attached to the SEND section of the same token as
+/// [`Send::intentional_burn_amount`]; if there's no SEND for that token, they are reported as a
+/// SEND without any outputs.
+pub fn parse_tx(txid: &Sha256d, tx: &UnhashedTx) -> Parsed {
+    let mut parsed = Parsed::default();
+    let output = match tx.outputs.first() {
+        Some(output) => output,
+        None => return parsed,
+    };
+    let mut ops = output.script.ops();
+    if !matches!(ops.next(), Some(Ok(Op::Code(OP_RETURN)))) {
+        return parsed;
+    }
+    if !matches!(ops.next(), Some(Ok(Op::Code(OP_RESERVED)))) {
+        return parsed;
+    }
+    // token_id -> (section idx, has SEND section)
+    let mut send_sections = HashMap::<TokenId, (usize, bool)>::new();
+    for (op_idx, op) in ops.enumerate() {
+        let pushdata = match op {
+            Ok(Op::Push(_, pushdata)) => pushdata,
+            Ok(Op::Code(OP_0)) => Bytes::new(),
+            Ok(Op::Code(opcode)) => {
+                parsed.parse_errors.push(ParseError::NonPushOp {
+                    opcode,
+                    op_idx: op_idx + 2,
+                });
+                break;
+            }
+            Err(err) => {
+                parsed.parse_errors.push(err.into());
+                break;
+            }
+        };
+        let section = match parse_section(txid, pushdata) {
+            Ok(Some(section)) => section,
+            Ok(None) => continue,
+            Err(err) => {
+                parsed.parse_errors.push(err);
+                continue;
+            }
+        };
+        let send = match &section.variant {
+            SectionVariant::Send(send) => send,
+            _ => {
+                parsed.sections.push(section);
+                continue;
+            }
+        };
+        let token_id = &section.meta.token_id;
+        let is_burn = send.intentional_burn_amount.is_some();
+        match send_sections.get_mut(token_id) {
+            Some(&mut (section_idx, ref mut has_send)) => {
+                let existing = match &mut parsed.sections[section_idx].variant {
+                    SectionVariant::Send(existing) => existing,
+                    _ => unreachable!(),
+                };
+                if is_burn {
+                    if existing.intentional_burn_amount.is_some() {
+                        parsed
+                            .parse_errors
+                            .push(ParseError::DuplicateBurn(token_id.clone()));
+                        continue;
+                    }
+                    existing.intentional_burn_amount = send.intentional_burn_amount;
+                } else {
+                    if *has_send {
+                        parsed
+                            .parse_errors
+                            .push(ParseError::DuplicateSend(token_id.clone()));
+                        continue;
+                    }
+                    existing.output_amounts = send.output_amounts.clone();
+                    *has_send = true;
+                }
+            }
+            None => {
+                send_sections.insert(token_id.clone(), (parsed.sections.len(), !is_burn));
+                parsed.sections.push(section);
+            }
+        }
+    }
+    parsed
+}
+
+/// Parse a single SLPv2 section from the given pushdata.
+///
+/// Returns `Ok(None)` if the pushdata doesn't start with the SLPv2 LOKAD ID. BURN sections are
+/// returned as a [`SectionVariant::Send`] without outputs.
+pub fn parse_section(txid: &Sha256d, pushdata: Bytes) -> Result<Option<Section>, ParseError> {
+    if !pushdata.starts_with(&SLPV2_LOKAD_ID) {
+        return Ok(None);
+    }
+    let mut data = SectionData(pushdata);
+    data.read_bytes(SLPV2_LOKAD_ID.len(), "lokad_id")?;
+    let token_type = match data.read_u8("token_type")? {
+        DEFAULT_TOKEN_TYPE => TokenType::Standard,
+        token_type => return Err(ParseError::UnknownTokenType(token_type)),
+    };
+    let tx_type = data.read_var_bytes("tx_type")?;
+    let (token_id, variant) = match tx_type.as_ref() {
+        GENESIS => (TokenId::new(txid.clone()), parse_genesis(&mut data)?),
+        MINT => (
+            data.read_token_id()?,
+            SectionVariant::Mint(parse_mint_data(&mut data)?),
+        ),
+        SEND => (data.read_token_id()?, parse_send(&mut data)?),
+        BURN => (data.read_token_id()?, parse_burn(&mut data)?),
+        _ => return Err(ParseError::UnknownTxType(tx_type)),
+    };
+    if !data.0.is_empty() {
+        return Err(ParseError::LeftoverBytes(data.0));
+    }
+    Ok(Some(Section {
+        meta: TokenMeta {
+            token_id,
+            token_type,
+        },
+        variant,
+    }))
+}
+
+fn parse_genesis(data: &mut SectionData) -> Result<SectionVariant, ParseError> {
+    let info = GenesisInfo {
+        token_ticker: data.read_var_bytes("token_ticker")?,
+        token_name: data.read_var_bytes("token_name")?,
+        url: data.read_var_bytes("url")?,
+        data: data.read_var_bytes("data")?,
+        auth_pubkey: data.read_var_bytes("auth_pubkey")?,
+        decimals: data.read_u8("decimals")?,
+    };
+    let mint_data = parse_mint_data(data)?;
+    Ok(SectionVariant::Genesis(Genesis { info, mint_data }))
+}
+
+fn parse_mint_data(data: &mut SectionData) -> Result<MintData, ParseError> {
+    let num_amounts = data.read_u8("num_amounts")?;
+    let amounts = (0..num_amounts)
+        .map(|_| data.read_amount("amount"))
+        .collect::<Result<Vec<_>, _>>()?;
+    let num_batons = data.read_u8("num_batons")? as usize;
+    Ok(MintData {
+        amounts,
+        num_batons,
+    })
+}
+
+fn parse_send(data: &mut SectionData) -> Result<SectionVariant, ParseError> {
+    let num_amounts = data.read_u8("num_amounts")?;
+    let output_amounts = (0..num_amounts)
+        .map(|_| data.read_amount("output_amount"))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(SectionVariant::Send(Send {
+        output_amounts,
+        intentional_burn_amount: None,
+    }))
+}
+
+fn parse_burn(data: &mut SectionData) -> Result<SectionVariant, ParseError> {
+    let burn_amount = data.read_amount("burn_amount")?;
+    Ok(SectionVariant::Send(Send {
+        output_amounts: vec![],
+        intentional_burn_amount: Some(burn_amount),
+    }))
+}
+
+struct SectionData(Bytes);
+
+impl SectionData {
+    fn read_bytes(&mut self, len: usize, field_name: &'static str) -> Result<Bytes, ParseError> {
+        if self.0.len() < len {
+            return Err(ParseError::MissingBytes {
+                field_name,
+                expected: len,
+                actual: self.0.len(),
+            });
+        }
+        Ok(self.0.split_to(len)?)
+    }
+
+    fn read_u8(&mut self, field_name: &'static str) -> Result<u8, ParseError> {
+        Ok(self.read_bytes(1, field_name)?[0])
+    }
+
+    fn read_var_bytes(&mut self, field_name: &'static str) -> Result<Bytes, ParseError> {
+        let len = self.read_u8(field_name)?;
+        self.read_bytes(len as usize, field_name)
+    }
+
+    fn read_amount(&mut self, field_name: &'static str) -> Result<Amount, ParseError> {
+        let amount = self.read_bytes(AMOUNT_LENGTH, field_name)?;
+        let mut amount_le = [0; 8];
+        amount_le[..AMOUNT_LENGTH].copy_from_slice(&amount);
+        Ok(Amount::from_le_bytes(amount_le))
+    }
+
+    fn read_token_id(&mut self) -> Result<TokenId, ParseError> {
+        let token_id = self.read_bytes(32, "token_id")?;
+        Ok(TokenId::from_slice(&token_id).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoinsuite_core::{BytesError, Script, Sha256d, TxOutput, UnhashedTx};
+    use pretty_assertions::assert_eq;
+
+    use crate::{
+        burn_section, genesis_section, mint_section, parse_section, parse_tx, sections_opreturn,
+        send_section, Genesis, GenesisInfo, MintData, ParseError, Parsed, Section, SectionVariant,
+        Send, TokenId, TokenMeta, TokenType,
+    };
+
+    fn parse_script(script: Script) -> Parsed {
+        parse_tx(
+            &Sha256d::new([1; 32]),
+            &UnhashedTx {
+                outputs: vec![TxOutput { value: 0, script }],
+                ..Default::default()
+            },
+        )
+    }
+
+    fn meta(token_id: TokenId) -> TokenMeta {
+        TokenMeta {
+            token_id,
+            token_type: TokenType::Standard,
+        }
+    }
+
+    fn send(output_amounts: Vec<i64>, intentional_burn_amount: Option<i64>) -> SectionVariant {
+        SectionVariant::Send(Send {
+            output_amounts,
+            intentional_burn_amount,
+        })
+    }
+
+    #[test]
+    fn test_parse_tx_not_slpv2() {
+        assert_eq!(
+            parse_tx(&Sha256d::default(), &UnhashedTx::default()),
+            Parsed::default(),
+        );
+        for script in [
+            &[][..],
+            &[0x6a],
+            &[0x6a, 0x04, b'S', b'L', b'P', b'2'],
+            &[0x51, 0x50],
+            &[0x6a, 0x51],
+        ] {
+            assert_eq!(parse_script(Script::from_slice(script)), Parsed::default());
+        }
+        // Other eMPP protocols are ignored
+        assert_eq!(
+            parse_script(Script::from_slice(&[
+                0x6a, 0x50, 0x00, 0x04, b'S', b'L', b'P', 0x00, 0x03, b'S', b'L', b'P',
+            ])),
+            Parsed::default(),
+        );
+    }
+
+    #[test]
+    fn test_parse_tx_invalid_ops() {
+        assert_eq!(
+            parse_script(Script::from_slice(&[0x6a, 0x50, 0x01])),
+            Parsed {
+                sections: vec![],
+                parse_errors: vec![ParseError::BytesError(BytesError::InvalidSplit {
+                    split_idx: 1,
+                    len: 0,
+                })],
+            },
+        );
+        let token_id = TokenId::new(Sha256d::new([3; 32]));
+        let script = [
+            [0x6a, 0x50].as_ref(),
+            &sections_opreturn(vec![send_section(&token_id, TokenType::Standard, &[7])]).bytecode()
+                [2..],
+            &[0x51],
+        ]
+        .concat();
+        assert_eq!(
+            parse_script(Script::from_slice(&script)),
+            Parsed {
+                sections: vec![Section {
+                    meta: meta(token_id),
+                    variant: send(vec![7], None),
+                }],
+                parse_errors: vec![ParseError::NonPushOp {
+                    opcode: 0x51,
+                    op_idx: 3,
+                }],
+            },
+        );
+    }
+
+    #[test]
+    fn test_parse_section_failure() {
+        let txid = Sha256d::new([1; 32]);
+        let check = |pushdata: &[u8], expected: ParseError| {
+            assert_eq!(parse_section(&txid, pushdata.into()), Err(expected));
+        };
+        check(
+            b"SLP2",
+            ParseError::MissingBytes {
+                field_name: "token_type",
+                expected: 1,
+                actual: 0,
+            },
+        );
+        check(b"SLP2\x01", ParseError::UnknownTokenType(1));
+        check(
+            b"SLP2\0",
+            ParseError::MissingBytes {
+                field_name: "tx_type",
+                expected: 1,
+                actual: 0,
+            },
+        );
+        check(
+            b"SLP2\0\x04SEN",
+            ParseError::MissingBytes {
+                field_name: "tx_type",
+                expected: 4,
+                actual: 3,
+            },
+        );
+        check(
+            b"SLP2\0\x07INVALID",
+            ParseError::UnknownTxType(b"INVALID".as_ref().into()),
+        );
+        check(
+            b"SLP2\0\x07GENESIS\x01A\x01B",
+            ParseError::MissingBytes {
+                field_name: "url",
+                expected: 1,
+                actual: 0,
+            },
+        );
+        check(
+            b"SLP2\0\x07GENESIS\0\0\0\0\0\x04\x01\x01\x02\x03\x04\x05",
+            ParseError::MissingBytes {
+                field_name: "amount",
+                expected: 6,
+                actual: 5,
+            },
+        );
+        check(
+            b"SLP2\0\x07GENESIS\0\0\0\0\0\x04\0",
+            ParseError::MissingBytes {
+                field_name: "num_batons",
+                expected: 1,
+                actual: 0,
+            },
+        );
+        check(
+            b"SLP2\0\x04MINT\x03\x03\x03",
+            ParseError::MissingBytes {
+                field_name: "token_id",
+                expected: 32,
+                actual: 3,
+            },
+        );
+        check(
+            &[b"SLP2\0\x04SEND".as_ref(), &[3; 32], &[2, 1, 0, 0, 0, 0, 0]].concat(),
+            ParseError::MissingBytes {
+                field_name: "output_amount",
+                expected: 6,
+                actual: 0,
+            },
+        );
+        check(
+            &[b"SLP2\0\x04BURN".as_ref(), &[3; 32], &[1, 0, 0]].concat(),
+            ParseError::MissingBytes {
+                field_name: "burn_amount",
+                expected: 6,
+                actual: 3,
+            },
+        );
+        check(
+            &[b"SLP2\0\x04SEND".as_ref(), &[3; 32], &[0, 0xff]].concat(),
+            ParseError::LeftoverBytes([0xff].into()),
+        );
+    }
+
+    #[test]
+    fn test_parse_section_success() {
+        let txid = Sha256d::new([1; 32]);
+        let token_id = TokenId::new(Sha256d::new([3; 32]));
+        assert_eq!(parse_section(&txid, b"SLP\0".as_ref().into()), Ok(None));
+        let genesis_info = GenesisInfo {
+            token_ticker: b"TEST".as_ref().into(),
+            token_name: b"Test Token".as_ref().into(),
+            url: b"https://example.com".as_ref().into(),
+            data: [0x12, 0x34].into(),
+            auth_pubkey: [2; 33].into(),
+            decimals: 4,
+        };
+        let mint_data = MintData {
+            amounts: vec![0, 1, 0xffff_ffff_ffff],
+            num_batons: 2,
+        };
+        assert_eq!(
+            parse_section(
+                &txid,
+                genesis_section(TokenType::Standard, &genesis_info, &mint_data),
+            ),
+            Ok(Some(Section {
+                meta: meta(TokenId::new(txid.clone())),
+                variant: SectionVariant::Genesis(Genesis {
+                    info: genesis_info,
+                    mint_data: mint_data.clone(),
+                }),
+            })),
+        );
+        assert_eq!(
+            parse_section(
+                &txid,
+                mint_section(&token_id, TokenType::Standard, &mint_data),
+            ),
+            Ok(Some(Section {
+                meta: meta(token_id.clone()),
+                variant: SectionVariant::Mint(mint_data),
+            })),
+        );
+        assert_eq!(
+            parse_section(
+                &txid,
+                send_section(&token_id, TokenType::Standard, &[5, 0, 6]),
+            ),
+            Ok(Some(Section {
+                meta: meta(token_id.clone()),
+                variant: send(vec![5, 0, 6], None),
+            })),
+        );
+        assert_eq!(
+            parse_section(&txid, burn_section(&token_id, TokenType::Standard, 9)),
+            Ok(Some(Section {
+                meta: meta(token_id),
+                variant: send(vec![], Some(9)),
+            })),
+        );
+    }
+
+    #[test]
+    fn test_parse_tx_sections() {
+        let token1 = TokenId::new(Sha256d::new([3; 32]));
+        let token2 = TokenId::new(Sha256d::new([4; 32]));
+        let token3 = TokenId::new(Sha256d::new([5; 32]));
+        let mint_data = MintData {
+            amounts: vec![10],
+            num_batons: 1,
+        };
+        assert_eq!(
+            parse_script(sections_opreturn(vec![
+                genesis_section(TokenType::Standard, &GenesisInfo::default(), &mint_data),
+                b"SLP2\x01".as_ref().into(),
+                mint_section(&token1, TokenType::Standard, &mint_data),
+                burn_section(&token2, TokenType::Standard, 2),
+                send_section(&token1, TokenType::Standard, &[1, 2]),
+                b"OTHER".as_ref().into(),
+                send_section(&token2, TokenType::Standard, &[3]),
+                burn_section(&token1, TokenType::Standard, 4),
+                send_section(&token2, TokenType::Standard, &[5]),
+                burn_section(&token1, TokenType::Standard, 6),
+                burn_section(&token3, TokenType::Standard, 7),
+            ])),
+            Parsed {
+                sections: vec![
+                    Section {
+                        meta: meta(TokenId::new(Sha256d::new([1; 32]))),
+                        variant: SectionVariant::Genesis(Genesis {
+                            info: GenesisInfo::default(),
+                            mint_data: mint_data.clone(),
+                        }),
+                    },
+                    Section {
+                        meta: meta(token1.clone()),
+                        variant: SectionVariant::Mint(mint_data),
+                    },
+                    Section {
+                        meta: meta(token2.clone()),
+                        variant: send(vec![3], Some(2)),
+                    },
+                    Section {
+                        meta: meta(token1.clone()),
+                        variant: send(vec![1, 2], Some(4)),
+                    },
+                    Section {
+                        meta: meta(token3),
+                        variant: send(vec![], Some(7)),
+                    },
+                ],
+                parse_errors: vec![
+                    ParseError::UnknownTokenType(1),
+                    ParseError::DuplicateSend(token2),
+                    ParseError::DuplicateBurn(token1),
+                ],
+            },
+        );
+    }
+}
@@ -2,7 +2,7 @@ use std::ops::Range;
 
 use bitcoinsuite_core::Bytes;
 
-use crate::TokenId;
+use crate::{ParseError, TokenId};
 
 pub type LokadId = [u8; 4];
 pub type Amount = i64;
@@ -15,32 +15,34 @@ pub const MINT: &[u8] = b"MINT";
 pub const SEND: &[u8] = b"SEND";
 pub const BURN: &[u8] = b"BURN";
 
+pub const AMOUNT_LENGTH: usize = 6;
+
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 #[repr(u8)]
 pub enum TokenType {
     Standard = DEFAULT_TOKEN_TYPE,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct TokenMeta {
     pub token_id: TokenId,
     pub token_type: TokenType,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Section {
     pub meta: TokenMeta,
     pub variant: SectionVariant,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum SectionVariant {
     Genesis(Genesis),
     Mint(MintData),
     Send(Send),
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct GenesisInfo {
     pub token_ticker: Bytes,
     pub token_name: Bytes,
@@ -50,27 +52,28 @@ pub struct GenesisInfo {
     pub decimals: u8,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Genesis {
     pub info: GenesisInfo,
     pub mint_data: MintData,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct MintData {
     pub amounts: Vec<Amount>,
     pub num_batons: usize,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Send {
     pub output_amounts: Vec<Amount>,
     pub intentional_burn_amount: Option<Amount>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct Parsed {
     pub sections: Vec<Section>,
+    pub parse_errors: Vec<ParseError>,
 }
 
 pub struct TokenAmount<'a> {
@@ -0,0 +1,691 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    Amount, MintData, Parsed, Section, SectionVariant, Send, TokenId, TokenMeta, ValidationError,
+};
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TokenVariant {
+    Amount(Amount),
+    MintBaton,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SectionType {
+    Genesis,
+    Mint,
+    Send,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SpentToken {
+    pub meta: TokenMeta,
+    pub variant: TokenVariant,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ValidSection {
+    pub meta: TokenMeta,
+    pub section_type: SectionType,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TokenOutput {
+    /// Index into [`ValidTxData::sections`]
+    pub section_idx: usize,
+    pub variant: TokenVariant,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct IntentionalBurn {
+    pub meta: TokenMeta,
+    pub amount: Amount,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Burn {
+    pub meta: TokenMeta,
+    pub variant: TokenVariant,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SectionFailure {
+    pub meta: TokenMeta,
+    pub error: ValidationError,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ValidTxData {
+    pub sections: Vec<ValidSection>,
+    pub outputs: Vec<Option<TokenOutput>>,
+    pub intentional_burns: Vec<IntentionalBurn>,
+    /// Tokens of each input that have been burned without being declared in a BURN section
+    pub burns: Vec<Option<Box<Burn>>>,
+    pub failures: Vec<SectionFailure>,
+}
+
+/// Color the outputs of a tx using its parsed SLPv2 sections and the tokens of the spent outputs.
+///
+/// Sections are validated in order; a section that fails validation doesn't color any outputs
+/// and is reported in [`ValidTxData::failures`], while the remaining sections are unaffected.
+/// Input tokens not consumed by any valid section are reported as burned.
+pub fn validate_tx(
+    parsed: &Parsed,
+    num_outputs: usize,
+    spent_tokens: &[Option<&SpentToken>],
+) -> ValidTxData {
+    let mut valid = ValidTxData {
+        outputs: vec![None; num_outputs],
+        ..Default::default()
+    };
+    // Amount each valid SEND still has to consume from the inputs
+    let mut send_required = HashMap::<&TokenId, i128>::new();
+    let mut seen_token_ids = HashSet::new();
+    for (section_idx, section) in parsed.sections.iter().enumerate() {
+        let is_new_token_id = seen_token_ids.insert(&section.meta.token_id);
+        let result = if is_new_token_id {
+            validate_section(&valid.outputs, section_idx, section, spent_tokens)
+        } else {
+            Err(ValidationError::DuplicateTokenId(
+                section.meta.token_id.clone(),
+            ))
+        };
+        let (section_type, colored) = match result {
+            Ok(result) => result,
+            Err(error) => {
+                valid.failures.push(SectionFailure {
+                    meta: section.meta.clone(),
+                    error,
+                });
+                continue;
+            }
+        };
+        if let SectionVariant::Send(send) = &section.variant {
+            send_required.insert(&section.meta.token_id, send_required_sum(send));
+            if let Some(amount) = send.intentional_burn_amount {
+                valid.intentional_burns.push(IntentionalBurn {
+                    meta: section.meta.clone(),
+                    amount,
+                });
+            }
+        }
+        for (output_idx, variant) in colored {
+            valid.outputs[output_idx] = Some(TokenOutput {
+                section_idx: valid.sections.len(),
+                variant,
+            });
+        }
+        valid.sections.push(ValidSection {
+            meta: section.meta.clone(),
+            section_type,
+        });
+    }
+
+    for spent_token in spent_tokens {
+        let spent_token = match spent_token {
+            Some(spent_token) => spent_token,
+            None => {
+                valid.burns.push(None);
+                continue;
+            }
+        };
+        let section_type = valid
+            .sections
+            .iter()
+            .find(|section| section.meta == spent_token.meta)
+            .map(|section| section.section_type);
+        let burned_variant = match (section_type, spent_token.variant) {
+            (Some(SectionType::Send), TokenVariant::Amount(amount)) => {
+                let required = send_required
+                    .get_mut(&spent_token.meta.token_id)
+                    .expect("Valid SEND without required amount");
+                let consumed = (amount as i128).min(*required);
+                *required -= consumed;
+                match amount as i128 - consumed {
+                    0 => None,
+                    burned => Some(TokenVariant::Amount(burned as Amount)),
+                }
+            }
+            (Some(SectionType::Mint), TokenVariant::MintBaton) => None,
+            (_, variant) => match variant {
+                TokenVariant::Amount(0) => None,
+                variant => Some(variant),
+            },
+        };
+        valid.burns.push(burned_variant.map(|variant| {
+            Box::new(Burn {
+                meta: spent_token.meta.clone(),
+                variant,
+            })
+        }));
+    }
+    valid
+}
+
+type ColoredOutputs = Vec<(usize, TokenVariant)>;
+
+fn validate_section(
+    outputs: &[Option<TokenOutput>],
+    section_idx: usize,
+    section: &Section,
+    spent_tokens: &[Option<&SpentToken>],
+) -> Result<(SectionType, ColoredOutputs), ValidationError> {
+    let (section_type, colored, max_output_idx) = match &section.variant {
+        SectionVariant::Genesis(genesis) => {
+            if section_idx != 0 {
+                return Err(ValidationError::GenesisNotFirst);
+            }
+            let (colored, max_output_idx) = mint_outputs(&genesis.mint_data);
+            (SectionType::Genesis, colored, max_output_idx)
+        }
+        SectionVariant::Mint(mint_data) => {
+            let has_mint_baton = spent_tokens.iter().flatten().any(|spent_token| {
+                spent_token.meta == section.meta && spent_token.variant == TokenVariant::MintBaton
+            });
+            if !has_mint_baton {
+                return Err(ValidationError::MissingMintBaton);
+            }
+            let (colored, max_output_idx) = mint_outputs(mint_data);
+            (SectionType::Mint, colored, max_output_idx)
+        }
+        SectionVariant::Send(send) => {
+            let required = send_required_sum(send);
+            let input_sum = spent_tokens
+                .iter()
+                .flatten()
+                .filter(|spent_token| spent_token.meta == section.meta)
+                .map(|spent_token| match spent_token.variant {
+                    TokenVariant::Amount(amount) => amount as i128,
+                    TokenVariant::MintBaton => 0,
+                })
+                .sum::<i128>();
+            if input_sum < required {
+                return Err(ValidationError::InsufficientInputSum {
+                    required,
+                    input_sum,
+                });
+            }
+            let colored = send
+                .output_amounts
+                .iter()
+                .enumerate()
+                .map(|(idx, &amount)| (idx + 1, TokenVariant::Amount(amount)))
+                .collect::<Vec<_>>();
+            (SectionType::Send, colored, send.output_amounts.len())
+        }
+    };
+    if max_output_idx >= outputs.len() {
+        return Err(ValidationError::TooFewOutputs {
+            expected: max_output_idx + 1,
+            actual: outputs.len(),
+        });
+    }
+    let colored = colored
+        .into_iter()
+        .filter(|&(_, variant)| variant != TokenVariant::Amount(0))
+        .collect::<Vec<_>>();
+    for &(output_idx, _) in &colored {
+        if outputs[output_idx].is_some() {
+            return Err(ValidationError::OverlappingOutput { output_idx });
+        }
+    }
+    Ok((section_type, colored))
+}
+
+fn mint_outputs(mint_data: &MintData) -> (ColoredOutputs, usize) {
+    let amounts = mint_data
+        .amounts_range()
+        .zip(&mint_data.amounts)
+        .map(|(output_idx, &amount)| (output_idx, TokenVariant::Amount(amount)));
+    let batons = mint_data
+        .batons_range()
+        .map(|output_idx| (output_idx, TokenVariant::MintBaton));
+    (
+        amounts.chain(batons).collect(),
+        mint_data.batons_range().end - 1,
+    )
+}
+
+fn send_required_sum(send: &Send) -> i128 {
+    send.output_amounts
+        .iter()
+        .map(|&amount| amount as i128)
+        .sum::<i128>()
+        + send.intentional_burn_amount.unwrap_or_default() as i128
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoinsuite_core::Sha256d;
+    use pretty_assertions::assert_eq;
+
+    use crate::{
+        validate_tx, Burn, Genesis, GenesisInfo, IntentionalBurn, MintData, Parsed, Section,
+        SectionFailure, SectionType, SectionVariant, Send, SpentToken, TokenId, TokenMeta,
+        TokenOutput, TokenType, TokenVariant, ValidSection, ValidTxData, ValidationError,
+    };
+
+    fn meta(num: u8) -> TokenMeta {
+        TokenMeta {
+            token_id: TokenId::new(Sha256d::new([num; 32])),
+            token_type: TokenType::Standard,
+        }
+    }
+
+    fn genesis(num: u8, amounts: Vec<i64>, num_batons: usize) -> Section {
+        Section {
+            meta: meta(num),
+            variant: SectionVariant::Genesis(Genesis {
+                info: GenesisInfo::default(),
+                mint_data: MintData {
+                    amounts,
+                    num_batons,
+                },
+            }),
+        }
+    }
+
+    fn mint(num: u8, amounts: Vec<i64>, num_batons: usize) -> Section {
+        Section {
+            meta: meta(num),
+            variant: SectionVariant::Mint(MintData {
+                amounts,
+                num_batons,
+            }),
+        }
+    }
+
+    fn send(num: u8, output_amounts: Vec<i64>, intentional_burn_amount: Option<i64>) -> Section {
+        Section {
+            meta: meta(num),
+            variant: SectionVariant::Send(Send {
+                output_amounts,
+                intentional_burn_amount,
+            }),
+        }
+    }
+
+    fn parsed(sections: Vec<Section>) -> Parsed {
+        Parsed {
+            sections,
+            parse_errors: vec![],
+        }
+    }
+
+    fn spent(num: u8, variant: TokenVariant) -> SpentToken {
+        SpentToken {
+            meta: meta(num),
+            variant,
+        }
+    }
+
+    fn valid_section(num: u8, section_type: SectionType) -> ValidSection {
+        ValidSection {
+            meta: meta(num),
+            section_type,
+        }
+    }
+
+    fn amount(section_idx: usize, amount: i64) -> Option<TokenOutput> {
+        Some(TokenOutput {
+            section_idx,
+            variant: TokenVariant::Amount(amount),
+        })
+    }
+
+    fn baton(section_idx: usize) -> Option<TokenOutput> {
+        Some(TokenOutput {
+            section_idx,
+            variant: TokenVariant::MintBaton,
+        })
+    }
+
+    fn burn(num: u8, variant: TokenVariant) -> Option<Box<Burn>> {
+        Some(Box::new(Burn {
+            meta: meta(num),
+            variant,
+        }))
+    }
+
+    fn failure(num: u8, error: ValidationError) -> SectionFailure {
+        SectionFailure {
+            meta: meta(num),
+            error,
+        }
+    }
+
+    #[test]
+    fn test_validate_tx_no_sections() {
+        assert_eq!(
+            validate_tx(&Parsed::default(), 2, &[None]),
+            ValidTxData {
+                outputs: vec![None, None],
+                burns: vec![None],
+                ..Default::default()
+            },
+        );
+        // All tokens are burned
+        assert_eq!(
+            validate_tx(
+                &Parsed::default(),
+                1,
+                &[
+                    Some(&spent(1, TokenVariant::Amount(5))),
+                    None,
+                    Some(&spent(2, TokenVariant::MintBaton)),
+                    Some(&spent(3, TokenVariant::Amount(0))),
+                ],
+            ),
+            ValidTxData {
+                outputs: vec![None],
+                burns: vec![
+                    burn(1, TokenVariant::Amount(5)),
+                    None,
+                    burn(2, TokenVariant::MintBaton),
+                    None,
+                ],
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    fn test_validate_tx_genesis() {
+        // Amounts and batons
+        assert_eq!(
+            validate_tx(
+                &parsed(vec![genesis(1, vec![10, 0, 20], 2)]),
+                6,
+                &[Some(&spent(2, TokenVariant::Amount(3)))],
+            ),
+            ValidTxData {
+                sections: vec![valid_section(1, SectionType::Genesis)],
+                outputs: vec![None, amount(0, 10), None, amount(0, 20), baton(0), baton(0)],
+                burns: vec![burn(2, TokenVariant::Amount(3))],
+                ..Default::default()
+            },
+        );
+        // Too few outputs
+        assert_eq!(
+            validate_tx(&parsed(vec![genesis(1, vec![10], 2)]), 3, &[None]),
+            ValidTxData {
+                outputs: vec![None; 3],
+                burns: vec![None],
+                failures: vec![failure(
+                    1,
+                    ValidationError::TooFewOutputs {
+                        expected: 4,
+                        actual: 3,
+                    },
+                )],
+                ..Default::default()
+            },
+        );
+        // GENESIS not first
+        assert_eq!(
+            validate_tx(
+                &parsed(vec![send(2, vec![4], None), genesis(1, vec![10], 0),]),
+                3,
+                &[Some(&spent(2, TokenVariant::Amount(4)))],
+            ),
+            ValidTxData {
+                sections: vec![valid_section(2, SectionType::Send)],
+                outputs: vec![None, amount(0, 4), None],
+                burns: vec![None],
+                failures: vec![failure(1, ValidationError::GenesisNotFirst)],
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    fn test_validate_tx_mint() {
+        // Missing mint baton
+        for spent_tokens in [
+            vec![],
+            vec![None],
+            vec![Some(spent(1, TokenVariant::Amount(4)))],
+            vec![Some(spent(2, TokenVariant::MintBaton))],
+        ] {
+            let spent_tokens = spent_tokens.iter().map(Option::as_ref).collect::<Vec<_>>();
+            assert_eq!(
+                validate_tx(&parsed(vec![mint(1, vec![5], 1)]), 3, &spent_tokens),
+                ValidTxData {
+                    outputs: vec![None; 3],
+                    burns: spent_tokens
+                        .iter()
+                        .map(|spent_token| {
+                            spent_token.map(|spent_token| {
+                                Box::new(Burn {
+                                    meta: spent_token.meta.clone(),
+                                    variant: spent_token.variant,
+                                })
+                            })
+                        })
+                        .collect(),
+                    failures: vec![failure(1, ValidationError::MissingMintBaton)],
+                    ..Default::default()
+                },
+            );
+        }
+        // Valid MINT, burning amounts of the same token
+        assert_eq!(
+            validate_tx(
+                &parsed(vec![mint(1, vec![5, 6], 1)]),
+                4,
+                &[
+                    Some(&spent(1, TokenVariant::Amount(4))),
+                    Some(&spent(1, TokenVariant::MintBaton)),
+                    Some(&spent(1, TokenVariant::MintBaton)),
+                ],
+            ),
+            ValidTxData {
+                sections: vec![valid_section(1, SectionType::Mint)],
+                outputs: vec![None, amount(0, 5), amount(0, 6), baton(0)],
+                burns: vec![burn(1, TokenVariant::Amount(4)), None, None],
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    fn test_validate_tx_send() {
+        // Insufficient input sum
+        assert_eq!(
+            validate_tx(
+                &parsed(vec![send(1, vec![5, 6], Some(2))]),
+                3,
+                &[
+                    Some(&spent(1, TokenVariant::Amount(12))),
+                    Some(&spent(1, TokenVariant::MintBaton)),
+                    Some(&spent(2, TokenVariant::Amount(1))),
+                ],
+            ),
+            ValidTxData {
+                outputs: vec![None; 3],
+                burns: vec![
+                    burn(1, TokenVariant::Amount(12)),
+                    burn(1, TokenVariant::MintBaton),
+                    burn(2, TokenVariant::Amount(1)),
+                ],
+                failures: vec![failure(
+                    1,
+                    ValidationError::InsufficientInputSum {
+                        required: 13,
+                        input_sum: 12,
+                    },
+                )],
+                ..Default::default()
+            },
+        );
+        // Exact input sum, with intentional burn
+        assert_eq!(
+            validate_tx(
+                &parsed(vec![send(1, vec![5, 0, 6], Some(2))]),
+                4,
+                &[
+                    Some(&spent(1, TokenVariant::Amount(10))),
+                    Some(&spent(1, TokenVariant::Amount(3))),
+                ],
+            ),
+            ValidTxData {
+                sections: vec![valid_section(1, SectionType::Send)],
+                outputs: vec![None, amount(0, 5), None, amount(0, 6)],
+                intentional_burns: vec![IntentionalBurn {
+                    meta: meta(1),
+                    amount: 2,
+                }],
+                burns: vec![None, None],
+                ..Default::default()
+            },
+        );
+        // Excess inputs are burned unintentionally, in input order; mint batons are burned too
+        assert_eq!(
+            validate_tx(
+                &parsed(vec![send(1, vec![5], None)]),
+                2,
+                &[
+                    Some(&spent(1, TokenVariant::Amount(3))),
+                    Some(&spent(1, TokenVariant::Amount(4))),
+                    Some(&spent(1, TokenVariant::MintBaton)),
+                    Some(&spent(1, TokenVariant::Amount(6))),
+                ],
+            ),
+            ValidTxData {
+                sections: vec![valid_section(1, SectionType::Send)],
+                outputs: vec![None, amount(0, 5)],
+                burns: vec![
+                    None,
+                    burn(1, TokenVariant::Amount(2)),
+                    burn(1, TokenVariant::MintBaton),
+                    burn(1, TokenVariant::Amount(6)),
+                ],
+                ..Default::default()
+            },
+        );
+        // Pure BURN
+        assert_eq!(
+            validate_tx(
+                &parsed(vec![send(1, vec![], Some(7))]),
+                1,
+                &[Some(&spent(1, TokenVariant::Amount(7)))],
+            ),
+            ValidTxData {
+                sections: vec![valid_section(1, SectionType::Send)],
+                outputs: vec![None],
+                intentional_burns: vec![IntentionalBurn {
+                    meta: meta(1),
+                    amount: 7,
+                }],
+                burns: vec![None],
+                ..Default::default()
+            },
+        );
+        // Too few outputs
+        assert_eq!(
+            validate_tx(
+                &parsed(vec![send(1, vec![5, 0], None)]),
+                2,
+                &[Some(&spent(1, TokenVariant::Amount(5)))],
+            ),
+            ValidTxData {
+                outputs: vec![None; 2],
+                burns: vec![burn(1, TokenVariant::Amount(5))],
+                failures: vec![failure(
+                    1,
+                    ValidationError::TooFewOutputs {
+                        expected: 3,
+                        actual: 2,
+                    },
+                )],
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    fn test_validate_tx_multi_section() {
+        // GENESIS, MINT and SEND coloring disjoint outputs
+        assert_eq!(
+            validate_tx(
+                &parsed(vec![
+                    genesis(1, vec![10], 0),
+                    mint(2, vec![0, 20], 1),
+                    send(3, vec![0, 0, 0, 30, 0, 40], None),
+                ]),
+                7,
+                &[
+                    Some(&spent(3, TokenVariant::Amount(50))),
+                    Some(&spent(2, TokenVariant::MintBaton)),
+                    None,
+                    Some(&spent(3, TokenVariant::Amount(30))),
+                    Some(&spent(4, TokenVariant::Amount(1))),
+                ],
+            ),
+            ValidTxData {
+                sections: vec![
+                    valid_section(1, SectionType::Genesis),
+                    valid_section(2, SectionType::Mint),
+                    valid_section(3, SectionType::Send),
+                ],
+                outputs: vec![
+                    None,
+                    amount(0, 10),
+                    amount(1, 20),
+                    baton(1),
+                    amount(2, 30),
+                    None,
+                    amount(2, 40),
+                ],
+                burns: vec![
+                    None,
+                    None,
+                    None,
+                    burn(3, TokenVariant::Amount(10)),
+                    burn(4, TokenVariant::Amount(1)),
+                ],
+                ..Default::default()
+            },
+        );
+        // Overlapping outputs and duplicate token IDs fail only the offending section
+        assert_eq!(
+            validate_tx(
+                &parsed(vec![
+                    send(1, vec![5], None),
+                    send(2, vec![0, 6], None),
+                    send(3, vec![7], None),
+                    mint(1, vec![0, 0, 8], 0),
+                    mint(4, vec![0], 1),
+                ]),
+                4,
+                &[
+                    Some(&spent(1, TokenVariant::Amount(5))),
+                    Some(&spent(2, TokenVariant::Amount(6))),
+                    Some(&spent(3, TokenVariant::Amount(7))),
+                    Some(&spent(1, TokenVariant::MintBaton)),
+                    Some(&spent(4, TokenVariant::MintBaton)),
+                ],
+            ),
+            ValidTxData {
+                sections: vec![
+                    valid_section(1, SectionType::Send),
+                    valid_section(2, SectionType::Send),
+                ],
+                outputs: vec![None, amount(0, 5), amount(1, 6), None],
+                burns: vec![
+                    None,
+                    None,
+                    burn(3, TokenVariant::Amount(7)),
+                    burn(1, TokenVariant::MintBaton),
+                    burn(4, TokenVariant::MintBaton),
+                ],
+                failures: vec![
+                    failure(3, ValidationError::OverlappingOutput { output_idx: 1 }),
+                    failure(1, ValidationError::DuplicateTokenId(meta(1).token_id)),
+                    failure(4, ValidationError::OverlappingOutput { output_idx: 2 }),
+                ],
+                ..Default::default()
+            },
+        );
+    }
+}
@@ -0,0 +1,238 @@
+use bitcoinsuite_bitcoind::{cli::BitcoinCli, instance::BitcoindInstance};
+use bitcoinsuite_core::{
+    ecc::{DummyEcc, Ecc},
+    opcode::OP_1,
+    BitcoinCode, CashAddress, CoinSelectStrategy, Hashed, Network, OutPoint, Script, SequenceNo,
+    Sha256d, SignData, SignField, Signatory, SpendableUtxo, TxBuilder, TxBuilderOutput, TxInput,
+    TxOutput, UnsignedTxInput, Utxo,
+};
+use bitcoinsuite_error::Result;
+
+use crate::setup_bitcoind_coins;
+
+/// Fee rate of faucet txs, bitcoind's default `-minrelaytxfee`.
+pub const FAUCET_FEE_PER_KB: i64 = 1000;
+
+/// Funds scripts on a regtest node from matured coinbase coins, so tests can create
+/// exactly the UTXOs they need.
+///
+/// The coins are locked by a P2SH anyone-can-spend script (`OP_1`). Every funding tx is
+/// mined right away, and its change is kept by the faucet.
+#[derive(Debug)]
+pub struct RegtestFaucet {
+    cli: BitcoinCli,
+    network: Network,
+    redeem_script: Script,
+    address: String,
+    coins: Vec<Utxo>,
+}
+
+/// UTXO created by the faucet, with the sign data required to spend it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FundedUtxo {
+    pub utxo: Utxo,
+    /// Value and output script; P2SH UTXOs also need a RedeemScript to be signed.
+    pub sign_data: SignData,
+}
+
+/// Spends the faucet's P2SH coins by pushing the redeem script.
+struct RedeemScriptSignatory(Script);
+
+impl Signatory for RedeemScriptSignatory {
+    fn sign_input<'tx>(
+        &self,
+        _: &dyn Ecc,
+        mut input: UnsignedTxInput<'tx>,
+    ) -> bitcoinsuite_core::Result<()> {
+        *input.input_script_mut() = Script::new(self.0.bytecode().ser());
+        Ok(())
+    }
+}
+
+impl RegtestFaucet {
+    /// Mine `num_coins` coinbase coins to the faucet, plus 100 blocks so they mature.
+    pub fn setup(bitcoind: &BitcoindInstance, network: Network, num_coins: i32) -> Result<Self> {
+        let redeem_script = Script::from_slice(&[OP_1]);
+        let address = bitcoind.cmd_json("decodescript", &[&redeem_script.hex()])?;
+        let address = address["p2sh"].as_str().unwrap().to_string();
+        let script = redeem_script.to_p2sh();
+        let coins =
+            setup_bitcoind_coins(bitcoind.cli(), network, num_coins, &address, &script.hex())?
+                .into_iter()
+                .map(|(outpoint, value)| Utxo {
+                    outpoint,
+                    script: script.clone(),
+                    value,
+                })
+                .collect();
+        Ok(RegtestFaucet {
+            cli: bitcoind.cli().clone(),
+            network,
+            redeem_script,
+            address,
+            coins,
+        })
+    }
+
+    /// Coins the faucet can spend.
+    pub fn coins(&self) -> &[Utxo] {
+        &self.coins
+    }
+
+    pub fn balance(&self) -> i64 {
+        self.coins.iter().map(|coin| coin.value).sum()
+    }
+
+    pub fn fund_script(&mut self, script: &Script, value: i64) -> Result<FundedUtxo> {
+        let output = TxOutput {
+            value,
+            script: script.clone(),
+        };
+        Ok(self.fund_outputs(vec![output])?.remove(0))
+    }
+
+    pub fn fund_address(&mut self, address: &CashAddress<'_>, value: i64) -> Result<FundedUtxo> {
+        self.fund_script(&address.to_script(), value)
+    }
+
+    /// Send the exact outputs in one tx, mine it and return the UTXOs in output order.
+    pub fn fund_outputs(&mut self, outputs: Vec<TxOutput>) -> Result<Vec<FundedUtxo>> {
+        let num_outputs = outputs.len();
+        let mut tx_builder = TxBuilder {
+            version: 1,
+            outputs: outputs.into_iter().map(TxBuilderOutput::Fixed).collect(),
+            ..Default::default()
+        };
+        tx_builder
+            .outputs
+            .push(TxBuilderOutput::Leftover(self.redeem_script.to_p2sh()));
+        let coins = self
+            .coins
+            .iter()
+            .cloned()
+            .map(|coin| {
+                let signatory = RedeemScriptSignatory(self.redeem_script.clone());
+                SpendableUtxo::new(coin, Some(0), Box::new(signatory))
+            })
+            .collect();
+        let dust_limit = self.network.dust_amount();
+        let unspent = tx_builder.select_coins(
+            coins,
+            CoinSelectStrategy::LargestFirst,
+            FAUCET_FEE_PER_KB,
+            dust_limit,
+        )?;
+        let tx = tx_builder
+            .sign(&DummyEcc, FAUCET_FEE_PER_KB, dust_limit)?
+            .hashed();
+        self.cli
+            .cmd_string("sendrawtransaction", &[&tx.ser().hex()])?;
+        self.coins = unspent.into_iter().map(|coin| coin.utxo).collect();
+        self.mine_blocks(1)?;
+
+        let mut utxos = tx
+            .outputs()
+            .iter()
+            .enumerate()
+            .map(|(out_idx, output)| Utxo {
+                outpoint: OutPoint {
+                    txid: tx.hash().clone(),
+                    out_idx: out_idx as u32,
+                },
+                script: output.script.clone(),
+                value: output.value,
+            });
+        let funded = utxos
+            .by_ref()
+            .take(num_outputs)
+            .map(|utxo| FundedUtxo {
+                sign_data: SignData::new(vec![
+                    SignField::Value(utxo.value),
+                    SignField::OutputScript(utxo.script.clone()),
+                ]),
+                utxo,
+            })
+            .collect();
+        // Change, unless it would have been dust
+        self.coins.extend(utxos);
+        Ok(funded)
+    }
+
+    /// Mine blocks to the faucet; their coinbase coins aren't tracked by the faucet.
+    pub fn mine_blocks(&self, num_blocks: u32) -> Result<Vec<Sha256d>> {
+        let hashes = self.cli.cmd_json(
+            "generatetoaddress",
+            &[&num_blocks.to_string(), &self.address],
+        )?;
+        hashes
+            .members()
+            .map(|hash| Ok(Sha256d::from_hex_be(hash.as_str().unwrap())?))
+            .collect()
+    }
+}
+
+impl FundedUtxo {
+    /// Unsigned input spending the UTXO, with its sign data.
+    pub fn to_input(&self) -> TxInput {
+        TxInput {
+            prev_out: self.utxo.outpoint.clone(),
+            script: Script::default(),
+            sequence: SequenceNo::finalized(),
+            sign_data: Some(self.sign_data.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoinsuite_bitcoind::instance::{BitcoindChain, BitcoindConf, BitcoindInstance};
+    use bitcoinsuite_core::{Hashed, Network, Script, TxOutput};
+    use bitcoinsuite_error::Result;
+    use bitcoinsuite_test_utils::bin_folder;
+
+    use crate::RegtestFaucet;
+
+    #[test]
+    fn test_regtest_faucet() -> Result<()> {
+        let conf = BitcoindConf::from_chain_regtest(bin_folder(), BitcoindChain::BCH, vec![])?;
+        let mut bitcoind = BitcoindInstance::setup(conf)?;
+        bitcoind.wait_for_ready()?;
+        let mut faucet = RegtestFaucet::setup(&bitcoind, Network::BCH, 2)?;
+        assert_eq!(faucet.coins().len(), 2);
+        let balance = faucet.balance();
+
+        let script = Script::from_slice(&[0x52]).to_p2sh();
+        let funded = faucet.fund_script(&script, 10_000)?;
+        assert_eq!(funded.utxo.value, 10_000);
+        assert_eq!(funded.utxo.script, script);
+        assert_eq!(funded.sign_data.find_value()?, 10_000);
+
+        let outputs = vec![
+            TxOutput {
+                value: 546,
+                script: Script::from_slice(&[0x53]).to_p2sh(),
+            },
+            TxOutput {
+                value: 20_000,
+                script: script.clone(),
+            },
+        ];
+        let funded = faucet.fund_outputs(outputs.clone())?;
+        assert_eq!(funded.len(), 2);
+        for (out_idx, (funded, output)) in funded.iter().zip(&outputs).enumerate() {
+            assert_eq!(funded.utxo.outpoint.out_idx, out_idx as u32);
+            assert_eq!(funded.utxo.value, output.value);
+            assert_eq!(funded.sign_data.find_output_script()?, output.script);
+        }
+        // Besides the funded outputs, only fees were spent
+        let spent = balance - faucet.balance();
+        assert!(spent > 30_546 && spent < 31_546);
+
+        let tx = bitcoind.cmd_json(
+            "getrawtransaction",
+            &[&funded[0].utxo.outpoint.txid.to_hex_be(), "1"],
+        )?;
+        assert_eq!(tx["confirmations"], 1);
+        Ok(())
+    }
+}
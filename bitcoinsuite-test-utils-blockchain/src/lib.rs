@@ -11,6 +11,10 @@ use bitcoinsuite_core::{
 use bitcoinsuite_error::Result;
 use bitcoinsuite_test_utils::bin_folder;
 
+mod faucet;
+
+pub use crate::faucet::*;
+
 pub async fn setup_xec_chain(
     num_generated_utxos: i32,
     redeem_script: &Script,